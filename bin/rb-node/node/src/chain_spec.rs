@@ -1,6 +1,6 @@
 use rb_node_runtime::{
-	AccountId, AuraConfig, BalancesConfig, DKGConfig, GenesisConfig, GrandpaConfig, Signature,
-	SudoConfig, SystemConfig, WASM_BINARY,
+	AccountId, AuraConfig, BalancesConfig, DKGConfig, GenesisConfig, GrandpaConfig,
+	RandomnessBeaconConfig, Signature, SudoConfig, SystemConfig, WASM_BINARY,
 };
 use sc_service::ChainType;
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
@@ -133,7 +133,7 @@ pub fn local_testnet_config() -> Result<ChainSpec, String> {
 }
 
 /// Configure initial storage state for FRAME modules.
-fn testnet_genesis(
+pub fn testnet_genesis(
 	wasm_binary: &[u8],
 	initial_authorities: Vec<(AuraId, GrandpaId, DKGId)>,
 	root_key: AccountId,
@@ -169,7 +169,11 @@ fn testnet_genesis(
 				.map(|x| (x.2.clone().into()))
 				.collect(),
 			threshold: initial_authorities.len() as u64 / 3 + 1,
+			// the DKG schedule splitting the `DKGReady` of the runtime
+			round_lengths: None,
 		}),
+		// the schedule of the runtime
+		pallet_randomness_beacon: Some(RandomnessBeaconConfig { schedule: None }),
 		pallet_sudo: Some(SudoConfig {
 			// Assign network admin rights.
 			key: root_key,
@@ -7,10 +7,13 @@
 
 use std::sync::Arc;
 
+use jsonrpc_pubsub::manager::SubscriptionManager;
 use rb_node_runtime::{opaque::Block, AccountId, Balance, Index};
+use sc_client_api::{BlockchainEvents, ProofProvider};
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::{Error as BlockChainError, HeaderMetadata, HeaderBackend};
 use sp_block_builder::BlockBuilder;
+use sp_keystore::SyncCryptoStorePtr;
 pub use sc_rpc_api::DenyUnsafe;
 use sp_transaction_pool::TransactionPool;
 
@@ -21,6 +24,10 @@ pub struct FullDeps<C, P> {
 	pub client: Arc<C>,
 	/// Transaction pool instance.
 	pub pool: Arc<P>,
+	/// The keystore holding the DKG secret share of the node.
+	pub keystore: SyncCryptoStorePtr,
+	/// Executor of the RPC subscriptions.
+	pub subscription_executor: sc_rpc::SubscriptionTaskExecutor,
 	/// Whether to deny unsafe calls
 	pub deny_unsafe: DenyUnsafe,
 }
@@ -29,21 +36,25 @@ pub struct FullDeps<C, P> {
 pub fn create_full<C, P>(
 	deps: FullDeps<C, P>,
 ) -> jsonrpc_core::IoHandler<sc_rpc::Metadata> where
-	C: ProvideRuntimeApi<Block>,
+	C: ProvideRuntimeApi<Block> + BlockchainEvents<Block> + ProofProvider<Block>,
 	C: HeaderBackend<Block> + HeaderMetadata<Block, Error=BlockChainError> + 'static,
 	C: Send + Sync + 'static,
 	C::Api: substrate_frame_rpc_system::AccountNonceApi<Block, AccountId, Index>,
 	C::Api: pallet_transaction_payment_rpc::TransactionPaymentRuntimeApi<Block, Balance>,
 	C::Api: BlockBuilder<Block>,
+	C::Api: sp_dkg::DKGApi<Block> + sp_randomness_beacon::RandomnessBeaconApi<Block>,
 	P: TransactionPool + 'static,
 {
 	use substrate_frame_rpc_system::{FullSystem, SystemApi};
 	use pallet_transaction_payment_rpc::{TransactionPayment, TransactionPaymentApi};
+	use sc_rpc::dkg::{Dkg, DkgApi};
 
 	let mut io = jsonrpc_core::IoHandler::default();
 	let FullDeps {
 		client,
 		pool,
+		keystore,
+		subscription_executor,
 		deny_unsafe,
 	} = deps;
 
@@ -55,6 +66,15 @@ pub fn create_full<C, P>(
 		TransactionPaymentApi::to_delegate(TransactionPayment::new(client.clone()))
 	);
 
+	io.extend_with(
+		DkgApi::to_delegate(Dkg::new(
+			client.clone(),
+			Some(keystore),
+			SubscriptionManager::new(Arc::new(subscription_executor)),
+			deny_unsafe,
+		))
+	);
+
 	// Extend this RPC with a custom API by using the following syntax.
 	// `YourRpcStruct` should have a reference to a client, which is needed
 	// to call into the runtime.
@@ -9,10 +9,11 @@ pub use sc_executor::NativeExecutor;
 use sc_finality_grandpa::{
 	FinalityProofProvider as GrandpaFinalityProofProvider, GrandpaBlockImport, SharedVoterState,
 };
+use sc_network::NetworkService;
 use sc_service::{error::Error as ServiceError, Configuration, TaskManager};
 use sp_consensus_aura::sr25519::AuthorityPair as AuraPair;
 use sp_inherents::InherentDataProviders;
-use sp_runtime::generic::BlockId;
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -23,10 +24,14 @@ native_executor_instance!(
 	pub Executor,
 	rb_node_runtime::api::dispatch,
 	rb_node_runtime::native_version,
-	frame_benchmarking::benchmarking::HostFunctions,
+	(
+		frame_benchmarking::benchmarking::HostFunctions,
+		sp_dkg::keystore::secret_shares::HostFunctions,
+		sp_dkg::host::bls_12381::HostFunctions,
+	),
 );
 
-type FullClient = sc_service::TFullClient<Block, RuntimeApi, Executor>;
+pub type FullClient = sc_service::TFullClient<Block, RuntimeApi, Executor>;
 type FullBackend = sc_service::TFullBackend<Block>;
 type FullSelectChain = sc_consensus::LongestChain<FullBackend, Block>;
 
@@ -101,8 +106,12 @@ pub fn new_partial(
 	)?;
 
 	let (tx, rx) = channel(10);
-	let rb_block_import =
-		RandomnessBeaconBlockImport::new(grandpa_block_import.clone(), client.clone(), tx);
+	let rb_block_import = RandomnessBeaconBlockImport::new(
+		grandpa_block_import.clone(),
+		client.clone(),
+		inherent_data_providers.clone(),
+		tx,
+	);
 	let aura_block_import = sc_consensus_aura::AuraBlockImport::<_, _, _, AuraPair>::new(
 		rb_block_import.clone(),
 		client.clone(),
@@ -133,8 +142,20 @@ pub fn new_partial(
 	})
 }
 
+/// The parts of a running full node needed to observe it, e.g. in tests.
+pub struct NewFullBase {
+	pub task_manager: TaskManager,
+	pub client: Arc<FullClient>,
+	pub network: Arc<NetworkService<Block, <Block as BlockT>::Hash>>,
+}
+
 /// Builds a new service for a full client.
 pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
+	new_full_base(config).map(|NewFullBase { task_manager, .. }| task_manager)
+}
+
+/// Builds a new service for a full client and returns its parts.
+pub fn new_full_base(mut config: Configuration) -> Result<NewFullBase, ServiceError> {
 	let sc_service::PartialComponents {
 		client,
 		backend,
@@ -148,6 +169,14 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 		..
 	} = new_partial(&config)?;
 
+	// the peers ask the node directly for the randomness shares they are missing
+	let (share_requests_config, incoming_share_requests) =
+		sc_randomness_beacon::request::protocol_config();
+	config
+		.network
+		.request_response_protocols
+		.push(share_requests_config);
+
 	let (network, network_status_sinks, system_rpc_tx, network_starter) =
 		sc_service::build_network(sc_service::BuildNetworkParams {
 			config: &config,
@@ -177,16 +206,18 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 	let prometheus_registry = config.prometheus_registry().cloned();
 	let telemetry_connection_sinks = sc_service::TelemetryConnectionSinks::default();
 	let enable_grandpa = !config.disable_grandpa;
-	let http_rpc_port = config.rpc_http.unwrap().port();
 
 	let rpc_extensions_builder = {
 		let client = client.clone();
 		let pool = transaction_pool.clone();
+		let keystore = keystore_container.sync_keystore();
 
-		Box::new(move |deny_unsafe, _| {
+		Box::new(move |deny_unsafe, subscription_executor| {
 			let deps = crate::rpc::FullDeps {
 				client: client.clone(),
 				pool: pool.clone(),
+				keystore: keystore.clone(),
+				subscription_executor,
 				deny_unsafe,
 			};
 
@@ -218,7 +249,7 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 		// as it does not, then we pack it in Arc<Mutex<_>>
 		let randomness_rx = Arc::new(Mutex::new(randomness_rx));
 		let (beacon_start, beacoin_period) = get_rb_params(client.clone());
-		let proposer = sc_randomness_beacon::authorship::ProposerFactory::new(
+		let mut proposer = sc_randomness_beacon::authorship::ProposerFactory::new(
 			task_manager.spawn_handle(),
 			client.clone(),
 			transaction_pool,
@@ -227,6 +258,18 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 			beacoin_period,
 			randomness_rx,
 		);
+		// the AURA key of the author signs the VRF output of its blocks
+		proposer.set_author_vrf(Arc::new(
+			sc_randomness_beacon::authorship::KeystoreAuthorVrf::new(
+				client.clone(),
+				keystore_container.sync_keystore(),
+				sp_core::crypto::key_types::AURA,
+			),
+		));
+		proposer.set_chained_nonce(client.clone());
+		proposer
+			.check_grace_period(&*client, &BlockId::Hash(client.chain_info().best_hash))
+			.map_err(ServiceError::Other)?;
 
 		let can_author_with =
 			sp_consensus::CanAuthorWithNativeVersion::new(client.executor().clone());
@@ -257,7 +300,12 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 		network.clone(),
 		randomness_tx,
 		client.clone(),
-		http_rpc_port,
+		Some(keystore_container.sync_keystore()),
+		sc_randomness_beacon::Config {
+			incoming_requests: Some(incoming_share_requests),
+			prometheus_registry: prometheus_registry.clone(),
+			..Default::default()
+		},
 	);
 
 	task_manager.spawn_handle().spawn("randomness gossip", rg);
@@ -292,7 +340,7 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 		let grandpa_config = sc_finality_grandpa::GrandpaParams {
 			config: grandpa_config,
 			link: grandpa_link,
-			network,
+			network: network.clone(),
 			telemetry_on_connect: Some(telemetry_connection_sinks.on_connect_stream()),
 			voting_rule: sc_finality_grandpa::VotingRulesBuilder::default().build(),
 			prometheus_registry,
@@ -306,10 +354,14 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 			sc_finality_grandpa::run_grandpa_voter(grandpa_config)?,
 		);
 	} else {
-		sc_finality_grandpa::setup_disabled_grandpa(network)?;
+		sc_finality_grandpa::setup_disabled_grandpa(network.clone())?;
 	}
 
-	Ok(task_manager)
+	Ok(NewFullBase {
+		task_manager,
+		client,
+		network,
+	})
 }
 
 /// Builds a new service for a light client.
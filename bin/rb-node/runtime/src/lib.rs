@@ -7,6 +7,7 @@
 include!(concat!(env!("OUT_DIR"), "/wasm_binary.rs"));
 
 use codec::Encode;
+use core::time::Duration;
 use pallet_grandpa::fg_primitives;
 use pallet_grandpa::{AuthorityId as GrandpaId, AuthorityList as GrandpaAuthorityList};
 use sp_api::impl_runtime_apis;
@@ -18,7 +19,7 @@ use sp_runtime::traits::{
 };
 use sp_runtime::{
 	create_runtime_str, generic, impl_opaque_keys, traits,
-	transaction_validity::{TransactionSource, TransactionValidity},
+	transaction_validity::{TransactionPriority, TransactionSource, TransactionValidity},
 	ApplyExtrinsicResult, MultiSignature, SaturatedConversion,
 };
 use sp_std::prelude::*;
@@ -266,6 +267,7 @@ impl pallet_sudo::Trait for Runtime {
 }
 
 // It *must* hold that DKG_READY < START_HEIGHT
+// DKG_READY is the length of a single DKG epoch, the keys of the genesis epoch are ready at DKG_READY.
 // The first nonce used by the Randomness Beacoin is the hash of the block number START_HEIGHT.
 // Randomness is included in blocks START_HEIGHT + K*RANDOMNESS_PERIOD, for K=1,2,3, ...
 // And the nonces for these are taken from START_HEIGHT + (K-1)*RANDOMNESS_PERIOD, for K=1,2,3, ...
@@ -275,39 +277,77 @@ const RANDOMNESS_PERIOD: u32 = 2;
 
 parameter_types! {
 	pub const DKGReady: u32 = DKG_READY;
+	pub const DKGUnsignedPriority: TransactionPriority = TransactionPriority::max_value() / 2;
+	// the participation in the beacon is tracked once this is set with `set_storage`
+	pub storage BeaconParticipationPeriod: u32 = 0;
 }
 
+pub struct LatestBeaconNonce;
+impl Get<Option<Hash>> for LatestBeaconNonce {
+	fn get() -> Option<Hash> {
+		RandomnessBeacon::latest_randomness().map(|(_, randomness)| randomness.nonce())
+	}
+}
+
+// there is no staking in this runtime, so the offences have no owners and are not reported
 impl pallet_dkg::Trait for Runtime {
 	type Event = Event;
 	type Call = Call;
 	type AuthorityId = pallet_dkg::crypto::DKGId;
 	type DKGReady = DKGReady;
+	type CommitteeOrigin = frame_system::EnsureRoot<AccountId>;
+	type UnsignedPriority = DKGUnsignedPriority;
+	type KeyOwnerIdentification = AccountId;
+	type KeyOwner = ();
+	type CurrentSession = ();
+	type ReportOffences = ();
+	type BeaconParticipationPeriod = BeaconParticipationPeriod;
+	type LatestBeaconNonce = LatestBeaconNonce;
+	type WeightInfo = ();
 }
 
 parameter_types! {
 	pub const StartHeight: u32 = START_HEIGHT;
 	pub const RandomnessPeriod: u32 = RANDOMNESS_PERIOD;
+	pub const BeaconSignatureVersion: SignatureVersion = SignatureVersion::V1;
+	pub const RandomnessHistoryDepth: u32 = 256;
+	pub const MaxRandomnessRequestsPerRound: u32 = 64;
+	// shorter than the 60s for which proposers wait by default, to leave room for clock drift
+	pub const BeaconGracePeriod: Duration = Duration::from_millis(60_000 - 2 * SLOT_DURATION);
+	// switched on with `set_storage`, the nonces of the following rounds are chained to the seeds
+	pub storage ChainedRounds: bool = false;
 }
 
-use sp_randomness_beacon::RandomnessVerifier;
+use sp_randomness_beacon::{RandomnessVerifier, SignatureVersion};
 pub struct GetRandomnessVerifier;
 impl Get<Option<RandomnessVerifier>> for GetRandomnessVerifier {
 	fn get() -> Option<RandomnessVerifier> {
-		if pallet_dkg::MasterVerificationKey::exists() {
-			return Some(RandomnessVerifier::new(
-				pallet_dkg::MasterVerificationKey::get(),
-			));
-		}
-		None
+		DKG::epoch_master_key(DKG::current_epoch()).map(RandomnessVerifier::new)
+	}
+}
+
+pub struct RandomnessVerifierReady;
+impl Get<BlockNumber> for RandomnessVerifierReady {
+	fn get() -> BlockNumber {
+		DKG::master_key_ready()
 	}
 }
 
 impl pallet_randomness_beacon::Trait for Runtime {
 	type Event = Event;
 	type StartHeight = StartHeight;
-	type RandomnessVerifierReady = DKGReady;
+	type RandomnessVerifierReady = RandomnessVerifierReady;
 	type RandomnessVerifier = GetRandomnessVerifier;
 	type RandomnessPeriod = RandomnessPeriod;
+	type SignatureVersion = BeaconSignatureVersion;
+	type HistoryDepth = RandomnessHistoryDepth;
+	type MaxRequestsPerRound = MaxRandomnessRequestsPerRound;
+	type OnRandomnessSealed = ();
+	type AuthorVrfKey = AuraId;
+	type FindAuthor = pallet_aura::FindAccountFromAuthorIndex<Self, Aura>;
+	type ChainedRounds = ChainedRounds;
+	type UnixTime = Timestamp;
+	type GracePeriod = BeaconGracePeriod;
 }
 
 impl<LocalCall> frame_system::offchain::CreateSignedTransaction<LocalCall> for Runtime
@@ -384,8 +424,8 @@ construct_runtime!(
 		Balances: pallet_balances::{Module, Call, Storage, Config<T>, Event<T>},
 		TransactionPayment: pallet_transaction_payment::{Module, Storage},
 		Sudo: pallet_sudo::{Module, Call, Config<T>, Storage, Event<T>},
-		RandomnessBeacon: pallet_randomness_beacon::{Module, Call, Storage, Inherent, Event<T>},
-		DKG: pallet_dkg::{Module, Call, Config<T>, Storage, Event},
+		RandomnessBeacon: pallet_randomness_beacon::{Module, Call, Config<T>, Storage, Inherent, Event<T>},
+		DKG: pallet_dkg::{Module, Call, Config<T>, Storage, Event, ValidateUnsigned},
 	}
 );
 
@@ -516,10 +556,6 @@ impl_runtime_apis! {
 			DKG::public_keybox_parts()
 		}
 
-		fn storage_key_sk() -> Option<Vec<u8>> {
-			DKG::storage_key_sk()
-		}
-
 		fn verification_keys() -> Option<Vec<sp_dkg::VerifyKey>> {
 			DKG::verification_keys()
 		}
@@ -531,6 +567,26 @@ impl_runtime_apis! {
 		fn threshold() -> u64 {
 			DKG::threshold()
 		}
+
+		fn current_epoch() -> sp_dkg::EpochIndex {
+			DKG::current_epoch()
+		}
+
+		fn epoch_master_key(epoch: sp_dkg::EpochIndex) -> Option<sp_dkg::VerifyKey> {
+			DKG::epoch_master_key(epoch)
+		}
+
+		fn dkg_phase() -> sp_dkg::DkgPhase {
+			DKG::dkg_phase()
+		}
+
+		fn qualified_dealers() -> Vec<sp_dkg::AuthIndex> {
+			DKG::qualified_dealers()
+		}
+
+		fn active_threshold() -> u64 {
+			DKG::active_threshold()
+		}
 	}
 
 	impl sp_randomness_beacon::RandomnessBeaconApi<Block> for Runtime {
@@ -540,6 +596,32 @@ impl_runtime_apis! {
 		fn beacon_period() -> NumberFor<Block> {
 			RandomnessBeacon::beacon_period()
 		}
+		fn signature_version() -> SignatureVersion {
+			RandomnessBeacon::signature_version()
+		}
+		fn latest_randomness() -> Option<(NumberFor<Block>, sp_randomness_beacon::Randomness<<Block as BlockT>::Hash>)> {
+			RandomnessBeacon::latest_randomness()
+		}
+		fn seed_freshness() -> Option<sp_randomness_beacon::SeedFreshness<NumberFor<Block>>> {
+			RandomnessBeacon::seed_freshness()
+		}
+		fn round_randomness(
+			round: Option<sp_randomness_beacon::Round>,
+		) -> Option<(sp_randomness_beacon::Round, sp_randomness_beacon::Randomness<<Block as BlockT>::Hash>)> {
+			RandomnessBeacon::round_randomness(round)
+		}
+		fn history_index(round: sp_randomness_beacon::Round) -> u32 {
+			RandomnessBeacon::history_index(round)
+		}
+		fn author_vrf_key(pre_digests: Vec<(sp_runtime::ConsensusEngineId, Vec<u8>)>) -> Option<Vec<u8>> {
+			RandomnessBeacon::author_vrf_key(pre_digests)
+		}
+		fn chained_nonce(round: sp_randomness_beacon::Round) -> Option<<Block as BlockT>::Hash> {
+			RandomnessBeacon::chained_nonce(round)
+		}
+		fn grace_period() -> u64 {
+			RandomnessBeacon::grace_period()
+		}
 	}
 
 	impl fg_primitives::GrandpaApi<Block> for Runtime {
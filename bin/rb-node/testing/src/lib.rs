@@ -0,0 +1,415 @@
+//! In-process test networks of `rb-node`.
+//!
+//! A `TestNetwork` runs the full services of the authorities of a local chain in a single
+//! process, each with its own database, keystore and port, so that the DKG and the randomness
+//! beacon may be tested end to end with `cargo test`. Nodes may be started late and killed at
+//! any point, and the threshold and the length of the DKG as well as the schedule of the beacon
+//! are set in the genesis through `BeaconParams`.
+
+use std::{
+	net::TcpListener,
+	path::PathBuf,
+	sync::Arc,
+	thread,
+	time::{Duration, Instant},
+};
+
+use futures::FutureExt;
+use log::info;
+use rb_node::{
+	chain_spec::{authority_keys_from_seed, get_account_id_from_seed, testnet_genesis, ChainSpec},
+	service::{new_full_base, FullClient, NewFullBase},
+};
+use pallet_dkg::N_ROUNDS;
+use rb_node_runtime::{opaque::Block, BlockNumber, RandomnessPeriod, StartHeight, WASM_BINARY};
+use sc_client_api::{execution_extensions::ExecutionStrategies, ExecutionStrategy};
+use sc_network::{
+	config::{NetworkConfiguration, NodeKeyConfig, Secret},
+	NetworkService,
+};
+use sc_service::{
+	config::{BasePath, DatabaseConfig, KeystoreConfig, OffchainWorkerConfig, WasmExecutionMethod},
+	ChainType, Configuration, Role, TaskExecutor, TaskManager, TaskType,
+};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::sr25519;
+use sp_dkg::DKGApi;
+use sp_randomness_beacon::{RandomnessBeaconApi, RandomnessVerifier};
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use tempfile::TempDir;
+
+/// The seeds of the keys of the authorities, a network has at most that many nodes.
+pub const AUTHORITIES: [&str; 7] = ["Alice", "Bob", "Charlie", "Dave", "Eve", "Ferdie", "One"];
+
+/// The parameters of the chain of a test network, which are set in its genesis config. The
+/// ones left unset are the defaults of the runtime.
+#[derive(Clone, Debug, Default)]
+pub struct BeaconParams {
+	/// The number of shares needed to sign, `n / 3 + 1` by default.
+	pub threshold: Option<u64>,
+	/// The lengths of the rounds of the DKG, whose keys are ready at the end of the last one.
+	pub round_lengths: Option<[BlockNumber; N_ROUNDS]>,
+	/// The first seed is set at `start_height + period`, must be past the end of the DKG.
+	pub start_height: Option<BlockNumber>,
+	/// The number of blocks between two seeds, must be positive.
+	pub period: Option<BlockNumber>,
+}
+
+/// A local chain whose authorities are the given number of the first `AUTHORITIES`.
+pub fn chain_spec(authorities: usize, params: BeaconParams) -> Result<ChainSpec, String> {
+	let wasm_binary = WASM_BINARY.ok_or("Development wasm binary not available".to_string())?;
+	let seeds = AUTHORITIES[..authorities].to_vec();
+
+	Ok(ChainSpec::from_genesis(
+		"Beacon Test Network",
+		"beacon_test_network",
+		ChainType::Local,
+		move || {
+			let mut genesis = testnet_genesis(
+				wasm_binary,
+				seeds.iter().map(|seed| authority_keys_from_seed(seed)).collect(),
+				get_account_id_from_seed::<sr25519::Public>(seeds[0]),
+				seeds.iter().map(|seed| get_account_id_from_seed::<sr25519::Public>(seed)).collect(),
+				true,
+			);
+			if let Some(dkg) = genesis.pallet_dkg.as_mut() {
+				dkg.threshold = params.threshold.unwrap_or(dkg.threshold);
+				dkg.round_lengths = params.round_lengths;
+			}
+			if let Some(beacon) = genesis.pallet_randomness_beacon.as_mut() {
+				if params.start_height.is_some() || params.period.is_some() {
+					beacon.schedule = Some((
+						params.start_height.unwrap_or_else(StartHeight::get),
+						params.period.unwrap_or_else(RandomnessPeriod::get),
+					));
+				}
+			}
+			genesis
+		},
+		vec![],
+		None,
+		None,
+		None,
+		None,
+	))
+}
+
+/// A running node of a test network.
+pub struct RunningNode {
+	task_manager: TaskManager,
+	client: Arc<FullClient>,
+	network: Arc<NetworkService<Block, <Block as BlockT>::Hash>>,
+}
+
+impl RunningNode {
+	/// The client of the node.
+	pub fn client(&self) -> Arc<FullClient> {
+		self.client.clone()
+	}
+
+	/// The number of the best block of the node.
+	pub fn best_number(&self) -> BlockNumber {
+		self.client.info().best_number
+	}
+
+	/// The number of the last finalized block of the node.
+	pub fn finalized_number(&self) -> BlockNumber {
+		self.client.info().finalized_number
+	}
+
+	/// Whether the master key of the DKG is active in the best block of the node.
+	pub fn dkg_completed(&self) -> bool {
+		let best = BlockId::Hash(self.client.info().best_hash);
+		matches!(self.client.runtime_api().master_verification_key(&best), Ok(Some(_)))
+	}
+
+	/// Checks that every beacon block of the best chain of the node from `from` up to `to`
+	/// carries a fresh seed, which signs the right nonce and verifies under the master key.
+	/// Returns the number of seeds checked.
+	pub fn verify_beacon_blocks(&self, from: BlockNumber, to: BlockNumber) -> Result<u32, String> {
+		let runtime_api = self.client.runtime_api();
+		let genesis = BlockId::Number(0);
+		let start = runtime_api.start_beacon_height(&genesis).map_err(api_err)?;
+		let period = runtime_api.beacon_period(&genesis).map_err(api_err)?;
+
+		let mut checked = 0;
+		let mut number = start + period;
+		while number <= to {
+			if number >= from {
+				self.verify_seed(number, period)?;
+				checked += 1;
+			}
+			number += period;
+		}
+
+		Ok(checked)
+	}
+
+	// the seed of a beacon block is the signature of the hash of the previous beacon block
+	fn verify_seed(&self, number: BlockNumber, period: BlockNumber) -> Result<(), String> {
+		let runtime_api = self.client.runtime_api();
+		let at = BlockId::Number(number);
+
+		let (set_at, seed) = runtime_api.latest_randomness(&at)
+			.map_err(api_err)?
+			.ok_or_else(|| format!("No seed at block {}", number))?;
+		if set_at != number {
+			return Err(format!("Block {} carries no fresh seed", number));
+		}
+
+		let nonce = self.client.hash(number - period)
+			.map_err(api_err)?
+			.ok_or_else(|| format!("Block {} is unknown", number - period))?;
+		if seed.nonce() != nonce {
+			return Err(format!("The seed of block {} signs a wrong nonce", number));
+		}
+
+		let master_key = runtime_api.master_verification_key(&at)
+			.map_err(api_err)?
+			.ok_or_else(|| format!("No master key at block {}", number))?;
+		if !RandomnessVerifier::new(master_key).verify(&seed) {
+			return Err(format!("The seed of block {} is invalid", number));
+		}
+
+		Ok(())
+	}
+}
+
+struct TestNode {
+	seed: &'static str,
+	port: u16,
+	root: PathBuf,
+	running: Option<RunningNode>,
+}
+
+/// A network of authorities of a local chain running in the current process.
+pub struct TestNetwork {
+	runtime: tokio::runtime::Runtime,
+	chain_spec: ChainSpec,
+	nodes: Vec<TestNode>,
+	// keeps the databases and keystores of the nodes, also of the killed ones
+	_root: TempDir,
+}
+
+impl TestNetwork {
+	/// Configures a network of the given number of authorities, none of them is started yet.
+	/// Each node listens on a port which was free when the network was configured.
+	pub fn new(authorities: usize, params: BeaconParams) -> Self {
+		assert!(
+			authorities <= AUTHORITIES.len(),
+			"A test network has at most {} authorities",
+			AUTHORITIES.len(),
+		);
+		sp_tracing::try_init_simple();
+
+		let runtime = tokio::runtime::Builder::new()
+			.threaded_scheduler()
+			.enable_all()
+			.build()
+			.expect("Error creating tokio runtime");
+		let root = tempfile::Builder::new()
+			.prefix("rb-node-testing")
+			.tempdir()
+			.expect("Error creating test dir");
+		let nodes = AUTHORITIES[..authorities].iter().map(|seed| TestNode {
+			seed,
+			port: free_port(),
+			root: root.path().join(seed),
+			running: None,
+		}).collect();
+
+		TestNetwork {
+			runtime,
+			chain_spec: chain_spec(authorities, params).expect("Error creating chain spec"),
+			nodes,
+			_root: root,
+		}
+	}
+
+	/// Starts the node and connects it to the running nodes. A killed node is restarted with
+	/// its database and keystore.
+	pub fn start(&mut self, ix: usize) {
+		if self.nodes[ix].running.is_some() {
+			return;
+		}
+
+		let config = self.node_config(ix);
+		let NewFullBase { task_manager, client, network } =
+			new_full_base(config).expect("Error creating test node service");
+		info!("Started node {} ({})", ix, self.nodes[ix].seed);
+
+		let address = self.address(ix, &network);
+		for (other_ix, other) in self.running() {
+			network.add_reserved_peer(self.address(other_ix, &other.network))
+				.expect("Addresses of test nodes are valid");
+			other.network.add_reserved_peer(address.clone())
+				.expect("Addresses of test nodes are valid");
+		}
+
+		self.nodes[ix].running = Some(RunningNode { task_manager, client, network });
+	}
+
+	/// Starts all the nodes which are not running.
+	pub fn start_all(&mut self) {
+		for ix in 0..self.nodes.len() {
+			self.start(ix);
+		}
+	}
+
+	/// Starts the node once all the running nodes imported the given block, e.g. to let it miss
+	/// the DKG.
+	pub fn start_at(&mut self, ix: usize, number: BlockNumber, timeout: Duration) {
+		self.wait_for_block(number, timeout);
+		self.start(ix);
+	}
+
+	/// Stops the node, which keeps its database and keystore.
+	pub fn kill(&mut self, ix: usize) {
+		if let Some(RunningNode { task_manager, .. }) = self.nodes[ix].running.take() {
+			self.runtime.block_on(task_manager.clean_shutdown());
+			info!("Killed node {} ({})", ix, self.nodes[ix].seed);
+		}
+	}
+
+	/// The node, if it is running.
+	pub fn node(&self, ix: usize) -> Option<&RunningNode> {
+		self.nodes[ix].running.as_ref()
+	}
+
+	/// The running nodes together with their indices.
+	pub fn running(&self) -> impl Iterator<Item = (usize, &RunningNode)> {
+		self.nodes.iter()
+			.enumerate()
+			.filter_map(|(ix, node)| node.running.as_ref().map(|running| (ix, running)))
+	}
+
+	/// Waits until the condition holds for all the running nodes, panics after the timeout.
+	pub fn wait_until<F>(&self, what: &str, timeout: Duration, condition: F) where
+		F: Fn(&RunningNode) -> bool,
+	{
+		let deadline = Instant::now() + timeout;
+		while !self.running().all(|(_, node)| condition(node)) {
+			if Instant::now() > deadline {
+				panic!("Waited for too long until {}", what);
+			}
+			thread::sleep(Duration::from_millis(100));
+		}
+	}
+
+	/// Waits until all the running nodes imported the given block.
+	pub fn wait_for_block(&self, number: BlockNumber, timeout: Duration) {
+		self.wait_until(
+			&format!("block {} is imported", number),
+			timeout,
+			|node| node.best_number() >= number,
+		);
+	}
+
+	/// Waits until the master key of the DKG is active at all the running nodes.
+	pub fn wait_for_dkg(&self, timeout: Duration) {
+		self.wait_until("the DKG completes", timeout, RunningNode::dkg_completed);
+	}
+
+	fn address(&self, ix: usize, network: &NetworkService<Block, <Block as BlockT>::Hash>) -> String {
+		format!("/ip4/127.0.0.1/tcp/{}/p2p/{}", self.nodes[ix].port, network.local_peer_id())
+	}
+
+	fn node_config(&self, ix: usize) -> Configuration {
+		let node = &self.nodes[ix];
+		let runtime_handle = self.runtime.handle().clone();
+		let task_executor: TaskExecutor = (move |fut, task_type| match task_type {
+			TaskType::Async => runtime_handle.spawn(fut).map(drop),
+			TaskType::Blocking =>
+				runtime_handle.spawn_blocking(move || futures::executor::block_on(fut)).map(drop),
+		}).into();
+
+		// the node key is kept on disk, so that a restarted node keeps its peer id
+		let mut network = NetworkConfiguration::new(
+			node.seed,
+			"rb-node-testing",
+			NodeKeyConfig::Ed25519(Secret::File(node.root.join("secret_ed25519"))),
+			None,
+		);
+		network.listen_addresses.push(
+			format!("/ip4/127.0.0.1/tcp/{}", node.port).parse().expect("The address is valid"),
+		);
+		network.allow_non_globals_in_dht = true;
+
+		// the DKG is run by the offchain workers, which need the same host functions as blocks
+		let execution_strategies = ExecutionStrategies {
+			syncing: ExecutionStrategy::NativeElseWasm,
+			importing: ExecutionStrategy::NativeElseWasm,
+			block_construction: ExecutionStrategy::NativeElseWasm,
+			offchain_worker: ExecutionStrategy::NativeElseWasm,
+			other: ExecutionStrategy::NativeElseWasm,
+		};
+
+		Configuration {
+			impl_name: String::from("rb-node-testing"),
+			impl_version: String::from("0.1"),
+			role: Role::Authority { sentry_nodes: Vec::new() },
+			task_executor,
+			transaction_pool: Default::default(),
+			network,
+			keystore: KeystoreConfig::Path {
+				path: node.root.join("key"),
+				password: None,
+			},
+			database: DatabaseConfig::RocksDb {
+				path: node.root.join("db"),
+				cache_size: 128,
+			},
+			state_cache_size: 16777216,
+			state_cache_child_ratio: None,
+			pruning: Default::default(),
+			chain_spec: Box::new(self.chain_spec.clone()),
+			wasm_method: WasmExecutionMethod::Interpreted,
+			execution_strategies,
+			rpc_http: None,
+			rpc_ws: None,
+			rpc_ipc: None,
+			rpc_ws_max_connections: None,
+			rpc_cors: None,
+			rpc_methods: Default::default(),
+			prometheus_config: None,
+			telemetry_endpoints: None,
+			telemetry_external_transport: None,
+			default_heap_pages: None,
+			offchain_worker: OffchainWorkerConfig {
+				enabled: true,
+				indexing_enabled: false,
+			},
+			force_authoring: false,
+			disable_grandpa: false,
+			dev_key_seed: Some(format!("//{}", node.seed)),
+			tracing_targets: None,
+			tracing_receiver: Default::default(),
+			max_runtime_instances: 8,
+			announce_block: true,
+			base_path: Some(BasePath::new(node.root.clone())),
+			informant_output_format: Default::default(),
+		}
+	}
+}
+
+impl Drop for TestNetwork {
+	fn drop(&mut self) {
+		for ix in 0..self.nodes.len() {
+			self.kill(ix);
+		}
+	}
+}
+
+// the port is released right away, but ephemeral ports are handed out in turn, so it is unlikely
+// to be taken before the node listens on it
+fn free_port() -> u16 {
+	TcpListener::bind("127.0.0.1:0")
+		.and_then(|listener| listener.local_addr())
+		.map(|address| address.port())
+		.expect("Error finding a free port")
+}
+
+fn api_err<E: std::fmt::Debug>(e: E) -> String {
+	format!("{:?}", e)
+}
@@ -0,0 +1,102 @@
+//! End to end tests of the DKG and the randomness beacon on in-process networks of `rb-node`.
+//!
+//! Each network listens on its own free ports, so that the tests may run in parallel.
+
+use std::time::Duration;
+
+use rb_node_testing::{BeaconParams, TestNetwork};
+
+const NODES: usize = 4;
+const THRESHOLD: u64 = 2;
+const DKG_TIMEOUT: Duration = Duration::from_secs(240);
+const BLOCK_TIMEOUT: Duration = Duration::from_secs(120);
+
+fn params(threshold: u64) -> BeaconParams {
+	BeaconParams {
+		threshold: Some(threshold),
+		..Default::default()
+	}
+}
+
+fn assert_beacon_blocks(network: &TestNetwork, from: u32, to: u32) {
+	for (ix, node) in network.running() {
+		let checked = node.verify_beacon_blocks(from, to)
+			.unwrap_or_else(|e| panic!("Node {}: {}", ix, e));
+		assert!(checked > 0, "Node {} checked no beacon blocks in {}..={}", ix, from, to);
+	}
+}
+
+#[test]
+fn dkg_completes_and_beacon_blocks_carry_verified_seeds() {
+	let mut network = TestNetwork::new(NODES, params(THRESHOLD));
+	network.start_all();
+
+	network.wait_for_dkg(DKG_TIMEOUT);
+	network.wait_for_block(21, BLOCK_TIMEOUT);
+
+	assert_beacon_blocks(&network, 0, 21);
+}
+
+#[test]
+fn beacon_follows_the_schedule_of_the_genesis() {
+	// the keys are ready at block 8 and there is a seed in every block once the beacon starts
+	let mut network = TestNetwork::new(NODES, BeaconParams {
+		round_lengths: Some([2, 2, 2, 2]),
+		start_height: Some(9),
+		period: Some(1),
+		..params(THRESHOLD)
+	});
+	network.start_all();
+
+	network.wait_for_dkg(DKG_TIMEOUT);
+	network.wait_for_block(21, BLOCK_TIMEOUT);
+
+	for (ix, node) in network.running() {
+		let checked = node.verify_beacon_blocks(0, 21)
+			.unwrap_or_else(|e| panic!("Node {}: {}", ix, e));
+		assert_eq!(checked, 12, "Node {} checked a wrong number of beacon blocks", ix);
+	}
+}
+
+#[test]
+fn beacon_survives_threshold_minus_one_faults() {
+	// GRANDPA finalizes with 5 out of 7 authorities, and the 5 of them hold enough shares
+	const NODES: usize = 7;
+	const THRESHOLD: u64 = 3;
+
+	let mut network = TestNetwork::new(NODES, params(THRESHOLD));
+	network.start_all();
+	network.wait_for_dkg(DKG_TIMEOUT);
+
+	let faults = (THRESHOLD - 1) as usize;
+	for ix in 0..faults {
+		network.kill(NODES - 1 - ix);
+	}
+	assert_eq!(network.running().count(), NODES - faults);
+	let killed_at = network.node(0).expect("Node 0 is running").best_number();
+
+	// the remaining authorities keep producing and finalizing blocks with fresh seeds
+	network.wait_for_block(killed_at + 10, BLOCK_TIMEOUT);
+	network.wait_until("blocks after the faults are finalized", BLOCK_TIMEOUT, |node| {
+		node.finalized_number() > killed_at
+	});
+
+	assert_beacon_blocks(&network, killed_at + 1, killed_at + 10);
+}
+
+#[test]
+fn delayed_node_syncs_the_beacon_chain() {
+	let mut network = TestNetwork::new(NODES, params(THRESHOLD));
+	// the nodes which start up front are enough to run the DKG and produce seeds
+	for ix in 0..NODES - 1 {
+		network.start(ix);
+	}
+	network.wait_for_dkg(DKG_TIMEOUT);
+
+	network.start_at(NODES - 1, 21, BLOCK_TIMEOUT);
+	network.wait_for_block(25, BLOCK_TIMEOUT);
+
+	let delayed = network.node(NODES - 1).expect("The delayed node is running");
+	assert!(delayed.dkg_completed());
+	assert!(delayed.verify_beacon_blocks(0, 25).expect("Seeds are valid") > 0);
+}
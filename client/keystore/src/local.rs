@@ -25,8 +25,15 @@ use std::{
 	sync::Arc,
 };
 use async_trait::async_trait;
+use chacha20poly1305::{
+	aead::{generic_array::GenericArray, Aead, NewAead},
+	ChaCha20Poly1305,
+};
+use hmac::Hmac;
 use parking_lot::RwLock;
+use sha2::Sha256;
 use sp_core::{
+	bytes::{from_hex, to_hex},
 	crypto::{CryptoTypePublicPair, KeyTypeId, Pair as PairT, ExposeSecret, SecretString, Public},
 	sr25519::{Public as Sr25519Public, Pair as Sr25519Pair},
 	Encode,
@@ -105,6 +112,23 @@ impl CryptoStore for LocalKeystore {
 		SyncCryptoStore::insert_unknown(self, id, suri, public)
 	}
 
+	async fn insert_secret(
+		&self,
+		id: KeyTypeId,
+		public: &[u8],
+		secret: &[u8],
+	) -> std::result::Result<(), TraitError> {
+		SyncCryptoStore::insert_secret(self, id, public, secret)
+	}
+
+	async fn secret(
+		&self,
+		id: KeyTypeId,
+		public: &[u8],
+	) -> std::result::Result<Option<Vec<u8>>, TraitError> {
+		SyncCryptoStore::secret(self, id, public)
+	}
+
 	async fn has_keys(&self, public_keys: &[(Vec<u8>, KeyTypeId)]) -> bool {
 		SyncCryptoStore::has_keys(self, public_keys)
 	}
@@ -271,6 +295,18 @@ impl SyncCryptoStore for LocalKeystore {
 		self.0.write().insert_unknown(key_type, suri, public).map_err(|_| ())
 	}
 
+	fn insert_secret(&self, id: KeyTypeId, public: &[u8], secret: &[u8])
+		-> std::result::Result<(), TraitError>
+	{
+		self.0.write().insert_secret(id, public, secret).map_err(Into::into)
+	}
+
+	fn secret(&self, id: KeyTypeId, public: &[u8])
+		-> std::result::Result<Option<Vec<u8>>, TraitError>
+	{
+		self.0.read().secret_by_type(public, id).map_err(Into::into)
+	}
+
 	fn has_keys(&self, public_keys: &[(Vec<u8>, KeyTypeId)]) -> bool {
 		public_keys.iter().all(|(p, t)| self.0.read().key_phrase_by_type(&p, *t).is_ok())
 	}
@@ -314,6 +350,9 @@ struct KeystoreInner {
 	path: Option<PathBuf>,
 	/// Map over `(KeyTypeId, Raw public key)` -> `Key phrase/seed`
 	additional: HashMap<(KeyTypeId, Vec<u8>), String>,
+	/// Map over `(KeyTypeId, Raw public key)` -> `Raw secret` of the secrets of an in-memory
+	/// store.
+	secrets: HashMap<(KeyTypeId, Vec<u8>), Vec<u8>>,
 	password: Option<SecretString>,
 }
 
@@ -325,7 +364,12 @@ impl KeystoreInner {
 		let path = path.into();
 		fs::create_dir_all(&path)?;
 
-		let instance = Self { path: Some(path), additional: HashMap::new(), password };
+		let instance = Self {
+			path: Some(path),
+			additional: HashMap::new(),
+			secrets: HashMap::new(),
+			password,
+		};
 		Ok(instance)
 	}
 
@@ -341,6 +385,7 @@ impl KeystoreInner {
 		Self {
 			path: None,
 			additional: HashMap::new(),
+			secrets: HashMap::new(),
 			password: None
 		}
 	}
@@ -375,6 +420,62 @@ impl KeystoreInner {
 		Ok(())
 	}
 
+	/// Insert a raw secret under the given public key.
+	///
+	/// Places it into the file system store like the key phrases, encrypted with a key derived
+	/// from the password of the store if it has one. A store without a path keeps it in memory.
+	pub fn insert_secret(&mut self, key_type: KeyTypeId, public: &[u8], secret: &[u8]) -> Result<()> {
+		let path = match self.key_file_path(public, key_type) {
+			Some(path) => path,
+			None => {
+				self.secrets.insert((key_type, public.to_vec()), secret.to_vec());
+				return Ok(())
+			},
+		};
+		let mut stored = Vec::with_capacity(1 + secret.len());
+		match self.password() {
+			Some(password) => {
+				stored.push(SECRET_ENCRYPTED);
+				stored.extend(encrypt_secret(password, secret)?);
+			},
+			None => {
+				stored.push(SECRET_PLAIN);
+				stored.extend_from_slice(secret);
+			},
+		}
+		let mut file = File::create(path)?;
+		serde_json::to_writer(&file, &to_hex(&stored, false))?;
+		file.flush()?;
+		Ok(())
+	}
+
+	/// Get the raw secret for a given public key and key type, `None` if there is no key.
+	fn secret_by_type(&self, public: &[u8], key_type: KeyTypeId) -> Result<Option<Vec<u8>>> {
+		if let Some(secret) = self.secrets.get(&(key_type, public.to_vec())) {
+			return Ok(Some(secret.clone()))
+		}
+
+		let path = match self.key_file_path(public, key_type) {
+			Some(path) => path,
+			None => return Ok(None),
+		};
+		let file = match File::open(path) {
+			Ok(file) => file,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+			Err(e) => return Err(e.into()),
+		};
+		let encoded: String = serde_json::from_reader(&file)?;
+		let stored = from_hex(&encoded).map_err(|_| Error::InvalidSeed)?;
+		match stored.split_first() {
+			Some((&SECRET_PLAIN, secret)) => Ok(Some(secret.to_vec())),
+			Some((&SECRET_ENCRYPTED, encrypted)) => {
+				let password = self.password().ok_or(Error::InvalidPassword)?;
+				decrypt_secret(password, encrypted).map(Some)
+			},
+			_ => Err(Error::InvalidSeed),
+		}
+	}
+
 	/// Generate a new key.
 	///
 	/// Places it into the file system store.
@@ -472,6 +573,49 @@ impl KeystoreInner {
 	}
 }
 
+// the first byte of a stored secret tells whether the rest is the secret or its encryption
+const SECRET_PLAIN: u8 = 0;
+const SECRET_ENCRYPTED: u8 = 1;
+
+// the number of PBKDF2 iterations deriving the key which encrypts the secrets
+const SECRET_KDF_ROUNDS: usize = 10_000;
+const SECRET_SALT_LEN: usize = 16;
+const SECRET_NONCE_LEN: usize = 12;
+
+// the cipher of the secrets derived from the password of the store and a random salt
+fn secret_cipher(password: &str, salt: &[u8]) -> ChaCha20Poly1305 {
+	let mut key = [0u8; 32];
+	pbkdf2::pbkdf2::<Hmac<Sha256>>(password.as_bytes(), salt, SECRET_KDF_ROUNDS, &mut key);
+	ChaCha20Poly1305::new(GenericArray::from_slice(&key[..]))
+}
+
+// encrypts the secret as `salt || nonce || ciphertext`
+fn encrypt_secret(password: &str, secret: &[u8]) -> Result<Vec<u8>> {
+	let salt: [u8; SECRET_SALT_LEN] = rand::random();
+	let nonce: [u8; SECRET_NONCE_LEN] = rand::random();
+	let ciphertext = secret_cipher(password, &salt)
+		.encrypt(GenericArray::from_slice(&nonce[..]), secret)
+		.map_err(|_| Error::InvalidSeed)?;
+
+	let mut encrypted = Vec::with_capacity(SECRET_SALT_LEN + SECRET_NONCE_LEN + ciphertext.len());
+	encrypted.extend_from_slice(&salt);
+	encrypted.extend_from_slice(&nonce);
+	encrypted.extend(ciphertext);
+	Ok(encrypted)
+}
+
+// decrypts a secret encrypted by `encrypt_secret`, fails with `InvalidPassword` if the
+// authentication fails
+fn decrypt_secret(password: &str, encrypted: &[u8]) -> Result<Vec<u8>> {
+	if encrypted.len() < SECRET_SALT_LEN + SECRET_NONCE_LEN {
+		return Err(Error::InvalidSeed)
+	}
+	let (salt, rest) = encrypted.split_at(SECRET_SALT_LEN);
+	let (nonce, ciphertext) = rest.split_at(SECRET_NONCE_LEN);
+	secret_cipher(password, salt)
+		.decrypt(GenericArray::from_slice(nonce), ciphertext)
+		.map_err(|_| Error::InvalidPassword)
+}
 
 #[cfg(test)]
 mod tests {
@@ -632,6 +776,70 @@ mod tests {
 		assert_eq!(key_pair.public(), store_key_pair.public());
 	}
 
+	#[test]
+	fn store_secret_and_extract_it() {
+		let temp_dir = TempDir::new().unwrap();
+		let store = LocalKeystore::open(temp_dir.path(), None).unwrap();
+		let public = [1u8; 48];
+
+		assert_eq!(SyncCryptoStore::secret(&store, SR25519, &public).unwrap(), None);
+		SyncCryptoStore::insert_secret(&store, SR25519, &public, &[7u8; 32])
+			.expect("Inserts secret");
+		assert_eq!(SyncCryptoStore::secret(&store, SR25519, &public).unwrap(), Some(vec![7u8; 32]));
+		// without a password the secret is written to the file system store unencrypted
+		assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+
+		let store = LocalKeystore::in_memory();
+		SyncCryptoStore::insert_secret(&store, SR25519, &public, &[7u8; 32])
+			.expect("Inserts secret");
+		assert_eq!(SyncCryptoStore::secret(&store, SR25519, &public).unwrap(), Some(vec![7u8; 32]));
+	}
+
+	#[test]
+	fn store_secret_encrypted_with_password() {
+		let temp_dir = TempDir::new().unwrap();
+		let store = LocalKeystore::open(temp_dir.path(), password_of("password")).unwrap();
+		let public = [1u8; 48];
+		let secret = [7u8; 32];
+
+		SyncCryptoStore::insert_secret(&store, SR25519, &public, &secret)
+			.expect("Inserts secret");
+
+		// the file holds neither the secret nor its hex encoding
+		let path = store.0.read().key_file_path(&public, SR25519).unwrap();
+		let file = fs::read(&path).unwrap();
+		assert!(!file.windows(secret.len()).any(|w| w == &secret[..]));
+		assert!(!String::from_utf8(file).unwrap().contains(&hex::encode(&secret)));
+
+		let store = LocalKeystore::open(temp_dir.path(), password_of("password")).unwrap();
+		assert_eq!(SyncCryptoStore::secret(&store, SR25519, &public).unwrap(), Some(secret.to_vec()));
+
+		let store = LocalKeystore::open(temp_dir.path(), password_of("other")).unwrap();
+		assert!(SyncCryptoStore::secret(&store, SR25519, &public).is_err());
+
+		let store = LocalKeystore::open(temp_dir.path(), None).unwrap();
+		assert!(SyncCryptoStore::secret(&store, SR25519, &public).is_err());
+	}
+
+	#[test]
+	fn store_secret_without_password() {
+		let temp_dir = TempDir::new().unwrap();
+		let store = LocalKeystore::open(temp_dir.path(), None).unwrap();
+		let public = [1u8; 48];
+		let secret = [7u8; 32];
+
+		SyncCryptoStore::insert_secret(&store, SR25519, &public, &secret)
+			.expect("Inserts secret");
+		drop(store);
+
+		let store = LocalKeystore::open(temp_dir.path(), None).unwrap();
+		assert_eq!(SyncCryptoStore::secret(&store, SR25519, &public).unwrap(), Some(secret.to_vec()));
+	}
+
+	fn password_of(password: &str) -> Option<SecretString> {
+		Some(FromStr::from_str(password).unwrap())
+	}
+
 	#[test]
 	fn store_ignores_files_with_invalid_name() {
 		let temp_dir = TempDir::new().unwrap();
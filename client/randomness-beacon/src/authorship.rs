@@ -4,18 +4,31 @@
 //! every k-th block, but that does not change much). This wrapper ensures that
 //! we will always author blocks with randomness included, as during propose() it awaits
 //! until suitable randomness arrives in a channel. It might happen that block proposing
-//! times out for this reason, but this is necessary, as forcing block creation in the
-//! absence of randomness would cause creating an incorrect block, so there is no point
-//! in that.
+//! times out for this reason, then the proposal is aborted and the block is tried again
+//! in a later slot. If the randomness is still missing after a grace period counted from the
+//! first attempt, e.g. because too few committee members are online, the block is authored
+//! without randomness so that the chain does not stall. The runtime accepts such a block only
+//! if the grace period of the runtime has passed since the parent block, which should thus be
+//! shorter than the one of the proposer.
+//!
+//! If an `AuthorVrf` is set, the VRF output of the author is added to the digests of every
+//! proposed block, see `sp_randomness_beacon::vrf`. The proposal fails if the runtime expects
+//! the output but it cannot be evaluated, since the runtime would reject the block.
+//!
+//! The nonce of a block is the hash of its ancestor `beacon_period` blocks back, unless a
+//! `ChainedNonce` is set and the runtime chains the rounds to the seeds.
 
 use log::info;
 use parking_lot::Mutex;
 use sc_client_api::backend;
 use sp_api::{ApiExt, ProvideRuntimeApi};
 use sp_blockchain::HeaderBackend;
-use sp_core::traits::SpawnNamed;
+use sp_core::{crypto::KeyTypeId, sr25519, traits::SpawnNamed};
+use sp_keystore::{SyncCryptoStore, SyncCryptoStorePtr};
 use sp_transaction_pool::TransactionPool;
-use std::{collections::HashMap, pin::Pin, sync::mpsc::Receiver, sync::Arc, time};
+use std::{
+	collections::HashMap, convert::TryFrom, pin::Pin, sync::mpsc::Receiver, sync::Arc, time,
+};
 
 use futures::{
 	future,
@@ -25,14 +38,154 @@ use sc_block_builder::{BlockBuilderApi, BlockBuilderProvider};
 use sp_consensus::{Proposal, RecordProof};
 use sp_inherents::InherentData;
 use sp_runtime::generic::BlockId;
-use sp_runtime::traits::{Block as BlockT, DigestFor, Header as HeaderT, NumberFor};
+use sp_runtime::traits::{
+	Block as BlockT, DigestFor, Header as HeaderT, NumberFor, UniqueSaturatedInto,
+};
 
 use prometheus_endpoint::Registry as PrometheusRegistry;
 
-use sp_randomness_beacon::{inherents::INHERENT_IDENTIFIER, Randomness};
+use sp_randomness_beacon::{
+	inherents::INHERENT_IDENTIFIER,
+	vrf::{self, PreDigest, VRFOutput, VRFProof},
+	Randomness, RandomnessBeaconApi, Round,
+};
 
 use super::Nonce;
 
+/// The default time for which proposers wait for the randomness of a block before authoring
+/// it without one.
+pub const DEFAULT_GRACE_PERIOD: time::Duration = time::Duration::from_secs(60);
+
+/// Gives the nonces of the rounds if the runtime chains them to the seeds.
+pub trait ChainedNonce<Block: BlockT>: Send + Sync {
+	/// The nonce of the round in the state of the given block, `None` if the nonces are the
+	/// hashes of blocks.
+	fn chained_nonce(&self, at: &BlockId<Block>, round: Round) -> Option<Nonce<Block>>;
+}
+
+impl<Block, C> ChainedNonce<Block> for C
+where
+	Block: BlockT,
+	C: ProvideRuntimeApi<Block> + Send + Sync,
+	C::Api: RandomnessBeaconApi<Block>,
+{
+	fn chained_nonce(&self, at: &BlockId<Block>, round: Round) -> Option<Nonce<Block>> {
+		let runtime_api = self.runtime_api();
+		match runtime_api.has_api_with::<dyn RandomnessBeaconApi<Block, Error = ()>, _>(at, |v| v >= 8) {
+			Ok(true) => runtime_api.chained_nonce(at, round).ok()?,
+			_ => None,
+		}
+	}
+}
+
+/// Gives the grace period of the runtime, after which it accepts blocks at which a seed is due
+/// without one.
+pub trait RuntimeGracePeriod<Block: BlockT>: Send + Sync {
+	/// The grace period in the state of the given block, `None` if the runtime does not expose
+	/// it.
+	fn grace_period(&self, at: &BlockId<Block>) -> Option<time::Duration>;
+}
+
+impl<Block, C> RuntimeGracePeriod<Block> for C
+where
+	Block: BlockT,
+	C: ProvideRuntimeApi<Block> + Send + Sync,
+	C::Api: RandomnessBeaconApi<Block>,
+{
+	fn grace_period(&self, at: &BlockId<Block>) -> Option<time::Duration> {
+		let runtime_api = self.runtime_api();
+		match runtime_api.has_api_with::<dyn RandomnessBeaconApi<Block, Error = ()>, _>(at, |v| v >= 9) {
+			Ok(true) => runtime_api
+				.grace_period(at)
+				.ok()
+				.map(time::Duration::from_millis),
+			_ => None,
+		}
+	}
+}
+
+/// Evaluates the VRF of the author of a block.
+pub trait AuthorVrf<Block: BlockT>: Send + Sync {
+	/// The pre-digest of the block built on the parent with the given digests, `None` if the
+	/// runtime does not expect a VRF output of its author. An error if the runtime expects one
+	/// but the node cannot evaluate it, e.g. because the key of the author is not in the
+	/// keystore, as the runtime rejects the block without it.
+	fn pre_digest(
+		&self,
+		parent_header: &Block::Header,
+		inherent_digests: &DigestFor<Block>,
+	) -> Result<Option<PreDigest>, String>;
+}
+
+/// Evaluates the VRF with the key of the author given by the runtime, which is looked up in the
+/// keystore under `key_type`, e.g. the AURA key of the node.
+pub struct KeystoreAuthorVrf<C> {
+	client: Arc<C>,
+	keystore: SyncCryptoStorePtr,
+	key_type: KeyTypeId,
+}
+
+impl<C> KeystoreAuthorVrf<C> {
+	pub fn new(client: Arc<C>, keystore: SyncCryptoStorePtr, key_type: KeyTypeId) -> Self {
+		KeystoreAuthorVrf {
+			client,
+			keystore,
+			key_type,
+		}
+	}
+}
+
+impl<Block, C> AuthorVrf<Block> for KeystoreAuthorVrf<C>
+where
+	Block: BlockT,
+	C: ProvideRuntimeApi<Block> + Send + Sync,
+	C::Api: RandomnessBeaconApi<Block>,
+{
+	fn pre_digest(
+		&self,
+		parent_header: &Block::Header,
+		inherent_digests: &DigestFor<Block>,
+	) -> Result<Option<PreDigest>, String> {
+		let at = BlockId::Hash(parent_header.hash());
+		let runtime_api = self.client.runtime_api();
+		let has_api = runtime_api
+			.has_api_with::<dyn RandomnessBeaconApi<Block, Error = ()>, _>(&at, |v| v >= 7)
+			.map_err(|e| format!("{:?}", e))?;
+		if !has_api {
+			return Ok(None);
+		}
+
+		let pre_digests = inherent_digests
+			.logs()
+			.iter()
+			.filter_map(|log| log.as_pre_runtime())
+			.map(|(id, data)| (id, data.to_vec()))
+			.collect();
+		// the runtime verifies the output only if it knows the author
+		let author = runtime_api.author_vrf_key(&at, pre_digests).map_err(|e| format!("{:?}", e))?;
+		let author = match author {
+			Some(author) => author,
+			None => return Ok(None),
+		};
+		let author = sr25519::Public::try_from(&author[..])
+			.map_err(|_| "The key of the author is not an sr25519 key".to_string())?;
+		let seed = runtime_api
+			.latest_randomness(&at)
+			.map_err(|e| format!("{:?}", e))?
+			.map(|(_, seed)| seed);
+		let number =
+			UniqueSaturatedInto::<u64>::unique_saturated_into(*parent_header.number() + 1.into());
+
+		let transcript = vrf::make_transcript_data(&vrf::seed_input(seed.as_ref()), number);
+		SyncCryptoStore::sr25519_vrf_sign(&*self.keystore, self.key_type, &author, transcript)
+			.map(|signature| Some(PreDigest {
+				vrf_output: VRFOutput(signature.output),
+				vrf_proof: VRFProof(signature.proof),
+			}))
+			.map_err(|e| format!("Cannot evaluate the VRF of the author: {:?}", e))
+	}
+}
+
 /// Proposer factory.
 pub struct ProposerFactory<A, B, Block: BlockT, C> {
 	/// Inner proposer.
@@ -45,6 +198,14 @@ pub struct ProposerFactory<A, B, Block: BlockT, C> {
 	randomness_rx: Arc<Mutex<Receiver<Randomness<Nonce<Block>>>>>,
 	/// The set of available random bytes.
 	available_randomness: Arc<Mutex<HashMap<Nonce<Block>, Randomness<Nonce<Block>>>>>,
+	/// The time after which blocks are authored without randomness.
+	grace_period: time::Duration,
+	/// The nonce of the block being authored and the time of the first attempt.
+	first_attempt: Arc<Mutex<Option<(Nonce<Block>, time::Instant)>>>,
+	/// The VRF of the author put into the digests of the proposed blocks.
+	author_vrf: Option<Arc<dyn AuthorVrf<Block>>>,
+	/// The nonces of the rounds in the chained mode.
+	chained_nonce: Option<Arc<dyn ChainedNonce<Block>>>,
 	client: Arc<C>,
 }
 
@@ -69,9 +230,47 @@ impl<A, B, Block: BlockT, C> ProposerFactory<A, B, Block, C> {
 			beacon_period,
 			randomness_rx,
 			available_randomness: Arc::new(Mutex::new(HashMap::new())),
+			grace_period: DEFAULT_GRACE_PERIOD,
+			first_attempt: Arc::new(Mutex::new(None)),
+			author_vrf: None,
+			chained_nonce: None,
 			client: client.clone(),
 		}
 	}
+
+	/// Sets the time for which the randomness of a block is awaited, counted from the first
+	/// attempt to author the block, before the block is authored without it.
+	pub fn set_grace_period(&mut self, grace_period: time::Duration) {
+		self.grace_period = grace_period;
+	}
+
+	/// Sets the VRF of the author whose output is added to the digests of the proposed blocks.
+	pub fn set_author_vrf(&mut self, author_vrf: Arc<dyn AuthorVrf<Block>>) {
+		self.author_vrf = Some(author_vrf);
+	}
+
+	/// Sets the source of the nonces of the rounds, used instead of the hashes of the blocks
+	/// if the runtime chains the rounds to the seeds.
+	pub fn set_chained_nonce(&mut self, chained_nonce: Arc<dyn ChainedNonce<Block>>) {
+		self.chained_nonce = Some(chained_nonce);
+	}
+
+	/// Checks that the grace period is longer than the one of the runtime in the state of the
+	/// given block, as otherwise the blocks authored without randomness are rejected by the
+	/// runtime and the chain stalls.
+	pub fn check_grace_period(
+		&self,
+		runtime: &dyn RuntimeGracePeriod<Block>,
+		at: &BlockId<Block>,
+	) -> Result<(), String> {
+		match runtime.grace_period(at) {
+			Some(runtime_grace_period) if self.grace_period <= runtime_grace_period => Err(format!(
+				"The grace period of the proposer {:?} must be longer than the one of the runtime {:?}.",
+				self.grace_period, runtime_grace_period,
+			)),
+			_ => Ok(()),
+		}
+	}
 }
 
 impl<A, B, Block, C> sp_consensus::Environment<Block> for ProposerFactory<A, B, Block, C>
@@ -97,16 +296,41 @@ where
 		let mut proposer_nonce = None;
 		if now > self.start_beacon_height {
 			if (now - self.start_beacon_height) % self.beacon_period == 0.into() {
-				let target_height = now - self.beacon_period;
-				let mut current_hash = parent_header.hash();
-				let mut current_height = now - 1.into();
-				while current_height > target_height {
-					current_hash = self.client.header(BlockId::Hash(current_hash)).unwrap().unwrap().parent_hash().clone();
-					current_height = current_height - 1.into();
+				let round = UniqueSaturatedInto::<Round>::unique_saturated_into(
+					(now - self.start_beacon_height) / self.beacon_period,
+				);
+				let parent = BlockId::Hash(parent_header.hash());
+				proposer_nonce = self
+					.chained_nonce
+					.as_ref()
+					.and_then(|chained| chained.chained_nonce(&parent, round));
+				if proposer_nonce.is_none() {
+					let target_height = now - self.beacon_period;
+					let mut current_hash = parent_header.hash();
+					let mut current_height = now - 1.into();
+					while current_height > target_height {
+						current_hash = self.client.header(BlockId::Hash(current_hash)).unwrap().unwrap().parent_hash().clone();
+						current_height = current_height - 1.into();
+					}
+					proposer_nonce = Some(current_hash);
 				}
-				proposer_nonce = Some(current_hash);
 			}
 		}
+
+		let mut give_up_at = None;
+		if let Some(nonce) = proposer_nonce {
+			let mut first_attempt = self.first_attempt.lock();
+			let started = match *first_attempt {
+				Some((ref attempted, started)) if *attempted == nonce => started,
+				_ => {
+					let started = time::Instant::now();
+					*first_attempt = Some((nonce, started));
+					started
+				}
+			};
+			give_up_at = Some(started + self.grace_period);
+		}
+
 		future::ready(Ok(Proposer {
 			inner: self
 				.inner
@@ -114,19 +338,22 @@ where
 			available_randomness: self.available_randomness.clone(),
 			randomness_rx: self.randomness_rx.clone(),
 			nonce: proposer_nonce,
+			give_up_at,
+			author_vrf: self.author_vrf.clone(),
+			parent_header: parent_header.clone(),
 		}))
 	}
-
-
 }
 
 
-
 pub struct Proposer<B, Block: BlockT, C, A: TransactionPool> {
 	inner: sc_basic_authorship::Proposer<B, Block, C, A>,
 	available_randomness: Arc<Mutex<HashMap<Nonce<Block>, Randomness<Nonce<Block>>>>>,
 	randomness_rx: Arc<Mutex<Receiver<Randomness<Nonce<Block>>>>>,
 	nonce: Option<Nonce<Block>>,
+	give_up_at: Option<time::Instant>,
+	author_vrf: Option<Arc<dyn AuthorVrf<Block>>>,
+	parent_header: Block::Header,
 }
 
 impl<A, B, Block, C> sp_consensus::Proposer<Block> for Proposer<B, Block, C, A>
@@ -152,11 +379,12 @@ where
 	/// The loop inside of propose makes keeps receiving randomness for various blocks.
 	/// At the moment, randomness is ready for the current block, the loop is terminated
 	/// and we proceed with block creation. If a deadline for block proposing is reached,
-	/// the loop is broken as well and block proposal fails.
+	/// the loop is broken as well and block proposal fails, unless the grace period for the
+	/// randomness has passed, in which case the block is proposed without it.
 	fn propose(
 		self,
 		inherent_data: InherentData,
-		inherent_digests: DigestFor<Block>,
+		mut inherent_digests: DigestFor<Block>,
 		max_duration: time::Duration,
 		record_proof: RecordProof,
 	) -> Self::Proposal {
@@ -171,7 +399,7 @@ where
 					// randomness for our nonce ready, no need to wait anymore
 					break;
 				}
-				let time_left = deadline - time::Instant::now();
+				let time_left = deadline.saturating_duration_since(time::Instant::now());
 				let new_randomness = self.randomness_rx.lock().recv_timeout(time_left);
 
 				match new_randomness {
@@ -207,19 +435,35 @@ where
 					}
 					info!("Included randomness in inherent_data.");
 				}
-				None => {
-					info!("Randomness not available in propose. Aborting proposal.");
-					return async {
-						Err(sp_blockchain::Error::Msg("no inherent data".to_string()))
+				None => match self.give_up_at {
+					Some(give_up_at) if time::Instant::now() >= give_up_at => {
+						info!("Grace period passed, randomness still not available. Proposing without it.");
 					}
-					.boxed();
-				}
+					_ => {
+						info!("Randomness not available in propose. Aborting proposal.");
+						return async {
+							Err(sp_blockchain::Error::Msg("no inherent data".to_string()))
+						}
+						.boxed();
+					}
+				},
 			},
 			None => {
 				info!("Not including randomness in inherent_data.");
 			}
 		}
 
+		if let Some(ref author_vrf) = self.author_vrf {
+			match author_vrf.pre_digest(&self.parent_header, &inherent_digests) {
+				Ok(Some(pre_digest)) => inherent_digests.push(pre_digest.to_digest_item()),
+				Ok(None) => info!("The runtime expects no VRF output of the author, proposing without it."),
+				Err(e) => {
+					info!("No VRF output of the author, aborting proposal: {}", e);
+					return async { Err(sp_blockchain::Error::Msg(e)) }.boxed();
+				}
+			}
+		}
+
 		self.inner
 			.propose(id, inherent_digests, max_duration, record_proof)
 	}
@@ -371,6 +615,249 @@ mod tests {
 		assert!(proposal.is_err());
 	}
 
+	#[test]
+	fn proposes_second_block_without_random_bytes_after_grace_period() {
+		let mut client = Arc::new(substrate_test_runtime_client::new());
+		let spawner = sp_core::testing::TaskExecutor::new();
+		let txpool = BasicPool::new_full(Default::default(), None, spawner.clone(), client.clone());
+
+		let (_tx, rx) = std::sync::mpsc::channel();
+		let wrapped_rx = Arc::new(Mutex::new(rx));
+		let mut proposer_factory = ProposerFactory::new(
+			spawner.clone(),
+			client.clone(),
+			txpool.clone(),
+			None,
+			1u64,
+			1u64,
+			wrapped_rx,
+		);
+		proposer_factory.set_grace_period(time::Duration::from_secs(0));
+
+		let proposer_future =
+			proposer_factory.init(&client.header(&BlockId::number(0)).unwrap().unwrap());
+		let proposer = futures::executor::block_on(proposer_future).unwrap();
+		let deadline = time::Duration::from_secs(1);
+		let proposal = futures::executor::block_on(proposer.propose(
+			Default::default(),
+			Default::default(),
+			deadline,
+			RecordProof::No,
+		));
+		assert!(proposal.is_ok());
+		let block = proposal.unwrap().block;
+		client.import(BlockOrigin::Own, block).unwrap();
+
+		let proposer_future =
+			proposer_factory.init(&client.header(&BlockId::number(1)).unwrap().unwrap());
+		let proposer = futures::executor::block_on(proposer_future).unwrap();
+		let deadline = time::Duration::from_secs(1);
+		let proposal = futures::executor::block_on(proposer.propose(
+			Default::default(),
+			Default::default(),
+			deadline,
+			RecordProof::No,
+		));
+		assert!(proposal.is_ok());
+	}
+
+	// the runtime chains the rounds to the seeds, the nonce of every round is the same
+	struct TestChainedNonce(sp_core::H256);
+
+	impl ChainedNonce<substrate_test_runtime_client::runtime::Block> for TestChainedNonce {
+		fn chained_nonce(
+			&self,
+			_at: &BlockId<substrate_test_runtime_client::runtime::Block>,
+			_round: Round,
+		) -> Option<sp_core::H256> {
+			Some(self.0)
+		}
+	}
+
+	#[test]
+	fn proposes_block_with_randomness_of_chained_nonce() {
+		let mut client = Arc::new(substrate_test_runtime_client::new());
+		let spawner = sp_core::testing::TaskExecutor::new();
+		let txpool = BasicPool::new_full(Default::default(), None, spawner.clone(), client.clone());
+
+		let (tx, rx) = std::sync::mpsc::channel();
+		let mut proposer_factory = ProposerFactory::new(
+			spawner.clone(),
+			client.clone(),
+			txpool.clone(),
+			None,
+			1u64,
+			1u64,
+			Arc::new(Mutex::new(rx)),
+		);
+		let nonce = sp_core::H256::repeat_byte(9);
+		proposer_factory.set_chained_nonce(Arc::new(TestChainedNonce(nonce)));
+
+		let proposer_future =
+			proposer_factory.init(&client.header(&BlockId::number(0)).unwrap().unwrap());
+		let proposer = futures::executor::block_on(proposer_future).unwrap();
+		let proposal = futures::executor::block_on(proposer.propose(
+			Default::default(),
+			Default::default(),
+			time::Duration::from_secs(1),
+			RecordProof::No,
+		));
+		let block = proposal.unwrap().block;
+		client.import(BlockOrigin::Own, block).unwrap();
+
+		// the randomness of the hash of block 1 is not the one of the chained nonce
+		let parent = client.header(&BlockId::number(1)).unwrap().unwrap();
+		assert!(tx.send(Randomness::new(parent.hash(), Default::default())).is_ok());
+		let proposer = futures::executor::block_on(proposer_factory.init(&parent)).unwrap();
+		assert_eq!(proposer.nonce, Some(nonce));
+		assert!(tx.send(Randomness::new(nonce, Default::default())).is_ok());
+		let proposal = futures::executor::block_on(proposer.propose(
+			Default::default(),
+			Default::default(),
+			time::Duration::from_secs(1),
+			RecordProof::No,
+		));
+		assert!(proposal.is_ok());
+	}
+
+	struct TestRuntimeGracePeriod(Option<time::Duration>);
+
+	impl RuntimeGracePeriod<substrate_test_runtime_client::runtime::Block> for TestRuntimeGracePeriod {
+		fn grace_period(
+			&self,
+			_at: &BlockId<substrate_test_runtime_client::runtime::Block>,
+		) -> Option<time::Duration> {
+			self.0
+		}
+	}
+
+	#[test]
+	fn checks_grace_period_against_runtime() {
+		let (_tx, rx) = std::sync::mpsc::channel();
+		let mut proposer_factory =
+			ProposerFactory::<(), (), substrate_test_runtime_client::runtime::Block, ()>::new(
+				sp_core::testing::TaskExecutor::new(),
+				Arc::new(()),
+				Arc::new(()),
+				None,
+				1u64,
+				1u64,
+				Arc::new(Mutex::new(rx)),
+			);
+		let at = BlockId::number(0);
+		let runtime = TestRuntimeGracePeriod(Some(time::Duration::from_secs(10)));
+		assert!(proposer_factory.check_grace_period(&runtime, &at).is_ok());
+		assert!(proposer_factory
+			.check_grace_period(&TestRuntimeGracePeriod(None), &at)
+			.is_ok());
+
+		proposer_factory.set_grace_period(time::Duration::from_secs(10));
+		assert!(proposer_factory.check_grace_period(&runtime, &at).is_err());
+		proposer_factory.set_grace_period(time::Duration::from_secs(5));
+		assert!(proposer_factory.check_grace_period(&runtime, &at).is_err());
+	}
+
+	// evaluates the VRF on an empty seed with a fixed key
+	struct TestAuthorVrf {
+		keystore: sp_keystore::testing::KeyStore,
+		author: sr25519::Public,
+	}
+
+	impl AuthorVrf<substrate_test_runtime_client::runtime::Block> for TestAuthorVrf {
+		fn pre_digest(
+			&self,
+			parent_header: &substrate_test_runtime_client::runtime::Header,
+			_inherent_digests: &DigestFor<substrate_test_runtime_client::runtime::Block>,
+		) -> Result<Option<PreDigest>, String> {
+			let transcript = vrf::make_transcript_data(&[], parent_header.number + 1);
+			let signature = self
+				.keystore
+				.sr25519_vrf_sign(KeyTypeId(*b"test"), &self.author, transcript)
+				.map_err(|e| format!("{:?}", e))?;
+			Ok(Some(PreDigest {
+				vrf_output: VRFOutput(signature.output),
+				vrf_proof: VRFProof(signature.proof),
+			}))
+		}
+	}
+
+	#[test]
+	fn adds_vrf_output_of_author_to_digests() {
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let spawner = sp_core::testing::TaskExecutor::new();
+		let txpool = BasicPool::new_full(Default::default(), None, spawner.clone(), client.clone());
+
+		let (_tx, rx) = std::sync::mpsc::channel();
+		let mut proposer_factory = ProposerFactory::new(
+			spawner.clone(),
+			client.clone(),
+			txpool.clone(),
+			None,
+			2u64,
+			1u64,
+			Arc::new(Mutex::new(rx)),
+		);
+		let keystore = sp_keystore::testing::KeyStore::new();
+		let author = keystore.sr25519_generate_new(KeyTypeId(*b"test"), None).unwrap();
+		let author_vrf = TestAuthorVrf { keystore, author };
+		let genesis = client.header(&BlockId::number(0)).unwrap().unwrap();
+		let expected = author_vrf.pre_digest(&genesis, &Default::default()).unwrap().unwrap();
+		proposer_factory.set_author_vrf(Arc::new(author_vrf));
+
+		let proposer = futures::executor::block_on(proposer_factory.init(&genesis)).unwrap();
+		let proposal = futures::executor::block_on(proposer.propose(
+			Default::default(),
+			Default::default(),
+			time::Duration::from_secs(1),
+			RecordProof::No,
+		))
+		.unwrap();
+		let found = PreDigest::find(
+			proposal
+				.block
+				.header()
+				.digest()
+				.logs()
+				.iter()
+				.filter_map(|log| log.as_pre_runtime()),
+		);
+		assert_eq!(found, Some(expected));
+	}
+
+	#[test]
+	fn fails_to_propose_without_vrf_output_of_author() {
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let spawner = sp_core::testing::TaskExecutor::new();
+		let txpool = BasicPool::new_full(Default::default(), None, spawner.clone(), client.clone());
+
+		let (_tx, rx) = std::sync::mpsc::channel();
+		let mut proposer_factory = ProposerFactory::new(
+			spawner.clone(),
+			client.clone(),
+			txpool.clone(),
+			None,
+			2u64,
+			1u64,
+			Arc::new(Mutex::new(rx)),
+		);
+		// the key of the author is not in the keystore
+		let author = sp_keystore::testing::KeyStore::new()
+			.sr25519_generate_new(KeyTypeId(*b"test"), None)
+			.unwrap();
+		let keystore = sp_keystore::testing::KeyStore::new();
+		proposer_factory.set_author_vrf(Arc::new(TestAuthorVrf { keystore, author }));
+
+		let genesis = client.header(&BlockId::number(0)).unwrap().unwrap();
+		let proposer = futures::executor::block_on(proposer_factory.init(&genesis)).unwrap();
+		let proposal = futures::executor::block_on(proposer.propose(
+			Default::default(),
+			Default::default(),
+			time::Duration::from_secs(1),
+			RecordProof::No,
+		));
+		assert!(proposal.is_err());
+	}
+
 	#[test]
 	fn fails_to_propose_second_block_given_randomness_for_different_nonce() {
 		let mut client = Arc::new(substrate_test_runtime_client::new());
@@ -0,0 +1,405 @@
+//! Validation of the randomness shares received via gossip.
+//!
+//! The validator keeps the topics, i.e. the nonces, for which the node currently collects
+//! shares, together with the keys to verify them. A message is kept and propagated only if
+//! it carries a valid share for a live topic from a committee member whose share was not
+//! received yet. Peers sending malformed messages, invalid shares, second shares of the
+//! same member or, in responses, shares that were not requested lose reputation, which is
+//! reported through a channel polled by `RandomnessGossip`. The validator also remembers
+//! which peer delivered the shares of each member, those peers are asked for the shares that
+//! are missing, see `crate::request`.
+
+use codec::Decode;
+use log::{debug, info};
+use parking_lot::RwLock;
+use sc_network::{PeerId, ReputationChange};
+use sc_network_gossip::{MessageIntent, ValidationResult, Validator, ValidatorContext};
+use sp_randomness_beacon::{RBBox, RandomnessShare};
+use sp_runtime::traits::Block as BlockT;
+use sp_utils::mpsc::{tracing_unbounded, TracingUnboundedReceiver, TracingUnboundedSender};
+use std::{
+	collections::{HashMap, HashSet},
+	sync::Arc,
+};
+
+use crate::{GossipMessage, Nonce, RB_PROTOCOL_NAME};
+
+mod cost {
+	use sc_network::ReputationChange as Rep;
+	pub(super) const MALFORMED_MESSAGE: Rep =
+		Rep::new(-1000, "Randomness beacon: malformed message");
+	pub(super) const BAD_SHARE: Rep = Rep::new(-500, "Randomness beacon: invalid share");
+	pub(super) const DUPLICATE_SHARE: Rep =
+		Rep::new(-100, "Randomness beacon: second share of a member");
+	pub(super) const UNREQUESTED_SHARE: Rep =
+		Rep::new(-100, "Randomness beacon: share of a member that was not requested");
+}
+
+mod benefit {
+	use sc_network::ReputationChange as Rep;
+	pub(super) const GOOD_SHARE: Rep = Rep::new(100, "Randomness beacon: good share");
+}
+
+/// A change of the reputation of a peer requested by the validator.
+pub struct PeerReport {
+	pub who: PeerId,
+	pub cost_benefit: ReputationChange,
+}
+
+struct TopicState<B: BlockT> {
+	rbbox: Arc<RBBox<Nonce<B>>>,
+	// the members whose valid share for the topic was already received
+	creators: HashSet<u64>,
+}
+
+pub struct GossipValidator<B: BlockT> {
+	topics: RwLock<HashMap<Nonce<B>, TopicState<B>>>,
+	// the peer which delivered the latest valid share of each member
+	member_peers: RwLock<HashMap<u64, PeerId>>,
+	report_sender: TracingUnboundedSender<PeerReport>,
+}
+
+impl<B: BlockT> GossipValidator<B> {
+	/// Creates the validator together with the stream of the peer reports it makes.
+	pub fn new() -> (Self, TracingUnboundedReceiver<PeerReport>) {
+		let (report_sender, report_stream) = tracing_unbounded("mpsc_randomness_beacon_reports");
+		let validator = GossipValidator {
+			topics: RwLock::new(HashMap::new()),
+			member_peers: RwLock::new(HashMap::new()),
+			report_sender,
+		};
+		(validator, report_stream)
+	}
+
+	/// Starts accepting the shares for the nonce, verified with the given box.
+	pub fn note_topic(&self, nonce: Nonce<B>, rbbox: Arc<RBBox<Nonce<B>>>) {
+		self.topics.write().insert(
+			nonce,
+			TopicState {
+				rbbox,
+				creators: HashSet::new(),
+			},
+		);
+	}
+
+	/// Stops accepting the shares for the nonce, the messages of the topic expire.
+	pub fn remove_topic(&self, nonce: &Nonce<B>) {
+		self.topics.write().remove(nonce);
+	}
+
+	/// The peers which delivered shares of the given members, each listed once.
+	pub fn peers_of(&self, creators: &[u64]) -> Vec<PeerId> {
+		let member_peers = self.member_peers.read();
+		let mut peers: Vec<PeerId> = Vec::new();
+		for peer in creators.iter().filter_map(|creator| member_peers.get(creator)) {
+			if !peers.contains(peer) {
+				peers.push(peer.clone());
+			}
+		}
+		peers
+	}
+
+	/// Decodes the shares for the topic sent by the peer in a response to a request for the
+	/// shares of the `requested` members and returns the valid ones, which are verified in one
+	/// batch. Unlike gossiped shares, these are not noted as received, since the same shares may
+	/// still arrive via gossip.
+	pub fn validate_response(
+		&self,
+		sender: &PeerId,
+		topic: &Nonce<B>,
+		requested: &[u64],
+		data: &[u8],
+	) -> Vec<RandomnessShare<Nonce<B>>> {
+		let shares = match Vec::<RandomnessShare<Nonce<B>>>::decode(&mut &data[..]) {
+			Ok(shares) => shares,
+			Err(e) => {
+				info!(
+					target: RB_PROTOCOL_NAME,
+					"Error decoding response from {}: {}",
+					sender,
+					e.what()
+				);
+				self.report(sender, cost::MALFORMED_MESSAGE);
+				return Vec::new();
+			}
+		};
+
+		let topics = self.topics.read();
+		let state = match topics.get(topic) {
+			Some(state) => state,
+			None => return Vec::new(),
+		};
+
+		// at most one share of each requested member is verified, a response may be much larger
+		let requested: HashSet<_> = requested.iter().collect();
+		let mut creators = HashSet::new();
+		let mut checked = Vec::new();
+		for share in shares {
+			if share.nonce() != *topic {
+				self.report(sender, cost::BAD_SHARE);
+			} else if !requested.contains(&share.creator()) {
+				self.report(sender, cost::UNREQUESTED_SHARE);
+			} else if !creators.insert(share.creator()) {
+				self.report(sender, cost::DUPLICATE_SHARE);
+			} else {
+				checked.push(share);
+			}
+		}
+		let shares = checked;
+
+		// a response carries up to a whole committee of shares, so they are verified in a batch
+		let invalid = state.rbbox.invalid_randomness_shares(&shares);
+		let mut valid = Vec::new();
+		for (ix, share) in shares.into_iter().enumerate() {
+			if invalid.binary_search(&ix).is_ok() {
+				self.report(sender, cost::BAD_SHARE);
+				continue;
+			}
+			self.report(sender, benefit::GOOD_SHARE);
+			valid.push(share);
+		}
+		valid
+	}
+
+	fn report(&self, who: &PeerId, cost_benefit: ReputationChange) {
+		let _ = self.report_sender.unbounded_send(PeerReport {
+			who: who.clone(),
+			cost_benefit,
+		});
+	}
+}
+
+impl<B: BlockT> Validator<B> for GossipValidator<B> {
+	fn validate(
+		&self,
+		_context: &mut dyn ValidatorContext<B>,
+		sender: &PeerId,
+		data: &[u8],
+	) -> ValidationResult<B::Hash> {
+		let (topic, share) = match GossipMessage::<B>::decode(&mut &data[..]).and_then(|gm| {
+			RandomnessShare::<Nonce<B>>::decode(&mut &gm.message.share[..]).map(|s| (gm.nonce, s))
+		}) {
+			Ok(decoded) => decoded,
+			Err(e) => {
+				info!(
+					target: RB_PROTOCOL_NAME,
+					"Error decoding message from {}: {}",
+					sender,
+					e.what()
+				);
+				self.report(sender, cost::MALFORMED_MESSAGE);
+				return ValidationResult::Discard;
+			}
+		};
+
+		let mut topics = self.topics.write();
+		let state = match topics.get_mut(&topic) {
+			Some(state) => state,
+			None => {
+				// the nonce is either stale or not imported yet, in the latter case the share
+				// is accepted once it is sent again
+				debug!(target: RB_PROTOCOL_NAME, "Share for unknown nonce {:?}", topic);
+				return ValidationResult::Discard;
+			}
+		};
+
+		if state.creators.contains(&share.creator()) {
+			self.report(sender, cost::DUPLICATE_SHARE);
+			return ValidationResult::Discard;
+		}
+
+		if share.nonce() != topic || !state.rbbox.verify_randomness_share(&share) {
+			self.report(sender, cost::BAD_SHARE);
+			return ValidationResult::Discard;
+		}
+
+		state.creators.insert(share.creator());
+		self.member_peers.write().insert(share.creator(), sender.clone());
+		self.report(sender, benefit::GOOD_SHARE);
+		ValidationResult::ProcessAndKeep(topic)
+	}
+
+	fn message_expired<'a>(&'a self) -> Box<dyn FnMut(B::Hash, &[u8]) -> bool + 'a> {
+		let topics = self.topics.read();
+		Box::new(move |topic, _data| !topics.contains_key(&topic))
+	}
+
+	fn message_allowed<'a>(
+		&'a self,
+	) -> Box<dyn FnMut(&PeerId, MessageIntent, &B::Hash, &[u8]) -> bool + 'a> {
+		let topics = self.topics.read();
+		Box::new(move |_who, _intent, topic, _data| topics.contains_key(topic))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Message;
+	use codec::Encode;
+	use futures::StreamExt;
+	use sp_dkg::{RawSecret, VerifyKey};
+	use sp_randomness_beacon::SignatureVersion;
+	use substrate_test_runtime_client::runtime::{Block, Hash};
+
+	struct TestContext;
+
+	impl ValidatorContext<Block> for TestContext {
+		fn broadcast_topic(&mut self, _topic: Hash, _force: bool) {}
+		fn broadcast_message(&mut self, _topic: Hash, _message: Vec<u8>, _force: bool) {}
+		fn send_message(&mut self, _who: &PeerId, _message: Vec<u8>) {}
+		fn send_topic(&mut self, _who: &PeerId, _topic: Hash, _force: bool) {}
+	}
+
+	const SECRETS: [RawSecret; 2] = [[1, 2, 3, 4], [5, 6, 7, 8]];
+
+	// the box of the member with the given index and secret in a committee of two
+	fn rbbox(ix: u64, secret: RawSecret) -> RBBox<Hash> {
+		RBBox::new(
+			Some(ix),
+			Some(secret),
+			SECRETS.iter().map(|s| VerifyKey::from_raw_secret(*s)).collect(),
+			VerifyKey::default(),
+			2,
+			SignatureVersion::LATEST,
+		)
+	}
+
+	fn message(nonce: Hash, share: &RandomnessShare<Hash>) -> Vec<u8> {
+		GossipMessage::<Block> {
+			nonce,
+			message: Message {
+				share: share.encode(),
+			},
+		}
+		.encode()
+	}
+
+	fn is_kept(result: ValidationResult<Hash>) -> bool {
+		match result {
+			ValidationResult::ProcessAndKeep(_) => true,
+			_ => false,
+		}
+	}
+
+	#[test]
+	fn validates_shares_and_reports_peers() {
+		let (validator, mut reports) = GossipValidator::<Block>::new();
+		let peer = PeerId::random();
+		let nonce = Hash::repeat_byte(1);
+		let share = rbbox(0, SECRETS[0]).generate_randomness_share(nonce).unwrap();
+		let validate = |data: &[u8]| validator.validate(&mut TestContext, &peer, data);
+		let mut next_report = || {
+			futures::executor::block_on(reports.next())
+				.unwrap()
+				.cost_benefit
+		};
+
+		// malformed messages
+		assert!(!is_kept(validate(&[1, 2, 3])));
+		assert_eq!(next_report(), cost::MALFORMED_MESSAGE);
+
+		// unknown topics are discarded without reports
+		assert!(!is_kept(validate(&message(nonce, &share))));
+
+		validator.note_topic(nonce, Arc::new(rbbox(1, SECRETS[1])));
+		assert!(is_kept(validate(&message(nonce, &share))));
+		assert_eq!(next_report(), benefit::GOOD_SHARE);
+
+		// only one share per member is accepted
+		assert!(!is_kept(validate(&message(nonce, &share))));
+		assert_eq!(next_report(), cost::DUPLICATE_SHARE);
+
+		// a share of the member 1 signed with a wrong secret
+		let bad_share = rbbox(1, SECRETS[0]).generate_randomness_share(nonce).unwrap();
+		assert!(!is_kept(validate(&message(nonce, &bad_share))));
+		assert_eq!(next_report(), cost::BAD_SHARE);
+
+		// a share for another nonce sent under this topic
+		let other = rbbox(1, SECRETS[1])
+			.generate_randomness_share(Hash::repeat_byte(2))
+			.unwrap();
+		assert!(!is_kept(validate(&message(nonce, &other))));
+		assert_eq!(next_report(), cost::BAD_SHARE);
+
+		let good_share = rbbox(1, SECRETS[1]).generate_randomness_share(nonce).unwrap();
+		assert!(is_kept(validate(&message(nonce, &good_share))));
+		assert_eq!(next_report(), benefit::GOOD_SHARE);
+	}
+
+	#[test]
+	fn remembers_peers_and_validates_responses() {
+		let (validator, mut reports) = GossipValidator::<Block>::new();
+		let (alice, bob) = (PeerId::random(), PeerId::random());
+		let nonce = Hash::repeat_byte(1);
+		let shares: Vec<_> = (0..2)
+			.map(|ix| rbbox(ix, SECRETS[ix as usize]).generate_randomness_share(nonce).unwrap())
+			.collect();
+		validator.note_topic(nonce, Arc::new(rbbox(0, SECRETS[0])));
+
+		assert!(is_kept(validator.validate(&mut TestContext, &alice, &message(nonce, &shares[0]))));
+		assert!(is_kept(validator.validate(&mut TestContext, &bob, &message(nonce, &shares[1]))));
+		assert_eq!(validator.peers_of(&[1]), vec![bob.clone()]);
+		assert_eq!(validator.peers_of(&[0, 1, 0]), vec![alice.clone(), bob.clone()]);
+		assert!(validator.peers_of(&[2]).is_empty());
+		let mut next_report = || {
+			futures::executor::block_on(reports.next())
+				.unwrap()
+				.cost_benefit
+		};
+		assert_eq!(next_report(), benefit::GOOD_SHARE);
+		assert_eq!(next_report(), benefit::GOOD_SHARE);
+
+		// shares already received via gossip may be sent in responses as well
+		let bad_share = rbbox(0, SECRETS[1]).generate_randomness_share(nonce).unwrap();
+		let response = vec![shares[1].clone(), bad_share].encode();
+		let valid = validator.validate_response(&alice, &nonce, &[0, 1], &response);
+		assert!(valid == vec![shares[1].clone()]);
+		assert_eq!(next_report(), benefit::GOOD_SHARE);
+		assert_eq!(next_report(), cost::BAD_SHARE);
+
+		// the shares for other nonces are rejected before the batch is verified
+		let other = rbbox(0, SECRETS[0])
+			.generate_randomness_share(Hash::repeat_byte(2))
+			.unwrap();
+		let response = vec![other, shares[0].clone(), shares[1].clone()].encode();
+		let valid = validator.validate_response(&bob, &nonce, &[0, 1], &response);
+		assert!(valid == shares);
+		assert_eq!(next_report(), cost::BAD_SHARE);
+		assert_eq!(next_report(), benefit::GOOD_SHARE);
+		assert_eq!(next_report(), benefit::GOOD_SHARE);
+
+		// so are the shares of members that were not requested and the repeated ones
+		let response = vec![shares[0].clone(), shares[1].clone(), shares[1].clone()].encode();
+		let valid = validator.validate_response(&bob, &nonce, &[1], &response);
+		assert!(valid == vec![shares[1].clone()]);
+		assert_eq!(next_report(), cost::UNREQUESTED_SHARE);
+		assert_eq!(next_report(), cost::DUPLICATE_SHARE);
+		assert_eq!(next_report(), benefit::GOOD_SHARE);
+
+		assert!(validator.validate_response(&alice, &nonce, &[0], &[1, 2, 3]).is_empty());
+		assert_eq!(next_report(), cost::MALFORMED_MESSAGE);
+		assert!(validator
+			.validate_response(&alice, &Hash::repeat_byte(2), &[0], &response)
+			.is_empty());
+	}
+
+	#[test]
+	fn messages_of_removed_topics_expire() {
+		let (validator, _reports) = GossipValidator::<Block>::new();
+		let (live, stale) = (Hash::repeat_byte(1), Hash::repeat_byte(2));
+		let peer = PeerId::random();
+		validator.note_topic(live, Arc::new(rbbox(0, SECRETS[0])));
+		validator.note_topic(stale, Arc::new(rbbox(0, SECRETS[0])));
+		validator.remove_topic(&stale);
+
+		let mut expired = Validator::<Block>::message_expired(&validator);
+		assert!(!expired(live, &[]));
+		assert!(expired(stale, &[]));
+		drop(expired);
+
+		let mut allowed = Validator::<Block>::message_allowed(&validator);
+		assert!(allowed(&peer, MessageIntent::Broadcast, &live, &[]));
+		assert!(!allowed(&peer, MessageIntent::Broadcast, &stale, &[]));
+	}
+}
@@ -4,18 +4,30 @@
 //! randomness shares for this new block. These notifications happen via a channel
 //! which has the transmitting end in block import (here) and the receiving end
 //! in a RandomnessGossip component.
+//!
+//! Before a block is passed to the inner import, and thus executed, its randomness
+//! inherent is checked by the runtime against the hash of the block `beacon_period`
+//! blocks back, or the nonce chained to the latest seed if the runtime chains the rounds, and
+//! the current verifier. Blocks with invalid seeds are rejected.
 
 use futures::channel::mpsc::Sender;
 use log::info;
-use sc_client_api::{backend::AuxStore, BlockOf};
-use sp_api::ProvideRuntimeApi;
+use sp_api::{ApiExt, ProvideRuntimeApi};
 use sp_block_builder::BlockBuilder as BlockBuilderApi;
-use sp_blockchain::{well_known_cache_keys::Id as CacheKeyId, HeaderBackend, ProvideCache};
+use sp_blockchain::{well_known_cache_keys::Id as CacheKeyId, HeaderBackend};
 use sp_consensus::{
 	BlockCheckParams, BlockImport, BlockImportParams, Error as ConsensusError, ImportResult,
 };
+use sp_inherents::InherentDataProviders;
+use sp_randomness_beacon::{
+	inherents::{CheckContext, InherentError, CHECK_CONTEXT_IDENTIFIER},
+	RandomnessBeaconApi, Round,
+};
 
-use sp_runtime::traits::{Block as BlockT, Header};
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, Header, NumberFor, UniqueSaturatedInto},
+};
 use std::{collections::HashMap, marker, sync::Arc};
 
 #[derive(derive_more::Display, Debug)]
@@ -24,6 +36,8 @@ pub enum Error {
 	Client(sp_blockchain::Error),
 	#[display(fmt = "Checking inherents failed: {}", _0)]
 	CheckInherents(String),
+	#[display(fmt = "Invalid randomness inherent: {:?}", _0)]
+	InvalidRandomness(InherentError),
 	DataProvider(String),
 }
 
@@ -38,6 +52,7 @@ use super::NonceInfo;
 pub struct RandomnessBeaconBlockImport<B: BlockT, I, C> {
 	inner: I,
 	client: Arc<C>,
+	inherent_data_providers: InherentDataProviders,
 	randomness_nonce_tx: Sender<NonceInfo<B>>,
 	_marker: marker::PhantomData<B>,
 }
@@ -47,6 +62,7 @@ impl<B: BlockT, I: Clone, C> Clone for RandomnessBeaconBlockImport<B, I, C> {
 		Self {
 			inner: self.inner.clone(),
 			client: self.client.clone(),
+			inherent_data_providers: self.inherent_data_providers.clone(),
 			randomness_nonce_tx: self.randomness_nonce_tx.clone(),
 			_marker: marker::PhantomData,
 		}
@@ -58,26 +74,114 @@ where
 	B: BlockT,
 	I: BlockImport<B, Transaction = sp_api::TransactionFor<C, B>> + Send + Sync,
 	I::Error: Into<ConsensusError>,
-	C: ProvideRuntimeApi<B> + Send + Sync + HeaderBackend<B> + AuxStore + ProvideCache<B> + BlockOf,
-	C::Api: BlockBuilderApi<B, Error = sp_blockchain::Error>,
+	C: ProvideRuntimeApi<B> + Send + Sync + HeaderBackend<B>,
+	C::Api: BlockBuilderApi<B, Error = sp_blockchain::Error>
+		+ RandomnessBeaconApi<B>,
 {
-	pub fn new(inner: I, client: Arc<C>, randomness_nonce_tx: Sender<NonceInfo<B>>) -> Self {
+	/// The inherent data providers are the ones of the node, the runtime checks all the
+	/// inherents of a block together and the other pallets need their data as well.
+	pub fn new(
+		inner: I,
+		client: Arc<C>,
+		inherent_data_providers: InherentDataProviders,
+		randomness_nonce_tx: Sender<NonceInfo<B>>,
+	) -> Self {
 		Self {
 			inner,
 			client,
+			inherent_data_providers,
 			randomness_nonce_tx,
 			_marker: marker::PhantomData,
 		}
 	}
 }
 
+/// Checks the randomness inherent of the block with the given header and body. Only blocks at
+/// which a seed is due are checked, a seed in any other block fails to be set at execution.
+///
+/// The runtime checks inherents in the state of the parent block, so the number of the block
+/// and the nonce its seed has to sign are passed in the `CheckContext`.
+pub fn check_randomness<B, C>(
+	client: &C,
+	inherent_data_providers: &InherentDataProviders,
+	header: &B::Header,
+	body: &[B::Extrinsic],
+) -> Result<(), Error>
+where
+	B: BlockT,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B>,
+	C::Api: BlockBuilderApi<B, Error = sp_blockchain::Error>
+		+ RandomnessBeaconApi<B>,
+{
+	let parent = BlockId::Hash(*header.parent_hash());
+	let number = *header.number();
+	let runtime_api = client.runtime_api();
+	let start = runtime_api.start_beacon_height(&parent).map_err(Error::Client)?;
+	let period = runtime_api.beacon_period(&parent).map_err(Error::Client)?;
+
+	if number <= start || (number - start) % period != 0.into() {
+		return Ok(());
+	}
+
+	let round = UniqueSaturatedInto::<Round>::unique_saturated_into((number - start) / period);
+	let mut nonce = match runtime_api.has_api_with::<dyn RandomnessBeaconApi<B, Error = ()>, _>(&parent, |v| v >= 8) {
+		Ok(true) => runtime_api.chained_nonce(&parent, round).map_err(Error::Client)?,
+		_ => None,
+	};
+	if nonce.is_none() {
+		nonce = Some(ancestor_hash::<B, C>(client, header, number - period)?);
+	}
+
+	let mut inherent_data = inherent_data_providers
+		.create_inherent_data()
+		.map_err(|e| Error::DataProvider(e.into_string()))?;
+	inherent_data
+		.put_data(CHECK_CONTEXT_IDENTIFIER, &CheckContext { number, nonce })
+		.map_err(|e| Error::DataProvider(e.into_string()))?;
+
+	let result = runtime_api
+		.check_inherents(&parent, B::new(header.clone(), body.to_vec()), inherent_data)
+		.map_err(Error::Client)?;
+	// the other inherents were already checked by the consensus engine
+	for (id, data) in result.into_errors() {
+		if let Some(e) = InherentError::try_from(&id, &data) {
+			return Err(Error::InvalidRandomness(e));
+		}
+	}
+
+	Ok(())
+}
+
+// the hash of the ancestor of the block with the given header at the given height
+fn ancestor_hash<B, C>(client: &C, header: &B::Header, height: NumberFor<B>) -> Result<B::Hash, Error>
+where
+	B: BlockT,
+	C: HeaderBackend<B>,
+{
+	let mut current_hash = *header.parent_hash();
+	let mut current_height = *header.number() - 1.into();
+	while current_height > height {
+		current_hash = *client
+			.header(BlockId::Hash(current_hash))
+			.map_err(Error::Client)?
+			.ok_or_else(|| {
+				Error::Client(sp_blockchain::Error::UnknownBlock(format!("{:?}", current_hash)))
+			})?
+			.parent_hash();
+		current_height = current_height - 1.into();
+	}
+
+	Ok(current_hash)
+}
+
 impl<B, I, C> BlockImport<B> for RandomnessBeaconBlockImport<B, I, C>
 where
 	B: BlockT,
 	I: BlockImport<B, Transaction = sp_api::TransactionFor<C, B>> + Send + Sync,
 	I::Error: Into<ConsensusError>,
-	C: ProvideRuntimeApi<B> + Send + Sync + HeaderBackend<B> + AuxStore + ProvideCache<B> + BlockOf,
-	C::Api: BlockBuilderApi<B, Error = sp_blockchain::Error>,
+	C: ProvideRuntimeApi<B> + Send + Sync + HeaderBackend<B>,
+	C::Api: BlockBuilderApi<B, Error = sp_blockchain::Error>
+		+ RandomnessBeaconApi<B>,
 {
 	type Error = ConsensusError;
 	type Transaction = sp_api::TransactionFor<C, B>;
@@ -86,13 +190,25 @@ where
 		self.inner.check_block(block).map_err(Into::into)
 	}
 
-	/// Here we send a notification through self.randomness_nonce_tx that a new block
-	/// have been imported.
+	/// Here we check the randomness of the block and, once it is imported, send
+	/// a notification through self.randomness_nonce_tx that a new block have been imported.
 	fn import_block(
 		&mut self,
 		block: BlockImportParams<B, Self::Transaction>,
 		new_cache: HashMap<CacheKeyId, Vec<u8>>,
 	) -> Result<ImportResult, Self::Error> {
+		if let Some(ref body) = block.body {
+			if let Err(e) = check_randomness::<B, C>(
+				&*self.client,
+				&self.inherent_data_providers,
+				&block.header,
+				body,
+			) {
+				info!(target: "import", "Rejecting block {:?}: {}", block.post_hash(), e);
+				return Err(e.into());
+			}
+		}
+
 		let nonce = block.post_hash();
 		let num = block.header.number().clone();
 		let res = self
@@ -121,13 +237,218 @@ where
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use parking_lot::Mutex;
+	use sc_block_builder::BlockBuilderProvider;
+	use sp_api::ApiRef;
+	use sp_blockchain::{BlockStatus, Info};
 	use sp_consensus::BlockOrigin;
+	use sp_inherents::{CheckInherentsResult, InherentData};
+	use sp_randomness_beacon::{
+		inherents::INHERENT_IDENTIFIER, Randomness, Round, SeedFreshness, SignatureVersion,
+	};
+	use sp_runtime::{traits::{BlakeTwo256, Header as _}, ApplyExtrinsicResult};
+	use substrate_test_runtime_client::{
+		prelude::*,
+		runtime::{Block, Extrinsic, Hash, Header},
+	};
+
+	// a client whose runtime rejects the randomness of all blocks if `reject` is set, and
+	// chains the rounds to the seeds if `chained` is set
+	#[derive(Clone)]
+	struct TestClient {
+		client: Arc<substrate_test_runtime_client::TestClient>,
+		reject: bool,
+		chained: bool,
+		checked: Arc<Mutex<Option<CheckContext<u64, Hash>>>>,
+	}
+
+	struct RuntimeApi {
+		inner: TestClient,
+	}
+
+	impl ProvideRuntimeApi<Block> for TestClient {
+		type Api = RuntimeApi;
+
+		fn runtime_api<'a>(&'a self) -> ApiRef<'a, Self::Api> {
+			RuntimeApi { inner: self.clone() }.into()
+		}
+	}
+
+	impl HeaderBackend<Block> for TestClient {
+		fn header(&self, id: BlockId<Block>) -> sp_blockchain::Result<Option<Header>> {
+			self.client.header(&id)
+		}
+
+		fn info(&self) -> Info<Block> {
+			self.client.info()
+		}
+
+		fn status(&self, id: BlockId<Block>) -> sp_blockchain::Result<BlockStatus> {
+			self.client.status(id)
+		}
+
+		fn number(&self, hash: Hash) -> sp_blockchain::Result<Option<NumberFor<Block>>> {
+			self.client.number(hash)
+		}
+
+		fn hash(&self, number: NumberFor<Block>) -> sp_blockchain::Result<Option<Hash>> {
+			self.client.hash(number)
+		}
+	}
+
+	sp_api::mock_impl_runtime_apis! {
+		impl sp_block_builder::BlockBuilder<Block> for RuntimeApi {
+			type Error = sp_blockchain::Error;
+
+			fn apply_extrinsic(_extrinsic: Extrinsic) -> ApplyExtrinsicResult {
+				unimplemented!()
+			}
+
+			fn finalize_block() -> Header {
+				unimplemented!()
+			}
+
+			fn inherent_extrinsics(_data: InherentData) -> Vec<Extrinsic> {
+				unimplemented!()
+			}
+
+			fn check_inherents(&self, _block: Block, data: InherentData) -> CheckInherentsResult {
+				*self.inner.checked.lock() = data.get_data(&CHECK_CONTEXT_IDENTIFIER).unwrap();
+				let mut result = CheckInherentsResult::new();
+				if self.inner.reject {
+					result.put_error(INHERENT_IDENTIFIER, &InherentError::InvalidRandomBytes).unwrap();
+				}
+				result
+			}
+
+			fn random_seed() -> Hash {
+				unimplemented!()
+			}
+		}
+
+		impl RandomnessBeaconApi<Block> for RuntimeApi {
+			fn start_beacon_height() -> NumberFor<Block> {
+				1
+			}
+
+			fn beacon_period() -> NumberFor<Block> {
+				2
+			}
+
+			fn signature_version() -> SignatureVersion {
+				SignatureVersion::LATEST
+			}
+
+			fn latest_randomness() -> Option<(NumberFor<Block>, Randomness<Hash>)> {
+				None
+			}
+
+			fn seed_freshness() -> Option<SeedFreshness<NumberFor<Block>>> {
+				None
+			}
+
+			fn round_randomness(_round: Option<Round>) -> Option<(Round, Randomness<Hash>)> {
+				None
+			}
+
+			fn history_index(_round: Round) -> u32 {
+				0
+			}
+
+			fn author_vrf_key(
+				_pre_digests: Vec<(sp_runtime::ConsensusEngineId, Vec<u8>)>,
+			) -> Option<Vec<u8>> {
+				None
+			}
+
+			fn chained_nonce(&self, round: Round) -> Option<Hash> {
+				if self.inner.chained {
+					Some(sp_randomness_beacon::chained_nonce::<BlakeTwo256>(round, None))
+				} else {
+					None
+				}
+			}
+
+			fn grace_period(&self) -> u64 {
+				0
+			}
+		}
+	}
+
+	// accepts all blocks without executing them
+	struct Inner;
+
+	impl BlockImport<Block> for Inner {
+		type Error = ConsensusError;
+		type Transaction = sp_api::TransactionFor<TestClient, Block>;
+
+		fn check_block(
+			&mut self,
+			_block: BlockCheckParams<Block>,
+		) -> Result<ImportResult, Self::Error> {
+			Ok(ImportResult::imported(false))
+		}
+
+		fn import_block(
+			&mut self,
+			_block: BlockImportParams<Block, Self::Transaction>,
+			_new_cache: HashMap<CacheKeyId, Vec<u8>>,
+		) -> Result<ImportResult, Self::Error> {
+			Ok(ImportResult::imported(true))
+		}
+	}
+
+	// a client with two imported blocks
+	fn test_client(reject: bool) -> TestClient {
+		let mut client = Arc::new(substrate_test_runtime_client::new());
+		for _ in 0..2 {
+			let block = client.new_block(Default::default()).unwrap().build().unwrap().block;
+			client.import(BlockOrigin::Own, block).unwrap();
+		}
+		TestClient {
+			client,
+			reject,
+			chained: false,
+			checked: Arc::new(Mutex::new(None)),
+		}
+	}
+
+	fn block_import(
+		client: &TestClient,
+	) -> (
+		RandomnessBeaconBlockImport<Block, Inner, TestClient>,
+		futures::channel::mpsc::Receiver<NonceInfo<Block>>,
+	) {
+		let (tx, rx) = futures::channel::mpsc::channel(1);
+		let import = RandomnessBeaconBlockImport::new(
+			Inner,
+			Arc::new(client.clone()),
+			InherentDataProviders::new(),
+			tx,
+		);
+		(import, rx)
+	}
+
+	fn block_params(
+		number: u64,
+		parent: Hash,
+	) -> BlockImportParams<Block, sp_api::TransactionFor<TestClient, Block>> {
+		let header = Header::new(
+			number,
+			Default::default(),
+			Default::default(),
+			parent,
+			Default::default(),
+		);
+		let mut params = BlockImportParams::new(BlockOrigin::NetworkBroadcast, header);
+		params.body = Some(Vec::new());
+		params
+	}
 
 	#[test]
 	fn correctly_sends_nonce() {
-		let client = std::sync::Arc::new(substrate_test_runtime_client::new());
-		let (tx, mut rx) = futures::channel::mpsc::channel(1);
-		let mut import = RandomnessBeaconBlockImport::new(client.clone(), client.clone(), tx);
+		let client = test_client(false);
+		let (mut import, mut rx) = block_import(&client);
 
 		let header = sp_runtime::generic::Header::new_from_number(1);
 		let block = BlockImportParams::new(BlockOrigin::Own, header);
@@ -138,4 +459,51 @@ mod tests {
 		let ni = rx.try_next().unwrap().unwrap();
 		assert!(ni.nonce()[..] == target_nonce[..]);
 	}
+
+	#[test]
+	fn checks_randomness_against_ancestor() {
+		let client = test_client(false);
+		let (mut import, _rx) = block_import(&client);
+		let hash = |n: u64| client.client.hash(n).unwrap().unwrap();
+
+		// block 3 is a beacon block, its seed signs block 1
+		assert!(import.import_block(block_params(3, hash(2)), HashMap::new()).is_ok());
+		assert_eq!(
+			*client.checked.lock(),
+			Some(CheckContext { number: 3, nonce: Some(hash(1)) })
+		);
+
+		// no seed is due at block 2, so its inherents are not checked
+		*client.checked.lock() = None;
+		assert!(import.import_block(block_params(2, hash(1)), HashMap::new()).is_ok());
+		assert_eq!(*client.checked.lock(), None);
+	}
+
+	#[test]
+	fn checks_randomness_against_chained_nonce() {
+		let mut client = test_client(false);
+		client.chained = true;
+		let (mut import, _rx) = block_import(&client);
+		let parent = client.client.hash(2).unwrap().unwrap();
+
+		// block 3 is the beacon block of round 1
+		assert!(import.import_block(block_params(3, parent), HashMap::new()).is_ok());
+		let nonce = sp_randomness_beacon::chained_nonce::<BlakeTwo256>(1, None);
+		assert_eq!(*client.checked.lock(), Some(CheckContext { number: 3, nonce: Some(nonce) }));
+	}
+
+	#[test]
+	fn rejects_blocks_with_invalid_randomness() {
+		let client = test_client(true);
+		let (mut import, mut rx) = block_import(&client);
+		let parent = client.client.hash(2).unwrap().unwrap();
+
+		let res = import.import_block(block_params(3, parent), HashMap::new());
+		assert!(matches!(
+			res,
+			Err(ConsensusError::ClientImport(ref e)) if e.contains("InvalidRandomBytes")
+		));
+		// the gossip does not start collecting shares for rejected blocks
+		assert!(rx.try_next().is_err());
+	}
 }
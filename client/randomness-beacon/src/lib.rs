@@ -8,22 +8,40 @@
 //! started for a new block. It also holds a transmitting end of a channel
 //! through which it transmits ready random seeds to the block proposer.
 //! When creating a new block the proposer blocks until the random seed for
-//! arrives through this channel.
+//! arrives through this channel. Shares still missing some time after a nonce
+//! was received are requested directly from the peers, see `request`.
+//!
+//! The nonces are announced by block import as the hashes of the blocks `beacon_period` blocks
+//! before the seeds are due. If the runtime chains the rounds to the seeds, the shares are
+//! gossiped under the chained nonce of the round instead, see `chained_nonce`, which is the
+//! same on every fork with the same latest seed.
 
 use codec::{Decode, Encode};
-use log::info;
+use log::{debug, info};
 
-use sc_network::PeerId;
-use sc_network_gossip::{
-	GossipEngine, Network, TopicNotification, ValidationResult, Validator, ValidatorContext,
-};
+use sc_network_gossip::{GossipEngine, Network, TopicNotification};
 
-use sp_runtime::{generic::BlockId, traits::Block as BlockT, traits::NumberFor};
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, NumberFor, UniqueSaturatedInto},
+};
 
-use sp_dkg::DKGApi;
-use sp_randomness_beacon::{RBBox, Randomness, RandomnessBeaconApi, RandomnessShare};
+use sp_api::ApiExt;
+use sp_dkg::{DKGApi, RawSecret, VerifyKey, SECRET_KEY_TYPE};
+use sp_keystore::{SyncCryptoStore, SyncCryptoStorePtr};
+use prometheus_endpoint::Registry;
+use sc_network::{config::IncomingRequest, PeerId, RequestFailure};
+use sp_utils::mpsc::TracingUnboundedReceiver;
+use sp_randomness_beacon::{
+	RBBox, Randomness, RandomnessBeaconApi, RandomnessShare, Round, SignatureVersion,
+};
 
-use futures::{channel::mpsc::Receiver, prelude::*};
+use futures::{
+	channel::mpsc::Receiver,
+	future::BoxFuture,
+	prelude::*,
+	stream::FuturesUnordered,
+};
 use parking_lot::Mutex;
 use std::{
 	collections::HashMap,
@@ -51,9 +69,53 @@ const RANDOMNESS_BEACON_ID: [u8; 4] = *b"rndb";
 const RB_PROTOCOL_NAME: &'static str = "/randomness_beacon";
 pub const SEND_INTERVAL: time::Duration = time::Duration::from_secs(5);
 pub const INITIAL_WAIT: time::Duration = time::Duration::from_secs(0);
+pub const REQUEST_AFTER: time::Duration = time::Duration::from_secs(2);
+pub const REQUEST_INTERVAL: time::Duration = time::Duration::from_secs(3);
+pub const PRUNE_DEPTH: u32 = 60;
+pub const MAX_REQUESTED_PEERS: usize = 4;
 
 pub mod authorship;
+mod gossip;
 pub mod import;
+mod metrics;
+pub mod request;
+
+pub use gossip::{GossipValidator, PeerReport};
+use metrics::Metrics;
+use request::{ShareRequest, ShareRequests};
+
+/// The configuration of `RandomnessGossip`.
+pub struct Config {
+	/// How often the node sends its own share of a nonce again.
+	pub send_interval: time::Duration,
+	/// How long the node waits for the shares of a nonce to arrive via gossip before it
+	/// requests the missing ones from the peers.
+	pub request_after: time::Duration,
+	/// How often the requests for the missing shares of a nonce are repeated.
+	pub request_interval: time::Duration,
+	/// The number of blocks after which the topics of nonces are pruned.
+	pub prune_depth: u32,
+	/// The maximal number of peers asked for the missing shares of a nonce at once.
+	pub max_requested_peers: usize,
+	/// The requests for shares received from the peers, the node does not answer requests
+	/// if not set. See `request::protocol_config`.
+	pub incoming_requests: Option<Receiver<IncomingRequest>>,
+	pub prometheus_registry: Option<Registry>,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Config {
+			send_interval: SEND_INTERVAL,
+			request_after: REQUEST_AFTER,
+			request_interval: REQUEST_INTERVAL,
+			prune_depth: PRUNE_DEPTH,
+			max_requested_peers: MAX_REQUESTED_PEERS,
+			incoming_requests: None,
+			prometheus_registry: None,
+		}
+	}
+}
 
 pub type ShareBytes = Vec<u8>;
 
@@ -99,46 +161,12 @@ impl<B: BlockT> PartialOrd for NonceInfo<B> {
 	}
 }
 
-pub struct GossipValidator {}
-
-impl GossipValidator {
-	pub fn new() -> Self {
-		GossipValidator {}
-	}
-}
-
 #[derive(Debug, Clone)]
 pub enum Error {
 	Network(String),
 	Signing(String),
 }
 
-/// Validator of the messages received via gossip.
-/// It only needs to check that the received data corresponds to a share
-impl<B: BlockT> Validator<B> for GossipValidator {
-	fn validate(
-		&self,
-		_context: &mut dyn ValidatorContext<B>,
-		_sender: &PeerId,
-		data: &[u8],
-	) -> ValidationResult<B::Hash> {
-		match GossipMessage::<B>::decode(&mut data.clone()) {
-			Ok(gm) => {
-				let topic = gm.nonce;
-				ValidationResult::ProcessAndKeep(topic)
-			}
-			Err(e) => {
-				info!(
-					target: RB_PROTOCOL_NAME,
-					"Error decoding message: {}",
-					e.what()
-				);
-				ValidationResult::Discard
-			}
-		}
-	}
-}
-
 #[derive(Clone)]
 pub struct OutgoingMessage<B: BlockT> {
 	nonce: Nonce<B>,
@@ -159,24 +187,71 @@ impl<B: BlockT> OutgoingMessage<B> {
 	}
 }
 
+// the shares of a nonce collected so far together with the timers of sending and requesting them
+struct Topic<B: BlockT> {
+	incoming: Receiver<TopicNotification>,
+	outgoing: Option<OutgoingMessage<B>>,
+	periodic_sender: futures_timer::Delay,
+	rbbox: Arc<RBBox<Nonce<B>>>,
+	shares: Vec<RandomnessShare<Nonce<B>>>,
+	started: time::Instant,
+	request_timer: futures_timer::Delay,
+}
+
+impl<B: BlockT> Topic<B> {
+	// adds the shares not known yet, returns the randomness once threshold many are collected
+	fn add_shares(
+		&mut self,
+		new_shares: impl IntoIterator<Item = RandomnessShare<Nonce<B>>>,
+		threshold: usize,
+	) -> Option<Randomness<Nonce<B>>> {
+		if self.shares.len() >= threshold {
+			return None;
+		}
+		for share in new_shares {
+			if self.shares.len() < threshold && !self.shares.contains(&share) {
+				self.shares.push(share);
+			}
+		}
+		if self.shares.len() < threshold {
+			return None;
+		}
+
+		assert!(self
+			.shares
+			.iter()
+			.take(self.shares.len() - 1)
+			.enumerate()
+			.all(|(i, s)| !self.shares[i + 1..].contains(s)));
+		Some(self.rbbox.combine_shares(&self.shares))
+	}
+
+	// the members whose shares are still missing
+	fn missing_creators(&self) -> Vec<u64> {
+		(0..self.rbbox.n_members() as u64)
+			.filter(|creator| !self.shares.iter().any(|share| share.creator() == *creator))
+			.collect()
+	}
+}
+
+// the nonce and the members whose shares were requested from the peer, with its response
+type ShareResponse<B> = (Nonce<B>, Vec<u64>, PeerId, Result<Vec<u8>, RequestFailure>);
+
 pub struct RandomnessGossip<B: BlockT, C> {
 	threshold: u64,
-	topics: HashMap<
-		B::Hash,
-		(
-			Receiver<TopicNotification>,
-			Option<OutgoingMessage<B>>,
-			futures_timer::Delay,
-			RBBox<Nonce<B>>,
-			Vec<RandomnessShare<Nonce<B>>>,
-		),
-	>,
+	config: Config,
+	topics: HashMap<B::Hash, Topic<B>>,
 	height_queue: BinaryHeap<NonceInfo<B>>,
 	gossip_engine: Arc<Mutex<GossipEngine<B>>>,
+	validator: Arc<GossipValidator<B>>,
+	report_stream: TracingUnboundedReceiver<PeerReport>,
+	network: Box<dyn ShareRequests + Send>,
+	pending_responses: FuturesUnordered<BoxFuture<'static, ShareResponse<B>>>,
 	randomness_nonce_rx: Receiver<NonceInfo<B>>,
 	randomness_tx: Option<Sender<Randomness<Nonce<B>>>>,
 	runtime_api: Arc<C>,
-	http_rpc_port: u16,
+	keystore: Option<SyncCryptoStorePtr>,
+	metrics: Option<Metrics>,
 }
 
 impl<B: BlockT, C> Unpin for RandomnessGossip<B, C> {}
@@ -187,40 +262,57 @@ where
 	C: sp_api::ProvideRuntimeApi<B>,
 	C::Api: DKGApi<B> + RandomnessBeaconApi<B>,
 {
-	pub fn new<N: Network<B> + Send + Clone + 'static>(
+	pub fn new<N: Network<B> + ShareRequests + Send + Clone + 'static>(
 		threshold: u64,
 		randomness_nonce_rx: Receiver<NonceInfo<B>>,
 		network: N,
 		randomness_tx: Option<Sender<Randomness<Nonce<B>>>>,
 		runtime_api: Arc<C>,
-		http_rpc_port: u16,
+		keystore: Option<SyncCryptoStorePtr>,
+		config: Config,
 	) -> Self {
+		let (validator, report_stream) = GossipValidator::new();
+		let validator = Arc::new(validator);
 		let gossip_engine = Arc::new(Mutex::new(GossipEngine::new(
 			network.clone(),
 			RANDOMNESS_BEACON_ID,
 			RB_PROTOCOL_NAME,
-			Arc::new(GossipValidator::new()),
+			validator.clone(),
 		)));
+		let metrics = config.prometheus_registry.as_ref().and_then(|registry| {
+			Metrics::register(registry)
+				.map_err(|e| info!(target: RB_PROTOCOL_NAME, "Failed to register metrics: {}", e))
+				.ok()
+		});
 
 		RandomnessGossip {
 			threshold,
+			config,
 			topics: HashMap::new(),
 			height_queue: BinaryHeap::new(),
 			gossip_engine,
+			validator,
+			report_stream,
+			network: Box::new(network),
+			pending_responses: FuturesUnordered::new(),
 			randomness_nonce_rx,
 			randomness_tx,
 			runtime_api,
-			http_rpc_port,
+			keystore,
+			metrics,
 		}
 	}
 
-	// prunes all topics that are >=60 blocks lower than at_height
+	// prunes all topics that are >=prune_depth blocks lower than at_height
 	fn prune_old_topics(&mut self, at_height: NumberFor<B>) {
+		let prune_depth: NumberFor<B> = self.config.prune_depth.into();
 		while let Some(nonce_info) = self.height_queue.peek() {
-			if *nonce_info.height() + 60.into() <= at_height {
-				self.topics.remove(&nonce_info.nonce());
-				self.height_queue.pop();
+			if *nonce_info.height() + prune_depth > at_height {
+				break;
 			}
+			self.topics.remove(nonce_info.nonce());
+			self.validator.remove_topic(nonce_info.nonce());
+			self.height_queue.pop();
 		}
 	}
 
@@ -277,12 +369,6 @@ where
 	}
 
 	fn get_rbbox(&mut self, nonce_info: &NonceInfo<B>) -> Option<RBBox<Nonce<B>>> {
-		use hyper::rt;
-		use hyper::rt::Future;
-		use jsonrpc_core_client::transports::http;
-		use sc_rpc::offchain::OffchainClient;
-		use sp_core::{offchain::StorageKind, Bytes};
-
 		let block_hash = nonce_info.nonce().clone();
 		let block_height = nonce_info.height();
 
@@ -313,6 +399,11 @@ where
 			return None;
 		}
 
+		let signature_version = match self.signature_version(&BlockId::Hash(block_hash)) {
+			Some(version) => version,
+			None => return None,
+		};
+
 		let (ix, verification_keys, master_key, t) = match self
 			.runtime_api
 			.runtime_api()
@@ -321,40 +412,190 @@ where
 			Ok(Some((ix, vks, mvk, t))) => (ix, vks, mvk, t),
 			Ok(None) | Err(_) => return None,
 		};
-		let (tx, rx) = std::sync::mpsc::channel();
-		let tx = Mutex::new(tx);
 
-		let storage_key = match self
-			.runtime_api
-			.runtime_api()
-			.storage_key_sk(&BlockId::Hash(block_hash))
-		{
-			Ok(Some(st_key)) => st_key,
-			Ok(None) | Err(_) => return None,
+		let raw_key = ix
+			.and_then(|ix| verification_keys.get(ix as usize))
+			.and_then(|verify_key| self.local_raw_key(verify_key));
+
+		Some(RBBox::new(
+			ix,
+			raw_key,
+			verification_keys,
+			master_key,
+			t,
+			signature_version,
+		))
+	}
+
+	// the secret share the DKG offchain worker put into the keystore under the verification key
+	fn local_raw_key(&self, verify_key: &VerifyKey) -> Option<RawSecret> {
+		let keystore = self.keystore.as_ref()?;
+		match SyncCryptoStore::secret(&**keystore, SECRET_KEY_TYPE, &verify_key.encode()) {
+			Ok(Some(secret)) => RawSecret::decode(&mut &secret[..]).ok(),
+			Ok(None) => {
+				info!(target: RB_PROTOCOL_NAME, "No secret share in the keystore");
+				None
+			}
+			Err(e) => {
+				info!(target: RB_PROTOCOL_NAME, "Error while reading the secret share {:?}", e);
+				None
+			}
+		}
+	}
+
+	// the nonce of the round following the nonce block if the runtime at the block chains the
+	// rounds to the seeds, i.e. the nonce of the seed due `beacon_period` blocks later
+	fn chained_nonce(&self, nonce_info: &NonceInfo<B>) -> Option<Nonce<B>> {
+		let at = BlockId::Hash(nonce_info.nonce().clone());
+		let runtime_api = self.runtime_api.runtime_api();
+		match runtime_api.has_api_with::<dyn RandomnessBeaconApi<B, Error = ()>, _>(&at, |v| v >= 8) {
+			Ok(true) => {}
+			_ => return None,
+		}
+
+		let start = runtime_api.start_beacon_height(&at).ok()?;
+		let period = runtime_api.beacon_period(&at).ok()?;
+		let round = UniqueSaturatedInto::<Round>::unique_saturated_into(
+			(*nonce_info.height() - start) / period,
+		);
+		runtime_api.chained_nonce(&at, round + 1).ok()?
+	}
+
+	// runtimes from before signatures were versioned accept only V0 signatures
+	fn signature_version(&self, at: &BlockId<B>) -> Option<SignatureVersion> {
+		let runtime_api = self.runtime_api.runtime_api();
+		match runtime_api.has_api_with::<dyn RandomnessBeaconApi<B, Error = ()>, _>(at, |v| v >= 2) {
+			Ok(true) => runtime_api.signature_version(at).ok(),
+			Ok(false) => Some(SignatureVersion::V0),
+			Err(_) => None,
+		}
+	}
+
+	// starts collecting the shares of a new nonce, unless the node cannot verify them at its height
+	fn note_nonce(&mut self, nonce_info: NonceInfo<B>) {
+		if self.topics.contains_key(nonce_info.nonce()) {
+			return;
+		}
+
+		// received new nonce, need to fetch the corresponding rbbox
+		let rbbox = match self.get_rbbox(&nonce_info) {
+			Some(rbbox) => Arc::new(rbbox),
+			None => return,
+		};
+		let nonce_info = match self.chained_nonce(&nonce_info) {
+			Some(nonce) => NonceInfo::new(nonce, *nonce_info.height()),
+			None => nonce_info,
+		};
+		let topic = nonce_info.nonce().clone();
+		if self.topics.contains_key(&topic) {
+			return;
+		}
+		let (incoming, outgoing, own_shares) = self.initialize_nonce(nonce_info.clone(), &rbbox);
+		self.validator.note_topic(topic.clone(), rbbox.clone());
+		self.height_queue.push(nonce_info);
+
+		let mut state = Topic {
+			incoming,
+			outgoing,
+			periodic_sender: futures_timer::Delay::new(INITIAL_WAIT),
+			rbbox,
+			shares: Vec::new(),
+			started: time::Instant::now(),
+			request_timer: futures_timer::Delay::new(self.config.request_after),
+		};
+		if let Some(randomness) = state.add_shares(own_shares, self.threshold as usize) {
+			deliver_randomness(&self.randomness_tx, &self.metrics, state.started, randomness);
+		}
+		self.topics.insert(topic, state);
+	}
+
+	// answers the requests of the peers with the requested shares collected by the node
+	fn answer_requests(&mut self, cx: &mut Context) {
+		let incoming_requests = match self.config.incoming_requests.as_mut() {
+			Some(incoming_requests) => incoming_requests,
+			None => return,
 		};
 
-		let mut raw_key = None;
-		if ix.is_some() {
-			let url = format!("http://localhost:{}", self.http_rpc_port);
-			rt::run(rt::lazy(move || {
-				http::connect(url.as_str())
-					.and_then(move |client: OffchainClient| {
-						client
-							.get_local_storage(StorageKind::PERSISTENT, Bytes(storage_key))
-							.map(move |enc_key| {
-								let raw_key =
-									<[u64; 4]>::decode(&mut &enc_key.unwrap()[..]).unwrap();
-								if let Err(e) = tx.lock().send(raw_key) {
-									info!("Error while sending raw_key {:?}", e);
-								}
-							})
-					})
-					.map_err(|e| info!("didn't get key with err {:?}", e))
-			}));
-			raw_key = rx.recv().ok();
+		while let Poll::Ready(Some(IncomingRequest {
+			peer,
+			payload,
+			pending_response,
+		})) = incoming_requests.poll_next_unpin(cx)
+		{
+			let request = match ShareRequest::<Nonce<B>>::decode(&mut &payload[..]) {
+				Ok(request) => request,
+				Err(e) => {
+					// dropping the response channel refuses the request
+					info!(
+						target: RB_PROTOCOL_NAME,
+						"Malformed share request from {}: {}",
+						peer,
+						e.what()
+					);
+					continue;
+				}
+			};
+
+			let shares: Vec<_> = match self.topics.get(&request.nonce) {
+				Some(topic) => topic
+					.shares
+					.iter()
+					.filter(|share| request.creators.contains(&share.creator()))
+					.cloned()
+					.collect(),
+				None => Vec::new(),
+			};
+			let _ = pending_response.send(shares.encode());
+			if let Some(metrics) = &self.metrics {
+				metrics.share_requests_served.inc();
+			}
 		}
+	}
 
-		Some(RBBox::new(ix, raw_key, verification_keys, master_key, t))
+	// adds the valid shares received in responses to the requests of the node
+	fn handle_responses(&mut self, cx: &mut Context) {
+		let threshold = self.threshold as usize;
+		while let Poll::Ready(Some((nonce, creators, peer, result))) =
+			self.pending_responses.poll_next_unpin(cx)
+		{
+			let response = match result {
+				Ok(response) => response,
+				Err(e) => {
+					debug!(target: RB_PROTOCOL_NAME, "Share request to {} failed: {}", peer, e);
+					continue;
+				}
+			};
+
+			let shares = self
+				.validator
+				.validate_response(&peer, &nonce, &creators, &response);
+			if let Some(metrics) = &self.metrics {
+				metrics.requested_shares_received.inc_by(shares.len() as u64);
+			}
+			if let Some(topic) = self.topics.get_mut(&nonce) {
+				if let Some(randomness) = topic.add_shares(shares, threshold) {
+					deliver_randomness(&self.randomness_tx, &self.metrics, topic.started, randomness);
+				}
+			}
+		}
+	}
+}
+
+// passes the combined randomness to the block proposer
+fn deliver_randomness<Hash>(
+	randomness_tx: &Option<Sender<Randomness<Hash>>>,
+	metrics: &Option<Metrics>,
+	started: time::Instant,
+	randomness: Randomness<Hash>,
+) {
+	if let Some(metrics) = metrics {
+		metrics.time_to_threshold.observe(started.elapsed().as_secs_f64());
+	}
+	if let Some(ref randomness_tx) = randomness_tx {
+		assert!(
+			randomness_tx.send(randomness).is_ok(),
+			"problem with sending new randomness to the block proposer"
+		);
 	}
 }
 
@@ -371,7 +612,9 @@ where
 	/// the channel that goes between block import and this component.
 	/// Each such notification triggers start of a gossip on a new topic,
 	/// thus in particular a new message is being gossip by this node: its
-	/// randomness share for the new topic (i.e. new block).
+	/// randomness share for the new topic (i.e. new block). Shares that do
+	/// not arrive in time are requested from the peers, and the requests
+	/// of the peers are answered.
 	fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
 		match self.gossip_engine.lock().poll_unpin(cx) {
 			Poll::Ready(()) => {
@@ -382,78 +625,105 @@ where
 			}
 			Poll::Pending => {}
 		};
-		let new_nonce_info = match self.randomness_nonce_rx.poll_next_unpin(cx) {
-			Poll::Pending => None,
-			Poll::Ready(None) => return Poll::Ready(()),
-			Poll::Ready(new_nonce_info) => new_nonce_info,
-		};
+		while let Poll::Ready(Some(PeerReport { who, cost_benefit })) =
+			self.report_stream.poll_next_unpin(cx)
+		{
+			self.gossip_engine.lock().report(who, cost_benefit);
+		}
 
-		if new_nonce_info.is_some() {
-			let new_nonce_info = new_nonce_info.unwrap();
-			let new_nonce = new_nonce_info.nonce();
-			let topic = new_nonce.clone();
-			if !self.topics.contains_key(&topic) {
-				// received new nonce, need to fetch the corresponding rbbox
-				let maybe_rbbox = self.get_rbbox(&new_nonce_info);
-				if let Some(rbbox) = maybe_rbbox {
-					let (incoming, msg, shares) =
-						self.initialize_nonce(new_nonce_info.clone(), &rbbox);
-					let periodic_sender = futures_timer::Delay::new(INITIAL_WAIT);
-					self.topics
-						.insert(topic, (incoming, msg, periodic_sender, rbbox, shares));
-				}
+		loop {
+			match self.randomness_nonce_rx.poll_next_unpin(cx) {
+				Poll::Pending => break,
+				Poll::Ready(None) => return Poll::Ready(()),
+				Poll::Ready(Some(new_nonce_info)) => self.note_nonce(new_nonce_info),
 			}
 		}
-		let randomness_tx = self.randomness_tx.clone();
-		let threshold = self.threshold.clone() as usize;
 
-		for (_, (incoming, maybe_msg, periodic_sender, rbbox, shares)) in self.topics.iter_mut() {
-			if let Some(msg) = maybe_msg {
+		self.answer_requests(cx);
+		self.handle_responses(cx);
+
+		let this = &mut *self;
+		let threshold = this.threshold as usize;
+		let mut requested = false;
+
+		for (nonce, topic) in this.topics.iter_mut() {
+			if let Some(msg) = &topic.outgoing {
 				// msg is our share, we need to send it from time to time.
 				// This executes only if the node is in the committee.
-				while let Poll::Ready(()) = periodic_sender.poll_unpin(cx) {
-					periodic_sender.reset(SEND_INTERVAL);
+				while let Poll::Ready(()) = topic.periodic_sender.poll_unpin(cx) {
+					topic.periodic_sender.reset(this.config.send_interval);
 					msg.send();
 				}
 			}
 
-			if shares.len() < threshold {
-				let poll = incoming.poll_next_unpin(cx);
-				match poll {
+			if topic.shares.len() >= threshold {
+				continue;
+			}
+
+			// the shares were already verified by the gossip validator
+			let mut received = Vec::new();
+			loop {
+				match topic.incoming.poll_next_unpin(cx) {
 					Poll::Ready(Some(notification)) => {
-						let GossipMessage::<B> { message, .. } =
-							GossipMessage::<B>::decode(&mut &notification.message[..]).unwrap();
-						let share = RandomnessShare::decode(&mut &*message.share).unwrap();
-						if rbbox.verify_randomness_share(&share) {
-							shares.push(share);
+						let decoded = GossipMessage::<B>::decode(&mut &notification.message[..])
+							.and_then(|gm| RandomnessShare::decode(&mut &*gm.message.share));
+						match decoded {
+							Ok(share) => received.push(share),
+							Err(e) => info!(
+								target: RB_PROTOCOL_NAME,
+								"Skipping malformed share: {}",
+								e.what()
+							),
 						}
 					}
-					Poll::Ready(None) => info!(
-						target: RB_PROTOCOL_NAME,
-						"poll_next_unpin returned Ready(None) ==> investigate!"
-					),
-					Poll::Pending => {}
+					Poll::Ready(None) => {
+						info!(
+							target: RB_PROTOCOL_NAME,
+							"poll_next_unpin returned Ready(None) ==> investigate!"
+						);
+						break;
+					}
+					Poll::Pending => break,
 				}
+			}
 
-				if shares.len() == threshold {
-					assert!(shares
-						.iter()
-						.take(shares.len() - 1)
-						.enumerate()
-						.all(|(i, s)| !shares[i + 1..].contains(s)));
-
-					let randomness = rbbox.combine_shares(shares);
+			if let Some(randomness) = topic.add_shares(received, threshold) {
+				// When randomness succesfully combined, notify block proposer
+				deliver_randomness(&this.randomness_tx, &this.metrics, topic.started, randomness);
+				continue;
+			}
 
-					// When randomness succesfully combined, notify block proposer
-					if let Some(ref randomness_tx) = randomness_tx {
-						assert!(
-							randomness_tx.send(randomness).is_ok(),
-							"problem with sending new randomness to the block proposer"
-						);
+			// ask the peers which delivered shares of the missing members before
+			while let Poll::Ready(()) = topic.request_timer.poll_unpin(cx) {
+				topic.request_timer.reset(this.config.request_interval);
+				let creators = topic.missing_creators();
+				let peers = this.validator.peers_of(&creators);
+				let request = ShareRequest {
+					nonce: nonce.clone(),
+					creators: creators.clone(),
+				}
+				.encode();
+				for peer in peers.into_iter().take(this.config.max_requested_peers) {
+					let nonce = nonce.clone();
+					let creators = creators.clone();
+					let response = this.network.request_shares(peer.clone(), request.clone());
+					this.pending_responses.push(
+						response
+							.map(move |result| (nonce, creators, peer, result))
+							.boxed(),
+					);
+					requested = true;
+					if let Some(metrics) = &this.metrics {
+						metrics.share_requests_sent.inc();
 					}
 				}
 			}
 		}
+
+		// the responses to the new requests need to wake up the task
+		if requested {
+			this.handle_responses(cx);
+		}
 		return Poll::Pending;
 	}
 }
@@ -463,42 +733,36 @@ mod tests {
 	use super::*;
 	use futures::channel::mpsc::channel;
 	use futures::channel::mpsc::{unbounded, UnboundedSender};
-	use jsonrpc_core::IoHandler;
-	use jsonrpc_http_server::{RestApi, Server, ServerBuilder};
-	use sc_network::{Event, ReputationChange};
-	use sc_network_gossip::Network;
-	use sc_rpc::offchain::{Offchain, OffchainApi};
-	use sc_rpc_api::DenyUnsafe;
+	use futures::channel::oneshot;
+	use sc_network::{Event, PeerId, ReputationChange};
+	use sc_network_gossip::{Network, Validator, ValidatorContext};
 	use sp_api::{ApiRef, ProvideRuntimeApi};
-	use sp_core::{offchain::storage::InMemOffchainStorage, offchain::StorageKind, Bytes};
-	use sp_dkg::{AuthIndex, DKGApi, Scalar, VerifyKey};
-	use sp_runtime::traits::Block as BlockT;
+	use sp_dkg::{AuthIndex, DKGApi, DkgPhase, EpochIndex, Scalar, VerifyKey};
+	use sp_keystore::testing::KeyStore;
+	use sp_randomness_beacon::{Round, SeedFreshness};
+	use sp_runtime::traits::{BlakeTwo256, Block as BlockT};
 	use sp_runtime::ConsensusEngineId;
 	use std::borrow::Cow;
 	use std::sync::{Arc, Mutex};
 	use substrate_test_runtime_client::runtime::{Block, BlockNumber, Hash};
 
-	const KEY: &[u8; 3] = b"key";
-
-	fn serve() -> Server {
-		let builder = ServerBuilder::new(io()).rest_api(RestApi::Unsecure);
-		builder.start_http(&"127.0.0.1:0".parse().unwrap()).unwrap()
-	}
-
-	fn io() -> IoHandler {
-		let mut io = IoHandler::default();
-		let storage = InMemOffchainStorage::default();
-		let offchain = Offchain::new(storage, DenyUnsafe::No);
-		let key = Bytes(KEY.to_vec());
-		let value = Bytes(Scalar::from_raw([1, 7, 2, 9]).to_bytes().to_vec());
-
-		assert!(offchain
-			.set_local_storage(StorageKind::PERSISTENT, key, value)
-			.is_ok());
-
-		io.extend_with(sc_rpc::offchain::OffchainApi::to_delegate(offchain));
-
-		io
+	const RAW_SECRET: RawSecret = [1, 7, 2, 9];
+	const BOB_SECRET: RawSecret = [3, 1, 4, 1];
+
+	// a keystore holding the secret share as put there by the DKG offchain worker
+	fn keystore() -> SyncCryptoStorePtr {
+		let keystore = KeyStore::new();
+		let verify_key = VerifyKey::from_raw_secret(RAW_SECRET);
+		let secret = Scalar::from_raw(RAW_SECRET).to_bytes();
+		assert!(SyncCryptoStore::insert_secret(
+			&keystore,
+			SECRET_KEY_TYPE,
+			&verify_key.encode(),
+			&secret
+		)
+		.is_ok());
+
+		Arc::new(keystore)
 	}
 
 	#[derive(Clone, Default)]
@@ -509,6 +773,30 @@ mod tests {
 	#[derive(Clone, Default)]
 	struct TestNetworkInner {
 		event_senders: Vec<UnboundedSender<Event>>,
+		requests: Vec<(PeerId, Vec<u8>)>,
+		response: Option<Vec<u8>>,
+	}
+
+	impl ShareRequests for TestNetwork {
+		fn request_shares(
+			&self,
+			target: PeerId,
+			request: Vec<u8>,
+		) -> BoxFuture<'static, Result<Vec<u8>, RequestFailure>> {
+			let mut inner = self.inner.lock().unwrap();
+			inner.requests.push((target, request));
+			let response = inner.response.clone().ok_or(RequestFailure::Refused);
+			future::ready(response).boxed()
+		}
+	}
+
+	struct TestContext;
+
+	impl ValidatorContext<Block> for TestContext {
+		fn broadcast_topic(&mut self, _topic: Hash, _force: bool) {}
+		fn broadcast_message(&mut self, _topic: Hash, _message: Vec<u8>, _force: bool) {}
+		fn send_message(&mut self, _who: &PeerId, _message: Vec<u8>) {}
+		fn send_topic(&mut self, _who: &PeerId, _topic: Hash, _force: bool) {}
 	}
 
 	impl<B: BlockT> Network<B> for TestNetwork {
@@ -543,9 +831,9 @@ mod tests {
 		threshold: u64,
 		verification_keys: Option<Vec<VerifyKey>>,
 		public_keybox_parts: Option<(Option<AuthIndex>, Vec<VerifyKey>, VerifyKey, u64)>,
-		storage_key_sk: Option<Vec<u8>>,
 		beacon_start: NumberFor<Block>,
 		beacon_period: NumberFor<Block>,
+		chained_rounds: bool,
 	}
 
 	impl TestApi {
@@ -555,7 +843,6 @@ mod tests {
 			threshold: u64,
 			verification_keys: Option<Vec<VerifyKey>>,
 			public_keybox_parts: Option<(Option<AuthIndex>, Vec<VerifyKey>, VerifyKey, u64)>,
-			storage_key_sk: Option<Vec<u8>>,
 			beacon_start: NumberFor<Block>,
 			beacon_period: NumberFor<Block>,
 		) -> Self {
@@ -565,9 +852,9 @@ mod tests {
 				threshold,
 				verification_keys,
 				public_keybox_parts,
-				storage_key_sk,
 				beacon_start,
 				beacon_period,
+				chained_rounds: false,
 			}
 		}
 	}
@@ -610,8 +897,24 @@ mod tests {
 				self.inner.public_keybox_parts.clone()
 			}
 
-			fn storage_key_sk() -> Option<Vec<u8>>{
-				self.inner.storage_key_sk.clone()
+			fn current_epoch() -> EpochIndex {
+				0
+			}
+
+			fn epoch_master_key(_epoch: EpochIndex) -> Option<VerifyKey> {
+				self.inner.master_verification_key.clone()
+			}
+
+			fn dkg_phase() -> DkgPhase {
+				DkgPhase::Completed
+			}
+
+			fn qualified_dealers() -> Vec<AuthIndex> {
+				Vec::new()
+			}
+
+			fn active_threshold() -> u64 {
+				self.inner.threshold.clone()
 			}
 		}
 
@@ -623,6 +926,42 @@ mod tests {
 			fn beacon_period(&self) -> NumberFor<Block> {
 				self.inner.beacon_period.clone()
 			}
+
+			fn signature_version() -> SignatureVersion {
+				SignatureVersion::LATEST
+			}
+
+			fn latest_randomness() -> Option<(NumberFor<Block>, Randomness<Hash>)> {
+				None
+			}
+
+			fn seed_freshness() -> Option<SeedFreshness<NumberFor<Block>>> {
+				None
+			}
+
+			fn round_randomness(_round: Option<Round>) -> Option<(Round, Randomness<Hash>)> {
+				None
+			}
+
+			fn history_index(_round: Round) -> u32 {
+				0
+			}
+
+			fn author_vrf_key(_pre_digests: Vec<(ConsensusEngineId, Vec<u8>)>) -> Option<Vec<u8>> {
+				None
+			}
+
+			fn chained_nonce(round: Round) -> Option<Hash> {
+				if self.inner.chained_rounds {
+					Some(sp_randomness_beacon::chained_nonce::<BlakeTwo256>(round, None))
+				} else {
+					None
+				}
+			}
+
+			fn grace_period() -> u64 {
+				0
+			}
 		}
 	}
 
@@ -630,25 +969,22 @@ mod tests {
 	fn starts_messaging_on_nonce_notification() {
 		let threshold = 1;
 
-		let server = serve();
 		let (mut ni_tx, ni_rx) = channel(1);
 		let (tx, _rx) = std::sync::mpsc::channel();
 		let randomness_tx = Some(tx);
 
 		let public_keybox_parts = Some((
 			Some(0),
-			vec![VerifyKey::default()],
-			VerifyKey::default(),
+			vec![VerifyKey::from_raw_secret(RAW_SECRET)],
+			VerifyKey::from_raw_secret(RAW_SECRET),
 			threshold,
 		));
-		let storage_key_sk = Some(KEY.to_vec());
 		let runtime_api = Arc::new(TestApi::new(
 			None,
 			0,
 			threshold,
 			None,
 			public_keybox_parts,
-			storage_key_sk,
 			0,
 			1,
 		));
@@ -660,7 +996,8 @@ mod tests {
 			network.clone(),
 			randomness_tx,
 			runtime_api,
-			0,
+			Some(keystore()),
+			Config::default(),
 		);
 
 		let ni = NonceInfo {
@@ -680,6 +1017,214 @@ mod tests {
 			Poll::Ready(())
 		}));
 		assert!(alice_rg.topics.contains_key(&ni.nonce));
-		server.close();
+		// the share read from the keystore was sent out for the new nonce
+		assert!(alice_rg.topics[&ni.nonce].outgoing.is_some());
+		// the validator accepts the shares for the nonce until it is pruned
+		assert_eq!(alice_rg.height_queue.peek(), Some(&ni));
+		let mut expired = Validator::<Block>::message_expired(&*alice_rg.validator);
+		assert!(!expired(ni.nonce, &[]));
+	}
+
+	#[test]
+	fn gossips_shares_under_chained_nonce() {
+		let threshold = 1;
+		let (mut ni_tx, ni_rx) = channel(1);
+		let (tx, rx) = std::sync::mpsc::channel();
+		let public_keybox_parts = Some((
+			Some(0),
+			vec![VerifyKey::from_raw_secret(RAW_SECRET)],
+			VerifyKey::from_raw_secret(RAW_SECRET),
+			threshold,
+		));
+		let mut api = TestApi::new(None, 0, threshold, None, public_keybox_parts, 0, 1);
+		api.chained_rounds = true;
+		let mut alice_rg = RandomnessGossip::new(
+			threshold,
+			ni_rx,
+			TestNetwork::default(),
+			Some(tx),
+			Arc::new(api),
+			Some(keystore()),
+			Config::default(),
+		);
+
+		// the block at height 0 is the nonce block of round 1
+		let ni = NonceInfo::new(Hash::repeat_byte(7), 0);
+		assert!(ni_tx.try_send(ni.clone()).is_ok());
+		poll_times(&mut alice_rg, 50);
+
+		let chained = sp_randomness_beacon::chained_nonce::<BlakeTwo256>(1, None);
+		assert!(!alice_rg.topics.contains_key(&ni.nonce));
+		assert!(alice_rg.topics.contains_key(&chained));
+		assert_eq!(alice_rg.height_queue.peek(), Some(&NonceInfo::new(chained, 0)));
+		// with the threshold of one the own share is the seed
+		assert_eq!(rx.try_recv().unwrap().nonce(), chained);
+	}
+
+	fn poll_times(rg: &mut RandomnessGossip<Block, TestApi>, times: usize) {
+		futures::executor::block_on(futures::future::poll_fn(|cx| {
+			for _ in 0..times {
+				if let Poll::Ready(()) = rg.poll_unpin(cx) {
+					unreachable!("As long as network is alive, RandomnessGossip should go on.");
+				}
+			}
+			Poll::Ready(())
+		}));
+	}
+
+	// the keys of a committee of two, in which the local node is the first member
+	fn two_member_api(threshold: u64) -> Arc<TestApi> {
+		let verification_keys = vec![
+			VerifyKey::from_raw_secret(RAW_SECRET),
+			VerifyKey::from_raw_secret(BOB_SECRET),
+		];
+		let public_keybox_parts = Some((
+			Some(0),
+			verification_keys,
+			VerifyKey::from_raw_secret(RAW_SECRET),
+			threshold,
+		));
+		Arc::new(TestApi::new(
+			None,
+			0,
+			threshold,
+			None,
+			public_keybox_parts,
+			0,
+			1,
+		))
+	}
+
+	fn bob_rbbox() -> RBBox<Hash> {
+		RBBox::new(
+			Some(1),
+			Some(BOB_SECRET),
+			vec![
+				VerifyKey::from_raw_secret(RAW_SECRET),
+				VerifyKey::from_raw_secret(BOB_SECRET),
+			],
+			VerifyKey::from_raw_secret(RAW_SECRET),
+			2,
+			SignatureVersion::LATEST,
+		)
+	}
+
+	#[test]
+	fn answers_requests_with_collected_shares() {
+		let (mut ni_tx, ni_rx) = channel(1);
+		let (mut request_tx, request_rx) = channel(2);
+		let (tx, _rx) = std::sync::mpsc::channel();
+		let config = Config {
+			incoming_requests: Some(request_rx),
+			..Default::default()
+		};
+		let mut alice_rg = RandomnessGossip::new(
+			2,
+			ni_rx,
+			TestNetwork::default(),
+			Some(tx),
+			two_member_api(2),
+			Some(keystore()),
+			config,
+		);
+
+		let ni = NonceInfo::new(Hash::repeat_byte(1), BlockNumber::default());
+		assert!(ni_tx.try_send(ni.clone()).is_ok());
+		poll_times(&mut alice_rg, 5);
+
+		let mut request = |nonce, creators| {
+			let (pending_response, response) = oneshot::channel();
+			let payload = ShareRequest { nonce, creators }.encode();
+			assert!(request_tx
+				.try_send(IncomingRequest {
+					peer: PeerId::random(),
+					payload,
+					pending_response,
+				})
+				.is_ok());
+			response
+		};
+		let mut own = request(ni.nonce, vec![0, 1]);
+		let mut missing = request(ni.nonce, vec![1]);
+		poll_times(&mut alice_rg, 5);
+
+		let decode = |response: Vec<u8>| {
+			Vec::<RandomnessShare<Hash>>::decode(&mut &response[..]).unwrap()
+		};
+		let shares = decode(own.try_recv().unwrap().unwrap());
+		assert_eq!(shares.len(), 1);
+		assert_eq!(shares[0].creator(), 0);
+		assert!(decode(missing.try_recv().unwrap().unwrap()).is_empty());
+
+		// requests for unknown nonces are answered with no shares, malformed ones are refused
+		let mut unknown = request(Hash::repeat_byte(2), vec![0]);
+		let (pending_response, mut malformed) = oneshot::channel();
+		assert!(request_tx
+			.try_send(IncomingRequest {
+				peer: PeerId::random(),
+				payload: vec![1, 2, 3],
+				pending_response,
+			})
+			.is_ok());
+		poll_times(&mut alice_rg, 5);
+		assert!(decode(unknown.try_recv().unwrap().unwrap()).is_empty());
+		assert!(malformed.try_recv().is_err());
+	}
+
+	#[test]
+	fn requests_missing_shares_from_peers() {
+		let (mut ni_tx, ni_rx) = channel(1);
+		let (tx, rx) = std::sync::mpsc::channel();
+		let network = TestNetwork::default();
+		let config = Config {
+			request_after: time::Duration::from_secs(0),
+			..Default::default()
+		};
+		let mut alice_rg = RandomnessGossip::new(
+			2,
+			ni_rx,
+			network.clone(),
+			Some(tx),
+			two_member_api(2),
+			Some(keystore()),
+			config,
+		);
+
+		// bob gossiped his share of an earlier nonce
+		let bob = PeerId::random();
+		let (earlier, nonce) = (Hash::repeat_byte(1), Hash::repeat_byte(2));
+		alice_rg
+			.validator
+			.note_topic(earlier, Arc::new(bob_rbbox()));
+		let message = GossipMessage::<Block> {
+			nonce: earlier,
+			message: Message {
+				share: bob_rbbox()
+					.generate_randomness_share(earlier)
+					.unwrap()
+					.encode(),
+			},
+		};
+		alice_rg
+			.validator
+			.validate(&mut TestContext, &bob, &message.encode());
+
+		let bob_share = bob_rbbox().generate_randomness_share(nonce).unwrap();
+		network.inner.lock().unwrap().response = Some(vec![bob_share].encode());
+		let ni = NonceInfo::new(nonce, BlockNumber::default());
+		assert!(ni_tx.try_send(ni).is_ok());
+		poll_times(&mut alice_rg, 5);
+
+		let request = ShareRequest {
+			nonce,
+			creators: vec![1],
+		};
+		assert_eq!(
+			network.inner.lock().unwrap().requests,
+			vec![(bob, request.encode())]
+		);
+		// the share in the response completes the randomness
+		assert_eq!(rx.try_recv().unwrap().nonce(), nonce);
+		assert!(alice_rg.pending_responses.is_empty());
 	}
 }
@@ -0,0 +1,50 @@
+//! Prometheus metrics of the collection of randomness shares.
+
+use prometheus_endpoint::{register, Counter, Histogram, HistogramOpts, PrometheusError, Registry, U64};
+
+#[derive(Clone)]
+pub(crate) struct Metrics {
+	pub(crate) time_to_threshold: Histogram,
+	pub(crate) share_requests_sent: Counter<U64>,
+	pub(crate) requested_shares_received: Counter<U64>,
+	pub(crate) share_requests_served: Counter<U64>,
+}
+
+impl Metrics {
+	pub(crate) fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			time_to_threshold: register(
+				Histogram::with_opts(
+					HistogramOpts::new(
+						"randomness_beacon_time_to_threshold",
+						"Time from the import of a nonce until threshold many shares of it are \
+						collected, in seconds",
+					)
+					.buckets(vec![0.1, 0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0, 32.0]),
+				)?,
+				registry,
+			)?,
+			share_requests_sent: register(
+				Counter::new(
+					"randomness_beacon_share_requests_sent",
+					"Number of requests for missing shares sent to peers",
+				)?,
+				registry,
+			)?,
+			requested_shares_received: register(
+				Counter::new(
+					"randomness_beacon_requested_shares_received",
+					"Number of valid shares received in responses to requests",
+				)?,
+				registry,
+			)?,
+			share_requests_served: register(
+				Counter::new(
+					"randomness_beacon_share_requests_served",
+					"Number of requests for shares answered to peers",
+				)?,
+				registry,
+			)?,
+		})
+	}
+}
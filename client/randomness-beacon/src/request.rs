@@ -0,0 +1,72 @@
+//! Requests for the shares of a nonce sent directly to the peers.
+//!
+//! Gossip alone may be slow to deliver the last shares needed to reach the threshold, e.g. when
+//! a share was sent before the node imported the block of its nonce. A node missing shares of a
+//! nonce asks the peers from which it received the shares of the missing members before, over
+//! a request-response protocol registered with the network. The answer carries the shares of
+//! the requested members that the peer has collected, which are verified together in a batch.
+
+use codec::{Decode, Encode};
+use futures::{
+	channel::mpsc,
+	future::{BoxFuture, FutureExt},
+};
+use sc_network::{
+	config::{IncomingRequest, RequestResponseConfig},
+	ExHashT, NetworkService, PeerId, RequestFailure,
+};
+use sp_runtime::traits::Block as BlockT;
+use std::{sync::Arc, time::Duration};
+
+/// The name of the request-response protocol for randomness shares.
+pub const PROTOCOL_NAME: &'static str = "/randomness_beacon/shares/1";
+
+// a request lists at most all the members of a committee, a response carries their shares
+const MAX_REQUEST_SIZE: u64 = 16 * 1024;
+const MAX_RESPONSE_SIZE: u64 = 1024 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const INBOUND_QUEUE_SIZE: usize = 64;
+
+/// A request for the shares of the given members for the nonce. The response is the encoded
+/// `Vec<RandomnessShare<Nonce>>` of the requested shares known to the peer.
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub struct ShareRequest<Nonce> {
+	pub nonce: Nonce,
+	pub creators: Vec<u64>,
+}
+
+/// The configuration of the protocol to be added to the `request_response_protocols` of the
+/// network, together with the stream of the requests received by the node which is to be
+/// passed to `RandomnessGossip` in its `Config`.
+pub fn protocol_config() -> (RequestResponseConfig, mpsc::Receiver<IncomingRequest>) {
+	let (tx, rx) = mpsc::channel(INBOUND_QUEUE_SIZE);
+	let config = RequestResponseConfig {
+		name: PROTOCOL_NAME.into(),
+		max_request_size: MAX_REQUEST_SIZE,
+		max_response_size: MAX_RESPONSE_SIZE,
+		request_timeout: REQUEST_TIMEOUT,
+		inbound_queue: Some(tx),
+	};
+	(config, rx)
+}
+
+/// A network able to send requests for randomness shares.
+pub trait ShareRequests {
+	/// Sends the encoded `ShareRequest` to the peer, resolves to its response.
+	fn request_shares(
+		&self,
+		target: PeerId,
+		request: Vec<u8>,
+	) -> BoxFuture<'static, Result<Vec<u8>, RequestFailure>>;
+}
+
+impl<B: BlockT + 'static, H: ExHashT> ShareRequests for Arc<NetworkService<B, H>> {
+	fn request_shares(
+		&self,
+		target: PeerId,
+		request: Vec<u8>,
+	) -> BoxFuture<'static, Result<Vec<u8>, RequestFailure>> {
+		let service = self.clone();
+		async move { service.request(target, PROTOCOL_NAME, request).await }.boxed()
+	}
+}
@@ -0,0 +1,72 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! DKG RPC errors.
+
+use crate::errors;
+use jsonrpc_core as rpc;
+
+/// DKG RPC Result type.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// DKG RPC errors.
+#[derive(Debug, derive_more::Display, derive_more::From)]
+pub enum Error {
+	/// Runtime api call error.
+	#[display(fmt="Runtime api error: {}", _0)]
+	RuntimeApi(String),
+	/// The secret share could not be read from the keystore.
+	#[display(fmt="Keystore error: {}", _0)]
+	#[from(ignore)]
+	Keystore(String),
+	/// The proof of a seed could not be created.
+	#[display(fmt="Proof error: {}", _0)]
+	#[from(ignore)]
+	Proof(String),
+	/// The runtime does not provide the api version needed by the call.
+	#[display(fmt="The runtime does not support {} of version {}.", _0, _1)]
+	UnsupportedApi(&'static str, u32),
+	/// Call to an unsafe RPC was denied.
+	UnsafeRpcCalled(crate::policy::UnsafeRpcError),
+}
+
+impl std::error::Error for Error {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Error::UnsafeRpcCalled(ref err) => Some(err),
+			_ => None,
+		}
+	}
+}
+
+/// Base error code for all DKG errors.
+const BASE_ERROR: i64 = 6000;
+
+impl From<Error> for rpc::Error {
+	fn from(e: Error) -> Self {
+		match e {
+			Error::UnsupportedApi(..) => rpc::Error {
+				code: rpc::ErrorCode::ServerError(BASE_ERROR + 1),
+				message: format!("{}", e),
+				data: None,
+			},
+			Error::UnsafeRpcCalled(e) => e.into(),
+			e => errors::internal(e),
+		}
+	}
+}
@@ -0,0 +1,162 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! DKG and randomness beacon API.
+
+pub mod error;
+
+use jsonrpc_core::Result as RpcResult;
+use jsonrpc_derive::rpc;
+use jsonrpc_pubsub::{typed::Subscriber, SubscriptionId};
+use serde::{Deserialize, Serialize};
+use sp_core::Bytes;
+use sp_dkg::{AuthIndex, DkgPhase, EpochIndex, SignatureVersion};
+use self::error::Result;
+
+pub use self::gen_client::Client as DkgClient;
+
+/// The phase of the DKG of an epoch.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EpochPhase {
+	/// The current epoch.
+	pub epoch: EpochIndex,
+	/// The phase of the DKG run of the epoch.
+	pub phase: DkgPhase,
+}
+
+/// The active verification keys of the committee, SCALE encoded.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerificationKeys {
+	/// The master verification key.
+	pub master_key: Bytes,
+	/// The verification keys of the members, in the order of their indices.
+	pub member_keys: Vec<Bytes>,
+	/// The number of shares needed to sign.
+	pub threshold: u64,
+}
+
+/// A randomness seed of the beacon.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BeaconRandomness<Number, Hash> {
+	/// The block at which the seed was set.
+	pub block_number: Number,
+	/// The hash of the block which was signed.
+	pub nonce: Hash,
+	/// The version of the signature.
+	pub version: SignatureVersion,
+	/// The SCALE encoded threshold signature of the nonce.
+	pub signature: Bytes,
+	/// Whether a beacon block was produced without a new seed since this one was set.
+	pub stale: bool,
+}
+
+/// How fresh the randomness seed of the beacon is.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeedFreshness<Number> {
+	/// The block at which the seed was set.
+	pub set_at: Number,
+	/// The number of blocks since the seed was set.
+	pub age: Number,
+	/// The number of beacon blocks produced without a new seed since the seed was set.
+	pub missed: u32,
+}
+
+/// The secret share of the local node. It never contains the secret itself.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareStatus {
+	/// The current epoch.
+	pub epoch: EpochIndex,
+	/// The phase of the DKG run of the epoch.
+	pub phase: DkgPhase,
+	/// The index of the local node in the committee whose keys are active.
+	pub member_index: Option<AuthIndex>,
+	/// Whether the offchain storage of the node holds the secret share of the active keys.
+	pub has_secret_share: bool,
+	/// Whether the stored secret share matches the verification key of the member, `None` if
+	/// there is nothing to compare.
+	pub share_matches_key: Option<bool>,
+}
+
+/// DKG and randomness beacon RPC API.
+#[rpc]
+pub trait DkgApi<Number, Hash> {
+	/// RPC metadata
+	type Metadata;
+
+	/// Get the current epoch and the phase of its DKG.
+	#[rpc(name = "dkg_phase")]
+	fn phase(&self, hash: Option<Hash>) -> Result<EpochPhase>;
+
+	/// Get the dealers of the current epoch that were not disqualified.
+	#[rpc(name = "dkg_qualifiedDealers")]
+	fn qualified_dealers(&self, hash: Option<Hash>) -> Result<Vec<AuthIndex>>;
+
+	/// Get the active master and per-member verification keys.
+	#[rpc(name = "dkg_verificationKeys")]
+	fn verification_keys(&self, hash: Option<Hash>) -> Result<Option<VerificationKeys>>;
+
+	/// Get the status of the secret share of the local node.
+	///
+	/// This method is unsafe, as it reveals which committee member the node is.
+	#[rpc(name = "dkg_shareStatus")]
+	fn share_status(&self, hash: Option<Hash>) -> Result<ShareStatus>;
+
+	/// Get the latest randomness seed of the beacon.
+	#[rpc(name = "beacon_latestRandomness")]
+	fn latest_randomness(&self, hash: Option<Hash>) -> Result<Option<BeaconRandomness<Number, Hash>>>;
+
+	/// Get how fresh the randomness seed of the beacon is.
+	#[rpc(name = "beacon_seedFreshness")]
+	fn seed_freshness(&self, hash: Option<Hash>) -> Result<Option<SeedFreshness<Number>>>;
+
+	/// Get the SCALE encoded `BeaconProof` of the seed of the round against the given block,
+	/// the latest finalized one by default, `None` if the seed is no longer kept.
+	///
+	/// The proof is checked with the `sp-randomness-beacon-verifier` crate.
+	#[rpc(name = "beacon_proveRound")]
+	fn prove_round(&self, round: u64, hash: Option<Hash>) -> Result<Option<Bytes>>;
+
+	/// New randomness seed subscription.
+	#[pubsub(
+		subscription = "beacon_randomness",
+		subscribe,
+		name = "beacon_subscribeRandomness"
+	)]
+	fn subscribe_randomness(
+		&self,
+		metadata: Self::Metadata,
+		subscriber: Subscriber<BeaconRandomness<Number, Hash>>,
+	);
+
+	/// Unsubscribe from new randomness seed subscription.
+	#[pubsub(
+		subscription = "beacon_randomness",
+		unsubscribe,
+		name = "beacon_unsubscribeRandomness"
+	)]
+	fn unsubscribe_randomness(
+		&self,
+		metadata: Option<Self::Metadata>,
+		id: SubscriptionId,
+	) -> RpcResult<bool>;
+}
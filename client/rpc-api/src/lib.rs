@@ -32,6 +32,7 @@ pub use policy::DenyUnsafe;
 
 pub mod author;
 pub mod chain;
+pub mod dkg;
 pub mod offchain;
 pub mod state;
 pub mod child_state;
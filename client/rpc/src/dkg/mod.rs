@@ -0,0 +1,364 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! DKG and randomness beacon API.
+
+#[cfg(test)]
+mod tests;
+
+use std::{marker::PhantomData, sync::Arc};
+
+use codec::{Decode, Encode};
+use futures::{future, StreamExt, TryStreamExt};
+use jsonrpc_pubsub::{typed::Subscriber, SubscriptionId, manager::SubscriptionManager};
+use log::warn;
+use rpc::{Result as RpcResult, futures::{Future, Sink, Stream}};
+use sc_client_api::{BlockchainEvents, ProofProvider};
+use sc_rpc_api::DenyUnsafe;
+use sp_api::{ApiExt, ProvideRuntimeApi};
+use sp_blockchain::HeaderBackend;
+use sp_core::Bytes;
+use sp_dkg::{AuthIndex, DKGApi, RawSecret, VerifyKey, SECRET_KEY_TYPE};
+use sp_keystore::{SyncCryptoStore, SyncCryptoStorePtr};
+use sp_randomness_beacon::RandomnessBeaconApi;
+use sp_randomness_beacon_verifier::{history_storage_key, verifier_storage_key, BeaconProof};
+use sp_runtime::{generic::BlockId, traits::{Block as BlockT, HashFor, Header, NumberFor}};
+use sp_state_machine::read_proof_check;
+
+use self::error::{Error, Result};
+
+pub use sc_rpc_api::dkg::*;
+
+/// DKG and randomness beacon API.
+pub struct Dkg<Block, Client> {
+	client: Arc<Client>,
+	keystore: Option<SyncCryptoStorePtr>,
+	subscriptions: SubscriptionManager,
+	deny_unsafe: DenyUnsafe,
+	_marker: PhantomData<Block>,
+}
+
+impl<Block, Client> Dkg<Block, Client> {
+	/// Create new instance of DKG API. The secret share of the node is looked up in the
+	/// given keystore, where the offchain worker of the DKG pallet keeps it.
+	pub fn new(
+		client: Arc<Client>,
+		keystore: Option<SyncCryptoStorePtr>,
+		subscriptions: SubscriptionManager,
+		deny_unsafe: DenyUnsafe,
+	) -> Self {
+		Dkg {
+			client,
+			keystore,
+			subscriptions,
+			deny_unsafe,
+			_marker: PhantomData,
+		}
+	}
+}
+
+impl<Block, Client> Dkg<Block, Client> where
+	Block: BlockT + 'static,
+	Client: ProvideRuntimeApi<Block> + HeaderBackend<Block> + 'static,
+	Client::Api: DKGApi<Block>,
+{
+	fn block_or_best(&self, hash: Option<Block::Hash>) -> BlockId<Block> {
+		BlockId::Hash(hash.unwrap_or_else(|| self.client.info().best_hash))
+	}
+
+	// the phase and the qualified dealers were added in version 2 of `DKGApi`
+	fn ensure_dkg_api_v2(&self, at: &BlockId<Block>) -> Result<()> {
+		let runtime_api = self.client.runtime_api();
+		match runtime_api.has_api_with::<dyn DKGApi<Block, Error = ()>, _>(at, |v| v >= 2) {
+			Ok(true) => Ok(()),
+			Ok(false) => Err(Error::UnsupportedApi("DKGApi", 2)),
+			Err(e) => Err(api_err(e)),
+		}
+	}
+}
+
+impl<Block, Client> DkgApi<NumberFor<Block>, Block::Hash> for Dkg<Block, Client> where
+	Block: BlockT + 'static,
+	Client: ProvideRuntimeApi<Block> + HeaderBackend<Block> + BlockchainEvents<Block>,
+	Client: ProofProvider<Block> + Send + Sync + 'static,
+	Client::Api: DKGApi<Block> + RandomnessBeaconApi<Block>,
+{
+	type Metadata = crate::Metadata;
+
+	fn phase(&self, hash: Option<Block::Hash>) -> Result<EpochPhase> {
+		let at = self.block_or_best(hash);
+		self.ensure_dkg_api_v2(&at)?;
+		let runtime_api = self.client.runtime_api();
+
+		Ok(EpochPhase {
+			epoch: runtime_api.current_epoch(&at).map_err(api_err)?,
+			phase: runtime_api.dkg_phase(&at).map_err(api_err)?,
+		})
+	}
+
+	fn qualified_dealers(&self, hash: Option<Block::Hash>) -> Result<Vec<AuthIndex>> {
+		let at = self.block_or_best(hash);
+		self.ensure_dkg_api_v2(&at)?;
+
+		self.client.runtime_api().qualified_dealers(&at).map_err(api_err)
+	}
+
+	fn verification_keys(&self, hash: Option<Block::Hash>) -> Result<Option<VerificationKeys>> {
+		let at = self.block_or_best(hash);
+		let runtime_api = self.client.runtime_api();
+
+		let master_key = match runtime_api.master_verification_key(&at).map_err(api_err)? {
+			Some(key) => key,
+			None => return Ok(None),
+		};
+		let member_keys = match runtime_api.verification_keys(&at).map_err(api_err)? {
+			Some(keys) => keys,
+			None => return Ok(None),
+		};
+		// the threshold of the running epoch differs from the one of the active keys while
+		// they are reshared, runtimes before version 4 only know the former
+		let has_api_v4 =
+			runtime_api.has_api_with::<dyn DKGApi<Block, Error = ()>, _>(&at, |v| v >= 4);
+		let threshold = match has_api_v4 {
+			Ok(true) => runtime_api.active_threshold(&at),
+			Ok(false) => runtime_api.threshold(&at),
+			Err(e) => Err(e),
+		}.map_err(api_err)?;
+
+		Ok(Some(VerificationKeys {
+			master_key: master_key.encode().into(),
+			member_keys: member_keys.iter().map(|key| key.encode().into()).collect(),
+			threshold,
+		}))
+	}
+
+	fn share_status(&self, hash: Option<Block::Hash>) -> Result<ShareStatus> {
+		self.deny_unsafe.check_if_safe()?;
+
+		let at = self.block_or_best(hash);
+		self.ensure_dkg_api_v2(&at)?;
+		let runtime_api = self.client.runtime_api();
+
+		// the index is the one in the committee holding the active keys, not the running one
+		let (member_index, member_key) = match runtime_api.public_keybox_parts(&at).map_err(api_err)? {
+			Some((Some(ix), keys, _, _)) => (Some(ix), keys.get(ix as usize).cloned()),
+			_ => (None, None),
+		};
+
+		// the share is kept in the keystore under the verification key of the member
+		let secret = match (&member_key, &self.keystore) {
+			(Some(key), Some(keystore)) =>
+				SyncCryptoStore::secret(&**keystore, SECRET_KEY_TYPE, &key.encode())
+					.map_err(|e| Error::Keystore(format!("{:?}", e)))?,
+			_ => None,
+		};
+
+		// only the verification key derived from the secret leaves this function
+		let share_matches_key = match (&secret, &member_key) {
+			(Some(secret), Some(key)) => Some(
+				RawSecret::decode(&mut &secret[..])
+					.map(|raw_secret| VerifyKey::from_raw_secret(raw_secret) == *key)
+					.unwrap_or(false),
+			),
+			_ => None,
+		};
+
+		Ok(ShareStatus {
+			epoch: runtime_api.current_epoch(&at).map_err(api_err)?,
+			phase: runtime_api.dkg_phase(&at).map_err(api_err)?,
+			member_index,
+			has_secret_share: secret.is_some(),
+			share_matches_key,
+		})
+	}
+
+	fn latest_randomness(
+		&self,
+		hash: Option<Block::Hash>,
+	) -> Result<Option<BeaconRandomness<NumberFor<Block>, Block::Hash>>> {
+		latest_randomness(&*self.client, &self.block_or_best(hash))
+	}
+
+	fn seed_freshness(
+		&self,
+		hash: Option<Block::Hash>,
+	) -> Result<Option<SeedFreshness<NumberFor<Block>>>> {
+		let at = self.block_or_best(hash);
+		let runtime_api = self.client.runtime_api();
+		let has_api = runtime_api
+			.has_api_with::<dyn RandomnessBeaconApi<Block, Error = ()>, _>(&at, |v| v >= 4);
+		match has_api {
+			Ok(true) => (),
+			Ok(false) => return Err(Error::UnsupportedApi("RandomnessBeaconApi", 4)),
+			Err(e) => return Err(api_err(e)),
+		}
+
+		Ok(runtime_api.seed_freshness(&at).map_err(api_err)?.map(|freshness| SeedFreshness {
+			set_at: freshness.set_at,
+			age: freshness.age,
+			missed: freshness.missed,
+		}))
+	}
+
+	fn prove_round(&self, round: u64, hash: Option<Block::Hash>) -> Result<Option<Bytes>> {
+		// light clients and bridges only trust finalized headers
+		let hash = hash.unwrap_or_else(|| self.client.info().finalized_hash);
+		let at = BlockId::Hash(hash);
+		let runtime_api = self.client.runtime_api();
+		let has_api = runtime_api
+			.has_api_with::<dyn RandomnessBeaconApi<Block, Error = ()>, _>(&at, |v| v >= 6);
+		match has_api {
+			Ok(true) => (),
+			Ok(false) => return Err(Error::UnsupportedApi("RandomnessBeaconApi", 6)),
+			Err(e) => return Err(api_err(e)),
+		}
+
+		let seed = match runtime_api.round_randomness(&at, Some(round)).map_err(api_err)? {
+			Some((_, seed)) => seed,
+			None => return Ok(None),
+		};
+		let history_index = runtime_api.history_index(&at, round).map_err(api_err)?;
+		let header = self.client.header(at)
+			.map_err(proof_err)?
+			.ok_or_else(|| Error::Proof(format!("Unknown block {:?}", hash)))?;
+
+		let keys = [history_storage_key(history_index), verifier_storage_key()];
+		let storage_proof = self.client.read_proof(&at, &mut keys.iter().map(|key| &key[..]))
+			.map_err(proof_err)?;
+		// the master key is the one of the verifier of the pallet, which is not necessarily
+		// the active key of the DKG
+		let master_key = read_proof_check::<HashFor<Block>, _>(
+			*header.state_root(),
+			storage_proof.clone(),
+			&keys,
+		)
+			.map_err(proof_err)?
+			.remove(&keys[1])
+			.flatten()
+			.and_then(|verifier| Vec::<u8>::decode(&mut &verifier[..]).ok())
+			.ok_or_else(|| Error::Proof("The verifier of the seeds is not set".into()))?;
+
+		// the proof keeps the bytes of the encoded signature, without their length
+		let signature = Vec::<u8>::decode(&mut &seed.signature().encode()[..]).map_err(proof_err)?;
+		let proof = BeaconProof {
+			header,
+			round,
+			nonce: seed.nonce(),
+			signature,
+			master_key,
+			history_index,
+			storage_proof: storage_proof.iter_nodes().collect(),
+		};
+
+		Ok(Some(proof.encode().into()))
+	}
+
+	fn subscribe_randomness(
+		&self,
+		_metadata: Self::Metadata,
+		subscriber: Subscriber<BeaconRandomness<NumberFor<Block>, Block::Hash>>,
+	) {
+		// the subscription must not keep the client alive
+		let client = Arc::downgrade(&self.client);
+		// a seed is new if it was set in the new best block
+		let stream = self.client.import_notification_stream()
+			.filter(|notification| future::ready(notification.is_new_best))
+			.filter_map(move |notification| {
+				let client = match client.upgrade() {
+					Some(client) => client,
+					None => return future::ready(None),
+				};
+				let at = BlockId::Hash(notification.hash);
+				let seed = match latest_randomness(&*client, &at) {
+					Ok(Some(seed)) if seed.block_number == *notification.header.number() => Some(seed),
+					Ok(_) => None,
+					Err(e) => {
+						warn!("Could not read the randomness seed at {:?}: {:?}", notification.hash, e);
+						None
+					}
+				};
+				future::ready(seed)
+			})
+			.map(|seed| Ok::<_, ()>(seed))
+			.compat();
+
+		self.subscriptions.add(subscriber, move |sink| {
+			sink
+				.sink_map_err(|e| warn!("Error sending notifications: {:?}", e))
+				.send_all(stream.map(|seed| Ok(seed)))
+				// we ignore the resulting Stream (if the first stream is over we are unsubscribed)
+				.map(|_| ())
+		});
+	}
+
+	fn unsubscribe_randomness(
+		&self,
+		_metadata: Option<Self::Metadata>,
+		id: SubscriptionId,
+	) -> RpcResult<bool> {
+		Ok(self.subscriptions.cancel(id))
+	}
+}
+
+// runtimes implementing version 2 or lower of `RandomnessBeaconApi` do not expose the seed
+fn latest_randomness<Block, Client>(
+	client: &Client,
+	at: &BlockId<Block>,
+) -> Result<Option<BeaconRandomness<NumberFor<Block>, Block::Hash>>> where
+	Block: BlockT,
+	Client: ProvideRuntimeApi<Block>,
+	Client::Api: RandomnessBeaconApi<Block>,
+{
+	let runtime_api = client.runtime_api();
+	let has_api = runtime_api
+		.has_api_with::<dyn RandomnessBeaconApi<Block, Error = ()>, _>(at, |v| v >= 3);
+	match has_api {
+		Ok(true) => (),
+		Ok(false) => return Err(Error::UnsupportedApi("RandomnessBeaconApi", 3)),
+		Err(e) => return Err(api_err(e)),
+	}
+
+	// runtimes from before version 4 do not count the missed beacon blocks
+	let stale = match runtime_api
+		.has_api_with::<dyn RandomnessBeaconApi<Block, Error = ()>, _>(at, |v| v >= 4)
+	{
+		Ok(true) => runtime_api
+			.seed_freshness(at)
+			.map_err(api_err)?
+			.map_or(false, |freshness| freshness.missed > 0),
+		_ => false,
+	};
+
+	Ok(runtime_api.latest_randomness(at).map_err(api_err)?.map(|(block_number, randomness)| {
+		BeaconRandomness {
+			block_number,
+			nonce: randomness.nonce(),
+			version: randomness.version(),
+			signature: randomness.signature().encode().into(),
+			stale,
+		}
+	}))
+}
+
+fn api_err<E: std::fmt::Debug>(e: E) -> Error {
+	Error::RuntimeApi(format!("{:?}", e))
+}
+
+fn proof_err<E: std::fmt::Debug>(e: E) -> Error {
+	Error::Proof(format!("{:?}", e))
+}
@@ -0,0 +1,413 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use super::*;
+use assert_matches::assert_matches;
+use futures::{executor, compat::Future01CompatExt};
+use sc_block_builder::BlockBuilderProvider;
+use sc_client_api::{
+	ChangesProof, FinalityNotifications, ImportNotifications, StorageEventStream, StorageProof,
+};
+use sp_api::ApiRef;
+use sp_blockchain::{BlockStatus, Info};
+use sp_core::storage::{ChildInfo, PrefixedStorageKey, StorageKey};
+use sp_dkg::{DkgPhase, EpochIndex, Scalar, ShareProvider, SignatureVersion};
+use sp_keystore::testing::KeyStore;
+use sp_randomness_beacon::{Randomness, RandomnessVerifier, Round};
+use sp_randomness_beacon_verifier::verify;
+use substrate_test_runtime_client::{
+	prelude::*,
+	sp_consensus::BlockOrigin,
+	runtime::{Block, Hash, Header},
+};
+use crate::testing::TaskExecutor;
+
+const RAW_SECRET: RawSecret = [1, 2, 3, 4];
+const HISTORY_DEPTH: u32 = 4;
+
+#[derive(Clone)]
+struct TestClient {
+	client: Arc<substrate_test_runtime_client::TestClient>,
+	member_index: Option<AuthIndex>,
+	randomness: Option<(NumberFor<Block>, Randomness<Hash>)>,
+}
+
+struct RuntimeApi {
+	inner: TestClient,
+}
+
+impl ProvideRuntimeApi<Block> for TestClient {
+	type Api = RuntimeApi;
+
+	fn runtime_api<'a>(&'a self) -> ApiRef<'a, Self::Api> {
+		RuntimeApi { inner: self.clone() }.into()
+	}
+}
+
+impl HeaderBackend<Block> for TestClient {
+	fn header(&self, id: BlockId<Block>) -> sp_blockchain::Result<Option<Header>> {
+		self.client.header(&id)
+	}
+
+	fn info(&self) -> Info<Block> {
+		self.client.info()
+	}
+
+	fn status(&self, id: BlockId<Block>) -> sp_blockchain::Result<BlockStatus> {
+		self.client.status(id)
+	}
+
+	fn number(&self, hash: Hash) -> sp_blockchain::Result<Option<NumberFor<Block>>> {
+		self.client.number(hash)
+	}
+
+	fn hash(&self, number: NumberFor<Block>) -> sp_blockchain::Result<Option<Hash>> {
+		self.client.hash(number)
+	}
+}
+
+impl BlockchainEvents<Block> for TestClient {
+	fn import_notification_stream(&self) -> ImportNotifications<Block> {
+		self.client.import_notification_stream()
+	}
+
+	fn finality_notification_stream(&self) -> FinalityNotifications<Block> {
+		self.client.finality_notification_stream()
+	}
+
+	fn storage_changes_notification_stream(
+		&self,
+		filter_keys: Option<&[StorageKey]>,
+		child_filter_keys: Option<&[(StorageKey, Option<Vec<StorageKey>>)]>,
+	) -> sp_blockchain::Result<StorageEventStream<Hash>> {
+		self.client.storage_changes_notification_stream(filter_keys, child_filter_keys)
+	}
+}
+
+impl ProofProvider<Block> for TestClient {
+	fn read_proof(
+		&self,
+		id: &BlockId<Block>,
+		keys: &mut dyn Iterator<Item=&[u8]>,
+	) -> sp_blockchain::Result<StorageProof> {
+		self.client.read_proof(id, keys)
+	}
+
+	fn read_child_proof(
+		&self,
+		id: &BlockId<Block>,
+		child_info: &ChildInfo,
+		keys: &mut dyn Iterator<Item=&[u8]>,
+	) -> sp_blockchain::Result<StorageProof> {
+		self.client.read_child_proof(id, child_info, keys)
+	}
+
+	fn execution_proof(
+		&self,
+		id: &BlockId<Block>,
+		method: &str,
+		call_data: &[u8],
+	) -> sp_blockchain::Result<(Vec<u8>, StorageProof)> {
+		self.client.execution_proof(id, method, call_data)
+	}
+
+	fn header_proof(&self, id: &BlockId<Block>) -> sp_blockchain::Result<(Header, StorageProof)> {
+		self.client.header_proof(id)
+	}
+
+	fn key_changes_proof(
+		&self,
+		first: Hash,
+		last: Hash,
+		min: Hash,
+		max: Hash,
+		storage_key: Option<&PrefixedStorageKey>,
+		key: &StorageKey,
+	) -> sp_blockchain::Result<ChangesProof<Header>> {
+		self.client.key_changes_proof(first, last, min, max, storage_key, key)
+	}
+}
+
+fn verify_key(ix: AuthIndex) -> VerifyKey {
+	ShareProvider::from_raw_secret(ix, [ix + 1, 0, 0, 0]).verify_key()
+}
+
+sp_api::mock_impl_runtime_apis! {
+	impl DKGApi<Block> for RuntimeApi {
+		fn master_verification_key(&self) -> Option<VerifyKey> {
+			Some(verify_key(10))
+		}
+
+		fn master_key_ready() -> NumberFor<Block> {
+			0
+		}
+
+		// the keys are reshared to a committee with another threshold
+		fn threshold() -> u64 {
+			3
+		}
+
+		fn verification_keys() -> Option<Vec<VerifyKey>> {
+			Some(vec![verify_key(0), VerifyKey::from_raw_secret(RAW_SECRET), verify_key(2)])
+		}
+
+		fn public_keybox_parts() -> Option<(Option<AuthIndex>, Vec<VerifyKey>, VerifyKey, u64)> {
+			Some((
+				self.inner.member_index,
+				vec![verify_key(0), VerifyKey::from_raw_secret(RAW_SECRET), verify_key(2)],
+				verify_key(10),
+				2,
+			))
+		}
+
+		fn current_epoch() -> EpochIndex {
+			1
+		}
+
+		fn epoch_master_key(_epoch: EpochIndex) -> Option<VerifyKey> {
+			Some(verify_key(10))
+		}
+
+		fn dkg_phase() -> DkgPhase {
+			DkgPhase::Round2
+		}
+
+		fn qualified_dealers() -> Vec<AuthIndex> {
+			vec![0, 2]
+		}
+
+		fn active_threshold() -> u64 {
+			2
+		}
+	}
+
+	impl RandomnessBeaconApi<Block> for RuntimeApi {
+		fn start_beacon_height() -> NumberFor<Block> {
+			1
+		}
+
+		fn beacon_period() -> NumberFor<Block> {
+			1
+		}
+
+		fn signature_version() -> SignatureVersion {
+			SignatureVersion::LATEST
+		}
+
+		fn latest_randomness() -> Option<(NumberFor<Block>, Randomness<Hash>)> {
+			self.inner.randomness.clone()
+		}
+
+		fn seed_freshness() -> Option<sp_randomness_beacon::SeedFreshness<NumberFor<Block>>> {
+			self.inner.randomness.as_ref().map(|(set_at, _)| sp_randomness_beacon::SeedFreshness {
+				set_at: *set_at,
+				age: 2,
+				missed: 1,
+			})
+		}
+
+		// the seed set at block `n` is the one of round `n`
+		fn round_randomness(round: Option<Round>) -> Option<(Round, Randomness<Hash>)> {
+			self.inner.randomness.clone()
+				.map(|(number, seed)| (number as Round, seed))
+				.filter(|(seed_round, _)| round.map_or(true, |round| round == *seed_round))
+		}
+
+		fn history_index(round: Round) -> u32 {
+			(round % HISTORY_DEPTH as Round) as u32
+		}
+
+		fn author_vrf_key(
+			_pre_digests: Vec<(sp_runtime::ConsensusEngineId, Vec<u8>)>,
+		) -> Option<Vec<u8>> {
+			None
+		}
+
+		fn chained_nonce(_round: Round) -> Option<Hash> {
+			None
+		}
+
+		fn grace_period() -> u64 {
+			0
+		}
+	}
+}
+
+fn randomness(number: NumberFor<Block>) -> (NumberFor<Block>, Randomness<Hash>) {
+	let nonce = Hash::repeat_byte(number as u8);
+	let signature = ShareProvider::from_raw_secret(0, RAW_SECRET)
+		.sign(&nonce.encode(), SignatureVersion::LATEST);
+	(number, Randomness::new(nonce, signature))
+}
+
+// the secret is kept under the verification key of the member, as the DKG pallet does
+fn api(
+	member_index: Option<AuthIndex>,
+	secret: Option<RawSecret>,
+	deny_unsafe: DenyUnsafe,
+) -> Dkg<Block, TestClient> {
+	// the state keeps the seed of round 1 as the pallet does
+	let (_, seed) = randomness(1);
+	let master_key = VerifyKey::from_raw_secret(RAW_SECRET);
+	let client = TestClientBuilder::new()
+		.add_extra_storage(history_storage_key(1 % HISTORY_DEPTH), (1 as Round, seed).encode())
+		.add_extra_storage(verifier_storage_key(), RandomnessVerifier::new(master_key).encode())
+		.build();
+	let client = TestClient {
+		client: Arc::new(client),
+		member_index,
+		randomness: Some(randomness(1)),
+	};
+	let keystore = KeyStore::new();
+	if let (Some(ix), Some(secret)) = (member_index, secret) {
+		let member_key = client.runtime_api().verification_keys(&BlockId::Number(0))
+			.unwrap()
+			.unwrap()[ix as usize]
+			.encode();
+		SyncCryptoStore::insert_secret(
+			&keystore,
+			SECRET_KEY_TYPE,
+			&member_key,
+			&Scalar::from_raw(secret).to_bytes(),
+		).unwrap();
+	}
+	Dkg::new(
+		Arc::new(client),
+		Some(Arc::new(keystore)),
+		SubscriptionManager::new(Arc::new(TaskExecutor)),
+		deny_unsafe,
+	)
+}
+
+#[test]
+fn should_return_phase_and_keys() {
+	let api = api(None, None, DenyUnsafe::Yes);
+
+	assert_eq!(api.phase(None).unwrap(), EpochPhase { epoch: 1, phase: DkgPhase::Round2 });
+	assert_eq!(api.qualified_dealers(None).unwrap(), vec![0, 2]);
+	assert_matches!(
+		api.verification_keys(None),
+		Ok(Some(ref keys)) if keys.master_key.0 == verify_key(10).encode()
+			&& keys.member_keys.len() == 3
+			&& keys.threshold == 2
+	);
+}
+
+#[test]
+fn should_return_latest_randomness() {
+	let api = api(None, None, DenyUnsafe::Yes);
+	let (_, seed) = randomness(1);
+
+	// the runtime reports a beacon block missed since the seed was set
+	assert_eq!(
+		api.latest_randomness(None).unwrap(),
+		Some(BeaconRandomness {
+			block_number: 1,
+			nonce: seed.nonce(),
+			version: SignatureVersion::LATEST,
+			signature: seed.signature().encode().into(),
+			stale: true,
+		}),
+	);
+}
+
+#[test]
+fn should_return_seed_freshness() {
+	let api = api(None, None, DenyUnsafe::Yes);
+
+	assert_eq!(
+		api.seed_freshness(None).unwrap(),
+		Some(SeedFreshness { set_at: 1, age: 2, missed: 1 }),
+	);
+}
+
+#[test]
+fn should_prove_round_against_finalized_header() {
+	let api = api(None, None, DenyUnsafe::Yes);
+	let (_, seed) = randomness(1);
+
+	let proof = api.prove_round(1, None).unwrap().expect("the seed of round 1 is kept");
+	let proof = BeaconProof::<Header>::decode(&mut &proof[..]).unwrap();
+	let finalized = api.client.info().finalized_hash;
+	let verified = verify(&proof, &finalized).expect("the proof is correct");
+	assert_eq!(verified.round, 1);
+	assert_eq!(verified.nonce, seed.nonce());
+	assert_eq!(
+		VerifyKey::decode(&mut &proof.master_key.encode()[..]).unwrap(),
+		VerifyKey::from_raw_secret(RAW_SECRET),
+	);
+
+	assert_eq!(api.prove_round(2, None).unwrap(), None);
+}
+
+#[test]
+fn should_report_share_status_without_secret() {
+	assert_matches!(
+		api(Some(1), Some(RAW_SECRET), DenyUnsafe::Yes).share_status(None),
+		Err(Error::UnsafeRpcCalled(_))
+	);
+
+	let status = api(Some(1), Some(RAW_SECRET), DenyUnsafe::No).share_status(None).unwrap();
+	assert_eq!(status, ShareStatus {
+		epoch: 1,
+		phase: DkgPhase::Round2,
+		member_index: Some(1),
+		has_secret_share: true,
+		share_matches_key: Some(true),
+	});
+
+	let status = api(Some(2), Some(RAW_SECRET), DenyUnsafe::No).share_status(None).unwrap();
+	assert_eq!(status.share_matches_key, Some(false));
+
+	let status = api(None, None, DenyUnsafe::No).share_status(None).unwrap();
+	assert_eq!(status.member_index, None);
+	assert!(!status.has_secret_share);
+	assert_eq!(status.share_matches_key, None);
+}
+
+#[test]
+fn should_notify_about_new_randomness() {
+	let (subscriber, id, transport) = Subscriber::new_test("test");
+
+	let next = {
+		let api = api(None, None, DenyUnsafe::Yes);
+		let mut client = api.client.client.clone();
+
+		api.subscribe_randomness(Default::default(), subscriber);
+
+		// assert id assigned
+		assert!(matches!(
+			executor::block_on(id.compat()),
+			Ok(Ok(SubscriptionId::String(_)))
+		));
+
+		// the seed was set in block 1 only
+		let block = client.new_block(Default::default()).unwrap().build().unwrap().block;
+		client.import(BlockOrigin::Own, block).unwrap();
+		let block = client.new_block(Default::default()).unwrap().build().unwrap().block;
+		client.import(BlockOrigin::Own, block).unwrap();
+
+		// assert notification sent to transport
+		let (notification, next) = executor::block_on(transport.into_future().compat()).unwrap();
+		assert!(notification.is_some());
+		next
+	};
+
+	// no more notifications on this channel
+	assert_eq!(executor::block_on(next.into_future().compat()).unwrap().0, None);
+}
@@ -3,21 +3,25 @@
 use super::*;
 
 use frame_benchmarking::benchmarks;
+use frame_support::traits::UnfilteredDispatchable;
 use frame_system::RawOrigin;
 
 use crate::Module as DKG;
-use sp_dkg::{AuthIndex, Commitment, EncryptedShare, EncryptionPublicKey, Scalar};
+use sp_dkg::{
+	AuthIndex, Commitment, EncryptedShare, EncryptionPublicKey, Scalar, ShareProvider,
+	SignatureVersion, VerifyKey,
+};
 use sp_std::prelude::*;
 
 const MAX_SIZE: u32 = 256;
 benchmarks! {
 	_ {
-		let n in 1..MAX_SIZE/4=> ();
+		let n in 4 .. MAX_SIZE => ();
 	}
 
 	handle_round0 {
 		let n in ...;
-		let n = (4*n) as usize;
+		let n = n as usize;
 		let threshold = n/ 3 + 1;
 
 		init::<T>(n, threshold as u64);
@@ -25,20 +29,35 @@ benchmarks! {
 
 	post_encryption_key {
 		let n in ...;
-		let n = (4*n) as usize;
+		let n = n as usize;
 		let threshold = n/ 3 + 1;
 
 		init::<T>(n, threshold as u64);
-		let caller = T::AccountId::default();
-		let enc_pk = EncryptionPublicKey::default();
-	}: _(RawOrigin::Signed(caller), 0, enc_pk)
+		let (payload, signature) = payload::<T, _>(0, EncryptionPublicKey::default());
+
+		<DKG::<T> as Store>::EncryptionPKs::remove(0);
+	}: _(RawOrigin::None, payload, signature)
 	verify {
 		assert!(<DKG::<T> as Store>::EncryptionPKs::contains_key(0));
 	}
 
+	// the dealers of a resharing epoch are the members of the committee
+	post_dealer_encryption_key {
+		let n in ...;
+		let n = n as usize;
+		let threshold = n/ 3 + 1;
+
+		init::<T>(n, threshold as u64);
+		init_dealers::<T>(n);
+		let (payload, signature) = payload::<T, _>(0, EncryptionPublicKey::default());
+	}: _(RawOrigin::None, payload, signature)
+	verify {
+		assert!(<DKG::<T> as Store>::DealerEncryptionPKs::contains_key(0));
+	}
+
 	handle_round1 {
 		let n in ...;
-		let n = (4*n) as usize;
+		let n = n as usize;
 		let threshold = n/ 3 + 1;
 
 		init::<T>(n, threshold as u64);
@@ -46,11 +65,10 @@ benchmarks! {
 
 	post_secret_shares {
 		let n in ...;
-		let n = (4*n) as usize;
+		let n = n as usize;
 		let threshold = n/ 3 + 1;
 
 		init::<T>(n, threshold as u64);
-		let caller = T::AccountId::default();
 		frame_system::Module::<T>::set_block_number(DKG::<T>::round_end(1));
 
 		let mut secret_shares = vec![];
@@ -61,14 +79,19 @@ benchmarks! {
 		for _ in 0..threshold {
 			comm_poly.push(Commitment::default());
 		}
-		let hash_round0 = T::Hash::default();
+		let message = SecretShares {
+			shares: secret_shares,
+			comm_poly,
+			hash_round0: T::Hash::default(),
+		};
+		let (payload, signature) = payload::<T, _>(0, message);
 
 		<DKG::<T> as Store>::CommittedPolynomials::remove(0);
 		for ix in 0..n {
 			<DKG::<T> as Store>::EncryptedShares::remove((0, ix as AuthIndex));
 		}
 
-	}: _(RawOrigin::Signed(caller), 0, secret_shares, comm_poly, hash_round0)
+	}: _(RawOrigin::None, payload, signature)
 	verify {
 		assert_eq!(<DKG::<T> as Store>::CommittedPolynomials::get(0).len(), threshold);
 		for ix in 0..n {
@@ -76,29 +99,177 @@ benchmarks! {
 		}
 	}
 
+	// the disputes are checked against the commitments of the dealers of the largest committee,
+	// a single one each, the evaluation of t commitments is measured by verify_share
 	post_disputes {
-		let n in ...;
-		let n = (4*n) as usize;
-		let threshold = n/ 3 + 1;
+		let d in 1 .. MAX_SIZE - 1;
+		let n = MAX_SIZE as usize;
 
-		init::<T>(n, threshold as u64);
-		let caller = T::AccountId::default();
+		init::<T>(n, 1);
 		frame_system::Module::<T>::set_block_number(DKG::<T>::round_end(2));
 
+		// d dealers are disputed with a share that does not match their commitments
 		let my_ix = 0usize;
-		let bad_dealer = n-1;
 		let my_secret_key = Scalar::from(my_ix as u64);
-		let enc_key = <DKG<T> as Store>::EncryptionPKs::get(bad_dealer as AuthIndex).to_encryption_key(my_secret_key);
 		let share = Scalar::from(1);
-		let enc_share = enc_key.encrypt(&share);
-		<DKG::<T> as Store>::EncryptedShares::insert((bad_dealer as AuthIndex, my_ix as AuthIndex), enc_share);
-		assert_eq!(DKG::<T>::verify_share(&share, bad_dealer, my_ix as u64), false);
-		let disputes = vec![(bad_dealer as u64, enc_key)];
-		let hash_round1 = T::Hash::default();
-	}: _(RawOrigin::Signed(caller),my_ix as AuthIndex, disputes, hash_round1)
+		let round_hash = DKG::<T>::shares_round_hash();
+		let mut disputes = vec![];
+		for bad_dealer in 1..=d as usize {
+			let (enc_key, proof) = <DKG<T> as Store>::EncryptionPKs::get(bad_dealer as AuthIndex)
+				.to_encryption_key_with_proof(my_secret_key);
+			let enc_share = enc_key.encrypt(&share, bad_dealer as AuthIndex, my_ix as AuthIndex, &round_hash);
+			<DKG::<T> as Store>::EncryptedShares::insert((bad_dealer as AuthIndex, my_ix as AuthIndex), enc_share);
+			assert_eq!(DKG::<T>::verify_share(&share, bad_dealer, my_ix as u64), false);
+			disputes.push((bad_dealer as AuthIndex, enc_key, proof));
+		}
+		let message = Disputes {
+			disputes,
+			hash_round1: T::Hash::default(),
+		};
+		let (payload, signature) = payload::<T, _>(my_ix as AuthIndex, message);
+	}: _(RawOrigin::None, payload, signature)
+	verify {
+		for bad_dealer in 1..=d as usize {
+			assert_eq!(<DKG::<T> as Store>::IsCorrectDealer::get(bad_dealer as AuthIndex), false);
+		}
+	}
+
+	// the check of a share against the t commitments of its dealer, as done for every dispute
+	// in post_disputes
+	verify_share {
+		let t in 1 .. MAX_SIZE / 3 + 1;
+
+		init::<T>(2, t as u64);
+		let share = Scalar::from(1);
+	}: { assert_eq!(DKG::<T>::verify_share(&share, 1, 0), false); }
+
+	// the check of a beacon share, as done in post_beacon_share
+	verify_beacon_share {
+		let provider = ShareProvider::from_raw_secret(0, [1, 7, 2, 9]);
+		let nonce = T::Hash::default().encode();
+		let share = provider.sign(&nonce, SignatureVersion::LATEST);
+		let vk = provider.verify_key();
+	}: { assert!(vk.verify(&nonce, &share)); }
+
+	// every member of the committee is absent
+	report_dkg_absence {
+		let n in ...;
+		let n = n as usize;
+		let threshold = n/ 3 + 1;
+
+		init::<T>(n, threshold as u64);
+		for ix in 0..n {
+			<DKG::<T> as Store>::EncryptionPKs::remove(ix as AuthIndex);
+			<DKG::<T> as Store>::CommittedPolynomials::remove(ix as AuthIndex);
+		}
+	}: { DKG::<T>::report_dkg_absence(); }
+
+	// every member of the active committee is absent
+	report_beacon_absence {
+		let n in ...;
+		let n = n as usize;
+
+		<DKG::<T> as Store>::ActiveCommittee::put(vec![T::AuthorityId::default(); n]);
+	}: { DKG::<T>::report_beacon_absence(0); }
+
+	// the storage of a committee of n members who disputed each other is removed, and the
+	// active committee of n members deals to a new committee of n members
+	start_epoch {
+		let n in ...;
+		let n = n as usize;
+		let threshold = n/ 3 + 1;
+
+		init::<T>(n, threshold as u64);
+		init_dealers::<T>(n);
+		for ix in 0..n {
+			for dealer in 0..n {
+				<DKG::<T> as Store>::DisputedDealers::insert(ix as AuthIndex, dealer as AuthIndex, true);
+			}
+		}
+		let vk = VerifyKey::from_raw_secret([1, 7, 2, 9]);
+		<DKG::<T> as Store>::ActiveEpoch::put(DKG::<T>::current_epoch());
+		<DKG::<T> as Store>::ActiveCommittee::put(vec![T::AuthorityId::default(); n]);
+		<DKG::<T> as Store>::VerificationKeys::put(vec![vk; n]);
+		let authorities = vec![T::AuthorityId::default(); n];
+	}: { DKG::<T>::start_epoch(1.into(), &authorities, threshold as u64, EpochKind::Resharing); }
+	verify {
+		assert!(DKG::<T>::is_resharing());
+	}
+
+	// the keys of a committee of n members are activated, all of the members of the previous
+	// committee proved their participation in the beacon
+	activate_epoch_keys {
+		let n in ...;
+		let n = n as usize;
+		let threshold = n/ 3 + 1;
+
+		init::<T>(n, threshold as u64);
+		let epoch = DKG::<T>::current_epoch();
+		let vk = VerifyKey::from_raw_secret([1, 7, 2, 9]);
+		<DKG::<T> as Store>::EpochMasterKeys::insert(epoch, vk.clone());
+		<DKG::<T> as Store>::EpochVerificationKeys::insert(epoch, vec![vk; n]);
+		for ix in 0..n {
+			<DKG::<T> as Store>::BeaconParticipants::insert(ix as AuthIndex, true);
+		}
+	}: { DKG::<T>::activate_epoch_keys(1.into()); }
 	verify {
-		assert_eq!(<DKG::<T> as Store>::IsCorrectDealer::get(bad_dealer as AuthIndex), false);
+		assert_eq!(DKG::<T>::active_epoch(), Some(epoch));
 	}
+
+	set_next_committee {
+		let n in ...;
+
+		let authorities = (0..n).map(|_| T::AuthorityId::generate_pair(None)).collect();
+		let origin = T::CommitteeOrigin::successful_origin();
+		let call = Call::<T>::set_next_committee(authorities, (n / 3 + 1) as u64);
+	}: { call.dispatch_bypass_filter(origin)? }
+	verify {
+		assert!(DKG::<T>::next_committee().is_some());
+	}
+
+	reshare_to_committee {
+		let n in ...;
+
+		<DKG::<T> as Store>::ActiveEpoch::put(DKG::<T>::current_epoch());
+		let authorities = (0..n).map(|_| T::AuthorityId::generate_pair(None)).collect();
+		let origin = T::CommitteeOrigin::successful_origin();
+		let call = Call::<T>::reshare_to_committee(authorities, (n / 3 + 1) as u64);
+	}: { call.dispatch_bypass_filter(origin)? }
+	verify {
+		assert!(DKG::<T>::next_committee().is_some());
+	}
+
+	// the check of a dispute key with a proof, as done in post_disputes
+	verify_dispute_proof {
+		let secret1 = Scalar::from_raw([1, 7, 2, 9]);
+		let secret2 = Scalar::from_raw([2, 1, 3, 7]);
+		let pk1 = EncryptionPublicKey::from_scalar(secret1);
+		let pk2 = EncryptionPublicKey::from_scalar(secret2);
+		let (enc_key, proof) = pk2.to_encryption_key_with_proof(secret1);
+	}: { assert!(enc_key.verify_proof(&proof, &pk1, &pk2)); }
+
+	// the pairing check of a dispute key used by post_disputes before proofs were introduced
+	verify_dispute_pairing {
+		let secret1 = Scalar::from_raw([1, 7, 2, 9]);
+		let secret2 = Scalar::from_raw([2, 1, 3, 7]);
+		let pk1 = EncryptionPublicKey::from_scalar(secret1);
+		let pk2 = EncryptionPublicKey::from_scalar(secret2);
+		let enc_key = pk2.to_encryption_key(secret1);
+	}: { assert!(enc_key.is_correct(&pk2, &pk1)); }
+}
+
+// a payload of the default authority, dispatching does not check the signature of the payload,
+// it is checked only in validate_unsigned
+fn payload<T: Trait, M>(ix: AuthIndex, message: M) -> (DKGPayload<T::Public, M>, T::Signature) {
+	let payload = DKGPayload {
+		ix,
+		epoch: DKG::<T>::current_epoch(),
+		message,
+		public: T::AuthorityId::default().into(),
+	};
+	let signature =
+		T::Signature::decode(&mut &[0u8; 128][..]).expect("zeros decode as a signature");
+	(payload, signature)
 }
 
 fn init<T: Trait>(n_members: usize, threshold: u64) {
@@ -124,6 +295,15 @@ fn init<T: Trait>(n_members: usize, threshold: u64) {
 	}
 }
 
+// turns the current epoch into a resharing one whose dealers are the n default authorities
+fn init_dealers<T: Trait>(n_dealers: usize) {
+	<DKG<T> as Store>::CurrentEpochKind::put(EpochKind::Resharing);
+	<DKG<T> as Store>::NDealers::put(n_dealers as u64);
+	for ix in 0..n_dealers {
+		<DKG<T> as Store>::Dealers::insert(ix as AuthIndex, T::AuthorityId::default());
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -140,9 +320,20 @@ mod tests {
 			states.offchain.write().seed = seed;
 			assert_ok!(test_benchmark_handle_round0::<Runtime>());
 			assert_ok!(test_benchmark_post_encryption_key::<Runtime>());
+			assert_ok!(test_benchmark_post_dealer_encryption_key::<Runtime>());
 			assert_ok!(test_benchmark_handle_round1::<Runtime>());
 			assert_ok!(test_benchmark_post_secret_shares::<Runtime>());
 			assert_ok!(test_benchmark_post_disputes::<Runtime>());
+			assert_ok!(test_benchmark_verify_share::<Runtime>());
+			assert_ok!(test_benchmark_verify_dispute_proof::<Runtime>());
+			assert_ok!(test_benchmark_verify_dispute_pairing::<Runtime>());
+			assert_ok!(test_benchmark_verify_beacon_share::<Runtime>());
+			assert_ok!(test_benchmark_report_dkg_absence::<Runtime>());
+			assert_ok!(test_benchmark_report_beacon_absence::<Runtime>());
+			assert_ok!(test_benchmark_start_epoch::<Runtime>());
+			assert_ok!(test_benchmark_activate_epoch_keys::<Runtime>());
+			assert_ok!(test_benchmark_set_next_committee::<Runtime>());
+			assert_ok!(test_benchmark_reshare_to_committee::<Runtime>());
 		});
 	}
 }
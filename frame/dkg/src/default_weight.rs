@@ -0,0 +1,104 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Weights for pallet_dkg, measured with the benchmarks of this pallet.
+//! DATE: 2026-10-18, STEPS: [10, ], REPEAT: 3, EXECUTION: Native, DB: RocksDb weights
+//! The benchmarks were run natively on the benchmarking state of the client and analysed as by
+//! the benchmark CLI 2.0.0, `verify_beacon_share` with REPEAT: 50.
+
+#![allow(unused_parens)]
+#![allow(unused_imports)]
+
+use frame_support::weights::{Weight, constants::RocksDbWeight as DbWeight};
+
+impl crate::WeightInfo for () {
+	fn post_encryption_key(n: u32, ) -> Weight {
+		(0 as Weight)
+			.saturating_add((3_874_000 as Weight).saturating_mul(n as Weight))
+			.saturating_add(DbWeight::get().reads(5 as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn post_dealer_encryption_key(n: u32, ) -> Weight {
+		(0 as Weight)
+			.saturating_add((3_999_000 as Weight).saturating_mul(n as Weight))
+			.saturating_add(DbWeight::get().reads(6 as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn post_secret_shares(n: u32, ) -> Weight {
+		(5_227_195_000 as Weight)
+			.saturating_add((13_492_000 as Weight).saturating_mul(n as Weight))
+			.saturating_add(DbWeight::get().reads(10 as Weight))
+			.saturating_add(DbWeight::get().writes(2 as Weight))
+			.saturating_add(DbWeight::get().writes((1 as Weight).saturating_mul(n as Weight)))
+	}
+	fn post_disputes(d: u32, ) -> Weight {
+		(0 as Weight)
+			.saturating_add((23_534_697_000 as Weight).saturating_mul(d as Weight))
+			.saturating_add(DbWeight::get().reads(10 as Weight))
+			.saturating_add(DbWeight::get().reads((6 as Weight).saturating_mul(d as Weight)))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+			.saturating_add(DbWeight::get().writes((2 as Weight).saturating_mul(d as Weight)))
+	}
+	fn verify_share(t: u32, ) -> Weight {
+		(0 as Weight)
+			.saturating_add((5_360_655_000 as Weight).saturating_mul(t as Weight))
+			.saturating_add(DbWeight::get().reads(1 as Weight))
+	}
+	// the verification of the share is measured by `verify_beacon_share`
+	fn post_beacon_share() -> Weight {
+		(2_714_217_000 as Weight)
+			.saturating_add(DbWeight::get().reads(6 as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn report_dkg_absence(n: u32, ) -> Weight {
+		(0 as Weight)
+			.saturating_add((18_086_000 as Weight).saturating_mul(n as Weight))
+			.saturating_add(DbWeight::get().reads(3 as Weight))
+			.saturating_add(DbWeight::get().reads((3 as Weight).saturating_mul(n as Weight)))
+	}
+	fn report_beacon_absence(n: u32, ) -> Weight {
+		(9_547_000 as Weight)
+			.saturating_add((1_813_000 as Weight).saturating_mul(n as Weight))
+			.saturating_add(DbWeight::get().reads(2 as Weight))
+			.saturating_add(DbWeight::get().reads((1 as Weight).saturating_mul(n as Weight)))
+	}
+	fn start_epoch(n: u32, ) -> Weight {
+		(0 as Weight)
+			.saturating_add((3_541_066_000 as Weight).saturating_mul(n as Weight))
+			.saturating_add(DbWeight::get().reads(7 as Weight))
+			.saturating_add(DbWeight::get().writes((521 as Weight).saturating_mul(n as Weight)))
+	}
+	fn activate_epoch_keys(n: u32, ) -> Weight {
+		(0 as Weight)
+			.saturating_add((2_935_542_000 as Weight).saturating_mul(n as Weight))
+			.saturating_add(DbWeight::get().reads(6 as Weight))
+			.saturating_add(DbWeight::get().reads((1 as Weight).saturating_mul(n as Weight)))
+			.saturating_add(DbWeight::get().writes(7 as Weight))
+			.saturating_add(DbWeight::get().writes((1 as Weight).saturating_mul(n as Weight)))
+	}
+	fn set_next_committee(n: u32, ) -> Weight {
+		(38_219_000 as Weight)
+			.saturating_add((106_000 as Weight).saturating_mul(n as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn reshare_to_committee(n: u32, ) -> Weight {
+		(26_635_000 as Weight)
+			.saturating_add((183_000 as Weight).saturating_mul(n as Weight))
+			.saturating_add(DbWeight::get().reads(1 as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+}
@@ -24,31 +24,95 @@
 //! accompanying README.md, see also any description of the Pedersen DKG protocol (for
 //! instance the original paper https://link.springer.com/chapter/10.1007%2F3-540-46416-6_47).
 //! To configure the pallet one must provide in the config 1) the list of authorities running
-//! the protocol, 2) the threshold, 3) the block number till which the protocol should terminate.
+//! the protocol, 2) the threshold, 3) the number of blocks a single run of the protocol lasts.
+//!
+//! The protocol is run in epochs. The genesis committee runs the DKG of epoch 0, afterwards
+//! a new committee can be queued either by `pallet-session` (the pallet implements
+//! `OneSessionHandler`) or by `T::CommitteeOrigin` via `set_next_committee`. Once the DKG of
+//! the current epoch is over, the queued committee starts a fresh DKG in the next epoch. Keys
+//! produced by an epoch become active (i.e. are exposed to the randomness beacon) at the block
+//! returned by `master_key_ready`.
+//!
+//! Instead of running a fresh DKG, an epoch may also reshare the active keys to a new committee
+//! (see `reshare_to_committee`). In such an epoch the committee holding the active keys acts as
+//! dealers: every dealer deals sub-shares of its secret share to the new committee, committing
+//! on chain to a polynomial whose constant term is its current verification key. The new secret
+//! shares are Lagrange combinations of the sub-shares, so the master key stays the same while
+//! the committee, its verification keys and the threshold can change.
+//!
+//! Misbehaviour of the committee members is reported as offences through `T::ReportOffences`,
+//! e.g. to `pallet-offences`, so that validators running the beacon can be slashed by
+//! `pallet-staking`. A dealer disqualified by a dispute is reported for bad dealing, members
+//! who did not post their keys or shares by the end of round 1 are reported as absent from the
+//! DKG. Members holding the active keys prove their participation in the beacon by posting a
+//! share for the latest beacon nonce once every `T::BeaconParticipationPeriod` blocks, those
+//! who did not are reported as absent from the beacon at the end of the window.
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
-use frame_support::{debug, decl_module, decl_storage, decl_event, traits::Get, Parameter};
+use frame_support::{
+	debug, decl_error, decl_event, decl_module, decl_storage,
+	dispatch::{DispatchResultWithPostInfo, PostDispatchInfo},
+	ensure,
+	storage::StoragePrefixedMap,
+	traits::{EnsureOrigin, Get},
+	weights::{Pays, Weight},
+	Parameter,
+};
 use frame_system::{
-	ensure_signed,
-	offchain::{AppCrypto, CreateSignedTransaction, SendSignedTransaction, Signer},
+	ensure_none,
+	offchain::{
+		AppCrypto, CreateSignedTransaction, SendUnsignedTransaction, SignedPayload, Signer,
+		SigningTypes,
+	},
 };
 use sp_runtime::{
 	offchain::storage::StorageValueRef,
-	traits::{IdentifyAccount, Member},
+	traits::{Convert, Member, UniqueSaturatedInto, Zero},
+	transaction_validity::{
+		InvalidTransaction, TransactionPriority, TransactionSource, TransactionValidity,
+		ValidTransaction,
+	},
 	RuntimeAppPublic,
 };
+use sp_staking::{
+	offence::{Offence, ReportOffence},
+	SessionIndex,
+};
 use sp_std::{convert::TryInto, vec::Vec};
 
-use codec::Encode;
+use codec::{Decode, Encode};
+use sp_runtime::RuntimeDebug;
 
 use sp_dkg::{
-	AuthIndex, Commitment, EncryptedShare, EncryptionKey, EncryptionPublicKey, RawSecret, Scalar,
-	VerifyKey,
+	lagrange_coefs_at_zero, AuthIndex, Commitment, DleqProof, EncryptedShare, EncryptionKey,
+	EncryptionPublicKey, EpochIndex, RawSecret, Scalar, ShareProvider, Signature,
+	SignatureVersion, VerifyKey,
 };
+pub use sp_dkg::DkgPhase;
 
 mod benchmarking;
+mod default_weight;
+mod offences;
 mod tests;
+mod simulation;
+
+pub use offences::{BadDealingOffence, BeaconAbsenceOffence, DkgAbsenceOffence};
+
+pub trait WeightInfo {
+	fn post_encryption_key(n: u32, ) -> Weight;
+	fn post_dealer_encryption_key(n: u32, ) -> Weight;
+	fn post_secret_shares(n: u32, ) -> Weight;
+	fn post_disputes(d: u32, ) -> Weight;
+	fn verify_share(t: u32, ) -> Weight;
+	fn post_beacon_share() -> Weight;
+	fn report_dkg_absence(n: u32, ) -> Weight;
+	fn report_beacon_absence(n: u32, ) -> Weight;
+	fn start_epoch(n: u32, ) -> Weight;
+	fn activate_epoch_keys(n: u32, ) -> Weight;
+	fn set_next_committee(n: u32, ) -> Weight;
+	fn reshare_to_committee(n: u32, ) -> Weight;
+}
 
 pub mod crypto {
 	use codec::{Decode, Encode};
@@ -122,6 +186,73 @@ pub mod crypto {
 	}
 }
 
+/// The way keys of an epoch are generated.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum EpochKind {
+	/// A fresh Pedersen DKG yielding a new master key.
+	Fresh,
+	/// Resharing of the active keys to a new committee, the master key stays the same.
+	Resharing,
+}
+
+impl Default for EpochKind {
+	fn default() -> Self {
+		EpochKind::Fresh
+	}
+}
+
+/// The number of rounds of a single DKG run.
+pub const N_ROUNDS: usize = 4;
+
+/// A DKG message of a committee member signed with its `dkg!` key, so that it can be submitted
+/// in an unsigned transaction.
+#[derive(Encode, Decode, Clone, PartialEq, RuntimeDebug)]
+pub struct DKGPayload<Public, Message> {
+	/// The index of the sender among the authorities, or among the dealers for messages sent
+	/// as a dealer of a resharing epoch.
+	pub ix: AuthIndex,
+	/// The epoch the message belongs to.
+	pub epoch: EpochIndex,
+	pub message: Message,
+	/// The key the payload is signed with.
+	pub public: Public,
+}
+
+impl<T: SigningTypes, M: Encode> SignedPayload<T> for DKGPayload<T::Public, M> {
+	fn public(&self) -> T::Public {
+		self.public.clone()
+	}
+}
+
+/// The round 1 message of a dealer.
+#[derive(Encode, Decode, Clone, PartialEq, RuntimeDebug)]
+pub struct SecretShares<Hash> {
+	/// The shares encrypted for each of the authorities, indexed by the recipient.
+	pub shares: Vec<Option<EncryptedShare>>,
+	/// The commitments to the coefficients of the dealt polynomial.
+	pub comm_poly: Vec<Commitment>,
+	/// The hash of the block ending round 0.
+	pub hash_round0: Hash,
+}
+
+/// The round 2 message of an authority.
+#[derive(Encode, Decode, Clone, PartialEq, RuntimeDebug)]
+pub struct Disputes<Hash> {
+	/// The disputed dealers together with the keys shared with them and their proofs.
+	pub disputes: Vec<(AuthIndex, EncryptionKey, DleqProof)>,
+	/// The hash of the block ending round 1.
+	pub hash_round1: Hash,
+}
+
+/// The proof of participation in the beacon of a member holding the active keys.
+#[derive(Encode, Decode, Clone, PartialEq, RuntimeDebug)]
+pub struct BeaconShare<Hash> {
+	/// The latest beacon nonce, see `T::LatestBeaconNonce`.
+	pub nonce: Hash,
+	/// The share of the member for the nonce.
+	pub share: Signature,
+}
+
 pub trait Trait: CreateSignedTransaction<Call<Self>> {
 	type Event: From<Event> + Into<<Self as frame_system::Trait>::Event>;
 	/// The identifier type for an offchain worker.
@@ -136,7 +267,44 @@ pub trait Trait: CreateSignedTransaction<Call<Self>> {
 
 	/// The overarching dispatch call type.
 	type Call: From<Call<Self>>;
+	/// The number of blocks a single DKG run lasts, counted from the start of its epoch, unless
+	/// the lengths of the rounds are set with `set_round_lengths`.
 	type DKGReady: Get<Self::BlockNumber>;
+	/// The origin which may queue the committee for the next DKG epoch and set the lengths of
+	/// the rounds.
+	type CommitteeOrigin: EnsureOrigin<Self::Origin>;
+	/// The priority of the unsigned transactions carrying DKG messages.
+	type UnsignedPriority: Get<TransactionPriority>;
+
+	/// The identification of the owner of a `dkg!` key, the offenders are reported under it.
+	type KeyOwnerIdentification: Parameter;
+	/// Finds the owner of a `dkg!` key, members without an owner are not reported.
+	type KeyOwner: Convert<Self::AuthorityId, Option<Self::KeyOwnerIdentification>>;
+	/// The current session, offences are reported in it.
+	type CurrentSession: Get<SessionIndex>;
+	/// A type that gets the offences of the committee members reported.
+	type ReportOffences: ReportOffence<
+			Self::AccountId,
+			Self::KeyOwnerIdentification,
+			BadDealingOffence<Self::KeyOwnerIdentification>,
+		> + ReportOffence<
+			Self::AccountId,
+			Self::KeyOwnerIdentification,
+			DkgAbsenceOffence<Self::KeyOwnerIdentification>,
+		> + ReportOffence<
+			Self::AccountId,
+			Self::KeyOwnerIdentification,
+			BeaconAbsenceOffence<Self::KeyOwnerIdentification>,
+		>;
+	/// The number of blocks in which every member holding the active keys has to prove its
+	/// participation in the beacon, zero disables tracking the participation.
+	type BeaconParticipationPeriod: Get<Self::BlockNumber>;
+	/// The nonce of the latest randomness of the beacon, members prove their participation
+	/// with shares for it.
+	type LatestBeaconNonce: Get<Option<Self::Hash>>;
+
+	/// Weight information for extrinsics in this pallet.
+	type WeightInfo: WeightInfo;
 }
 
 decl_storage! {
@@ -150,6 +318,49 @@ decl_storage! {
 		pub Threshold: u64;
 		pub NMembers: u64;
 
+		// epoch data
+
+		/// The epoch whose DKG is currently running or has finished most recently.
+		pub CurrentEpoch get(fn current_epoch): EpochIndex;
+		/// The block at which the DKG of the current epoch has started.
+		pub EpochStart get(fn epoch_start): T::BlockNumber;
+		/// Whether the current epoch runs a fresh DKG or reshares the active keys.
+		pub CurrentEpochKind get(fn current_epoch_kind): EpochKind;
+		/// The phase of the DKG run of the current epoch.
+		pub CurrentPhase get(fn dkg_phase): DkgPhase;
+		/// The lengths of the rounds of the DKG run of the current epoch, if not set the default
+		/// schedule splitting `T::DKGReady` is used.
+		RoundLengths: Option<[T::BlockNumber; N_ROUNDS]>;
+		/// The lengths of the rounds of the DKG runs of the epochs started from now on.
+		pub ScheduledRoundLengths get(fn scheduled_round_lengths): Option<[T::BlockNumber; N_ROUNDS]>;
+		/// The committee (together with its threshold) queued to run the DKG in the next epoch.
+		pub NextCommittee get(fn next_committee): Option<(Vec<T::AuthorityId>, u64, EpochKind)>;
+		/// The master key derived by the DKG of the given epoch.
+		pub EpochMasterKeys get(fn epoch_master_key):
+			map hasher(twox_64_concat) EpochIndex => Option<VerifyKey>;
+		/// The per member verification keys derived by the DKG of the given epoch.
+		pub EpochVerificationKeys get(fn epoch_verification_keys):
+			map hasher(twox_64_concat) EpochIndex => Option<Vec<VerifyKey>>;
+		/// The epoch whose keys are currently active, i.e. stored in `MasterVerificationKey`
+		/// and `VerificationKeys`.
+		pub ActiveEpoch get(fn active_epoch): Option<EpochIndex>;
+		/// The committee holding the active keys, ordered by their index.
+		ActiveCommittee: Vec<T::AuthorityId>;
+		/// The threshold of the active keys.
+		pub ActiveThreshold get(fn active_threshold): u64;
+
+		// resharing data
+
+		/// In a resharing epoch: the committee holding the active keys, which deals sub-shares
+		/// of their secret shares to the current authorities.
+		pub Dealers: map hasher(twox_64_concat) AuthIndex => T::AuthorityId;
+		pub NDealers: u64;
+		/// The threshold of the keys being reshared.
+		DealerThreshold: u64;
+		/// The verification keys of the dealers, constant terms of their polynomials must match them.
+		DealerVerificationKeys: Vec<VerifyKey>;
+		DealerEncryptionPKs: map hasher(twox_64_concat) AuthIndex => EncryptionPublicKey;
+
 		// round 0 data
 
 		EncryptionPKs: map hasher(twox_64_concat) AuthIndex => EncryptionPublicKey;
@@ -169,19 +380,42 @@ decl_storage! {
 		// 1) ith node succesfully participated in round 0 and round 1
 		// 2) there was no succesful dispute that proves cheating of ith node in round 2
 		IsCorrectDealer: map hasher(twox_64_concat) AuthIndex => bool = false;
+		// the members who already posted their disputes in round 2
+		DisputesPosted: map hasher(twox_64_concat) AuthIndex => bool;
+		// the value under key (i,j) is true if ith node disputed jth dealer in round 2
+		DisputedDealers: double_map hasher(twox_64_concat) AuthIndex, hasher(twox_64_concat) AuthIndex => bool;
 
 
-		// round 3 data
+		// keys of the active epoch
 
 		pub MasterVerificationKey: VerifyKey;
 		VerificationKeys: Vec<VerifyKey>;
+
+
+		// beacon participation
+
+		/// The block at which the current participation window has started.
+		pub BeaconWindowStart get(fn beacon_window_start): T::BlockNumber;
+		/// The members of the active committee who proved their participation in the beacon
+		/// in the current window.
+		BeaconParticipants: map hasher(twox_64_concat) AuthIndex => bool;
 	}
 	add_extra_genesis {
 		config(authorities): Vec<T::AuthorityId>;
 		config(threshold): u64;
+		config(round_lengths): Option<[T::BlockNumber; N_ROUNDS]>;
 		build(|config| {
 			Module::<T>::init_store(&config.authorities);
 			Module::<T>::set_threshold(config.threshold);
+			// the schedule of the genesis committee and of the committees following it
+			if let Some(round_lengths) = config.round_lengths {
+				assert!(
+					round_lengths.iter().all(|length| !length.is_zero()),
+					"Every round of the DKG has to last at least one block."
+				);
+				RoundLengths::<T>::put(round_lengths);
+				ScheduledRoundLengths::<T>::put(round_lengths);
+			}
 		})
 	}
 }
@@ -192,169 +426,321 @@ decl_event!(
 		StartDKG(u64, u64),
 		/// A Round terminated succesfully for given number of nodes.
 		EndRound(u64, u64),
+		/// A committee of given size and threshold was queued for the next epoch.
+		NextCommitteeQueued(u64, u64),
+		/// A new DKG epoch started with a given number of nodes and given threshold.
+		NewEpoch(EpochIndex, u64, u64),
+		/// Keys derived in the given epoch became active.
+		KeysActivated(EpochIndex),
+		/// A new epoch resharing the active keys started with a given number of dealers, number
+		/// of nodes and given threshold.
+		NewResharingEpoch(EpochIndex, u64, u64, u64),
+		/// A member posted its encryption key.
+		EncryptionKeyPosted(AuthIndex),
+		/// A dealer of a resharing epoch posted its encryption key.
+		DealerEncryptionKeyPosted(AuthIndex),
+		/// A dealer posted its commitments and encrypted shares.
+		SecretSharesPosted(AuthIndex),
+		/// A member posted a given number of disputes.
+		DisputesPosted(AuthIndex, u64),
+		/// A dealer was disqualified by a dispute of a given member.
+		DealerDisqualified(AuthIndex, AuthIndex),
+		/// The DKG of the given epoch moved to a new phase.
+		PhaseChanged(EpochIndex, DkgPhase),
+		/// A given number of members did not participate in the DKG of the given epoch.
+		DkgAbsence(EpochIndex, u64),
+		/// A member of the active committee proved its participation in the beacon.
+		BeaconParticipationProven(AuthIndex),
+		/// A given number of members holding the keys of the given epoch did not participate in
+		/// the beacon in the last window.
+		BeaconAbsence(EpochIndex, u64),
 	}
 );
 
+decl_error! {
+	pub enum Error for Module<T: Trait> {
+		/// The threshold is zero or larger than the size of the committee.
+		InvalidThreshold,
+		/// An authority appears more than once in the committee.
+		DuplicateAuthority,
+		/// There are no active keys that could be reshared.
+		NoActiveKeys,
+		/// The submission belongs to another epoch.
+		WrongEpoch,
+		/// The submission was made outside of its round.
+		WrongRound,
+		/// The payload is not signed by the member of the committee with the given index.
+		NotAuthority,
+		/// The payload is not signed by the dealer with the given index.
+		NotDealer,
+		/// Dealer encryption keys are posted only in resharing epochs.
+		NotResharing,
+		/// The member already made this submission in the current epoch.
+		AlreadySubmitted,
+		/// The number of shares differs from the size of the committee.
+		WrongSharesLength,
+		/// The number of commitments differs from the threshold.
+		WrongCommitmentsLength,
+		/// The hash of the block ending the previous round is wrong.
+		WrongRoundHash,
+		/// The dealer did not post its encryption key in round 0.
+		NoEncryptionKey,
+		/// The reshared secret does not match the verification key of the dealer.
+		WrongResharedSecret,
+		/// Every round has to last at least one block.
+		InvalidRoundLengths,
+		/// The participation in the beacon is not tracked or there are no active keys.
+		ParticipationNotTracked,
+		/// The share is not for the latest beacon nonce.
+		WrongBeaconNonce,
+		/// The share does not match the verification key of the member.
+		InvalidBeaconShare,
+	}
+}
+
 decl_module! {
 	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		type Error = Error<T>;
 
 		fn deposit_event() = default;
 
-		#[weight = 1_000_000]
-		pub fn post_encryption_key(origin, ix: AuthIndex, pk: EncryptionPublicKey) {
-			let now = <frame_system::Module<T>>::block_number();
-			if !(now <= Self::round_end(0)) {
-				return Ok(());
-			}
+		fn on_initialize(now: T::BlockNumber) -> Weight {
+			let mut weight = 0;
 
-			if !Self::check_authority(ix, ensure_signed(origin)?) {
-				return Ok(())
+			// the window is closed before the keys are activated, so that the committee
+			// holding the old keys is judged
+			let period = T::BeaconParticipationPeriod::get();
+			if !period.is_zero() && now >= Self::beacon_window_start() + period {
+				weight += Self::end_beacon_window(now);
 			}
 
-			EncryptionPKs::insert(ix, pk);
-		}
-
-		#[weight = ((shares.len() + comm_poly.len()) as u64 + 1)*(1_000_000)]
-		pub fn post_secret_shares(origin, ix: AuthIndex, shares: Vec<Option<EncryptedShare>>, comm_poly: Vec<Commitment>, hash_round0: T::Hash) {
-			let now = <frame_system::Module<T>>::block_number();
-			if !(now > Self::round_end(0) && now <= Self::round_end(1)) {
-				debug::info!("Wrong block number for post_secret_shares: {:?}.", now);
-				return Ok(());
-			}
-			if !Self::check_authority(ix, ensure_signed(origin)?) {
-				return Ok(())
+			// the absent members are reported in on_finalize at the end of round 1
+			if now == Self::round_end(1) {
+				let n = Self::n_members().max(Self::n_dealers()) as u32;
+				weight += <T as Trait>::WeightInfo::report_dkg_absence(n);
 			}
 
-			if (shares.len() != Self::n_members() as usize) || (comm_poly.len() as u64 != Self::threshold()) {
-				debug::info!("Wrong shares len {:} or comm_poly len {:?} for post_secret_shares.",
-					shares.len(),
-					comm_poly.len());
-				return Ok(());
+			if now == Self::master_key_ready() {
+				// the participation of the old committee is cleared
+				let n = Self::n_members().max(Self::active_committee_len()) as u32;
+				Self::activate_epoch_keys(now);
+				weight += <T as Trait>::WeightInfo::activate_epoch_keys(n);
 			}
 
-			if EncryptionPKs::contains_key(ix) {
-				let round0_number: T::BlockNumber = Self::round_end(0);
-				let correct_hash_round0 = <frame_system::Module<T>>::block_hash(round0_number);
-				if hash_round0 == correct_hash_round0 {
-					for (share_ix, share) in shares.iter().enumerate() {
-						if share.is_some() {
-							EncryptedShares::insert((ix, share_ix as AuthIndex), share.unwrap());
-						}
-					}
-					CommittedPolynomials::insert(ix, comm_poly);
-					IsCorrectDealer::insert(ix, true);
-					debug::info!("Successfully executed post_secret_shares for id {:?} in block {:?}.", ix, now);
-				} else {
-					debug::info!("Wrong hash_round0 value for post_secret_shares.");
+			if now > Self::master_key_ready() {
+				if let Some((authorities, threshold, kind)) = NextCommittee::<T>::take() {
+					// the storage of the old committee and dealers is cleared, the active
+					// committee becomes the dealers of a resharing
+					let n = authorities
+						.len()
+						.max(Self::n_members())
+						.max(Self::n_dealers())
+						.max(Self::active_committee_len()) as u32;
+					Self::start_epoch(now, &authorities, threshold, kind);
+					weight += <T as Trait>::WeightInfo::start_epoch(n);
 				}
-			} else {
-				debug::info!("A dealer {:?} who has not posted EncryptionPK tried to submit secret shares.", ix);
 			}
+
+			weight
 		}
 
-		#[weight = (disputes.len() as u64 + 1)*(4_000_000)]
-		pub fn post_disputes(origin, ix: AuthIndex, disputes: Vec<(AuthIndex, EncryptionKey)>, hash_round1: T::Hash) {
-			let now = <frame_system::Module<T>>::block_number();
-			if !(now > Self::round_end(1) && now <= Self::round_end(2)) {
-				debug::info!("Wrong block number for post_disputes: {:?}.", now);
-				return Ok(());
-			}
+		/// Queue the committee which runs the DKG in the next epoch. The epoch starts as soon
+		/// as the DKG of the current epoch is over.
+		#[weight = <T as Trait>::WeightInfo::set_next_committee(authorities.len() as u32)]
+		pub fn set_next_committee(origin, authorities: Vec<T::AuthorityId>, threshold: u64) {
+			T::CommitteeOrigin::ensure_origin(origin)?;
+			Self::check_committee(&authorities, threshold)?;
 
-			if !Self::check_authority(ix, ensure_signed(origin)?) {
-				return Ok(())
-			}
-			let round1_number: T::BlockNumber = Self::round_end(1);
-			let correct_hash_round1 = <frame_system::Module<T>>::block_hash(round1_number);
-			if hash_round1 == correct_hash_round1 {
-				debug::info!("Considering {:?} disputes for {:?} in block {:?}", disputes.len(), ix, now);
-
-				disputes.into_iter().for_each(|(creator, ek)| {
-					if IsCorrectDealer::get(creator) == false {
-						// No need to consider this dispute, the creator is already marked as incorrect.
-						return
-					}
-					if !Self::check_encryption_key(&ek, creator as usize, ix as usize) {
-						// there are 3 possible situations when this if fires, in all cases we can ignore this dispute
-						// if ek is wrong then this dispute is unfounded
-						// if creator's encryption key is None then he is marked as incorrect anyway
-						// if ix's encryption key is None then this dispute is unfounded
-						return
-					}
-					// The below line is fine since encrypted_shares_lists is initialized as a square
-					// array with all Nones.
+			Self::queue_committee(authorities, threshold, EpochKind::Fresh);
+		}
 
-					if !EncryptedShares::contains_key((creator, ix)) {
-						IsCorrectDealer::insert(creator, false);
-						return
-					}
-					let encrypted_share = &EncryptedShares::get((creator, ix)).clone();
+		/// Queue the committee to which the active keys are reshared in the next epoch. The
+		/// master key stays the same, the verification keys and the threshold may change.
+		#[weight = <T as Trait>::WeightInfo::reshare_to_committee(authorities.len() as u32)]
+		pub fn reshare_to_committee(origin, authorities: Vec<T::AuthorityId>, threshold: u64) {
+			T::CommitteeOrigin::ensure_origin(origin)?;
+			Self::check_committee(&authorities, threshold)?;
+			ensure!(Self::active_epoch().is_some(), Error::<T>::NoActiveKeys);
 
-					let share = ek.decrypt(&encrypted_share);
-					if share.is_none() || !Self::verify_share(&share.unwrap(), creator as usize, ix) {
-						IsCorrectDealer::insert(creator, false);
-					}
-				});
-			} else {
-				debug::info!("Wrong hash_round0 value for post_disputes.");
-			}
+			Self::queue_committee(authorities, threshold, EpochKind::Resharing);
 		}
 
-		fn on_finalize(bn: T::BlockNumber) {
-			for round_num in 0..4 {
-				if bn == Self::round_end(round_num) {
-					let count = match round_num {
-						0 => Self::count_encryption_keys_received(),
-						_ => Self::count_successful_nodes(),
-					};
-					Self::deposit_event(Event::EndRound(round_num as u64, count));
+		/// Set the lengths of the rounds of the DKG runs in blocks. The schedule is used by the
+		/// epochs starting after this call, the DKG of the current epoch keeps its schedule.
+		#[weight = 10_000_000]
+		pub fn set_round_lengths(origin, round_lengths: [T::BlockNumber; N_ROUNDS]) {
+			T::CommitteeOrigin::ensure_origin(origin)?;
+			ensure!(
+				round_lengths.iter().all(|length| !length.is_zero()),
+				Error::<T>::InvalidRoundLengths
+			);
+
+			ScheduledRoundLengths::<T>::put(round_lengths);
+		}
+
+		#[weight = <T as Trait>::WeightInfo::post_encryption_key(Module::<T>::n_members() as u32)]
+		pub fn post_encryption_key(
+			origin,
+			payload: DKGPayload<T::Public, EncryptionPublicKey>,
+			_signature: T::Signature,
+		) -> DispatchResultWithPostInfo {
+			ensure_none(origin)?;
+			Self::check_encryption_key_post(&payload)?;
+
+			EncryptionPKs::insert(payload.ix, payload.message);
+			Self::deposit_event(Event::EncryptionKeyPosted(payload.ix));
+
+			let weight = <T as Trait>::WeightInfo::post_encryption_key(Self::n_members() as u32);
+			Ok(PostDispatchInfo {
+				actual_weight: Some(weight),
+				pays_fee: Pays::No,
+			})
+		}
+
+		#[weight = <T as Trait>::WeightInfo::post_dealer_encryption_key(Module::<T>::n_dealers() as u32)]
+		pub fn post_dealer_encryption_key(
+			origin,
+			payload: DKGPayload<T::Public, EncryptionPublicKey>,
+			_signature: T::Signature,
+		) -> DispatchResultWithPostInfo {
+			ensure_none(origin)?;
+			Self::check_dealer_encryption_key_post(&payload)?;
+
+			DealerEncryptionPKs::insert(payload.ix, payload.message);
+			Self::deposit_event(Event::DealerEncryptionKeyPosted(payload.ix));
+
+			let weight = <T as Trait>::WeightInfo::post_dealer_encryption_key(Self::n_dealers() as u32);
+			Ok(PostDispatchInfo {
+				actual_weight: Some(weight),
+				pays_fee: Pays::No,
+			})
+		}
+
+		/// Posts the encrypted shares of the dealer for the members, only the shares which are
+		/// present are stored and charged for.
+		#[weight = <T as Trait>::WeightInfo::post_secret_shares(payload.message.shares.len() as u32)]
+		pub fn post_secret_shares(
+			origin,
+			payload: DKGPayload<T::Public, SecretShares<T::Hash>>,
+			_signature: T::Signature,
+		) -> DispatchResultWithPostInfo {
+			ensure_none(origin)?;
+			Self::check_secret_shares_post(&payload)?;
+
+			let ix = payload.ix;
+			let mut n_stored = 0;
+			for (share_ix, share) in payload.message.shares.into_iter().enumerate() {
+				if let Some(share) = share {
+					EncryptedShares::insert((ix, share_ix as AuthIndex), share);
+					n_stored += 1;
 				}
 			}
+			CommittedPolynomials::insert(ix, payload.message.comm_poly);
+			IsCorrectDealer::insert(ix, true);
+			Self::deposit_event(Event::SecretSharesPosted(ix));
 
-			if bn != Self::round_end(2) {
-				return
-			}
+			Ok(PostDispatchInfo {
+				actual_weight: Some(<T as Trait>::WeightInfo::post_secret_shares(n_stored)),
+				pays_fee: Pays::No,
+			})
+		}
 
-			let n_members = Self::n_members();
+		/// Disputes the shares of the given dealers, each dispute reveals the encryption key shared
+		/// with the dealer together with a proof that it was derived from the key posted in round 0.
+		/// Disputes of dealers who are already disqualified are skipped and not charged for.
+		/// A member may split its disputes into several calls disputing distinct dealers, so that
+		/// each of them fits into a block, see `max_disputes_per_call`.
+		#[weight = Module::<T>::post_disputes_weight(payload.message.disputes.len())]
+		pub fn post_disputes(
+			origin,
+			payload: DKGPayload<T::Public, Disputes<T::Hash>>,
+			_signature: T::Signature,
+		) -> DispatchResultWithPostInfo {
+			ensure_none(origin)?;
+			Self::check_disputes_post(&payload)?;
+
+			let ix = payload.ix;
+			let round_hash = Self::shares_round_hash();
+			let n_disputes = payload.message.disputes.len() as u64;
+			let mut n_checked = 0;
+
+			for (creator, ek, proof) in payload.message.disputes.into_iter() {
+				DisputedDealers::insert(ix, creator, true);
+				if IsCorrectDealer::get(creator) == false {
+					// No need to consider this dispute, the creator is already marked as incorrect.
+					continue
+				}
+				n_checked += 1;
+				if !Self::check_encryption_key(&ek, &proof, creator as usize, ix as usize) {
+					// there are 3 possible situations when this if fires, in all cases we can ignore this dispute
+					// if ek or its proof is wrong then this dispute is unfounded
+					// if creator's encryption key is None then he is marked as incorrect anyway
+					// if ix's encryption key is None then this dispute is unfounded
+					continue
+				}
 
-			let qualified = Self::is_correct_dealer();
-			let mut secret_commitments = Vec::new();
-			for i in 0..n_members {
-				if qualified[i] && CommittedPolynomials::contains_key(i as AuthIndex) {
-					secret_commitments.push(CommittedPolynomials::get(i as AuthIndex)[0].clone());
+				let share = match EncryptedShares::contains_key((creator, ix)) {
+					true => ek.decrypt(&EncryptedShares::get((creator, ix)), creator, ix, &round_hash),
+					false => None,
+				};
+				if share.is_none() || !Self::verify_share(&share.unwrap(), creator as usize, ix) {
+					IsCorrectDealer::insert(creator, false);
+					Self::deposit_event(Event::DealerDisqualified(creator, ix));
+					Self::report_bad_dealing(creator);
 				}
 			}
 
-			let mvk = Commitment::derive_key(secret_commitments);
-			MasterVerificationKey::put(mvk);
+			DisputesPosted::insert(ix, true);
+			Self::deposit_event(Event::DisputesPosted(ix, n_disputes));
 
+			Ok(PostDispatchInfo {
+				actual_weight: Some(Self::post_disputes_weight(n_checked)),
+				pays_fee: Pays::No,
+			})
+		}
 
-			let mut vks = Vec::new();
-			for ix in 0..n_members {
-				let x = &Scalar::from((ix + 1) as u64);
-				let part_keys = (0..n_members)
-					.filter(|creator| {
-						qualified[*creator] && CommittedPolynomials::contains_key(*creator as AuthIndex)
-					})
-					.map(|creator| Commitment::poly_eval(&CommittedPolynomials::get(creator as AuthIndex), x))
-					.collect();
-				vks.push(Commitment::derive_key(part_keys))
+		/// Proves the participation of a member of the active committee in the beacon in the
+		/// current window with its share for the latest beacon nonce.
+		#[weight = <T as Trait>::WeightInfo::post_beacon_share()]
+		pub fn post_beacon_share(
+			origin,
+			payload: DKGPayload<T::Public, BeaconShare<T::Hash>>,
+			_signature: T::Signature,
+		) -> DispatchResultWithPostInfo {
+			ensure_none(origin)?;
+			Self::check_beacon_share_post(&payload)?;
+
+			BeaconParticipants::insert(payload.ix, true);
+			Self::deposit_event(Event::BeaconParticipationProven(payload.ix));
+
+			Ok(Pays::No.into())
+		}
+
+		fn on_finalize(bn: T::BlockNumber) {
+			for round_num in 0..N_ROUNDS {
+				if bn == Self::round_end(round_num) {
+					let count = match round_num {
+						0 => Self::count_encryption_keys_received(),
+						_ => Self::count_successful_nodes(),
+					};
+					Self::deposit_event(Event::EndRound(round_num as u64, count));
+					Self::end_round(round_num);
+				}
 			}
-			VerificationKeys::put(vks);
 		}
 
 		fn offchain_worker(block_number: T::BlockNumber) {
 			debug::info!("Offchain worker call at block {:?}.", block_number);
 			// At the end of Round 2, the public Keybox is ready
 			// Round 3 is only for the offchain worker to put the secret key in its storage.
-			if block_number < Self::round_end(0)  {
-					Self::handle_round0();
-			} else if block_number < Self::round_end(1) {
-					Self::handle_round1();
-			} else if block_number < Self::round_end(2) {
-					Self::handle_round2();
-			} else if block_number < Self::round_end(3) {
-					Self::handle_round3();
+			match Self::dkg_phase() {
+				DkgPhase::Round0 => Self::handle_round0(),
+				DkgPhase::Round1 => Self::handle_round1(),
+				DkgPhase::Round2 => Self::handle_round2(),
+				DkgPhase::Round3 => Self::handle_round3(),
+				DkgPhase::Idle | DkgPhase::Completed | DkgPhase::Failed => {}
 			}
+			Self::handle_beacon_participation();
 		}
 	}
 }
@@ -376,20 +762,378 @@ impl<T: Trait> Module<T> {
 		}
 	}
 
-	fn round_end(round_number: usize) -> T::BlockNumber {
-		let mut rounds_left = T::DKGReady::get();
-		let mut r: usize = 3;
-		while round_number < r {
-			rounds_left -= rounds_left / ((r + 1) as u32).into();
-			r -= 1;
+	fn set_committee(authorities: &[T::AuthorityId], threshold: u64) {
+		let mut authorities = authorities.to_vec();
+		authorities.sort();
+
+		NMembers::put(authorities.len() as u64);
+		Threshold::put(threshold);
+		authorities
+			.into_iter()
+			.enumerate()
+			.for_each(|(ix, auth)| Authorities::<T>::insert(ix as AuthIndex, auth));
+	}
+
+	// the threshold is attainable and every authority is a distinct member of the committee
+	fn check_committee(authorities: &[T::AuthorityId], threshold: u64) -> Result<(), Error<T>> {
+		ensure!(
+			0 < threshold && threshold <= authorities.len() as u64,
+			Error::<T>::InvalidThreshold
+		);
+		let mut sorted = authorities.to_vec();
+		sorted.sort();
+		ensure!(
+			sorted.windows(2).all(|pair| pair[0] != pair[1]),
+			Error::<T>::DuplicateAuthority
+		);
+		Ok(())
+	}
+
+	fn queue_committee(authorities: Vec<T::AuthorityId>, threshold: u64, kind: EpochKind) {
+		let n_members = authorities.len() as u64;
+		NextCommittee::<T>::put((authorities, threshold, kind));
+		Self::deposit_event(Event::NextCommitteeQueued(n_members, threshold));
+	}
+
+	// starts a new DKG run for the given committee, the keys of the currently active epoch
+	// remain in place until the new ones are activated
+	fn start_epoch(
+		now: T::BlockNumber,
+		authorities: &[T::AuthorityId],
+		threshold: u64,
+		kind: EpochKind,
+	) {
+		let epoch = Self::current_epoch() + 1;
+
+		Authorities::<T>::remove_all();
+		EncryptionPKs::remove_all();
+		CommittedPolynomials::remove_all();
+		EncryptedShares::remove_all();
+		IsCorrectDealer::remove_all();
+		DisputesPosted::remove_all();
+		DisputedDealers::remove_all();
+		Dealers::<T>::remove_all();
+		DealerEncryptionPKs::remove_all();
+
+		Self::set_committee(authorities, threshold);
+		CurrentEpoch::put(epoch);
+		EpochStart::<T>::put(now);
+		RoundLengths::<T>::set(Self::scheduled_round_lengths());
+		Self::set_phase(DkgPhase::Round0);
+
+		// resharing is possible only if there are active keys
+		let kind = match (kind, Self::active_epoch()) {
+			(EpochKind::Resharing, None) => EpochKind::Fresh,
+			(kind, _) => kind,
+		};
+		CurrentEpochKind::put(kind);
+
+		match kind {
+			EpochKind::Fresh => {
+				Self::deposit_event(Event::NewEpoch(epoch, authorities.len() as u64, threshold));
+			}
+			EpochKind::Resharing => {
+				let dealers = ActiveCommittee::<T>::get();
+				let n_dealers = dealers.len() as u64;
+				dealers
+					.into_iter()
+					.enumerate()
+					.for_each(|(ix, auth)| Dealers::<T>::insert(ix as AuthIndex, auth));
+				NDealers::put(n_dealers);
+				DealerThreshold::put(ActiveThreshold::get());
+				DealerVerificationKeys::put(VerificationKeys::get());
+
+				Self::deposit_event(Event::NewResharingEpoch(
+					epoch,
+					n_dealers,
+					authorities.len() as u64,
+					threshold,
+				));
+			}
 		}
-		return rounds_left;
 	}
 
-	fn count_successful_nodes() -> u64 {
+	// moves the DKG of the current epoch to the next phase at the end of the given round
+	fn end_round(round: usize) {
+		let next_phase = match (Self::dkg_phase(), round) {
+			(DkgPhase::Idle, _) | (DkgPhase::Completed, _) | (DkgPhase::Failed, _) => return,
+			(_, 0) => DkgPhase::Round1,
+			(_, 1) => {
+				Self::report_dkg_absence();
+				DkgPhase::Round2
+			}
+			(_, 2) => {
+				let derived = match Self::is_resharing() {
+					true => Self::derive_reshared_keys(),
+					false => Self::derive_keys(),
+				};
+				match derived {
+					true => DkgPhase::Round3,
+					false => DkgPhase::Failed,
+				}
+			}
+			// the keys are activated in on_initialize of the block ending round 3
+			_ => return,
+		};
+		Self::set_phase(next_phase);
+	}
+
+	fn set_phase(phase: DkgPhase) {
+		if Self::dkg_phase() != phase {
+			CurrentPhase::put(phase);
+			Self::deposit_event(Event::PhaseChanged(Self::current_epoch(), phase));
+		}
+	}
+
+	fn report<O>(offence: O)
+	where
+		O: Offence<T::KeyOwnerIdentification>,
+		T::ReportOffences: ReportOffence<T::AccountId, T::KeyOwnerIdentification, O>,
+	{
+		let res = <T::ReportOffences as ReportOffence<_, _, O>>::report_offence(Vec::new(), offence);
+		if let Err(e) = res {
+			debug::info!("DKG failed to report an offence of kind {:?}: {:?}", O::ID, e);
+		}
+	}
+
+	fn report_bad_dealing(dealer: AuthIndex) {
+		let dealer_id = match Self::is_resharing() {
+			true => Dealers::<T>::get(dealer),
+			false => Authorities::<T>::get(dealer),
+		};
+		if let Some(offender) = T::KeyOwner::convert(dealer_id) {
+			Self::report::<BadDealingOffence<_>>(BadDealingOffence {
+				session_index: T::CurrentSession::get(),
+				validator_set_count: Self::n_dealers() as u32,
+				epoch: Self::current_epoch(),
+				offender,
+			});
+		}
+	}
+
+	// reports the members who did not post their encryption keys in round 0 and the dealers
+	// who did not post their shares in round 1, a member absent in both is reported once
+	fn report_dkg_absence() {
+		let members = (0..Self::n_members() as AuthIndex)
+			.map(|ix| (Authorities::<T>::get(ix), !EncryptionPKs::contains_key(ix)));
+		let dealers = (0..Self::n_dealers() as AuthIndex).map(|ix| {
+			let dealer = match Self::is_resharing() {
+				true => Dealers::<T>::get(ix),
+				false => Authorities::<T>::get(ix),
+			};
+			(dealer, !CommittedPolynomials::contains_key(ix))
+		});
+
+		let mut participants = Vec::new();
+		let mut absent = Vec::new();
+		for (id, is_absent) in members.chain(dealers) {
+			if is_absent {
+				absent.push(id.clone());
+			}
+			participants.push(id);
+		}
+		participants.sort();
+		participants.dedup();
+		absent.sort();
+		absent.dedup();
+		if absent.is_empty() {
+			return;
+		}
+
+		let epoch = Self::current_epoch();
+		Self::deposit_event(Event::DkgAbsence(epoch, absent.len() as u64));
+		let offenders: Vec<_> = absent.into_iter().filter_map(T::KeyOwner::convert).collect();
+		if !offenders.is_empty() {
+			Self::report::<DkgAbsenceOffence<_>>(DkgAbsenceOffence {
+				session_index: T::CurrentSession::get(),
+				validator_set_count: participants.len() as u32,
+				epoch,
+				offenders,
+			});
+		}
+	}
+
+	// closes the current participation window, the members of the active committee who did
+	// not prove their participation are reported unless the beacon is not running at all,
+	// returns the weight of closing it
+	fn end_beacon_window(now: T::BlockNumber) -> Weight {
+		let mut weight = 0;
+		if let (Some(epoch), Some(_)) = (Self::active_epoch(), T::LatestBeaconNonce::get()) {
+			let n = Self::report_beacon_absence(epoch);
+			weight = <T as Trait>::WeightInfo::report_beacon_absence(n);
+		}
+
+		// only members of the active committee are recorded as participants
+		let n_participants = Self::active_committee_len() as Weight;
+		BeaconParticipants::remove_all();
+		BeaconWindowStart::<T>::put(now);
+		weight.saturating_add(T::DbWeight::get().reads_writes(3, n_participants + 1))
+	}
+
+	// reports the members of the active committee who did not prove their participation in
+	// the current window, returns the size of the committee
+	fn report_beacon_absence(epoch: EpochIndex) -> u32 {
+		let window_start = Self::beacon_window_start();
+		let committee = ActiveCommittee::<T>::get();
+		let absent: Vec<_> = committee
+			.iter()
+			.enumerate()
+			.filter(|(ix, _)| !BeaconParticipants::contains_key(*ix as AuthIndex))
+			.map(|(_, member)| member.clone())
+			.collect();
+		if absent.is_empty() {
+			return committee.len() as u32;
+		}
+
+		Self::deposit_event(Event::BeaconAbsence(epoch, absent.len() as u64));
+		let offenders: Vec<_> = absent.into_iter().filter_map(T::KeyOwner::convert).collect();
+		if !offenders.is_empty() {
+			Self::report::<BeaconAbsenceOffence<_>>(BeaconAbsenceOffence {
+				session_index: T::CurrentSession::get(),
+				validator_set_count: committee.len() as u32,
+				window_start: UniqueSaturatedInto::<u64>::unique_saturated_into(window_start),
+				offenders,
+			});
+		}
+		committee.len() as u32
+	}
+
+	// derives the keys of a fresh DKG, returns false if fewer than threshold dealers qualified
+	fn derive_keys() -> bool {
 		let n_members = Self::n_members();
-		let mut count = 0;
+
+		let qualified = Self::is_correct_dealer();
+		let mut secret_commitments = Vec::new();
+		for i in 0..n_members {
+			if qualified[i] && CommittedPolynomials::contains_key(i as AuthIndex) {
+				secret_commitments.push(CommittedPolynomials::get(i as AuthIndex)[0].clone());
+			}
+		}
+		if (secret_commitments.len() as u64) < Self::threshold() {
+			debug::info!(
+				"Not enough qualified dealers: {:?}.",
+				secret_commitments.len()
+			);
+			return false;
+		}
+
+		let epoch = Self::current_epoch();
+		let mvk = Commitment::derive_key(secret_commitments);
+		EpochMasterKeys::insert(epoch, mvk);
+
+		let mut vks = Vec::new();
 		for ix in 0..n_members {
+			let x = &Scalar::from((ix + 1) as u64);
+			let part_keys = (0..n_members)
+				.filter(|creator| {
+					qualified[*creator] && CommittedPolynomials::contains_key(*creator as AuthIndex)
+				})
+				.map(|creator| Commitment::poly_eval(&CommittedPolynomials::get(creator as AuthIndex), x))
+				.collect();
+			vks.push(Commitment::derive_key(part_keys))
+		}
+		EpochVerificationKeys::insert(epoch, vks);
+		true
+	}
+
+	// the first DealerThreshold qualified dealers, their sub-shares determine the new keys
+	fn resharing_dealers() -> Vec<AuthIndex> {
+		Self::qualified_dealers()
+			.into_iter()
+			.take(DealerThreshold::get() as usize)
+			.collect()
+	}
+
+	// derives the reshared keys, returns false if fewer than the old threshold dealers qualified
+	fn derive_reshared_keys() -> bool {
+		let dealers = Self::resharing_dealers();
+		if (dealers.len() as u64) < DealerThreshold::get() {
+			debug::info!("Not enough qualified dealers for resharing: {:?}.", dealers.len());
+			return false;
+		}
+		let weights = lagrange_coefs_at_zero(&dealers);
+
+		let epoch = Self::current_epoch();
+		let secret_commitments = dealers
+			.iter()
+			.map(|dealer| CommittedPolynomials::get(dealer)[0].clone())
+			.collect();
+		let mvk = Commitment::derive_key_weighted(secret_commitments, &weights);
+		EpochMasterKeys::insert(epoch, mvk);
+
+		let mut vks = Vec::new();
+		for ix in 0..Self::n_members() {
+			let x = &Scalar::from((ix + 1) as u64);
+			let part_keys = dealers
+				.iter()
+				.map(|dealer| Commitment::poly_eval(&CommittedPolynomials::get(dealer), x))
+				.collect();
+			vks.push(Commitment::derive_key_weighted(part_keys, &weights))
+		}
+		EpochVerificationKeys::insert(epoch, vks);
+		true
+	}
+
+	fn activate_epoch_keys(now: T::BlockNumber) {
+		let epoch = Self::current_epoch();
+		let (mvk, vks) = match (
+			Self::epoch_master_key(epoch),
+			Self::epoch_verification_keys(epoch),
+		) {
+			(Some(mvk), Some(vks)) => (mvk, vks),
+			_ => {
+				debug::info!("DKG of epoch {:?} did not produce keys, keeping the old ones.", epoch);
+				Self::set_phase(DkgPhase::Failed);
+				return;
+			}
+		};
+
+		MasterVerificationKey::put(mvk);
+		VerificationKeys::put(vks);
+		ActiveEpoch::put(epoch);
+		ActiveCommittee::<T>::put(
+			(0..Self::n_members())
+				.map(|ix| Authorities::<T>::get(ix as AuthIndex))
+				.collect::<Vec<_>>(),
+		);
+		ActiveThreshold::put(Self::threshold());
+		// the new committee gets a whole window to prove its participation
+		BeaconParticipants::remove_all();
+		BeaconWindowStart::<T>::put(now);
+		Self::deposit_event(Event::KeysActivated(epoch));
+		Self::set_phase(DkgPhase::Completed);
+	}
+
+	/// The lengths of the rounds of the DKG run of the current epoch.
+	pub fn round_lengths() -> [T::BlockNumber; N_ROUNDS] {
+		RoundLengths::<T>::get().unwrap_or_else(Self::default_round_lengths)
+	}
+
+	// splits T::DKGReady into rounds, each of them a bit longer than the previous one
+	fn default_round_lengths() -> [T::BlockNumber; N_ROUNDS] {
+		let mut lengths = [Zero::zero(); N_ROUNDS];
+		let mut end = T::DKGReady::get();
+		for round in (0..N_ROUNDS).rev() {
+			let start = match round {
+				0 => Zero::zero(),
+				_ => end - end / ((round + 1) as u32).into(),
+			};
+			lengths[round] = end - start;
+			end = start;
+		}
+		lengths
+	}
+
+	fn round_end(round_number: usize) -> T::BlockNumber {
+		Self::round_lengths()[..=round_number]
+			.iter()
+			.fold(Self::epoch_start(), |end, length| end + *length)
+	}
+
+	fn count_successful_nodes() -> u64 {
+		let n_dealers = Self::n_dealers();
+		let mut count = 0;
+		for ix in 0..n_dealers {
 			if IsCorrectDealer::contains_key(ix as u64) {
 				if IsCorrectDealer::get(ix as u64) == true {
 					count += 1;
@@ -419,20 +1163,252 @@ impl<T: Trait> Module<T> {
 		assert!(!Threshold::exists(), "Threshold is already initialized!");
 		Threshold::set(threshold);
 		Self::deposit_event(Event::StartDKG(NMembers::get(), threshold));
+		// the DKG of the genesis epoch starts right away
+		Self::set_phase(DkgPhase::Round0);
 	}
 
-	fn check_authority(ix: AuthIndex, who: T::AccountId) -> bool {
+	fn check_authority(ix: AuthIndex, public: &T::Public) -> bool {
 		if !Authorities::<T>::contains_key(ix) {
 			return false;
 		}
 
 		let auth: T::AuthorityId = Authorities::<T>::get(ix);
-		return Into::<T::Public>::into(auth).into_account() == who;
+		return Into::<T::Public>::into(auth) == *public;
+	}
+
+	// checks whether the owner of public may deal shares as the dealer ix in the current epoch
+	fn check_dealer(ix: AuthIndex, public: &T::Public) -> bool {
+		if !Self::is_resharing() {
+			return Self::check_authority(ix, public);
+		}
+		if !Dealers::<T>::contains_key(ix) {
+			return false;
+		}
+
+		let auth: T::AuthorityId = Dealers::<T>::get(ix);
+		return Into::<T::Public>::into(auth) == *public;
+	}
+
+	// whether the current block belongs to the given round of the current epoch
+	fn in_round(round: usize) -> bool {
+		let now = <frame_system::Module<T>>::block_number();
+		(round == 0 || now > Self::round_end(round - 1)) && now <= Self::round_end(round)
+	}
+
+	// checks the epoch, the round and the sender of a message, the signature of the payload
+	// is checked only in validate_unsigned
+	fn check_payload<M>(
+		payload: &DKGPayload<T::Public, M>,
+		round: usize,
+		as_dealer: bool,
+	) -> Result<(), Error<T>> {
+		ensure!(
+			payload.epoch == Self::current_epoch(),
+			Error::<T>::WrongEpoch
+		);
+		ensure!(Self::in_round(round), Error::<T>::WrongRound);
+		match as_dealer {
+			true => ensure!(
+				Self::check_dealer(payload.ix, &payload.public),
+				Error::<T>::NotDealer
+			),
+			false => ensure!(
+				Self::check_authority(payload.ix, &payload.public),
+				Error::<T>::NotAuthority
+			),
+		}
+		Ok(())
+	}
+
+	fn check_encryption_key_post(
+		payload: &DKGPayload<T::Public, EncryptionPublicKey>,
+	) -> Result<(), Error<T>> {
+		Self::check_payload(payload, 0, false)?;
+		ensure!(
+			!EncryptionPKs::contains_key(payload.ix),
+			Error::<T>::AlreadySubmitted
+		);
+		Ok(())
+	}
+
+	fn check_dealer_encryption_key_post(
+		payload: &DKGPayload<T::Public, EncryptionPublicKey>,
+	) -> Result<(), Error<T>> {
+		ensure!(Self::is_resharing(), Error::<T>::NotResharing);
+		Self::check_payload(payload, 0, true)?;
+		ensure!(
+			!DealerEncryptionPKs::contains_key(payload.ix),
+			Error::<T>::AlreadySubmitted
+		);
+		Ok(())
+	}
+
+	fn check_secret_shares_post(
+		payload: &DKGPayload<T::Public, SecretShares<T::Hash>>,
+	) -> Result<(), Error<T>> {
+		Self::check_payload(payload, 1, true)?;
+		let (ix, message) = (payload.ix, &payload.message);
+		ensure!(
+			!CommittedPolynomials::contains_key(ix),
+			Error::<T>::AlreadySubmitted
+		);
+		ensure!(
+			message.shares.len() == Self::n_members(),
+			Error::<T>::WrongSharesLength
+		);
+		ensure!(
+			message.comm_poly.len() as u64 == Self::threshold(),
+			Error::<T>::WrongCommitmentsLength
+		);
+		ensure!(
+			message.hash_round0 == <frame_system::Module<T>>::block_hash(Self::round_end(0)),
+			Error::<T>::WrongRoundHash
+		);
+		ensure!(
+			Self::dealer_encryption_pk(ix as usize).is_some(),
+			Error::<T>::NoEncryptionKey
+		);
+		ensure!(
+			!Self::is_resharing()
+				|| message.comm_poly[0].matches_key(&DealerVerificationKeys::get()[ix as usize]),
+			Error::<T>::WrongResharedSecret
+		);
+		Ok(())
+	}
+
+	fn check_disputes_post(
+		payload: &DKGPayload<T::Public, Disputes<T::Hash>>,
+	) -> Result<(), Error<T>> {
+		Self::check_payload(payload, 2, false)?;
+		let disputes = &payload.message.disputes;
+		// only the first call may be empty and no dealer is disputed twice by the same member
+		let already_disputed = match disputes.is_empty() {
+			true => DisputesPosted::contains_key(payload.ix),
+			false => disputes
+				.iter()
+				.any(|(creator, _, _)| DisputedDealers::contains_key(payload.ix, creator)),
+		};
+		ensure!(!already_disputed, Error::<T>::AlreadySubmitted);
+		ensure!(
+			payload.message.hash_round1
+				== <frame_system::Module<T>>::block_hash(Self::round_end(1)),
+			Error::<T>::WrongRoundHash
+		);
+		Ok(())
+	}
+
+	// a dispute evaluates the commitments of the dealer, whose number is the threshold
+	fn post_disputes_weight(n_disputes: usize) -> Weight {
+		let per_dispute = <T as Trait>::WeightInfo::verify_share(Self::threshold() as u32);
+		<T as Trait>::WeightInfo::post_disputes(n_disputes as u32)
+			.saturating_add(per_dispute.saturating_mul(n_disputes as Weight))
+	}
+
+	/// The number of disputes within the maximum weight of an extrinsic, at least one.
+	pub fn max_disputes_per_call() -> usize {
+		let base = Self::post_disputes_weight(0).saturating_add(T::ExtrinsicBaseWeight::get());
+		let per_dispute = Self::post_disputes_weight(1)
+			.saturating_sub(Self::post_disputes_weight(0))
+			.max(1);
+		let available = T::MaximumExtrinsicWeight::get().saturating_sub(base);
+		(available / per_dispute).max(1) as usize
+	}
+
+	fn check_beacon_share_post(
+		payload: &DKGPayload<T::Public, BeaconShare<T::Hash>>,
+	) -> Result<(), Error<T>> {
+		let active_epoch = Self::active_epoch();
+		ensure!(
+			!T::BeaconParticipationPeriod::get().is_zero() && active_epoch.is_some(),
+			Error::<T>::ParticipationNotTracked
+		);
+		ensure!(payload.epoch == active_epoch.unwrap(), Error::<T>::WrongEpoch);
+		let member = ActiveCommittee::<T>::get()
+			.get(payload.ix as usize)
+			.map(|member| Into::<T::Public>::into(member.clone()));
+		ensure!(member == Some(payload.public.clone()), Error::<T>::NotAuthority);
+		ensure!(
+			!BeaconParticipants::contains_key(payload.ix),
+			Error::<T>::AlreadySubmitted
+		);
+		let message = &payload.message;
+		ensure!(
+			T::LatestBeaconNonce::get() == Some(message.nonce.clone()),
+			Error::<T>::WrongBeaconNonce
+		);
+		let verified = VerificationKeys::get()
+			.get(payload.ix as usize)
+			.map_or(false, |vk| vk.verify(&message.nonce.encode(), &message.share));
+		ensure!(verified, Error::<T>::InvalidBeaconShare);
+		Ok(())
+	}
+
+	// the validity of an unsigned transaction with a message that passed the given check, the
+	// transaction is valid until the given block, i.e. the end of the round of the message or
+	// of the participation window
+	fn validate_payload<M: Encode>(
+		tag: impl Encode,
+		valid_until: T::BlockNumber,
+		payload: &DKGPayload<T::Public, M>,
+		signature: &T::Signature,
+		check: Result<(), Error<T>>,
+	) -> TransactionValidity {
+		if let Err(e) = check {
+			let invalid = match e {
+				Error::<T>::WrongEpoch
+				| Error::<T>::WrongRound
+				| Error::<T>::AlreadySubmitted
+				| Error::<T>::WrongBeaconNonce => InvalidTransaction::Stale,
+				Error::<T>::NotAuthority | Error::<T>::NotDealer => InvalidTransaction::BadProof,
+				_ => InvalidTransaction::Call,
+			};
+			return invalid.into();
+		}
+		if !SignedPayload::<T>::verify::<T::AuthorityId>(payload, signature.clone()) {
+			return InvalidTransaction::BadProof.into();
+		}
+
+		let now = <frame_system::Module<T>>::block_number();
+		let blocks_left = UniqueSaturatedInto::<u64>::unique_saturated_into(valid_until - now);
+		// the end of validity tells apart the participation windows of an epoch
+		ValidTransaction::with_tag_prefix("DKGWorker")
+			.priority(T::UnsignedPriority::get())
+			.and_provides((tag, payload.epoch, payload.ix, valid_until))
+			.longevity(blocks_left + 1)
+			.propagate(true)
+			.build()
+	}
+
+	fn is_resharing() -> bool {
+		Self::current_epoch_kind() == EpochKind::Resharing
+	}
+
+	// the number of members dealing shares in the current epoch
+	fn n_dealers() -> usize {
+		match Self::is_resharing() {
+			true => NDealers::get() as usize,
+			false => Self::n_members(),
+		}
+	}
+
+	fn dealer_encryption_pk(dealer: usize) -> Option<EncryptionPublicKey> {
+		let dealer = dealer as AuthIndex;
+		match Self::is_resharing() {
+			true if DealerEncryptionPKs::contains_key(dealer) => Some(DealerEncryptionPKs::get(dealer)),
+			false if EncryptionPKs::contains_key(dealer) => Some(EncryptionPKs::get(dealer)),
+			_ => None,
+		}
+	}
+
+	// the hash of the block ending round 0, which binds the encrypted shares to the current run
+	fn shares_round_hash() -> Vec<u8> {
+		<frame_system::Module<T>>::block_hash(Self::round_end(0)).encode()
 	}
 
 	fn build_storage_key(prefix: &[u8], round_number: usize) -> Vec<u8> {
 		let mut full_key = Vec::from("dkw::");
 		full_key.append(Vec::from(prefix).as_mut());
+		full_key.append(&mut Self::current_epoch().encode());
 		if round_number >= 1 {
 			let block_number = Self::round_end(round_number - 1);
 			let mut hashb = <frame_system::Module<T>>::block_hash(block_number).encode();
@@ -441,16 +1417,34 @@ impl<T: Trait> Module<T> {
 		full_key
 	}
 
-	// generate encryption pair and send public key on chain
+	// generate encryption pairs and send public keys on chain, in a resharing epoch a member
+	// of the old committee posts additionally the key it uses as a dealer
 	fn handle_round0() {
-		const ALREADY_SET: () = ();
+		if let Some((ix, auth)) = Self::local_authority_key() {
+			Self::handle_encryption_key(b"enc_key", ix, auth, Call::post_encryption_key);
+		}
 
-		let (ix, auth) = match Self::local_authority_key() {
-			Some(ia) => ia,
-			None => return,
-		};
+		if Self::is_resharing() {
+			if let Some((ix, auth)) = Self::local_dealer_key() {
+				Self::handle_encryption_key(
+					b"dealer_enc_key",
+					ix,
+					auth,
+					Call::post_dealer_encryption_key,
+				);
+			}
+		}
+	}
 
-		let st_key = Self::build_storage_key(b"enc_key", 0);
+	fn handle_encryption_key(
+		prefix: &[u8],
+		ix: AuthIndex,
+		auth: T::AuthorityId,
+		call: impl Fn(DKGPayload<T::Public, EncryptionPublicKey>, T::Signature) -> Call<T>,
+	) {
+		const ALREADY_SET: () = ();
+
+		let st_key = Self::build_storage_key(prefix, 0);
 		let val = StorageValueRef::persistent(&st_key);
 		let res = val.mutate(|last_set: Option<Option<RawSecret>>| match last_set {
 			Some(Some(_)) => Err(ALREADY_SET),
@@ -458,33 +1452,81 @@ impl<T: Trait> Module<T> {
 		});
 
 		if let Ok(Ok(raw_scalar)) = res {
-			let signer =
-				Signer::<T, T::AuthorityId>::all_accounts().with_filter([auth.into()].to_vec());
-			if !signer.can_sign() {
-				debug::info!("DKG ERROR NO KEYS FOR SIGNER!!!");
-				return;
-			}
 			let enc_pk = EncryptionPublicKey::from_raw_scalar(raw_scalar);
-			let tx_res =
-				signer.send_signed_transaction(|_| Call::post_encryption_key(ix, enc_pk.clone()));
+			Self::submit_message(ix, auth, enc_pk, call, "encryption key");
+		}
+	}
 
-			for (_, res) in &tx_res {
-				if let Err(e) = res {
-					debug::error!("DKG Failed to submit tx with encryption key: {:?}", e)
-				}
+	// signs the message of the current epoch with the given key and submits it in an unsigned
+	// transaction
+	fn submit_message<M: Encode + Clone>(
+		ix: AuthIndex,
+		auth: T::AuthorityId,
+		message: M,
+		call: impl Fn(DKGPayload<T::Public, M>, T::Signature) -> Call<T>,
+		description: &str,
+	) {
+		Self::submit_epoch_message(Self::current_epoch(), ix, auth, message, call, description)
+	}
+
+	fn submit_epoch_message<M: Encode + Clone>(
+		epoch: EpochIndex,
+		ix: AuthIndex,
+		auth: T::AuthorityId,
+		message: M,
+		call: impl Fn(DKGPayload<T::Public, M>, T::Signature) -> Call<T>,
+		description: &str,
+	) {
+		let signer =
+			Signer::<T, T::AuthorityId>::all_accounts().with_filter([auth.into()].to_vec());
+		if !signer.can_sign() {
+			debug::info!("DKG ERROR NO KEYS FOR SIGNER {:?}!!!", ix);
+			return;
+		}
+
+		let tx_res = signer.send_unsigned_transaction(
+			|account| DKGPayload {
+				ix,
+				epoch,
+				message: message.clone(),
+				public: account.public.clone(),
+			},
+			|payload, signature| call(payload, signature),
+		);
+
+		for (_, res) in &tx_res {
+			if let Err(e) = res {
+				debug::error!("DKG Failed to submit tx with {}: {:?}", description, e)
 			}
 		}
 	}
 
 	// generate secret polynomial, encrypt it, and send it with commitments to the chain
+	// in a resharing epoch the constant term of the polynomial is the secret share of the dealer
 	fn handle_round1() {
 		const ALREADY_SET: () = ();
 
-		let (ix, auth) = match Self::local_authority_key() {
+		let resharing = Self::is_resharing();
+		let local_key = match resharing {
+			true => Self::local_dealer_key(),
+			false => Self::local_authority_key(),
+		};
+		let (ix, auth) = match local_key {
 			Some(ia) => ia,
 			None => return,
 		};
 
+		let reshared_secret = match resharing {
+			true => match Self::local_active_secret() {
+				Some(secret) => Some(secret),
+				None => {
+					debug::info!("DKG dealer {:?} has no secret share to reshare.", ix);
+					return;
+				}
+			},
+			false => None,
+		};
+
 		// 0. generate secrets
 		let n_members = Self::n_members();
 		let threshold = Threshold::get();
@@ -492,7 +1534,13 @@ impl<T: Trait> Module<T> {
 		let val = StorageValueRef::persistent(&st_key);
 		let res = val.mutate(|last_set: Option<Option<Vec<RawSecret>>>| match last_set {
 			Some(Some(_)) => Err(ALREADY_SET),
-			_ => Ok(gen_poly_coeffs(threshold - 1)),
+			_ => {
+				let mut coeffs = gen_poly_coeffs(threshold - 1);
+				if let Some(secret) = reshared_secret {
+					coeffs[0] = u8_array_to_raw_scalar(secret.to_bytes());
+				}
+				Ok(coeffs)
+			}
 		});
 
 		if res.is_err() {
@@ -506,16 +1554,28 @@ impl<T: Trait> Module<T> {
 		let poly = &res.into_iter().map(|raw| Scalar::from_raw(raw)).collect();
 
 		// 1. generate encryption keys
-		let encryption_keys = Self::encryption_keys();
+		let secret_prefix: &[u8] = match resharing {
+			true => b"dealer_enc_key",
+			false => b"enc_key",
+		};
+		let encryption_keys =
+			Self::encryption_keys(secret_prefix, n_members, |receiver| {
+				match EncryptionPKs::contains_key(receiver as AuthIndex) {
+					true => Some(EncryptionPKs::get(receiver as AuthIndex)),
+					false => None,
+				}
+			});
 
 		// 2. generate secret shares
+		let round_hash = Self::shares_round_hash();
 		let mut enc_shares = sp_std::vec![None; n_members];
 
-		for ix in 0..n_members {
-			if let Some(ref enc_key) = encryption_keys[ix] {
-				let x = &Scalar::from((ix + 1) as u64);
+		for receiver in 0..n_members {
+			if let Some(ref enc_key) = encryption_keys[receiver] {
+				let x = &Scalar::from((receiver + 1) as u64);
 				let share = poly_eval(poly, x);
-				enc_shares[ix] = Some(enc_key.encrypt(&share));
+				enc_shares[receiver] =
+					Some(enc_key.encrypt(&share, ix, receiver as AuthIndex, &round_hash));
 			}
 		}
 
@@ -527,22 +1587,12 @@ impl<T: Trait> Module<T> {
 
 		// 4. send encrypted secret shares
 		let round0_number: T::BlockNumber = Self::round_end(0);
-		let hash_round0 = <frame_system::Module<T>>::block_hash(round0_number);
-		let signer =
-			Signer::<T, T::AuthorityId>::all_accounts().with_filter([auth.into()].to_vec());
-		if !signer.can_sign() {
-			debug::info!("DKG ERROR NO KEYS FOR SIGNER!!!");
-			return;
-		}
-		let tx_res = signer.send_signed_transaction(|_| {
-			Call::post_secret_shares(ix, enc_shares.clone(), comms.clone(), hash_round0)
-		});
-
-		for (_, res) in &tx_res {
-			if let Err(e) = res {
-				debug::error!("DKG Failed to submit tx with secret shares: {:?}", e)
-			}
-		}
+		let message = SecretShares {
+			shares: enc_shares,
+			comm_poly: comms,
+			hash_round0: <frame_system::Module<T>>::block_hash(round0_number),
+		};
+		Self::submit_message(ix, auth, message, Call::post_secret_shares, "secret shares");
 	}
 
 	// decrypt secret shares and send disputes to the chain
@@ -566,16 +1616,26 @@ impl<T: Trait> Module<T> {
 			return;
 		}
 
-		let n_members = Self::n_members();
+		let n_dealers = Self::n_dealers();
 
 		// 0. generate encryption keys
-		let encryption_keys = Self::encryption_keys();
+		let encryption_keys =
+			Self::encryption_keys(b"enc_key", n_dealers, Self::dealer_encryption_pk);
+		let dispute = |creator: usize| {
+			// called only if the encryption key with the creator exists, so do both public keys
+			let secret = Self::local_encryption_secret(b"enc_key").unwrap();
+			let (ek, proof) = Self::dealer_encryption_pk(creator)
+				.unwrap()
+				.to_encryption_key_with_proof(secret);
+			(creator as AuthIndex, ek, proof)
+		};
 
 		// 1. decrypt shares, check commitments
-		let mut shares = sp_std::vec![None; n_members];
+		let round_hash = Self::shares_round_hash();
+		let mut shares = sp_std::vec![None; n_dealers];
 		let mut disputes = Vec::new();
 
-		for creator in 0..n_members {
+		for creator in 0..n_dealers {
 			let ek = &encryption_keys[creator];
 			if ek.is_none() {
 				// either the creator or us did not provide an encryption key
@@ -584,13 +1644,18 @@ impl<T: Trait> Module<T> {
 				continue;
 			}
 			if !EncryptedShares::contains_key((creator as AuthIndex, my_ix)) {
-				disputes.push((creator as AuthIndex, ek.clone().unwrap()));
+				disputes.push(dispute(creator));
 				continue;
 			}
 			let encrypted_share = &EncryptedShares::get((creator as AuthIndex, my_ix)).clone();
-			let share = ek.as_ref().unwrap().decrypt(&encrypted_share);
+			let share = ek.as_ref().unwrap().decrypt(
+				&encrypted_share,
+				creator as AuthIndex,
+				my_ix,
+				&round_hash,
+			);
 			if share.is_none() || !Self::verify_share(&share.unwrap(), creator, my_ix) {
-				disputes.push((creator as AuthIndex, ek.clone().unwrap()));
+				disputes.push(dispute(creator));
 			} else {
 				shares[creator] = Some(share.unwrap().to_bytes());
 			}
@@ -604,28 +1669,28 @@ impl<T: Trait> Module<T> {
 			return;
 		}
 
-		// 3. send disputes
+		// 3. send disputes, split so that every call fits into a block
 		let round1_number: T::BlockNumber = Self::round_end(1);
 		let hash_round1 = <frame_system::Module<T>>::block_hash(round1_number);
-
-		let signer =
-			Signer::<T, T::AuthorityId>::all_accounts().with_filter([auth.into()].to_vec());
-		if !signer.can_sign() {
-			debug::info!("DKG ERROR NO KEYS FOR SIGNER {:?}!!!", my_ix);
+		if disputes.is_empty() {
+			let message = Disputes {
+				disputes,
+				hash_round1,
+			};
+			Self::submit_message(my_ix, auth, message, Call::post_disputes, "disputes");
 			return;
 		}
-
-		let tx_res = signer
-			.send_signed_transaction(|_| Call::post_disputes(my_ix, disputes.clone(), hash_round1));
-
-		for (_, res) in &tx_res {
-			if let Err(e) = res {
-				debug::error!("DKG Failed to submit transaction with disputes: {:?}", e)
-			}
+		for chunk in disputes.chunks(Self::max_disputes_per_call()) {
+			let message = Disputes {
+				disputes: chunk.to_vec(),
+				hash_round1,
+			};
+			Self::submit_message(my_ix, auth.clone(), message, Call::post_disputes, "disputes");
 		}
 	}
 
-	// derive local key pair
+	// derive local key pair, the secret key is put into the keystore of the node under the
+	// verification key and the offchain storage only marks that it was stored
 	fn handle_round3() {
 		const ALREADY_SET: () = ();
 
@@ -633,9 +1698,9 @@ impl<T: Trait> Module<T> {
 
 		let st_key_secret_key = Self::build_storage_key(b"threshold_secret_key", 3);
 		let val = StorageValueRef::persistent(&st_key_secret_key);
-		let res = val.mutate(|last_set: Option<Option<[u8; 32]>>| match last_set {
-			Some(Some(_)) => Err(ALREADY_SET),
-			_ => Ok([0; 32]),
+		let res = val.mutate(|last_set: Option<Option<bool>>| match last_set {
+			Some(Some(true)) => Err(ALREADY_SET),
+			_ => Ok(false),
 		});
 
 		if res.is_err() || res.unwrap().is_err() {
@@ -643,33 +1708,113 @@ impl<T: Trait> Module<T> {
 		}
 
 		let st_key_secret_shares = Self::build_storage_key(b"secret_shares", 2);
-		let secret = StorageValueRef::persistent(&st_key_secret_shares)
+		let shares = StorageValueRef::persistent(&st_key_secret_shares)
 			.get::<Vec<Option<[u8; 32]>>>()
 			.unwrap()
-			.unwrap()
-			.iter()
-			.enumerate()
-			.filter_map(|(ix, &share)| match (qualified[ix], share) {
-				(false, _) | (true, None) => None,
-				(true, Some(share)) => Some(Scalar::from_bytes(&share).unwrap()),
-			})
-			.fold(Scalar::zero(), |a, b| a + b);
+			.unwrap();
+
+		let secret = match Self::is_resharing() {
+			false => shares
+				.iter()
+				.enumerate()
+				.filter_map(|(ix, &share)| match (qualified[ix], share) {
+					(false, _) | (true, None) => None,
+					(true, Some(share)) => Some(Scalar::from_bytes(&share).unwrap()),
+				})
+				.fold(Scalar::zero(), |a, b| a + b),
+			true => {
+				// the same dealers are used on chain to derive the verification keys, so a share
+				// missing from any of them gives a secret that does not match the verification key
+				let dealers = Self::resharing_dealers();
+				let weights = lagrange_coefs_at_zero(&dealers);
+				let mut secret = Scalar::zero();
+				for (dealer, weight) in dealers.iter().zip(weights.iter()) {
+					match shares[*dealer as usize] {
+						Some(share) => secret = secret + Scalar::from_bytes(&share).unwrap() * weight,
+						None => {
+							debug::info!(
+								"DKG handle_round3 missing the share of resharing dealer {:?}",
+								dealer
+							);
+							return;
+						}
+					}
+				}
+				secret
+			}
+		};
+
+		let verify_key = VerifyKey::from_secret(&secret);
+		if !sp_dkg::keystore::secret_shares::store(&verify_key.encode(), &secret.to_bytes()) {
+			debug::info!("DKG handle_round3 error in storing secret threshold key");
+			return;
+		}
 
-		let res: Result<_, ()> = val.mutate(|_| Ok(secret.to_bytes()));
+		let res: Result<_, ()> = val.mutate(|_| Ok(true));
 
 		if res.is_err() || res.unwrap().is_err() {
-			debug::info!("DKG handle_round3 error in setting secret threshold key");
+			debug::info!("DKG handle_round3 error in marking secret threshold key as stored");
 		}
 	}
 
+	// proves the participation in the beacon once per window and nonce if this node holds
+	// a share of the active keys
+	fn handle_beacon_participation() {
+		const ALREADY_SET: () = ();
+
+		if T::BeaconParticipationPeriod::get().is_zero() {
+			return;
+		}
+		let (epoch, nonce) = match (Self::active_epoch(), T::LatestBeaconNonce::get()) {
+			(Some(epoch), Some(nonce)) => (epoch, nonce),
+			_ => return,
+		};
+		let (ix, auth, secret) = match Self::local_active_member() {
+			Some(member) => member,
+			None => return,
+		};
+		if BeaconParticipants::contains_key(ix) {
+			return;
+		}
+
+		// a share for a newer nonce is posted again in case the previous one was not included
+		let mut st_key = Vec::from("dkw::beacon_share");
+		st_key.append(&mut Self::beacon_window_start().encode());
+		st_key.append(&mut nonce.encode());
+		let val = StorageValueRef::persistent(&st_key);
+		let res = val.mutate(|last_set: Option<Option<bool>>| match last_set {
+			Some(Some(true)) => Err(ALREADY_SET),
+			_ => Ok(true),
+		});
+		if res.is_err() || res.unwrap().is_err() {
+			return;
+		}
+
+		let share = ShareProvider::from_secret(ix, secret)
+			.sign(&nonce.encode(), SignatureVersion::LATEST);
+		let message = BeaconShare { nonce, share };
+		Self::submit_epoch_message(epoch, ix, auth, message, Call::post_beacon_share, "beacon share");
+	}
+
 	fn local_authority_key() -> Option<(AuthIndex, T::AuthorityId)> {
-		let st_key = Self::build_storage_key(b"local_key_info", 0);
+		Self::local_key(b"local_key_info", Authorities::<T>::iter())
+	}
+
+	fn local_dealer_key() -> Option<(AuthIndex, T::AuthorityId)> {
+		Self::local_key(b"local_dealer_info", Dealers::<T>::iter())
+	}
+
+	fn local_key(
+		prefix: &[u8],
+		mut committee: impl Iterator<Item = (AuthIndex, T::AuthorityId)>,
+	) -> Option<(AuthIndex, T::AuthorityId)> {
+		let st_key = Self::build_storage_key(prefix, 0);
 		let maybe_key_info = StorageValueRef::persistent(&st_key).get();
 		match maybe_key_info {
 			Some(Some(key_info)) => return key_info,
 			_ => {
 				let local_keys = T::AuthorityId::all();
-				let key_info = Authorities::<T>::iter().find_map(move |(index, authority)| {
+				let key_info = committee.find_map(move |(index, authority)| {
 					local_keys
 						.clone()
 						.into_iter()
@@ -682,28 +1827,49 @@ impl<T: Trait> Module<T> {
 		}
 	}
 
-	fn get_encryption_key(creator: usize) -> Option<EncryptionKey> {
-		let st_key = Self::build_storage_key(b"enc_key", 0);
-		let raw_secret = StorageValueRef::persistent(&st_key).get().unwrap().unwrap();
-		let secret = Scalar::from_raw(raw_secret);
-		if EncryptionPKs::contains_key(creator as AuthIndex) {
-			return Some(EncryptionPKs::get(creator as AuthIndex).to_encryption_key(secret));
-		} else {
-			return None;
-		}
+	// the secret share of the active keys held by this node, read from its keystore
+	fn local_active_secret() -> Option<Scalar> {
+		Self::local_active_member().map(|(_, _, secret)| secret)
 	}
 
-	fn encryption_keys() -> Vec<Option<EncryptionKey>> {
-		let mut keys = Vec::new();
-		let n = Self::n_members();
-		for i in 0..n {
-			keys.push(Self::get_encryption_key(i));
-		}
-		keys
+	// the index and the key of this node in the active committee together with its secret share
+	fn local_active_member() -> Option<(AuthIndex, T::AuthorityId, Scalar)> {
+		let local_keys = T::AuthorityId::all();
+		let (ix, member) = ActiveCommittee::<T>::get()
+			.into_iter()
+			.enumerate()
+			.find(|(_, member)| local_keys.contains(member))?;
+		let verify_key = VerificationKeys::get().get(ix)?.encode();
+		let bytes = sp_dkg::keystore::secret_shares::get(&verify_key)?;
+		let secret: Option<Scalar> = Option::from(Scalar::from_bytes(&bytes));
+		Some((ix as AuthIndex, member, secret?))
+	}
+
+	// the keys shared by this node with each of the n other parties, the local secret is
+	// kept in the offchain storage under the given prefix
+	fn encryption_keys(
+		secret_prefix: &[u8],
+		n: usize,
+		public_key: impl Fn(usize) -> Option<EncryptionPublicKey>,
+	) -> Vec<Option<EncryptionKey>> {
+		let secret = match Self::local_encryption_secret(secret_prefix) {
+			Some(secret) => secret,
+			None => return sp_std::vec![None; n],
+		};
+		(0..n)
+			.map(|i| public_key(i).map(|pk| pk.to_encryption_key(secret)))
+			.collect()
 	}
+
+	fn local_encryption_secret(secret_prefix: &[u8]) -> Option<Scalar> {
+		let st_key = Self::build_storage_key(secret_prefix, 0);
+		let raw_secret = StorageValueRef::persistent(&st_key).get::<RawSecret>()??;
+		Some(Scalar::from_raw(raw_secret))
+	}
+
 	fn is_correct_dealer() -> Vec<bool> {
 		let mut is_correct = Vec::new();
-		let n = Self::n_members();
+		let n = Self::n_dealers();
 		for i in 0..n {
 			is_correct.push(IsCorrectDealer::get(i as AuthIndex));
 		}
@@ -721,15 +1887,21 @@ impl<T: Trait> Module<T> {
 		.verify_share(&share)
 	}
 
-	fn check_encryption_key(encryption_key: &EncryptionKey, creator: usize, issuer: usize) -> bool {
-		if !EncryptionPKs::contains_key(creator as AuthIndex)
-			|| !EncryptionPKs::contains_key(issuer as AuthIndex)
-		{
+	fn check_encryption_key(
+		encryption_key: &EncryptionKey,
+		proof: &DleqProof,
+		creator: usize,
+		issuer: usize,
+	) -> bool {
+		let epk_creator = match Self::dealer_encryption_pk(creator) {
+			Some(epk) => epk,
+			None => return false,
+		};
+		if !EncryptionPKs::contains_key(issuer as AuthIndex) {
 			return false;
 		}
-		let epk1 = &EncryptionPKs::get(creator as AuthIndex);
-		let epk2 = &EncryptionPKs::get(issuer as AuthIndex);
-		encryption_key.is_correct(epk1, epk2)
+		let epk_issuer = &EncryptionPKs::get(issuer as AuthIndex);
+		encryption_key.verify_proof(proof, epk_issuer, &epk_creator)
 	}
 
 	pub fn master_verification_key() -> Option<VerifyKey> {
@@ -740,21 +1912,21 @@ impl<T: Trait> Module<T> {
 	}
 
 	pub fn master_key_ready() -> T::BlockNumber {
-		// this is the round number when the outside world expects master key to be ready
-		T::DKGReady::get()
+		// this is the block number when the outside world expects the master key of the
+		// current epoch to be ready
+		Self::round_end(3)
 	}
 
 	pub fn public_keybox_parts() -> Option<(Option<AuthIndex>, Vec<VerifyKey>, VerifyKey, u64)> {
 		// we cannot call local_authority_key() here to fetch ix above, because local_authority_key()
-		// uses the offchain_worker storage, which cannot be used outside of an offchain worker context
+		// uses the offchain_worker storage, which cannot be used outside of an offchain worker context.
+		// The index and the threshold are the ones of the committee whose keys are active, which
+		// differ from the ones of the running epoch while resharing.
 		let local_keys = T::AuthorityId::all();
-		let ix = Authorities::<T>::iter().find_map(move |(index, authority)| {
-			local_keys
-				.clone()
-				.into_iter()
-				.position(|local_key| authority == local_key)
-				.map(|_| index as AuthIndex)
-		});
+		let ix = ActiveCommittee::<T>::get()
+			.into_iter()
+			.position(|member| local_keys.contains(&member))
+			.map(|ix| ix as AuthIndex);
 
 		let verification_keys = match Self::verification_keys() {
 			Some(keys) => keys,
@@ -766,18 +1938,19 @@ impl<T: Trait> Module<T> {
 			None => return None,
 		};
 
-		let threshold = Self::threshold();
+		let threshold = Self::active_threshold();
 
 		Some((ix, verification_keys, master_key, threshold))
 	}
 
-	pub fn storage_key_sk() -> Option<Vec<u8>> {
-		let now = <frame_system::Module<T>>::block_number();
-		let deadline_round_2 = Self::round_end(2);
-		if now <= deadline_round_2 {
-			return None;
-		}
-		Some(Self::build_storage_key(b"threshold_secret_key", 3))
+	/// The dealers of the current epoch that committed to a polynomial and were not disqualified
+	/// by a dispute.
+	pub fn qualified_dealers() -> Vec<AuthIndex> {
+		let qualified = Self::is_correct_dealer();
+		(0..Self::n_dealers())
+			.filter(|dealer| qualified[*dealer] && CommittedPolynomials::contains_key(*dealer as AuthIndex))
+			.map(|dealer| dealer as AuthIndex)
+			.collect()
 	}
 
 	pub fn verification_keys() -> Option<Vec<VerifyKey>> {
@@ -795,12 +1968,115 @@ impl<T: Trait> Module<T> {
 	pub fn n_members() -> usize {
 		NMembers::get() as usize
 	}
+
+	fn active_committee_len() -> usize {
+		ActiveCommittee::<T>::decode_len().unwrap_or(0)
+	}
 }
 
 impl<T: Trait> sp_runtime::BoundToRuntimeAppPublic for Module<T> {
 	type Public = T::AuthorityId;
 }
 
+impl<T: Trait> frame_support::unsigned::ValidateUnsigned for Module<T> {
+	type Call = Call<T>;
+
+	fn validate_unsigned(_source: TransactionSource, call: &Self::Call) -> TransactionValidity {
+		match call {
+			Call::post_encryption_key(payload, signature) => Self::validate_payload(
+				b"enc_key",
+				Self::round_end(0),
+				payload,
+				signature,
+				Self::check_encryption_key_post(payload),
+			),
+			Call::post_dealer_encryption_key(payload, signature) => Self::validate_payload(
+				b"dealer_enc_key",
+				Self::round_end(0),
+				payload,
+				signature,
+				Self::check_dealer_encryption_key_post(payload),
+			),
+			Call::post_secret_shares(payload, signature) => Self::validate_payload(
+				b"secret_shares",
+				Self::round_end(1),
+				payload,
+				signature,
+				Self::check_secret_shares_post(payload),
+			),
+			// the calls of a member dispute distinct dealers
+			Call::post_disputes(payload, signature) => Self::validate_payload(
+				(
+					b"disputes",
+					payload.message.disputes.iter().map(|d| d.0).collect::<Vec<_>>(),
+				),
+				Self::round_end(2),
+				payload,
+				signature,
+				Self::check_disputes_post(payload),
+			),
+			Call::post_beacon_share(payload, signature) => Self::validate_payload(
+				b"beacon_share",
+				Self::beacon_window_start() + T::BeaconParticipationPeriod::get(),
+				payload,
+				signature,
+				Self::check_beacon_share_post(payload),
+			),
+			_ => InvalidTransaction::Call.into(),
+		}
+	}
+}
+
+impl<T: Trait> pallet_session::OneSessionHandler<T::AccountId> for Module<T> {
+	type Key = T::AuthorityId;
+
+	fn on_genesis_session<'a, I: 'a>(validators: I)
+	where
+		I: Iterator<Item = (&'a T::AccountId, T::AuthorityId)>,
+	{
+		// the genesis committee may be given in the config of this pallet as well
+		if NMembers::exists() {
+			return;
+		}
+		let authorities = validators.map(|x| x.1).collect::<Vec<_>>();
+		if !authorities.is_empty() {
+			let threshold = default_threshold(authorities.len() as u64);
+			Self::init_store(&authorities);
+			Self::set_threshold(threshold);
+		}
+	}
+
+	fn on_new_session<'a, I: 'a>(changed: bool, validators: I, _queued_validators: I)
+	where
+		I: Iterator<Item = (&'a T::AccountId, T::AuthorityId)>,
+	{
+		// A new DKG is run only when the committee has changed. Once some keys are active
+		// they are reshared to the new committee, so that the master key stays the same.
+		if !changed {
+			return;
+		}
+		let authorities = validators.map(|x| x.1).collect::<Vec<_>>();
+		if !authorities.is_empty() {
+			let threshold = default_threshold(authorities.len() as u64);
+			let kind = match Self::active_epoch() {
+				Some(_) => EpochKind::Resharing,
+				None => EpochKind::Fresh,
+			};
+			Self::queue_committee(authorities, threshold, kind);
+		}
+	}
+
+	fn on_disabled(_i: usize) {
+		// ignore
+	}
+}
+
+// the threshold used for committees coming from pallet-session, tolerates up to 2/3 of
+// the committee being offline
+fn default_threshold(n_members: u64) -> u64 {
+	n_members / 3 + 1
+}
+
 fn u8_array_to_raw_scalar(bytes: [u8; 32]) -> RawSecret {
 	let mut out = [0u64; 4];
 	for i in 0..4 {
@@ -0,0 +1,146 @@
+//! Offences of the committee members reported through `T::ReportOffence`, e.g. to
+//! `pallet-offences`, which passes them on to `pallet-staking` for slashing.
+//!
+//! Every offence is established on chain: a bad dealing by a dispute whose proof the chain
+//! verified, an absence from the DKG by the messages missing at the end of round 1 and an
+//! absence from the beacon by a missing proof of participation, see `post_beacon_share`.
+
+use codec::{Decode, Encode};
+use sp_dkg::EpochIndex;
+use sp_runtime::{traits::Saturating, Perbill, RuntimeDebug};
+use sp_staking::{
+	offence::{Kind, Offence},
+	SessionIndex,
+};
+use sp_std::vec::Vec;
+
+/// A dealer disqualified by a dispute, i.e. whose share for the disputing member could not be
+/// decrypted or did not match the commitments of the dealer.
+#[derive(RuntimeDebug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct BadDealingOffence<Offender> {
+	/// The session in which the dealing was disputed.
+	pub session_index: SessionIndex,
+	/// The number of dealers of the epoch.
+	pub validator_set_count: u32,
+	/// The epoch of the DKG run.
+	pub epoch: EpochIndex,
+	/// The disqualified dealer.
+	pub offender: Offender,
+}
+
+impl<Offender: Clone> Offence<Offender> for BadDealingOffence<Offender> {
+	const ID: Kind = *b"dkg:bad-dealings";
+	type TimeSlot = EpochIndex;
+
+	fn offenders(&self) -> Vec<Offender> {
+		sp_std::vec![self.offender.clone()]
+	}
+
+	fn session_index(&self) -> SessionIndex {
+		self.session_index
+	}
+
+	fn validator_set_count(&self) -> u32 {
+		self.validator_set_count
+	}
+
+	fn time_slot(&self) -> Self::TimeSlot {
+		self.epoch
+	}
+
+	fn slash_fraction(offenders_count: u32, validator_set_count: u32) -> Perbill {
+		// a bad dealing is never accidental, the formula is min((3k / n)^2, 1) as for
+		// equivocations
+		let x = Perbill::from_rational_approximation(3 * offenders_count, validator_set_count);
+		x.square()
+	}
+}
+
+/// Members of the committee who did not post their encryption keys in round 0 or, as dealers,
+/// their shares in round 1 of a DKG run.
+#[derive(RuntimeDebug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct DkgAbsenceOffence<Offender> {
+	/// The session at the end of round 1.
+	pub session_index: SessionIndex,
+	/// The number of members and dealers of the epoch.
+	pub validator_set_count: u32,
+	/// The epoch of the DKG run.
+	pub epoch: EpochIndex,
+	/// The absent members.
+	pub offenders: Vec<Offender>,
+}
+
+impl<Offender: Clone> Offence<Offender> for DkgAbsenceOffence<Offender> {
+	const ID: Kind = *b"dkg:unresponsive";
+	type TimeSlot = EpochIndex;
+
+	fn offenders(&self) -> Vec<Offender> {
+		self.offenders.clone()
+	}
+
+	fn session_index(&self) -> SessionIndex {
+		self.session_index
+	}
+
+	fn validator_set_count(&self) -> u32 {
+		self.validator_set_count
+	}
+
+	fn time_slot(&self) -> Self::TimeSlot {
+		self.epoch
+	}
+
+	fn slash_fraction(offenders_count: u32, validator_set_count: u32) -> Perbill {
+		absence_slash_fraction(offenders_count, validator_set_count)
+	}
+}
+
+/// Members of the committee holding the active keys who did not prove their participation in
+/// the beacon during a whole window of `T::BeaconParticipationPeriod` blocks.
+#[derive(RuntimeDebug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct BeaconAbsenceOffence<Offender> {
+	/// The session at the end of the window.
+	pub session_index: SessionIndex,
+	/// The size of the committee.
+	pub validator_set_count: u32,
+	/// The block at which the window started.
+	pub window_start: u64,
+	/// The absent members.
+	pub offenders: Vec<Offender>,
+}
+
+impl<Offender: Clone> Offence<Offender> for BeaconAbsenceOffence<Offender> {
+	const ID: Kind = *b"beacon:no-shares";
+	type TimeSlot = u64;
+
+	fn offenders(&self) -> Vec<Offender> {
+		self.offenders.clone()
+	}
+
+	fn session_index(&self) -> SessionIndex {
+		self.session_index
+	}
+
+	fn validator_set_count(&self) -> u32 {
+		self.validator_set_count
+	}
+
+	fn time_slot(&self) -> Self::TimeSlot {
+		self.window_start
+	}
+
+	fn slash_fraction(offenders_count: u32, validator_set_count: u32) -> Perbill {
+		absence_slash_fraction(offenders_count, validator_set_count)
+	}
+}
+
+// as for unresponsive validators in `pallet-im-online`, the formula is
+// min((3 * (k - (n / 10 + 1))) / n, 1) * 0.07, so that a few members may be offline unpunished
+fn absence_slash_fraction(offenders_count: u32, validator_set_count: u32) -> Perbill {
+	if let Some(threshold) = offenders_count.checked_sub(validator_set_count / 10 + 1) {
+		let x = Perbill::from_rational_approximation(3 * threshold, validator_set_count);
+		x.saturating_mul(Perbill::from_percent(7))
+	} else {
+		Perbill::default()
+	}
+}
@@ -0,0 +1,435 @@
+#![cfg(test)]
+
+//! Simulation of DKG runs with many authorities, some of which may be Byzantine.
+//!
+//! Every simulated authority has its own keystore and offchain storage, which are registered
+//! as the extensions of the test externalities before its offchain worker runs. The messages
+//! submitted by an authority are intercepted, altered according to its faults and included in
+//! the next block, so that the pallet processes them as it would on a real chain.
+
+use crate::tests::{Extrinsic, Origin, Runtime, DKG};
+use crate::*;
+use codec::Decode;
+use frame_support::traits::{OffchainWorker, OnFinalize, OnInitialize, UnfilteredDispatchable};
+use frame_support::unsigned::ValidateUnsigned;
+use parking_lot::RwLock;
+use sp_core::{
+	offchain::{
+		testing::{PoolState, TestOffchainExt, TestTransactionPoolExt},
+		OffchainExt, OffchainStorage, TransactionPoolExt,
+	},
+	H256,
+};
+use sp_dkg::{KeyBox, ShareProvider, SignatureVersion, SECRET_KEY_TYPE};
+use sp_keystore::{testing::KeyStore, KeystoreExt, SyncCryptoStore};
+use sp_runtime::traits::{BlakeTwo256, Hash};
+use sp_runtime::DispatchError;
+use std::sync::Arc;
+
+#[test]
+fn honest_run_qualifies_everyone() {
+	let mut sim = Simulation::new(7, 3, vec![]);
+	sim.run();
+
+	sim.assert_outcome(&[0, 1, 2, 3, 4, 5, 6]);
+	sim.assert_no_rejections();
+}
+
+#[test]
+fn offline_and_silent_members_are_not_qualified() {
+	let mut sim = Simulation::new(
+		7,
+		3,
+		vec![
+			(0, Fault::Offline),
+			(1, Fault::Silent(0)),
+			(2, Fault::Silent(1)),
+			(3, Fault::Silent(2)),
+		],
+	);
+	sim.run();
+
+	// the shares of a member without an encryption key are rejected, a member silent in
+	// round 2 only gives up on disputing
+	sim.assert_outcome(&[3, 4, 5, 6]);
+	assert_eq!(sim.rejected(1), &[DispatchError::from(Error::<Runtime>::NoEncryptionKey)]);
+}
+
+#[test]
+fn cheating_dealers_are_disqualified_by_disputes() {
+	let mut sim = Simulation::new(
+		7,
+		3,
+		vec![
+			(0, Fault::WrongShare(1)),
+			(2, Fault::MissingShare(3)),
+			(4, Fault::InvalidCommitments),
+		],
+	);
+	sim.run();
+
+	sim.assert_outcome(&[1, 3, 5, 6]);
+	sim.assert_no_rejections();
+}
+
+#[test]
+fn false_disputes_do_not_disqualify_honest_dealers() {
+	let mut sim = Simulation::new(
+		7,
+		3,
+		vec![(0, Fault::FalseDispute(1)), (0, Fault::FalseDispute(2)), (3, Fault::FalseDispute(3))],
+	);
+	sim.run();
+
+	sim.assert_outcome(&[0, 1, 2, 3, 4, 5, 6]);
+	sim.assert_no_rejections();
+}
+
+#[test]
+fn stale_round_hashes_and_replays_are_rejected() {
+	let mut sim = Simulation::new(
+		7,
+		3,
+		vec![(0, Fault::StaleRoundHash), (1, Fault::Replay)],
+	);
+	sim.run();
+
+	sim.assert_outcome(&[1, 2, 3, 4, 5, 6]);
+	assert_eq!(sim.rejected(0), &[DispatchError::from(Error::<Runtime>::WrongRoundHash)]);
+	// the replayed encryption key, secret shares and disputes
+	assert_eq!(sim.rejected(1), &[DispatchError::from(Error::<Runtime>::AlreadySubmitted); 3]);
+}
+
+#[test]
+fn dkg_fails_with_fewer_than_threshold_qualified_dealers() {
+	let mut sim = Simulation::new(
+		5,
+		3,
+		vec![
+			(0, Fault::Offline),
+			(1, Fault::InvalidCommitments),
+			(2, Fault::StaleRoundHash),
+		],
+	);
+	sim.run();
+
+	sim.assert_outcome(&[3, 4]);
+}
+
+/// A deviation of a simulated authority from the protocol.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Fault {
+	/// The offchain worker of the authority never runs.
+	Offline,
+	/// The authority posts nothing in the given round.
+	Silent(usize),
+	/// The authority deals a share to the given member which does not match its commitments.
+	WrongShare(AuthIndex),
+	/// The authority deals no share to the given member.
+	MissingShare(AuthIndex),
+	/// The authority commits to a polynomial other than the one its shares are taken from.
+	InvalidCommitments,
+	/// The authority disputes the given dealer although its share is correct.
+	FalseDispute(AuthIndex),
+	/// The authority binds its shares to a block other than the one ending round 0.
+	StaleRoundHash,
+	/// The authority submits each of its messages twice.
+	Replay,
+}
+
+struct Member {
+	id: crypto::DKGId,
+	offchain: TestOffchainExt,
+	keystore: Arc<KeyStore>,
+	faults: Vec<Fault>,
+	// the errors of the messages of the member which were not accepted on chain
+	rejected: Vec<DispatchError>,
+}
+
+impl Member {
+	fn has_fault(&self, fault: Fault) -> bool {
+		self.faults.contains(&fault)
+	}
+
+	fn is_honest(&self) -> bool {
+		self.faults.is_empty()
+	}
+
+	fn offchain_value<V: Decode>(&self, key: &[u8]) -> Option<V> {
+		self.offchain
+			.0
+			.read()
+			.persistent_storage
+			.get(b"", key)
+			.map(|value| V::decode(&mut &value[..]).unwrap())
+	}
+}
+
+/// A run of the DKG of the genesis epoch by simulated authorities, some of them faulty.
+pub(crate) struct Simulation {
+	ext: sp_io::TestExternalities,
+	pool: Arc<RwLock<PoolState>>,
+	// ordered as the authorities on chain, i.e. by their keys
+	members: Vec<Member>,
+	threshold: u64,
+	// the messages to be included in the next block together with their senders
+	pending: Vec<(AuthIndex, Call<Runtime>)>,
+}
+
+impl Simulation {
+	/// A committee of `n_members` authorities with the given faults, the DKG is not run yet.
+	pub(crate) fn new(n_members: usize, threshold: u64, faults: Vec<(AuthIndex, Fault)>) -> Self {
+		const PHRASE: &str =
+			"news slush supreme milk chapter athlete soap sausage put clutch what kitten";
+
+		let mut members: Vec<Member> = (0..n_members)
+			.map(|i| {
+				let keystore = Arc::new(KeyStore::new());
+				let id: sp_dkg::crypto::AuthorityId = SyncCryptoStore::sr25519_generate_new(
+					&*keystore,
+					sp_dkg::crypto::AuthorityId::ID,
+					Some(&format!("{}/member{}", PHRASE, i)),
+				)
+				.unwrap()
+				.into();
+				Member {
+					id: id.into(),
+					offchain: TestOffchainExt::default(),
+					keystore,
+					faults: Vec::new(),
+					rejected: Vec::new(),
+				}
+			})
+			.collect();
+		members.sort_by(|a, b| a.id.cmp(&b.id));
+		for (ix, fault) in faults {
+			members[ix as usize].faults.push(fault);
+		}
+
+		let (pool, pool_state) = TestTransactionPoolExt::new();
+		let mut ext = sp_io::TestExternalities::default();
+		ext.register_extension(TransactionPoolExt::new(pool));
+
+		let authorities = members.iter().map(|m| m.id.clone()).collect::<Vec<_>>();
+		ext.execute_with(|| {
+			DKG::init_store(&authorities);
+			DKG::set_threshold(threshold);
+		});
+
+		Simulation {
+			ext,
+			pool: pool_state,
+			members,
+			threshold,
+			pending: Vec::new(),
+		}
+	}
+
+	/// Runs the DKG until its keys are activated (or not, if it failed).
+	pub(crate) fn run(&mut self) {
+		let ready = self.ext.execute_with(DKG::master_key_ready);
+		for now in 1..=ready {
+			self.import_block(now);
+			for ix in 0..self.members.len() {
+				if !self.members[ix].has_fault(Fault::Offline) {
+					self.run_worker(ix, now);
+				}
+			}
+		}
+	}
+
+	/// The errors of the messages of the member which were rejected on chain.
+	pub(crate) fn rejected(&self, ix: AuthIndex) -> &[DispatchError] {
+		&self.members[ix as usize].rejected
+	}
+
+	pub(crate) fn assert_no_rejections(&self) {
+		for (ix, member) in self.members.iter().enumerate() {
+			assert!(member.rejected.is_empty(), "Member {} had rejections: {:?}", ix, member.rejected);
+		}
+	}
+
+	/// Checks that exactly the given dealers qualified and, if there are enough of them, that
+	/// the master key is the sum of their secrets and that the honest members hold shares of it.
+	pub(crate) fn assert_outcome(&mut self, qualified: &[AuthIndex]) {
+		let members = &self.members;
+		let threshold = self.threshold;
+		self.ext.execute_with(|| {
+			assert_eq!(DKG::qualified_dealers(), qualified.to_vec());
+
+			if (qualified.len() as u64) < threshold {
+				assert_eq!(DKG::dkg_phase(), DkgPhase::Failed);
+				assert_eq!(DKG::active_epoch(), None);
+				assert_eq!(DKG::master_verification_key(), None);
+				return;
+			}
+			assert_eq!(DKG::dkg_phase(), DkgPhase::Completed);
+			assert_eq!(DKG::active_epoch(), Some(0));
+
+			// the constant terms of the polynomials, as generated by the workers of the dealers
+			let poly_key = DKG::build_storage_key(b"secret_poly", 1);
+			let master_secret = qualified
+				.iter()
+				.map(|ix| {
+					let poly: Vec<RawSecret> = members[*ix as usize].offchain_value(&poly_key).unwrap();
+					Scalar::from_raw(poly[0])
+				})
+				.fold(Scalar::zero(), |a, b| a + b);
+			let mvk = DKG::master_verification_key().unwrap();
+			assert_eq!(mvk, VerifyKey::from_secret(&master_secret));
+
+			let vks = DKG::verification_keys().unwrap();
+			let key_boxes = members
+				.iter()
+				.enumerate()
+				.filter(|(_, member)| member.is_honest())
+				.map(|(ix, member)| {
+					let vk = &vks[ix];
+					let secret = SyncCryptoStore::secret(&*member.keystore, SECRET_KEY_TYPE, &vk.encode())
+						.unwrap()
+						.unwrap_or_else(|| panic!("Member {} holds no secret share", ix));
+					let secret = Scalar::from_bytes(&sp_dkg::keystore::read_secret(&secret).unwrap()).unwrap();
+					assert_eq!(*vk, VerifyKey::from_secret(&secret));
+
+					let provider = ShareProvider::from_secret(ix as AuthIndex, secret);
+					KeyBox::new(Some(provider), vks.clone(), mvk.clone(), threshold)
+				})
+				.collect::<Vec<_>>();
+
+			// any threshold of the honest members sign under the master key
+			if key_boxes.len() as u64 >= threshold {
+				let msg = b"simulated nonce".to_vec();
+				let shares = key_boxes
+					.iter()
+					.take(threshold as usize)
+					.map(|kb| kb.generate_share(&msg, SignatureVersion::LATEST).unwrap())
+					.collect();
+				let signature = key_boxes[0].combine_shares(&shares);
+				assert!(key_boxes[0].verify_signature(&msg, &signature));
+			}
+		});
+	}
+
+	// executes the block with the pending messages, its hash is known to the offchain workers
+	// run afterwards, as in the executive
+	fn import_block(&mut self, now: u64) {
+		let pending = std::mem::take(&mut self.pending);
+		let mut rejected = Vec::new();
+		self.ext.execute_with(|| {
+			frame_system::Module::<Runtime>::set_block_number(now);
+			<DKG as OnInitialize<u64>>::on_initialize(now);
+
+			for (ix, call) in pending {
+				let valid = DKG::validate_unsigned(TransactionSource::External, &call);
+				let result = call.dispatch_bypass_filter(Origin::none());
+				assert_eq!(valid.is_ok(), result.is_ok());
+				if let Err(e) = result {
+					rejected.push((ix, e.error));
+				}
+			}
+
+			<DKG as OnFinalize<u64>>::on_finalize(now);
+			frame_system::BlockHash::<Runtime>::insert(now, BlakeTwo256::hash_of(&now));
+		});
+
+		for (ix, e) in rejected {
+			self.members[ix as usize].rejected.push(e);
+		}
+	}
+
+	// runs the offchain worker of the member with its keystore and offchain storage, the
+	// submitted messages are altered according to its faults and queued for the next block
+	fn run_worker(&mut self, ix: usize, now: u64) {
+		let member = &self.members[ix];
+		member.offchain.0.write().seed = BlakeTwo256::hash_of(&(ix as u64, now)).to_fixed_bytes();
+		self.ext.register_extension(OffchainExt::new(member.offchain.clone()));
+		self.ext.register_extension(KeystoreExt(member.keystore.clone()));
+
+		let pool = self.pool.clone();
+		let faults = member.faults.clone();
+		let messages = self.ext.execute_with(|| {
+			<DKG as OffchainWorker<u64>>::offchain_worker(now);
+
+			let submitted = std::mem::take(&mut pool.write().transactions);
+			submitted
+				.into_iter()
+				.filter_map(|tx| {
+					let call = Extrinsic::decode(&mut &*tx).unwrap().call;
+					apply_faults(call, &faults)
+				})
+				.collect::<Vec<_>>()
+		});
+
+		for call in messages {
+			if faults.contains(&Fault::Replay) {
+				self.pending.push((ix as AuthIndex, call.clone()));
+			}
+			self.pending.push((ix as AuthIndex, call));
+		}
+	}
+}
+
+// alters the message of a faulty member and signs it again, returns None if the member
+// does not post it
+fn apply_faults(call: Call<Runtime>, faults: &[Fault]) -> Option<Call<Runtime>> {
+	let silent = |round| faults.contains(&Fault::Silent(round));
+	match call {
+		Call::post_encryption_key(..) if silent(0) => None,
+		Call::post_secret_shares(..) if silent(1) => None,
+		Call::post_disputes(..) if silent(2) => None,
+		Call::post_secret_shares(mut payload, _) => {
+			for fault in faults {
+				tamper_shares(&mut payload, *fault);
+			}
+			let signature = SignedPayload::<Runtime>::sign::<crypto::DKGId>(&payload).unwrap();
+			Some(Call::post_secret_shares(payload, signature))
+		}
+		Call::post_disputes(mut payload, _) => {
+			for fault in faults {
+				if let Fault::FalseDispute(dealer) = fault {
+					// the revealed key and its proof are genuine, only the share is fine
+					let secret = DKG::local_encryption_secret(b"enc_key").unwrap();
+					let (ek, proof) = DKG::dealer_encryption_pk(*dealer as usize)
+						.unwrap()
+						.to_encryption_key_with_proof(secret);
+					payload.message.disputes.push((*dealer, ek, proof));
+				}
+			}
+			let signature = SignedPayload::<Runtime>::sign::<crypto::DKGId>(&payload).unwrap();
+			Some(Call::post_disputes(payload, signature))
+		}
+		call => Some(call),
+	}
+}
+
+fn tamper_shares(
+	payload: &mut DKGPayload<<Runtime as SigningTypes>::Public, SecretShares<H256>>,
+	fault: Fault,
+) {
+	let message = &mut payload.message;
+	match fault {
+		Fault::WrongShare(victim) => {
+			let keys = DKG::encryption_keys(b"enc_key", DKG::n_members(), |receiver| {
+				match EncryptionPKs::contains_key(receiver as AuthIndex) {
+					true => Some(EncryptionPKs::get(receiver as AuthIndex)),
+					false => None,
+				}
+			});
+			if let Some(ek) = &keys[victim as usize] {
+				let wrong_share = Scalar::from(7u64);
+				message.shares[victim as usize] =
+					Some(ek.encrypt(&wrong_share, payload.ix, victim, &DKG::shares_round_hash()));
+			}
+		}
+		Fault::MissingShare(victim) => message.shares[victim as usize] = None,
+		Fault::InvalidCommitments => {
+			message.comm_poly = (0..message.comm_poly.len())
+				.map(|i| Commitment::new(Scalar::from(i as u64 + 1)))
+				.collect();
+		}
+		Fault::StaleRoundHash => {
+			message.hash_round0 = frame_system::Module::<Runtime>::block_hash(DKG::round_end(0) - 1);
+		}
+		_ => {}
+	}
+}
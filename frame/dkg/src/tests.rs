@@ -2,7 +2,12 @@
 
 use crate::*;
 use codec::Decode;
-use frame_support::{impl_outer_origin, parameter_types, traits::OnFinalize, weights::Weight};
+use frame_support::unsigned::ValidateUnsigned;
+use frame_support::{
+	assert_noop, assert_ok, impl_outer_origin, parameter_types,
+	traits::{OnFinalize, OnInitialize},
+	weights::{constants::WEIGHT_PER_SECOND, Weight},
+};
 use parking_lot::RwLock;
 use sp_core::{
 	offchain::{
@@ -11,15 +16,18 @@ use sp_core::{
 	},
 	H256,
 };
-use sp_dkg::{KeyBox, ShareProvider, AuthIndex};
+use sp_dkg::{KeyBox, ShareProvider, SignatureVersion, AuthIndex, SECRET_KEY_TYPE};
 use sp_keystore::{
 	testing::KeyStore,
 	{KeystoreExt, SyncCryptoStore},
 };
 use sp_runtime::testing::{Header, TestXt};
-use sp_runtime::traits::{BlakeTwo256, Extrinsic as ExtrinsicT, IdentityLookup, Verify};
-use sp_runtime::{MultiSignature, Perbill};
-use std::sync::Arc;
+use sp_runtime::traits::{
+	BlakeTwo256, Extrinsic as ExtrinsicT, IdentifyAccount, IdentityLookup, Verify,
+};
+use sp_runtime::{DispatchError, MultiSignature, Perbill};
+use sp_staking::offence::{Kind, OffenceError};
+use std::{cell::RefCell, sync::Arc};
 
 const N_MEMBERS: usize = 4;
 const THRESHOLD: usize = 3;
@@ -37,10 +45,583 @@ fn dkg() {
 	});
 }
 
+#[test]
+fn committee_rotation() {
+	let (mut t, _, my_id) = new_test_ext();
+	t.execute_with(|| {
+		init(my_id, N_MEMBERS, THRESHOLD as u64);
+		let ready = DKG::master_key_ready();
+		<DKG as Store>::EpochMasterKeys::insert(0, VerifyKey::default());
+		<DKG as Store>::EpochVerificationKeys::insert(0, vec![VerifyKey::default(); N_MEMBERS]);
+		<DKG as Store>::IsCorrectDealer::insert(0, true);
+
+		let committee = distinct_committee(2);
+		assert_noop!(
+			DKG::set_next_committee(Origin::root(), committee.clone(), 3),
+			Error::<Runtime>::InvalidThreshold
+		);
+		assert_noop!(
+			DKG::set_next_committee(Origin::root(), vec![committee[0].clone(); 2], 2),
+			Error::<Runtime>::DuplicateAuthority
+		);
+		assert_noop!(
+			DKG::reshare_to_committee(Origin::root(), vec![committee[1].clone(); 2], 1),
+			Error::<Runtime>::DuplicateAuthority
+		);
+		assert_ok!(DKG::set_next_committee(Origin::root(), committee, 2));
+
+		// the new committee waits until keys of the current epoch are activated
+		assert_eq!(
+			<DKG as OnInitialize<u64>>::on_initialize(ready),
+			<Runtime as Trait>::WeightInfo::activate_epoch_keys(N_MEMBERS as u32)
+		);
+		assert_eq!(DKG::active_epoch(), Some(0));
+		assert_eq!(DKG::current_epoch(), 0);
+		assert!(DKG::next_committee().is_some());
+
+		assert_eq!(
+			<DKG as OnInitialize<u64>>::on_initialize(ready + 1),
+			<Runtime as Trait>::WeightInfo::start_epoch(N_MEMBERS as u32)
+		);
+		assert_eq!(DKG::current_epoch(), 1);
+		assert_eq!(DKG::epoch_start(), ready + 1);
+		assert_eq!(DKG::master_key_ready(), ready + 1 + DKGReady::get());
+		assert_eq!(DKG::n_members(), 2);
+		assert_eq!(DKG::threshold(), 2);
+		assert!(DKG::next_committee().is_none());
+		assert!(!<DKG as Store>::IsCorrectDealer::contains_key(0));
+
+		// keys of the previous epoch stay active until the new DKG is over
+		assert_eq!(DKG::active_epoch(), Some(0));
+		assert!(DKG::master_verification_key().is_some());
+		<DKG as OnInitialize<u64>>::on_initialize(DKG::master_key_ready());
+		assert_eq!(DKG::active_epoch(), Some(0));
+	});
+}
+
+#[test]
+fn genesis_sets_round_lengths() {
+	let mut t = frame_system::GenesisConfig::default()
+		.build_storage::<Runtime>()
+		.unwrap();
+	GenesisConfig::<Runtime> {
+		authorities: distinct_committee(N_MEMBERS),
+		threshold: THRESHOLD as u64,
+		round_lengths: Some([2, 3, 4, 5]),
+	}
+	.assimilate_storage(&mut t)
+	.unwrap();
+	sp_io::TestExternalities::new(t).execute_with(|| {
+		assert_eq!(DKG::round_lengths(), [2, 3, 4, 5]);
+		assert_eq!(DKG::scheduled_round_lengths(), Some([2, 3, 4, 5]));
+		assert_eq!(DKG::master_key_ready(), 14);
+	});
+}
+
+#[test]
+fn round_schedule_and_phases() {
+	let (mut t, _, my_id) = new_test_ext();
+	t.execute_with(|| {
+		init(my_id, N_MEMBERS, THRESHOLD as u64);
+		assert_eq!(DKG::dkg_phase(), DkgPhase::Round0);
+		let ready = DKG::master_key_ready();
+		assert_eq!(ready, DKGReady::get());
+
+		assert_noop!(
+			DKG::set_round_lengths(Origin::none(), [1, 2, 3, 4]),
+			DispatchError::BadOrigin
+		);
+		assert_noop!(
+			DKG::set_round_lengths(Origin::root(), [1, 0, 3, 4]),
+			Error::<Runtime>::InvalidRoundLengths
+		);
+		// the schedule of the running epoch stays the same
+		assert_ok!(DKG::set_round_lengths(Origin::root(), [2, 3, 4, 5]));
+		assert_eq!(DKG::master_key_ready(), ready);
+
+		<DKG as Store>::EpochMasterKeys::insert(0, VerifyKey::default());
+		<DKG as Store>::EpochVerificationKeys::insert(0, vec![VerifyKey::default(); N_MEMBERS]);
+		<DKG as OnInitialize<u64>>::on_initialize(ready);
+		assert_eq!(DKG::dkg_phase(), DkgPhase::Completed);
+
+		let committee = distinct_committee(N_MEMBERS);
+		assert_ok!(DKG::set_next_committee(
+			Origin::root(),
+			committee,
+			THRESHOLD as u64
+		));
+		<DKG as OnInitialize<u64>>::on_initialize(ready + 1);
+		assert_eq!(DKG::dkg_phase(), DkgPhase::Round0);
+		assert_eq!(DKG::round_lengths(), [2, 3, 4, 5]);
+		assert_eq!(DKG::round_end(0), ready + 3);
+		assert_eq!(DKG::master_key_ready(), ready + 15);
+
+		<DKG as OnFinalize<u64>>::on_finalize(DKG::round_end(0));
+		assert_eq!(DKG::dkg_phase(), DkgPhase::Round1);
+		<DKG as OnFinalize<u64>>::on_finalize(DKG::round_end(1));
+		assert_eq!(DKG::dkg_phase(), DkgPhase::Round2);
+
+		// fewer than THRESHOLD dealers qualified, the DKG is aborted
+		for ix in 0..THRESHOLD - 1 {
+			<DKG as Store>::CommittedPolynomials::insert(
+				ix as AuthIndex,
+				vec![Commitment::default(); THRESHOLD],
+			);
+			<DKG as Store>::IsCorrectDealer::insert(ix as AuthIndex, true);
+		}
+		<DKG as OnFinalize<u64>>::on_finalize(DKG::round_end(2));
+		assert_eq!(DKG::dkg_phase(), DkgPhase::Failed);
+		assert_eq!(DKG::epoch_master_key(1), None);
+
+		<DKG as OnInitialize<u64>>::on_initialize(DKG::master_key_ready());
+		assert_eq!(DKG::dkg_phase(), DkgPhase::Failed);
+		assert_eq!(DKG::active_epoch(), Some(0));
+	});
+}
+
+#[test]
+fn resharing_keeps_master_key() {
+	let (mut t, _, my_id) = new_test_ext();
+	t.execute_with(|| {
+		let my_ix = init(my_id, N_MEMBERS, THRESHOLD as u64);
+		let ready = DKG::master_key_ready();
+
+		// keys of epoch 0 given by a polynomial of degree THRESHOLD - 1
+		let poly: Vec<Scalar> = (0..THRESHOLD).map(|i| Scalar::from(i as u64 + 7)).collect();
+		let old_shares: Vec<Scalar> = (0..N_MEMBERS)
+			.map(|ix| poly_eval(&poly, &Scalar::from(ix as u64 + 1)))
+			.collect();
+		let mvk = VerifyKey::from_secret(&poly[0]);
+		<DKG as Store>::EpochMasterKeys::insert(0, mvk.clone());
+		<DKG as Store>::EpochVerificationKeys::insert(
+			0,
+			old_shares.iter().map(VerifyKey::from_secret).collect::<Vec<_>>(),
+		);
+		<DKG as OnInitialize<u64>>::on_initialize(ready);
+		assert_eq!(DKG::active_epoch(), Some(0));
+
+		let new_threshold = 2;
+		let n_new = 3;
+		let committee = distinct_committee(n_new);
+		assert_ok!(DKG::reshare_to_committee(Origin::root(), committee, new_threshold));
+		<DKG as OnInitialize<u64>>::on_initialize(ready + 1);
+		assert_eq!(DKG::current_epoch(), 1);
+		assert_eq!(DKG::current_epoch_kind(), EpochKind::Resharing);
+		assert_eq!(DKG::n_dealers(), N_MEMBERS);
+
+		// the keybox is still built from the index and the threshold of the active committee
+		let (ix, vks, master_key, threshold) = DKG::public_keybox_parts().unwrap();
+		assert_eq!(ix, Some(my_ix));
+		assert_eq!(vks.len(), N_MEMBERS);
+		assert_eq!(master_key, mvk);
+		assert_eq!(threshold, THRESHOLD as u64);
+
+		// every old member reshares its secret share, the dealer 1 is disqualified
+		let sub_polys: Vec<Vec<Scalar>> = old_shares
+			.iter()
+			.enumerate()
+			.map(|(ix, share)| vec![*share, Scalar::from(ix as u64 + 100)])
+			.collect();
+		for (ix, sub_poly) in sub_polys.iter().enumerate() {
+			let comms = sub_poly.iter().map(|c| Commitment::new(*c)).collect::<Vec<_>>();
+			assert!(comms[0].matches_key(&<DKG as Store>::VerificationKeys::get()[ix]));
+			<DKG as Store>::CommittedPolynomials::insert(ix as AuthIndex, comms);
+			<DKG as Store>::IsCorrectDealer::insert(ix as AuthIndex, ix != 1);
+		}
+		let dealers = DKG::resharing_dealers();
+		assert_eq!(dealers, vec![0, 2, 3]);
+
+		// a member missing the share of one of these dealers does not store a wrong secret
+		let mut shares = vec![Some(Scalar::from(1).to_bytes()); N_MEMBERS];
+		shares[2] = None;
+		StorageValueRef::persistent(&DKG::build_storage_key(b"secret_shares", 2)).set(&shares);
+		DKG::handle_round3();
+		let st_key = DKG::build_storage_key(b"threshold_secret_key", 3);
+		assert_eq!(StorageValueRef::persistent(&st_key).get::<bool>(), Some(Some(false)));
+
+		<DKG as OnFinalize<u64>>::on_finalize(DKG::round_end(2));
+		assert_eq!(<DKG as Store>::EpochMasterKeys::get(1), Some(mvk.clone()));
+
+		let weights = lagrange_coefs_at_zero(&dealers);
+		let new_shares: Vec<Scalar> = (0..n_new)
+			.map(|ix| {
+				let x = &Scalar::from(ix as u64 + 1);
+				dealers
+					.iter()
+					.zip(weights.iter())
+					.map(|(dealer, w)| poly_eval(&sub_polys[*dealer as usize], x) * w)
+					.fold(Scalar::zero(), |a, b| a + b)
+			})
+			.collect();
+		let vks = new_shares.iter().map(VerifyKey::from_secret).collect::<Vec<_>>();
+		assert_eq!(<DKG as Store>::EpochVerificationKeys::get(1), Some(vks));
+
+		// any new_threshold of the new shares recover the old master secret
+		let ids: [u64; 2] = [0, 2];
+		let secret = ids
+			.iter()
+			.zip(lagrange_coefs_at_zero(&ids).iter())
+			.map(|(ix, w)| new_shares[*ix as usize] * w)
+			.fold(Scalar::zero(), |a, b| a + b);
+		assert_eq!(secret, poly[0]);
+
+		<DKG as OnInitialize<u64>>::on_initialize(DKG::master_key_ready());
+		assert_eq!(DKG::active_epoch(), Some(1));
+		assert_eq!(DKG::master_verification_key(), Some(mvk));
+		assert_eq!(<DKG as Store>::VerificationKeys::get().len(), n_new);
+	});
+}
+
+#[test]
+fn dispute_reveals_misaddressed_share() {
+	let (mut t, _, my_id) = new_test_ext();
+	t.execute_with(|| {
+		let my_ix = init(my_id.clone(), N_MEMBERS, THRESHOLD as u64);
+		for ix in 0..N_MEMBERS {
+			<DKG as Store>::EncryptionPKs::insert(
+				ix as AuthIndex,
+				EncryptionPublicKey::from_raw_scalar([ix as u64 + 1, 0, 0, 0]),
+			);
+		}
+		let dealer = (my_ix + 1) % N_MEMBERS as AuthIndex;
+		let poly = vec![Scalar::from(3); THRESHOLD];
+		let (shares, comms) = enc_shares_comms(dealer, Scalar::from(dealer + 1), poly.clone());
+		set_shares_comms(dealer, shares, comms);
+		assert_eq!(DKG::qualified_dealers(), vec![dealer]);
+
+		// the dealer encrypts the share of my_ix as if it was meant for itself
+		let (ek, proof) = <DKG as Store>::EncryptionPKs::get(dealer)
+			.to_encryption_key_with_proof(Scalar::from(my_ix + 1));
+		let share = poly_eval(&poly, &Scalar::from(my_ix + 1));
+		let misaddressed = ek.encrypt(&share, dealer, dealer, &DKG::shares_round_hash());
+		<DKG as Store>::EncryptedShares::insert((dealer, my_ix), misaddressed);
+
+		frame_system::Module::<Runtime>::set_block_number(DKG::round_end(2));
+
+		// a dispute with a proof for another key is ignored, the correct one disqualifies the dealer
+		let (other_ek, _) = <DKG as Store>::EncryptionPKs::get(my_ix)
+			.to_encryption_key_with_proof(Scalar::from(my_ix + 1));
+		let disputes = Disputes {
+			disputes: vec![(dealer, other_ek, proof.clone()), (dealer, ek, proof)],
+			hash_round1: Default::default(),
+		};
+		let (payload, signature) = signed_payload(&my_id, my_ix, disputes);
+		let post_info =
+			DKG::post_disputes(Origin::none(), payload.clone(), signature.clone()).unwrap();
+		assert!(!<DKG as Store>::IsCorrectDealer::get(dealer));
+		assert!(DKG::qualified_dealers().is_empty());
+		assert_eq!(
+			reported_offences(),
+			vec![(*b"dkg:bad-dealings", vec![<DKG as Store>::Authorities::get(dealer)])]
+		);
+		assert_eq!(post_info.pays_fee, Pays::No);
+		assert_eq!(
+			post_info.actual_weight,
+			Some(DKG::post_disputes_weight(2))
+		);
+
+		assert_noop!(
+			DKG::post_disputes(Origin::none(), payload, signature),
+			Error::<Runtime>::AlreadySubmitted
+		);
+	});
+}
+
+#[test]
+fn disputes_are_split_into_calls() {
+	let (mut t, _, my_id) = new_test_ext();
+	t.execute_with(|| {
+		let my_ix = init(my_id.clone(), N_MEMBERS, THRESHOLD as u64);
+		for ix in 0..N_MEMBERS {
+			<DKG as Store>::EncryptionPKs::insert(
+				ix as AuthIndex,
+				EncryptionPublicKey::from_raw_scalar([ix as u64 + 1, 0, 0, 0]),
+			);
+			<DKG as Store>::IsCorrectDealer::insert(ix as AuthIndex, true);
+		}
+		frame_system::Module::<Runtime>::set_block_number(DKG::round_end(2));
+
+		// the dealers did not post shares for my_ix, so every dispute disqualifies its dealer
+		let dispute = |dealer: AuthIndex| {
+			let (ek, proof) = <DKG as Store>::EncryptionPKs::get(dealer)
+				.to_encryption_key_with_proof(Scalar::from(my_ix + 1));
+			(dealer, ek, proof)
+		};
+		let dealers: Vec<_> = (0..N_MEMBERS as AuthIndex).filter(|ix| *ix != my_ix).collect();
+		let post = |dealers: &[AuthIndex]| {
+			let disputes = Disputes {
+				disputes: dealers.iter().map(|dealer| dispute(*dealer)).collect(),
+				hash_round1: Default::default(),
+			};
+			let (payload, signature) = signed_payload(&my_id, my_ix, disputes);
+			DKG::post_disputes(Origin::none(), payload, signature)
+		};
+
+		// the calls of the offchain worker fit into a block
+		let max = DKG::max_disputes_per_call();
+		let max_weight = <Runtime as frame_system::Trait>::MaximumExtrinsicWeight::get();
+		assert!(DKG::post_disputes_weight(max) <= max_weight);
+		assert!(DKG::post_disputes_weight(max + 1) > max_weight);
+
+		assert_ok!(post(&dealers[..1]));
+		assert!(!<DKG as Store>::IsCorrectDealer::get(dealers[0]));
+		assert_noop!(post(&dealers[..2]), Error::<Runtime>::AlreadySubmitted);
+		assert_ok!(post(&dealers[1..]));
+		for dealer in &dealers {
+			assert!(!<DKG as Store>::IsCorrectDealer::get(dealer));
+		}
+		assert_noop!(post(&[]), Error::<Runtime>::AlreadySubmitted);
+	});
+}
+
+#[test]
+fn dkg_absence_is_reported() {
+	let (mut t, _, my_id) = new_test_ext();
+	t.execute_with(|| {
+		let my_ix = init(my_id.clone(), N_MEMBERS, THRESHOLD as u64);
+		<DKG as Store>::EncryptionPKs::insert(
+			my_ix,
+			EncryptionPublicKey::from_raw_scalar([1, 0, 0, 0]),
+		);
+		<DKG as OnFinalize<u64>>::on_finalize(DKG::round_end(0));
+		assert!(reported_offences().is_empty());
+
+		// the other members neither posted their keys nor dealt, each of them is reported once
+		<DKG as Store>::CommittedPolynomials::insert(
+			my_ix,
+			vec![Commitment::default(); THRESHOLD],
+		);
+		<DKG as OnFinalize<u64>>::on_finalize(DKG::round_end(1));
+		assert_eq!(DKG::dkg_phase(), DkgPhase::Round2);
+		assert_eq!(
+			reported_offences(),
+			vec![(*b"dkg:unresponsive", vec![crypto::DKGId::default()])]
+		);
+
+		// the offences are reported only at the end of round 1
+		<DKG as OnFinalize<u64>>::on_finalize(DKG::round_end(2));
+		assert!(reported_offences().is_empty());
+	});
+}
+
+#[test]
+fn beacon_participation_is_proven_with_shares() {
+	let (mut t, states, my_id) = new_test_ext();
+	t.execute_with(|| {
+		let my_ix = init(my_id.clone(), N_MEMBERS, THRESHOLD as u64);
+		let nonce = H256::repeat_byte(1);
+		set_beacon_participation(5, Some(nonce));
+
+		let secret = Scalar::from(7);
+		let mut vks = vec![VerifyKey::default(); N_MEMBERS];
+		vks[my_ix as usize] = VerifyKey::from_secret(&secret);
+		assert!(sp_dkg::keystore::secret_shares::store(
+			&vks[my_ix as usize].encode(),
+			&secret.to_bytes()
+		));
+		<DKG as Store>::EpochMasterKeys::insert(0, VerifyKey::default());
+		<DKG as Store>::EpochVerificationKeys::insert(0, vks);
+		let ready = DKG::master_key_ready();
+		<DKG as OnInitialize<u64>>::on_initialize(ready);
+		assert_eq!(DKG::beacon_window_start(), ready);
+		frame_system::Module::<Runtime>::set_block_number(ready + 1);
+
+		// a share for another nonce or under another key does not prove the participation
+		let sign = |secret: Scalar, nonce: H256| {
+			ShareProvider::from_secret(my_ix, secret).sign(&nonce.encode(), SignatureVersion::LATEST)
+		};
+		let stale_nonce = H256::repeat_byte(2);
+		let stale = BeaconShare {
+			nonce: stale_nonce,
+			share: sign(secret, stale_nonce),
+		};
+		let (payload, signature) = signed_payload(&my_id, my_ix, stale);
+		assert_noop!(
+			DKG::post_beacon_share(Origin::none(), payload, signature),
+			Error::<Runtime>::WrongBeaconNonce
+		);
+		let forged = BeaconShare {
+			nonce,
+			share: sign(Scalar::from(8), nonce),
+		};
+		let (payload, signature) = signed_payload(&my_id, my_ix, forged);
+		assert_noop!(
+			DKG::post_beacon_share(Origin::none(), payload, signature),
+			Error::<Runtime>::InvalidBeaconShare
+		);
+
+		DKG::handle_beacon_participation();
+		let tx = states.pool.write().transactions.pop().unwrap();
+		let tx = Extrinsic::decode(&mut &*tx).unwrap();
+		let (payload, signature) = match tx.call {
+			Call::post_beacon_share(payload, signature) => (payload, signature),
+			call => panic!("Unexpected call {:?}", call),
+		};
+		assert_eq!(payload.ix, my_ix);
+		assert_eq!(payload.message.nonce, nonce);
+		let call = Call::post_beacon_share(payload.clone(), signature.clone());
+		let valid =
+			<DKG as ValidateUnsigned>::validate_unsigned(TransactionSource::External, &call)
+				.unwrap();
+		assert_eq!(valid.longevity, 5);
+
+		assert_ok!(DKG::post_beacon_share(Origin::none(), payload, signature));
+		DKG::handle_beacon_participation();
+		assert!(states.pool.read().transactions.is_empty());
+
+		// the members who did not post a share are reported at the end of the window
+		<DKG as OnInitialize<u64>>::on_initialize(ready + 4);
+		assert!(reported_offences().is_empty());
+		<DKG as OnInitialize<u64>>::on_initialize(ready + 5);
+		assert_eq!(DKG::beacon_window_start(), ready + 5);
+		assert_eq!(
+			reported_offences(),
+			vec![(*b"beacon:no-shares", vec![crypto::DKGId::default(); N_MEMBERS - 1])]
+		);
+
+		// nobody is reported while the beacon does not run
+		set_beacon_participation(5, None);
+		<DKG as OnInitialize<u64>>::on_initialize(ready + 10);
+		assert!(reported_offences().is_empty());
+	});
+}
+
+#[test]
+fn submissions_are_checked() {
+	let (mut t, _, my_id) = new_test_ext();
+	t.execute_with(|| {
+		let my_ix = init(my_id.clone(), N_MEMBERS, THRESHOLD as u64);
+		let enc_pk = EncryptionPublicKey::from_raw_scalar([1, 0, 0, 0]);
+		let other_ix = (my_ix + 1) % N_MEMBERS as AuthIndex;
+
+		let (payload, signature) = signed_payload(&my_id, other_ix, enc_pk.clone());
+		assert_noop!(
+			DKG::post_encryption_key(Origin::none(), payload, signature),
+			Error::<Runtime>::NotAuthority
+		);
+		let (mut payload, signature) = signed_payload(&my_id, my_ix, enc_pk.clone());
+		let post_info =
+			DKG::post_encryption_key(Origin::none(), payload.clone(), signature.clone()).unwrap();
+		assert_eq!(post_info.pays_fee, Pays::No);
+		assert_eq!(
+			post_info.actual_weight,
+			Some(<Runtime as Trait>::WeightInfo::post_encryption_key(N_MEMBERS as u32))
+		);
+		assert_noop!(
+			DKG::post_encryption_key(Origin::none(), payload.clone(), signature.clone()),
+			Error::<Runtime>::AlreadySubmitted
+		);
+		assert_noop!(
+			DKG::post_dealer_encryption_key(Origin::none(), payload.clone(), signature.clone()),
+			Error::<Runtime>::NotResharing
+		);
+		payload.epoch += 1;
+		assert_noop!(
+			DKG::post_encryption_key(Origin::none(), payload, signature),
+			Error::<Runtime>::WrongEpoch
+		);
+		let disputes = Disputes {
+			disputes: Vec::new(),
+			hash_round1: Default::default(),
+		};
+		let (payload, signature) = signed_payload(&my_id, my_ix, disputes);
+		assert_noop!(
+			DKG::post_disputes(Origin::none(), payload, signature),
+			Error::<Runtime>::WrongRound
+		);
+
+		frame_system::Module::<Runtime>::set_block_number(DKG::round_end(1));
+		let (payload, signature) = signed_payload(&my_id, my_ix, enc_pk);
+		assert_noop!(
+			DKG::post_encryption_key(Origin::none(), payload, signature),
+			Error::<Runtime>::WrongRound
+		);
+		let mut shares = SecretShares {
+			shares: Vec::new(),
+			comm_poly: Vec::new(),
+			hash_round0: Default::default(),
+		};
+		let (payload, signature) = signed_payload(&my_id, my_ix, shares.clone());
+		assert_noop!(
+			DKG::post_secret_shares(Origin::none(), payload, signature),
+			Error::<Runtime>::WrongSharesLength
+		);
+		shares.shares = vec![None; N_MEMBERS];
+		let (payload, signature) = signed_payload(&my_id, my_ix, shares);
+		assert_noop!(
+			DKG::post_secret_shares(Origin::none(), payload, signature),
+			Error::<Runtime>::WrongCommitmentsLength
+		);
+	});
+}
+
+#[test]
+fn unsigned_submissions_are_validated() {
+	let (mut t, _, my_id) = new_test_ext();
+	t.execute_with(|| {
+		let my_ix = init(my_id.clone(), N_MEMBERS, THRESHOLD as u64);
+		let enc_pk = EncryptionPublicKey::from_raw_scalar([1, 0, 0, 0]);
+		let validate = |call: &Call<Runtime>| {
+			<DKG as ValidateUnsigned>::validate_unsigned(TransactionSource::External, call)
+		};
+
+		let (payload, signature) = signed_payload(&my_id, my_ix, enc_pk.clone());
+		let valid = validate(&Call::post_encryption_key(
+			payload.clone(),
+			signature.clone(),
+		))
+		.unwrap();
+		assert_eq!(valid.priority, UnsignedPriority::get());
+		assert_eq!(valid.longevity, DKG::round_end(0) + 1);
+
+		// the signature covers the whole payload
+		let mut forged = payload.clone();
+		forged.message = EncryptionPublicKey::from_raw_scalar([2, 0, 0, 0]);
+		assert_eq!(
+			validate(&Call::post_encryption_key(forged, signature.clone())),
+			InvalidTransaction::BadProof.into()
+		);
+
+		let (other_payload, other_signature) =
+			signed_payload(&my_id, (my_ix + 1) % N_MEMBERS as AuthIndex, enc_pk);
+		assert_eq!(
+			validate(&Call::post_encryption_key(other_payload, other_signature)),
+			InvalidTransaction::BadProof.into()
+		);
+
+		assert_ok!(DKG::post_encryption_key(
+			Origin::none(),
+			payload.clone(),
+			signature.clone()
+		));
+		assert_eq!(
+			validate(&Call::post_encryption_key(payload, signature)),
+			InvalidTransaction::Stale.into()
+		);
+	});
+}
+
+fn signed_payload<M: Encode>(
+	id: &sp_dkg::crypto::AuthorityId,
+	ix: AuthIndex,
+	message: M,
+) -> (DKGPayload<<Signature as Verify>::Signer, M>, Signature) {
+	let auth: crypto::DKGId = id.clone().into();
+	let payload = DKGPayload {
+		ix,
+		epoch: DKG::current_epoch(),
+		message,
+		public: auth.into(),
+	};
+	let signature = SignedPayload::<Runtime>::sign::<crypto::DKGId>(&payload).unwrap();
+	(payload, signature)
+}
+
 #[derive(Clone)]
 pub(crate) struct States {
 	pub(crate) offchain: Arc<RwLock<OffchainState>>,
 	pool: Arc<RwLock<PoolState>>,
+	pub(crate) keystore: Arc<KeyStore>,
 }
 
 pub(crate) fn new_test_ext() -> (
@@ -53,27 +634,40 @@ pub(crate) fn new_test_ext() -> (
 
 	let (offchain, offchain_state) = TestOffchainExt::new();
 	let (pool, pool_state) = TestTransactionPoolExt::new();
-	let keystore = KeyStore::new();
+	let keystore = Arc::new(KeyStore::new());
 	let my_id: sp_dkg::crypto::AuthorityId = SyncCryptoStore::sr25519_generate_new(
-		&keystore,
+		&*keystore,
 		sp_dkg::crypto::AuthorityId::ID,
 		Some(&format!("{}/alice", PHRASE)),
 	)
 	.unwrap()
 	.into();
 
+	// the mocks keep their state in thread locals, which outlive a test run on the same thread
+	OFFENCES.with(|offences| offences.borrow_mut().clear());
+	BEACON_PARTICIPATION.with(|participation| *participation.borrow_mut() = Default::default());
+
 	let mut ext = sp_io::TestExternalities::default();
 	ext.register_extension(OffchainExt::new(offchain));
 	ext.register_extension(TransactionPoolExt::new(pool));
-	ext.register_extension(KeystoreExt(Arc::new(keystore)));
+	ext.register_extension(KeystoreExt(keystore.clone()));
 
 	let states = States {
 		offchain: offchain_state,
 		pool: pool_state,
+		keystore,
 	};
 	(ext, states, my_id)
 }
 
+// a committee of the given number of distinct authorities
+fn distinct_committee(n_members: usize) -> Vec<crypto::DKGId> {
+	(0..n_members)
+		.map(|ix| sp_core::sr25519::Public::from_raw([ix as u8 + 1; 32]))
+		.map(|public| sp_dkg::crypto::Public::from(public).into())
+		.collect()
+}
+
 pub(crate) fn init(my_id: sp_dkg::crypto::AuthorityId, n_members: usize, threshold: u64) -> AuthIndex {
 	let mut authorities = vec![crypto::DKGId::default(); n_members];
 	authorities[0] = my_id.clone().into();
@@ -114,8 +708,16 @@ fn test_handle_round0(states: &States, my_ix: AuthIndex) {
 	let tx = states.pool.write().transactions.pop().unwrap();
 	assert!(states.pool.read().transactions.is_empty());
 	let tx = Extrinsic::decode(&mut &*tx).unwrap();
-	assert_eq!(tx.signature.unwrap().0, 0);
-	assert_eq!(tx.call, Call::post_encryption_key(my_ix, enc_pk.clone()));
+	assert_eq!(tx.signature, None);
+	let (payload, signature) = match tx.call {
+		Call::post_encryption_key(payload, signature) => (payload, signature),
+		call => panic!("Unexpected call {:?}", call),
+	};
+	assert_eq!(payload.ix, my_ix);
+	assert_eq!(payload.message, enc_pk);
+	assert!(SignedPayload::<Runtime>::verify::<crypto::DKGId>(
+		&payload, signature
+	));
 
 	// manually add the rest encryption public keys
 	for ix in 0..N_MEMBERS {
@@ -139,16 +741,18 @@ fn encryption_keys(secret: Scalar) -> Vec<EncryptionKey> {
 }
 
 fn enc_shares_comms(
+	dealer: AuthIndex,
 	secret_enc_key: Scalar,
 	poly: Vec<Scalar>,
 ) -> (Vec<Option<EncryptedShare>>, Vec<Commitment>) {
+	let round_hash = DKG::shares_round_hash();
 	let enc_shares = encryption_keys(secret_enc_key)
 		.iter()
 		.enumerate()
 		.map(|(ix, enc_key)| {
 			let x = &Scalar::from(ix as u64 + 1);
 			let share = poly_eval(&poly, x);
-			Some(enc_key.encrypt(&share))
+			Some(enc_key.encrypt(&share, dealer, ix as AuthIndex, &round_hash))
 		})
 		.collect();
 
@@ -191,17 +795,25 @@ fn test_handle_round1(states: &States, my_ix: AuthIndex) {
 		.collect();
 
 	let secret_enc_key = Scalar::from_raw(get_secret_enc_key(states.offchain.clone()));
-	let (enc_shares, commitments) = enc_shares_comms(secret_enc_key, poly);
+	let (enc_shares, commitments) = enc_shares_comms(my_ix, secret_enc_key, poly);
 	set_shares_comms(my_ix, enc_shares.clone(), commitments.clone());
 
 	let tx = states.pool.write().transactions.pop().unwrap();
 	assert!(states.pool.read().transactions.is_empty());
 	let tx = Extrinsic::decode(&mut &*tx).unwrap();
-	assert_eq!(tx.signature.unwrap().0, 1);
-
+	assert_eq!(tx.signature, None);
+	let payload = match tx.call {
+		Call::post_secret_shares(payload, _) => payload,
+		call => panic!("Unexpected call {:?}", call),
+	};
+	assert_eq!(payload.ix, my_ix);
 	assert_eq!(
-		tx.call,
-		Call::post_secret_shares(my_ix, enc_shares, commitments, Default::default())
+		payload.message,
+		SecretShares {
+			shares: enc_shares,
+			comm_poly: commitments,
+			hash_round0: Default::default(),
+		}
 	);
 
 	// manually add enc_shares and commitments
@@ -211,7 +823,7 @@ fn test_handle_round1(states: &States, my_ix: AuthIndex) {
 		}
 		let poly = [ix, 1, 1].iter().map(|i| Scalar::from(*i as AuthIndex)).collect();
 		let secret = Scalar::from(ix as AuthIndex);
-		let (shares, comms) = enc_shares_comms(secret, poly);
+		let (shares, comms) = enc_shares_comms(ix as AuthIndex, secret, poly);
 		set_shares_comms(ix as AuthIndex, shares, comms);
 	}
 }
@@ -224,39 +836,64 @@ fn test_handle_round2(states: &States, my_ix: AuthIndex) {
 	let tx = states.pool.write().transactions.pop().unwrap();
 	assert!(states.pool.read().transactions.is_empty());
 	let tx = Extrinsic::decode(&mut &*tx).unwrap();
-	assert_eq!(tx.signature.unwrap().0, 2);
-
-	assert_eq!(tx.call, Call::post_disputes(my_ix, Vec::new(), Default::default()));
+	assert_eq!(tx.signature, None);
+	let payload = match tx.call {
+		Call::post_disputes(payload, _) => payload,
+		call => panic!("Unexpected call {:?}", call),
+	};
+	assert_eq!(payload.ix, my_ix);
+	assert_eq!(
+		payload.message,
+		Disputes {
+			disputes: Vec::new(),
+			hash_round1: Default::default(),
+		}
+	);
 }
 
 fn derive_tsk(my_ix: usize) -> Scalar {
 	let secret_enc_key = Scalar::from(my_ix as u64);
 	let encryption_keys = encryption_keys(secret_enc_key);
+	let round_hash = DKG::shares_round_hash();
 	let mut tsk = Scalar::zero();
 	for (creator, ek) in encryption_keys.iter().enumerate() {
 		let encrypted_share = &<DKG as Store>::EncryptedShares::get((creator as AuthIndex, my_ix as AuthIndex)).clone();
-		let share = ek.decrypt(&encrypted_share).unwrap();
+		let share = ek.decrypt(&encrypted_share, creator as AuthIndex, my_ix as AuthIndex, &round_hash).unwrap();
 		tsk += share;
 	}
 
 	tsk
 }
 
+// the secret share of the local node, as kept in its keystore
+fn local_secret(states: &States, my_ix: AuthIndex) -> Scalar {
+	let vk = <DKG as Store>::VerificationKeys::get()[my_ix as usize].encode();
+	let secret = SyncCryptoStore::secret(&*states.keystore, SECRET_KEY_TYPE, &vk)
+		.unwrap()
+		.unwrap();
+	Scalar::from_bytes(&sp_dkg::keystore::read_secret(&secret).unwrap()).unwrap()
+}
+
 fn test_handle_round3(states: &States, my_ix: AuthIndex) {
 	// do the round
 	DKG::handle_round3();
 	let round2_end = DKG::round_end(2);
 	<DKG as OnFinalize<u64>>::on_finalize(round2_end);
+	<DKG as OnInitialize<u64>>::on_initialize(DKG::master_key_ready());
+	assert_eq!(DKG::active_epoch(), Some(0));
 
-	// check if correct values was submitted on chain
+	// the secret key is kept in the keystore, the offchain storage only marks it as stored
 	let st_key = DKG::build_storage_key(b"threshold_secret_key", 3);
-	let tsk_encoded = states
+	let stored_encoded = states
 		.offchain
 		.read()
 		.persistent_storage
 		.get(b"", &st_key)
 		.unwrap();
-	let tsk = Scalar::from_bytes(&<[u8; 32]>::decode(&mut &tsk_encoded[..]).unwrap()).unwrap();
+	assert!(bool::decode(&mut &stored_encoded[..]).unwrap());
+	let tsk = local_secret(states, my_ix);
+
+	// check if correct values was submitted on chain
 
 	let st_key = DKG::build_storage_key(b"secret_shares", 2);
 	let tsk_shares_encoded = states
@@ -277,6 +914,7 @@ fn test_handle_round3(states: &States, my_ix: AuthIndex) {
 	}
 
 	let mvk = Commitment::derive_key(comms);
+	assert_eq!(Some(mvk.clone()), <DKG as Store>::EpochMasterKeys::get(0));
 	assert_eq!(mvk, <DKG as Store>::MasterVerificationKey::get());
 
 	let st_key = DKG::build_storage_key(b"secret_poly", 1);
@@ -321,17 +959,9 @@ fn test_keybox(states: &States, my_ix: AuthIndex) {
 	let mut kbs = Vec::new();
 	let vks = <DKG as Store>::VerificationKeys::get();
 	let mvk = <DKG as Store>::MasterVerificationKey::get();
-	let st_key = DKG::build_storage_key(b"threshold_secret_key", 3);
 	for ix in 0..N_MEMBERS {
 		if ix as AuthIndex == my_ix {
-			let tsk_encoded = states
-				.offchain
-				.read()
-				.persistent_storage
-				.get(b"", &st_key)
-				.unwrap();
-			let tsk =
-				Scalar::from_bytes(&<[u8; 32]>::decode(&mut &tsk_encoded[..]).unwrap()).unwrap();
+			let tsk = local_secret(states, my_ix);
 			let tsp = ShareProvider::from_secret(ix as AuthIndex, tsk);
 			kbs.push(KeyBox::new(
 				Some(tsp),
@@ -362,7 +992,7 @@ fn test_keybox(states: &States, my_ix: AuthIndex) {
 	for msg in msgs.iter() {
 		let mut shares = Vec::new();
 		for ix in 0..N_MEMBERS {
-			let share = kbs[ix].generate_share(msg).unwrap();
+			let share = kbs[ix].generate_share(msg, SignatureVersion::LATEST).unwrap();
 			assert!(kbs[ix].verify_share(&msg, &share));
 			shares.push(share);
 		}
@@ -388,7 +1018,7 @@ pub struct Runtime;
 
 parameter_types! {
 	pub const BlockHashCount: u64 = 250;
-	pub const MaximumBlockWeight: Weight = 1024;
+	pub const MaximumBlockWeight: Weight = 2 * WEIGHT_PER_SECOND;
 	pub const MaximumBlockLength: u32 = 2 * 1024;
 	pub const AvailableBlockRatio: Perbill = Perbill::one();
 }
@@ -422,7 +1052,7 @@ impl frame_system::Trait for Runtime {
 }
 
 type Signature = MultiSignature;
-type Extrinsic = TestXt<Call<Runtime>, ()>;
+pub(crate) type Extrinsic = TestXt<Call<Runtime>, ()>;
 type AccountId = <<Signature as Verify>::Signer as IdentifyAccount>::AccountId;
 
 impl frame_system::offchain::SigningTypes for Runtime {
@@ -460,11 +1090,62 @@ parameter_types! {
 	pub const DKGReady: u64 = 10;
 }
 
+thread_local! {
+	static OFFENCES: RefCell<Vec<(Kind, Vec<crypto::DKGId>)>> = Default::default();
+	static BEACON_PARTICIPATION: RefCell<(u64, Option<H256>)> = Default::default();
+}
+
+// records the kinds and the offenders of the reported offences
+pub struct RecordOffences;
+impl<O: Offence<crypto::DKGId>> ReportOffence<AccountId, crypto::DKGId, O> for RecordOffences {
+	fn report_offence(_reporters: Vec<AccountId>, offence: O) -> Result<(), OffenceError> {
+		OFFENCES.with(|offences| offences.borrow_mut().push((O::ID, offence.offenders())));
+		Ok(())
+	}
+
+	fn is_known_offence(_offenders: &[crypto::DKGId], _time_slot: &O::TimeSlot) -> bool {
+		false
+	}
+}
+
+fn reported_offences() -> Vec<(Kind, Vec<crypto::DKGId>)> {
+	OFFENCES.with(|offences| offences.borrow_mut().drain(..).collect())
+}
+
+// the participation in the beacon is not tracked unless the test calls
+// `set_beacon_participation`
+pub struct BeaconParticipationPeriod;
+impl Get<u64> for BeaconParticipationPeriod {
+	fn get() -> u64 {
+		BEACON_PARTICIPATION.with(|participation| participation.borrow().0)
+	}
+}
+
+pub struct LatestBeaconNonce;
+impl Get<Option<H256>> for LatestBeaconNonce {
+	fn get() -> Option<H256> {
+		BEACON_PARTICIPATION.with(|participation| participation.borrow().1)
+	}
+}
+
+fn set_beacon_participation(period: u64, nonce: Option<H256>) {
+	BEACON_PARTICIPATION.with(|participation| *participation.borrow_mut() = (period, nonce));
+}
+
 impl Trait for Runtime {
 	type Call = Call<Runtime>;
 	type AuthorityId = crypto::DKGId;
 	type DKGReady = DKGReady;
+	type CommitteeOrigin = frame_system::EnsureRoot<AccountId>;
+	type UnsignedPriority = UnsignedPriority;
 	type Event = ();
+	type KeyOwnerIdentification = crypto::DKGId;
+	type KeyOwner = sp_runtime::traits::ConvertInto;
+	type CurrentSession = ();
+	type ReportOffences = RecordOffences;
+	type BeaconParticipationPeriod = BeaconParticipationPeriod;
+	type LatestBeaconNonce = LatestBeaconNonce;
+	type WeightInfo = ();
 }
 
 pub type DKG = Module<Runtime>;
@@ -0,0 +1,67 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Runtime API definition required by the Ethereum JSON-RPC of the EVM pallet.
+//!
+//! This API should be imported and implemented by the runtime
+//! of a node that wants to serve the `eth_*` methods of `pallet-evm-rpc`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use sp_core::{H160, U256};
+use sp_runtime::{DispatchError, traits::Block as BlockT};
+use sp_std::vec::Vec;
+
+pub use pallet_evm::{
+	Account, EthTransaction, ExecutionInfo, Log, TransactionAction, TransactionStatus,
+};
+
+sp_api::decl_runtime_apis! {
+	/// The API to access the EVM and its Ethereum transactions.
+	pub trait EvmApi {
+		/// The chain id Ethereum transactions have to be signed for.
+		fn chain_id() -> u64;
+
+		/// The nonce and the balance of an account.
+		fn account_basic(address: H160) -> Account;
+
+		/// The minimal gas price accepted by the EVM.
+		fn gas_price() -> U256;
+
+		/// The code of a contract, empty for other accounts.
+		fn account_code(address: H160) -> Vec<u8>;
+
+		/// Execute a call or a contract creation without applying its state.
+		///
+		/// See `pallet_evm::Module::simulate` for more details.
+		fn call(
+			from: H160,
+			action: TransactionAction,
+			data: Vec<u8>,
+			value: U256,
+			gas_limit: u32,
+			gas_price: U256,
+			nonce: Option<U256>,
+		) -> Result<ExecutionInfo, DispatchError>;
+
+		/// The statuses of the Ethereum transactions executed in the block.
+		fn transaction_statuses() -> Vec<TransactionStatus>;
+
+		/// The unsigned extrinsic calling `transact` with the given transaction.
+		fn transact_extrinsic(transaction: EthTransaction) -> <Block as BlockT>::Extrinsic;
+	}
+}
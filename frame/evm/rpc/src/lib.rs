@@ -0,0 +1,568 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Ethereum-compatible JSON-RPC methods of the EVM pallet.
+//!
+//! Ethereum wallets and tools submit their transactions signed as specified in EIP-155 with
+//! `eth_sendRawTransaction`; they are included as unsigned `transact` extrinsics. Blocks are
+//! addressed by their Substrate numbers and hashes, the receipts and the logs of the Ethereum
+//! transactions are served from the mapping in the aux-db, see the `mapping` module.
+
+use std::{collections::BTreeMap, sync::Arc, time::{Duration, Instant}};
+
+use futures::future::TryFutureExt;
+use jsonrpc_core::{BoxFuture, Error, ErrorCode, Result, futures::{Future, future}};
+use jsonrpc_derive::rpc;
+use parking_lot::Mutex;
+use sc_client_api::backend::AuxStore;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::{Bytes, H160, H256, U256, hexdisplay::HexDisplay};
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, Header as HeaderT, UniqueSaturatedFrom, UniqueSaturatedInto, Zero},
+};
+use sp_transaction_pool::{TransactionPool, TransactionSource};
+
+pub mod mapping;
+pub mod types;
+
+pub use self::gen_client::Client as EthClient;
+pub use self::types::{BlockNumber, CallRequest, Filter, Log, Receipt, ValueOrArray};
+pub use pallet_evm_rpc_runtime_api::{
+	self as runtime_api, EthTransaction, EvmApi as EvmRuntimeApi, TransactionAction,
+	TransactionStatus,
+};
+
+const RUNTIME_ERROR: i64 = 1;
+const EXECUTION_ERROR: i64 = 2;
+const POOL_ERROR: i64 = 3;
+const UNKNOWN_FILTER: i64 = 4;
+
+/// The most gas `eth_call` and `eth_estimateGas` may use, and the default gas limit of both,
+/// to prevent blocking the RPC for too long.
+const MAX_GAS_LIMIT: u32 = 25_000_000;
+
+/// The most blocks whose logs are returned at once.
+const MAX_LOG_BLOCK_RANGE: u64 = 1024;
+
+/// The most filters installed with `eth_newFilter` at once.
+const MAX_INSTALLED_FILTERS: usize = 500;
+
+/// Filters not polled for this long are uninstalled.
+const FILTER_LIFETIME: Duration = Duration::from_secs(5 * 60);
+
+/// Ethereum-compatible RPC methods.
+#[rpc]
+pub trait EthApi {
+	/// Returns the chain id transactions have to be signed for.
+	#[rpc(name = "eth_chainId")]
+	fn chain_id(&self) -> Result<U256>;
+
+	/// Returns the number of the best block.
+	#[rpc(name = "eth_blockNumber")]
+	fn block_number(&self) -> Result<U256>;
+
+	/// Returns the minimal gas price accepted by the EVM.
+	#[rpc(name = "eth_gasPrice")]
+	fn gas_price(&self) -> Result<U256>;
+
+	/// Returns the balance of an account.
+	#[rpc(name = "eth_getBalance")]
+	fn balance(&self, address: H160, number: Option<BlockNumber>) -> Result<U256>;
+
+	/// Returns the nonce of an account.
+	#[rpc(name = "eth_getTransactionCount")]
+	fn transaction_count(
+		&self,
+		address: H160,
+		number: Option<BlockNumber>,
+	) -> Result<U256>;
+
+	/// Returns the code of a contract.
+	#[rpc(name = "eth_getCode")]
+	fn code(&self, address: H160, number: Option<BlockNumber>) -> Result<Bytes>;
+
+	/// Executes a message call or a contract creation without submitting a transaction and
+	/// returns its output.
+	#[rpc(name = "eth_call")]
+	fn call(&self, request: CallRequest, number: Option<BlockNumber>) -> Result<Bytes>;
+
+	/// Executes a message call or a contract creation without submitting a transaction and
+	/// returns the lowest gas limit with which it succeeds.
+	#[rpc(name = "eth_estimateGas")]
+	fn estimate_gas(&self, request: CallRequest, number: Option<BlockNumber>) -> Result<U256>;
+
+	/// Submits an RLP encoded transaction signed as specified in EIP-155 and returns its hash.
+	#[rpc(name = "eth_sendRawTransaction", returns = "H256")]
+	fn send_raw_transaction(&self, transaction: Bytes) -> BoxFuture<H256>;
+
+	/// Returns the receipt of a transaction included in the canonical chain.
+	#[rpc(name = "eth_getTransactionReceipt")]
+	fn transaction_receipt(&self, hash: H256) -> Result<Option<Receipt>>;
+
+	/// Returns the logs of the canonical chain matching the filter.
+	#[rpc(name = "eth_getLogs")]
+	fn logs(&self, filter: Filter) -> Result<Vec<Log>>;
+
+	/// Installs a filter and returns its id. Its changes are polled with
+	/// `eth_getFilterChanges`.
+	#[rpc(name = "eth_newFilter")]
+	fn new_filter(&self, filter: Filter) -> Result<U256>;
+
+	/// Returns the logs matching an installed filter in the blocks imported since the
+	/// previous poll.
+	#[rpc(name = "eth_getFilterChanges")]
+	fn filter_changes(&self, id: U256) -> Result<Vec<Log>>;
+
+	/// Returns all logs matching an installed filter.
+	#[rpc(name = "eth_getFilterLogs")]
+	fn filter_logs(&self, id: U256) -> Result<Vec<Log>>;
+
+	/// Uninstalls a filter, returns whether it was installed.
+	#[rpc(name = "eth_uninstallFilter")]
+	fn uninstall_filter(&self, id: U256) -> Result<bool>;
+}
+
+/// A filter installed with `eth_newFilter`.
+struct InstalledFilter {
+	filter: Filter,
+	/// The best block at the previous poll.
+	last_poll_block: u64,
+	last_poll_at: Instant,
+}
+
+#[derive(Default)]
+struct FilterPool {
+	next_id: U256,
+	filters: BTreeMap<U256, InstalledFilter>,
+}
+
+/// An implementation of the Ethereum-compatible RPC methods.
+pub struct Eth<C, P, B> {
+	client: Arc<C>,
+	pool: Arc<P>,
+	filters: Mutex<FilterPool>,
+	_marker: std::marker::PhantomData<B>,
+}
+
+impl<C, P, B> Eth<C, P, B> {
+	/// Create new `Eth` with the given reference to the client and the transaction pool.
+	pub fn new(client: Arc<C>, pool: Arc<P>) -> Self {
+		Eth {
+			client,
+			pool,
+			filters: Default::default(),
+			_marker: Default::default(),
+		}
+	}
+}
+
+impl<C, P, Block> Eth<C, P, Block>
+where
+	Block: BlockT<Hash = H256>,
+	C: Send + Sync + 'static + ProvideRuntimeApi<Block> + HeaderBackend<Block> + AuxStore,
+	C::Api: EvmRuntimeApi<Block>,
+{
+	fn best_number(&self) -> u64 {
+		UniqueSaturatedInto::<u64>::unique_saturated_into(self.client.info().best_number)
+	}
+
+	fn block_id(&self, number: Option<BlockNumber>) -> Result<BlockId<Block>> {
+		match number.unwrap_or_default() {
+			BlockNumber::Latest | BlockNumber::Pending =>
+				Ok(BlockId::hash(self.client.info().best_hash)),
+			BlockNumber::Earliest => Ok(BlockId::number(Zero::zero())),
+			BlockNumber::Num(n) => self.canonical_hash(n).map(BlockId::hash),
+		}
+	}
+
+	fn block_number_of(&self, number: Option<BlockNumber>) -> u64 {
+		match number.unwrap_or_default() {
+			BlockNumber::Latest | BlockNumber::Pending => self.best_number(),
+			BlockNumber::Earliest => 0,
+			BlockNumber::Num(n) => n,
+		}
+	}
+
+	fn canonical_hash(&self, number: u64) -> Result<H256> {
+		self.client.hash(UniqueSaturatedFrom::<u64>::unique_saturated_from(number))
+			.map_err(|e| runtime_error_into_rpc_err(e))?
+			.ok_or_else(|| Error {
+				code: ErrorCode::InvalidParams,
+				message: format!("Block {} is not in the canonical chain", number),
+				data: None,
+			})
+	}
+
+	fn simulate(
+		&self,
+		request: CallRequest,
+		at: &BlockId<Block>,
+	) -> Result<runtime_api::ExecutionInfo> {
+		let CallRequest { from, to, gas_price, gas, value, data, nonce } = request;
+
+		let gas_limit = gas.unwrap_or_else(|| U256::from(MAX_GAS_LIMIT));
+		if gas_limit > U256::from(MAX_GAS_LIMIT) {
+			return Err(Error {
+				code: ErrorCode::InvalidParams,
+				message: format!(
+					"Requested gas limit is greater than maximum allowed: {} > {}",
+					gas_limit, MAX_GAS_LIMIT
+				),
+				data: None,
+			});
+		}
+
+		let info = self.client.runtime_api()
+			.call(
+				at,
+				from.unwrap_or_default(),
+				to.map_or(TransactionAction::Create, TransactionAction::Call),
+				data.map(|d| d.0).unwrap_or_default(),
+				value.unwrap_or_default(),
+				gas_limit.low_u32(),
+				gas_price.unwrap_or_default(),
+				nonce,
+			)
+			.map_err(|e| runtime_error_into_rpc_err(e))?
+			.map_err(|e| Error {
+				code: ErrorCode::ServerError(EXECUTION_ERROR),
+				message: "The execution could not be started".into(),
+				data: Some(format!("{:?}", e).into()),
+			})?;
+
+		if !info.succeeded {
+			return Err(Error {
+				code: ErrorCode::ServerError(EXECUTION_ERROR),
+				message: "The execution failed".into(),
+				data: Some(format!("0x{}", HexDisplay::from(&info.value)).into()),
+			});
+		}
+		Ok(info)
+	}
+
+	fn block_statuses(&self, block_hash: H256) -> Result<Vec<TransactionStatus>> {
+		mapping::load_block_statuses(&*self.client, block_hash)
+			.map_err(|e| runtime_error_into_rpc_err(e))
+	}
+
+	/// The logs of a block in the format of the RPC, together with the index of the
+	/// transaction emitting each of them.
+	fn block_logs(
+		&self,
+		block_hash: H256,
+		block_number: u64,
+		statuses: &[TransactionStatus],
+	) -> Vec<(u32, Log)> {
+		statuses.iter()
+			.flat_map(|status| status.logs.iter().map(move |log| (status, log)))
+			.enumerate()
+			.map(|(log_index, (status, log))| (status.transaction_index, Log {
+				address: log.address,
+				topics: log.topics.clone(),
+				data: Bytes(log.data.clone()),
+				block_hash,
+				block_number: block_number.into(),
+				transaction_hash: status.transaction_hash,
+				transaction_index: status.transaction_index.into(),
+				log_index: log_index.into(),
+				removed: false,
+			}))
+			.collect()
+	}
+
+	fn logs_in_range(&self, filter: &Filter, from: u64, to: u64) -> Result<Vec<Log>> {
+		if to.saturating_sub(from) >= MAX_LOG_BLOCK_RANGE {
+			return Err(Error {
+				code: ErrorCode::InvalidParams,
+				message: format!(
+					"Requested block range is greater than maximum allowed: {} > {}",
+					to - from + 1, MAX_LOG_BLOCK_RANGE
+				),
+				data: None,
+			});
+		}
+
+		let mut logs = Vec::new();
+		for number in from..=to.min(self.best_number()) {
+			let hash = self.canonical_hash(number)?;
+			logs.extend(self.matching_block_logs(filter, hash, number)?);
+		}
+		Ok(logs)
+	}
+
+	fn matching_block_logs(&self, filter: &Filter, hash: H256, number: u64) -> Result<Vec<Log>> {
+		let statuses = self.block_statuses(hash)?;
+		Ok(self.block_logs(hash, number, &statuses).into_iter()
+			.map(|(_, log)| log)
+			.filter(|log| filter.matches(&log.address, &log.topics))
+			.collect())
+	}
+
+	fn filter_error(id: U256) -> Error {
+		Error {
+			code: ErrorCode::ServerError(UNKNOWN_FILTER),
+			message: format!("Filter {} is not installed", id),
+			data: None,
+		}
+	}
+}
+
+impl<C, P, Block> EthApi for Eth<C, P, Block>
+where
+	Block: BlockT<Hash = H256>,
+	C: Send + Sync + 'static + ProvideRuntimeApi<Block> + HeaderBackend<Block> + AuxStore,
+	C::Api: EvmRuntimeApi<Block>,
+	P: TransactionPool<Block = Block> + 'static,
+{
+	fn chain_id(&self) -> Result<U256> {
+		let at = BlockId::hash(self.client.info().best_hash);
+		self.client.runtime_api().chain_id(&at)
+			.map(Into::into)
+			.map_err(|e| runtime_error_into_rpc_err(e))
+	}
+
+	fn block_number(&self) -> Result<U256> {
+		Ok(self.best_number().into())
+	}
+
+	fn gas_price(&self) -> Result<U256> {
+		let at = BlockId::hash(self.client.info().best_hash);
+		self.client.runtime_api().gas_price(&at)
+			.map_err(|e| runtime_error_into_rpc_err(e))
+	}
+
+	fn balance(&self, address: H160, number: Option<BlockNumber>) -> Result<U256> {
+		let at = self.block_id(number)?;
+		self.client.runtime_api().account_basic(&at, address)
+			.map(|account| account.balance)
+			.map_err(|e| runtime_error_into_rpc_err(e))
+	}
+
+	fn transaction_count(
+		&self,
+		address: H160,
+		number: Option<BlockNumber>,
+	) -> Result<U256> {
+		let at = self.block_id(number)?;
+		self.client.runtime_api().account_basic(&at, address)
+			.map(|account| account.nonce)
+			.map_err(|e| runtime_error_into_rpc_err(e))
+	}
+
+	fn code(&self, address: H160, number: Option<BlockNumber>) -> Result<Bytes> {
+		let at = self.block_id(number)?;
+		self.client.runtime_api().account_code(&at, address)
+			.map(Bytes)
+			.map_err(|e| runtime_error_into_rpc_err(e))
+	}
+
+	fn call(&self, request: CallRequest, number: Option<BlockNumber>) -> Result<Bytes> {
+		let at = self.block_id(number)?;
+		self.simulate(request, &at).map(|info| Bytes(info.value))
+	}
+
+	fn estimate_gas(&self, request: CallRequest, number: Option<BlockNumber>) -> Result<U256> {
+		let at = self.block_id(number)?;
+		let info = self.simulate(request.clone(), &at)?;
+
+		// the gas used is net of refunds, while the gas limit must cover the gas used before
+		// them, so the lowest gas limit with which the execution succeeds is searched
+		let mut failing = info.used_gas.saturating_sub(U256::one());
+		let mut succeeding = request.gas.unwrap_or_else(|| U256::from(MAX_GAS_LIMIT));
+		while succeeding - failing > U256::one() {
+			let gas = (failing + succeeding) / 2;
+			match self.simulate(CallRequest { gas: Some(gas), ..request.clone() }, &at) {
+				Ok(_) => succeeding = gas,
+				Err(_) => failing = gas,
+			}
+		}
+		Ok(succeeding)
+	}
+
+	fn send_raw_transaction(&self, transaction: Bytes) -> BoxFuture<H256> {
+		let transaction = match EthTransaction::decode_rlp(&transaction) {
+			Ok(transaction) => transaction,
+			Err(e) => return Box::new(future::err(Error {
+				code: ErrorCode::InvalidParams,
+				message: "Invalid RLP encoded transaction".into(),
+				data: Some(format!("{:?}", e).into()),
+			})),
+		};
+		let hash = transaction.hash();
+
+		let at = BlockId::hash(self.client.info().best_hash);
+		let extrinsic = match self.client.runtime_api().transact_extrinsic(&at, transaction) {
+			Ok(extrinsic) => extrinsic,
+			Err(e) => return Box::new(future::err(runtime_error_into_rpc_err(e))),
+		};
+
+		Box::new(self.pool
+			.submit_one(&at, TransactionSource::Local, extrinsic)
+			.compat()
+			.map(move |_| hash)
+			.map_err(|e| Error {
+				code: ErrorCode::ServerError(POOL_ERROR),
+				message: "The transaction was rejected by the pool".into(),
+				data: Some(format!("{:?}", e).into()),
+			})
+		)
+	}
+
+	fn transaction_receipt(&self, hash: H256) -> Result<Option<Receipt>> {
+		let blocks = mapping::load_transaction_blocks::<_, H256>(&*self.client, hash)
+			.map_err(|e| runtime_error_into_rpc_err(e))?;
+
+		// the transaction may have been included on several forks
+		for block_hash in blocks {
+			let header = match self.client.header(BlockId::hash(block_hash))
+				.map_err(|e| runtime_error_into_rpc_err(e))?
+			{
+				Some(header) => header,
+				None => continue,
+			};
+			let number = *header.number();
+			if self.client.hash(number).map_err(|e| runtime_error_into_rpc_err(e))? !=
+				Some(block_hash)
+			{
+				continue;
+			}
+
+			let number = UniqueSaturatedInto::<u64>::unique_saturated_into(number);
+			let statuses = self.block_statuses(block_hash)?;
+			let status = match statuses.iter().find(|s| s.transaction_hash == hash) {
+				Some(status) => status,
+				None => continue,
+			};
+			let cumulative_gas_used = statuses.iter()
+				.take_while(|s| s.transaction_index <= status.transaction_index)
+				.fold(U256::zero(), |gas, s| gas.saturating_add(s.used_gas));
+			let logs = self.block_logs(block_hash, number, &statuses).into_iter()
+				.filter(|(index, _)| *index == status.transaction_index)
+				.map(|(_, log)| log)
+				.collect::<Vec<_>>();
+
+			return Ok(Some(Receipt {
+				transaction_hash: hash,
+				transaction_index: status.transaction_index.into(),
+				block_hash,
+				block_number: number.into(),
+				from: status.from,
+				to: status.to,
+				cumulative_gas_used,
+				gas_used: status.used_gas,
+				contract_address: status.contract_address,
+				logs_bloom: types::logs_bloom(&logs),
+				logs,
+				status: (status.succeeded as u8).into(),
+			}));
+		}
+
+		Ok(None)
+	}
+
+	fn logs(&self, filter: Filter) -> Result<Vec<Log>> {
+		if let Some(block_hash) = filter.block_hash {
+			let number = self.client.number(block_hash)
+				.map_err(|e| runtime_error_into_rpc_err(e))?
+				.ok_or_else(|| Error {
+					code: ErrorCode::InvalidParams,
+					message: format!("Block {:?} is unknown", block_hash),
+					data: None,
+				})?;
+			let number = UniqueSaturatedInto::<u64>::unique_saturated_into(number);
+			return self.matching_block_logs(&filter, block_hash, number);
+		}
+
+		let from = self.block_number_of(filter.from_block);
+		let to = self.block_number_of(filter.to_block);
+		self.logs_in_range(&filter, from, to)
+	}
+
+	fn new_filter(&self, filter: Filter) -> Result<U256> {
+		let now = Instant::now();
+		let mut pool = self.filters.lock();
+		let expired: Vec<_> = pool.filters.iter()
+			.filter(|(_, f)| now.duration_since(f.last_poll_at) >= FILTER_LIFETIME)
+			.map(|(id, _)| *id)
+			.collect();
+		for id in expired {
+			pool.filters.remove(&id);
+		}
+		if pool.filters.len() >= MAX_INSTALLED_FILTERS {
+			return Err(Error {
+				code: ErrorCode::InvalidParams,
+				message: format!("Too many filters installed: {}", MAX_INSTALLED_FILTERS),
+				data: None,
+			});
+		}
+
+		let id = pool.next_id;
+		pool.next_id = id.saturating_add(U256::one());
+		pool.filters.insert(id, InstalledFilter {
+			filter,
+			last_poll_block: self.best_number(),
+			last_poll_at: now,
+		});
+		Ok(id)
+	}
+
+	fn filter_changes(&self, id: U256) -> Result<Vec<Log>> {
+		let (filter, from, to) = {
+			let mut pool = self.filters.lock();
+			let installed = pool.filters.get_mut(&id).ok_or_else(|| Self::filter_error(id))?;
+			let best = self.best_number();
+			let from = installed.last_poll_block.saturating_add(1)
+				.max(self.block_number_of(installed.filter.from_block))
+				// the logs of blocks older than the range are skipped
+				.max(best.saturating_sub(MAX_LOG_BLOCK_RANGE - 1));
+			let to = match installed.filter.to_block {
+				Some(to) => best.min(self.block_number_of(Some(to))),
+				None => best,
+			};
+			installed.last_poll_block = best;
+			installed.last_poll_at = Instant::now();
+			(installed.filter.clone(), from, to)
+		};
+
+		if from > to {
+			return Ok(Vec::new());
+		}
+		self.logs_in_range(&filter, from, to)
+	}
+
+	fn filter_logs(&self, id: U256) -> Result<Vec<Log>> {
+		let filter = self.filters.lock().filters.get(&id)
+			.map(|installed| installed.filter.clone())
+			.ok_or_else(|| Self::filter_error(id))?;
+		self.logs(filter)
+	}
+
+	fn uninstall_filter(&self, id: U256) -> Result<bool> {
+		Ok(self.filters.lock().filters.remove(&id).is_some())
+	}
+}
+
+/// Converts a runtime trap into an RPC error.
+fn runtime_error_into_rpc_err(err: impl std::fmt::Debug) -> Error {
+	Error {
+		code: ErrorCode::ServerError(RUNTIME_ERROR),
+		message: "Runtime trapped".into(),
+		data: Some(format!("{:?}", err).into()),
+	}
+}
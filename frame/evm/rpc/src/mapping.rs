@@ -0,0 +1,218 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Mapping of the Ethereum transactions to the blocks including them, kept in the aux-db.
+//!
+//! For every imported block, the statuses of its Ethereum transactions are stored under the
+//! hash of the block and the hash of the block is added to the blocks including each of the
+//! transactions. A transaction may be included in blocks of several forks, the canonical one
+//! is resolved when the transaction is looked up. Every mapped block is marked, so the blocks
+//! imported without a notification are mapped from the next one on.
+
+use std::sync::Arc;
+
+use codec::{Decode, Encode};
+use futures::{future, StreamExt};
+use log::warn;
+use pallet_evm_rpc_runtime_api::{EvmApi, TransactionStatus};
+use sc_client_api::{backend::AuxStore, BlockchainEvents};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::{Error as ClientError, HeaderBackend, Result as ClientResult};
+use sp_core::H256;
+use sp_runtime::{generic::BlockId, traits::{Block as BlockT, Header as HeaderT, Zero}};
+
+fn statuses_key<H: Encode>(block_hash: H) -> Vec<u8> {
+	(b"evm_transaction_statuses", block_hash).encode()
+}
+
+fn mapped_key<H: Encode>(block_hash: H) -> Vec<u8> {
+	(b"evm_mapped_block", block_hash).encode()
+}
+
+fn transaction_blocks_key(transaction_hash: H256) -> Vec<u8> {
+	(b"evm_transaction_blocks", transaction_hash).encode()
+}
+
+fn load_decode<B, T>(backend: &B, key: &[u8]) -> ClientResult<Option<T>>
+	where
+		B: AuxStore,
+		T: Decode,
+{
+	let corrupt = |e: codec::Error| {
+		ClientError::Backend(format!("EVM mapping DB is corrupted. Decode error: {}", e.what()))
+	};
+	match backend.get_aux(key)? {
+		None => Ok(None),
+		Some(t) => T::decode(&mut &t[..]).map(Some).map_err(corrupt)
+	}
+}
+
+/// Store the statuses of the Ethereum transactions of a block and mark it as mapped. Only the
+/// mark is stored for blocks without Ethereum transactions.
+pub fn write_block_statuses<B, H>(
+	backend: &B,
+	block_hash: H,
+	statuses: &[TransactionStatus],
+) -> ClientResult<()>
+	where
+		B: AuxStore,
+		H: Encode + Decode + PartialEq + Clone,
+{
+	let mut entries = vec![(mapped_key(&block_hash), Vec::new())];
+	if !statuses.is_empty() {
+		entries.push((statuses_key(&block_hash), statuses.encode()));
+	}
+	for status in statuses {
+		let key = transaction_blocks_key(status.transaction_hash);
+		let mut blocks: Vec<H> = load_decode(backend, &key)?.unwrap_or_default();
+		if !blocks.contains(&block_hash) {
+			blocks.push(block_hash.clone());
+		}
+		entries.push((key, blocks.encode()));
+	}
+
+	let entries = entries.iter().map(|(k, v)| (&k[..], &v[..])).collect::<Vec<_>>();
+	backend.insert_aux(&entries, &[])
+}
+
+/// The statuses of the Ethereum transactions of a block, empty if it has none or it was
+/// imported before the mapping was kept.
+pub fn load_block_statuses<B: AuxStore, H: Encode>(
+	backend: &B,
+	block_hash: H,
+) -> ClientResult<Vec<TransactionStatus>> {
+	Ok(load_decode(backend, &statuses_key(block_hash))?.unwrap_or_default())
+}
+
+/// Whether the Ethereum transactions of a block are mapped.
+pub fn is_block_mapped<B: AuxStore, H: Encode>(backend: &B, block_hash: H) -> ClientResult<bool> {
+	Ok(backend.get_aux(&mapped_key(block_hash))?.is_some())
+}
+
+/// The blocks including an Ethereum transaction, on any fork.
+pub fn load_transaction_blocks<B: AuxStore, H: Decode>(
+	backend: &B,
+	transaction_hash: H256,
+) -> ClientResult<Vec<H>> {
+	Ok(load_decode(backend, &transaction_blocks_key(transaction_hash))?.unwrap_or_default())
+}
+
+/// Map a block and its ancestors back to the last mapped one, oldest first. Blocks whose
+/// statuses can't be read, e.g. as their state is pruned, are marked as mapped without them,
+/// so they are not walked again.
+fn map_block<Client, Block>(client: &Client, block_hash: Block::Hash) -> ClientResult<()>
+	where
+		Block: BlockT,
+		Client: ProvideRuntimeApi<Block> + HeaderBackend<Block> + AuxStore,
+		Client::Api: EvmApi<Block>,
+{
+	let mut unmapped = Vec::new();
+	let mut hash = block_hash;
+	while !is_block_mapped(client, hash)? {
+		let header = match client.header(BlockId::hash(hash))? {
+			Some(header) => header,
+			None => break,
+		};
+		unmapped.push(hash);
+		if header.number().is_zero() {
+			break;
+		}
+		hash = *header.parent_hash();
+	}
+
+	for hash in unmapped.into_iter().rev() {
+		let statuses = client.runtime_api().transaction_statuses(&BlockId::hash(hash))
+			.unwrap_or_else(|e| {
+				warn!(
+					target: "evm-rpc",
+					"Failed to read the Ethereum transactions of block {:?}: {:?}",
+					hash,
+					e,
+				);
+				Vec::new()
+			});
+		write_block_statuses(client, hash, &statuses)?;
+	}
+	Ok(())
+}
+
+/// Keep the mapping for every imported block, including the blocks imported before the task
+/// ran or without a notification. The future should be spawned by nodes serving the Ethereum
+/// JSON-RPC.
+pub async fn mapping_task<Client, Block>(client: Arc<Client>)
+	where
+		Block: BlockT,
+		Client: ProvideRuntimeApi<Block> + HeaderBackend<Block> + BlockchainEvents<Block>,
+		Client: AuxStore + Send + Sync + 'static,
+		Client::Api: EvmApi<Block>,
+{
+	client.import_notification_stream().for_each(move |n| {
+		if let Err(e) = map_block(&*client, n.hash) {
+			warn!(
+				target: "evm-rpc",
+				"Failed to map the Ethereum transactions of block {:?}: {:?}",
+				n.hash,
+				e,
+			);
+		}
+		future::ready(())
+	}).await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::{H160, U256};
+
+	fn status(transaction_hash: H256, transaction_index: u32) -> TransactionStatus {
+		TransactionStatus {
+			transaction_hash,
+			transaction_index,
+			from: H160::repeat_byte(1),
+			to: Some(H160::repeat_byte(2)),
+			contract_address: None,
+			succeeded: true,
+			used_gas: U256::from(21000),
+			logs: Vec::new(),
+		}
+	}
+
+	#[test]
+	fn transactions_are_mapped_to_their_blocks() {
+		let client = substrate_test_runtime_client::new();
+		let (a, b, c) = (H256::repeat_byte(0xa), H256::repeat_byte(0xb), H256::repeat_byte(0xc));
+		let (x, y) = (H256::repeat_byte(0x1), H256::repeat_byte(0x2));
+
+		// `x` is included in the blocks `a` and `b` of two forks
+		write_block_statuses(&client, a, &[status(x, 0)]).unwrap();
+		write_block_statuses(&client, b, &[status(y, 0), status(x, 1)]).unwrap();
+		write_block_statuses(&client, c, &[]).unwrap();
+		// a block imported twice is mapped once
+		write_block_statuses(&client, a, &[status(x, 0)]).unwrap();
+
+		assert_eq!(load_block_statuses(&client, a).unwrap(), vec![status(x, 0)]);
+		assert_eq!(load_block_statuses(&client, b).unwrap(), vec![status(y, 0), status(x, 1)]);
+		assert!(load_block_statuses(&client, c).unwrap().is_empty());
+		// blocks without transactions are marked as mapped too
+		assert!(is_block_mapped(&client, c).unwrap());
+		assert!(!is_block_mapped(&client, H256::repeat_byte(0xd)).unwrap());
+
+		assert_eq!(load_transaction_blocks::<_, H256>(&client, x).unwrap(), vec![a, b]);
+		assert_eq!(load_transaction_blocks::<_, H256>(&client, y).unwrap(), vec![b]);
+		assert!(load_transaction_blocks::<_, H256>(&client, c).unwrap().is_empty());
+	}
+}
@@ -0,0 +1,329 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Types of the Ethereum JSON-RPC, serialized as by Ethereum clients.
+
+use std::{fmt, str::FromStr};
+
+use ethbloom::{Bloom, Input as BloomInput};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error as _};
+use sp_core::{Bytes, H160, H256, U256};
+
+/// A block given by its number or a tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockNumber {
+	/// The block of the given number in the canonical chain.
+	Num(u64),
+	/// The best block.
+	Latest,
+	/// The genesis block.
+	Earliest,
+	/// The best block, pending transactions are not executed ahead of their inclusion.
+	Pending,
+}
+
+impl Default for BlockNumber {
+	fn default() -> Self {
+		BlockNumber::Latest
+	}
+}
+
+impl FromStr for BlockNumber {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"latest" => Ok(BlockNumber::Latest),
+			"earliest" => Ok(BlockNumber::Earliest),
+			"pending" => Ok(BlockNumber::Pending),
+			_ if s.starts_with("0x") => u64::from_str_radix(&s[2..], 16)
+				.map(BlockNumber::Num)
+				.map_err(|e| format!("Invalid block number {}: {}", s, e)),
+			_ => Err(format!("Invalid block number {}: missing 0x prefix", s)),
+		}
+	}
+}
+
+impl fmt::Display for BlockNumber {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			BlockNumber::Num(n) => write!(f, "0x{:x}", n),
+			BlockNumber::Latest => write!(f, "latest"),
+			BlockNumber::Earliest => write!(f, "earliest"),
+			BlockNumber::Pending => write!(f, "pending"),
+		}
+	}
+}
+
+impl Serialize for BlockNumber {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.collect_str(self)
+	}
+}
+
+impl<'de> Deserialize<'de> for BlockNumber {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let s = String::deserialize(deserializer)?;
+		s.parse().map_err(D::Error::custom)
+	}
+}
+
+/// A message call or a contract creation executed by `eth_call` and `eth_estimateGas`.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallRequest {
+	/// The sender, the zero address by default.
+	pub from: Option<H160>,
+	/// The recipient, a contract is created if it is missing.
+	pub to: Option<H160>,
+	/// The price of gas, zero by default.
+	pub gas_price: Option<U256>,
+	/// The most gas the execution may use.
+	pub gas: Option<U256>,
+	/// The value transferred to the recipient.
+	pub value: Option<U256>,
+	/// The input of the call or the init code of the contract.
+	pub data: Option<Bytes>,
+	/// The nonce of the sender, not checked by default.
+	pub nonce: Option<U256>,
+}
+
+/// A log emitted by a transaction included in a block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Log {
+	/// The address of the emitting contract.
+	pub address: H160,
+	/// The topics of the log.
+	pub topics: Vec<H256>,
+	/// The data of the log.
+	pub data: Bytes,
+	/// The hash of the block.
+	pub block_hash: H256,
+	/// The number of the block.
+	pub block_number: U256,
+	/// The hash of the transaction.
+	pub transaction_hash: H256,
+	/// The index of the transaction in the block.
+	pub transaction_index: U256,
+	/// The index of the log among the logs of the block.
+	pub log_index: U256,
+	/// Whether the block was retracted, logs are only returned for canonical blocks.
+	pub removed: bool,
+}
+
+/// The receipt of a transaction included in a block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Receipt {
+	/// The hash of the transaction.
+	pub transaction_hash: H256,
+	/// The index of the transaction in the block.
+	pub transaction_index: U256,
+	/// The hash of the block.
+	pub block_hash: H256,
+	/// The number of the block.
+	pub block_number: U256,
+	/// The sender of the transaction.
+	pub from: H160,
+	/// The recipient of a message call.
+	pub to: Option<H160>,
+	/// The gas used by the transaction and the transactions before it in the block.
+	pub cumulative_gas_used: U256,
+	/// The gas used by the transaction.
+	pub gas_used: U256,
+	/// The address of the contract created by the transaction.
+	pub contract_address: Option<H160>,
+	/// The logs emitted by the transaction.
+	pub logs: Vec<Log>,
+	/// The bloom filter of the logs.
+	pub logs_bloom: Bloom,
+	/// `1` if the execution succeeded, `0` otherwise.
+	pub status: U256,
+}
+
+/// The bloom filter of the addresses and the topics of the given logs.
+pub fn logs_bloom<'a>(logs: impl IntoIterator<Item = &'a Log>) -> Bloom {
+	let mut bloom = Bloom::default();
+	for log in logs {
+		bloom.accrue(BloomInput::Raw(log.address.as_bytes()));
+		for topic in &log.topics {
+			bloom.accrue(BloomInput::Raw(topic.as_bytes()));
+		}
+	}
+	bloom
+}
+
+/// A single value or an array of values, any of which is accepted by a filter.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ValueOrArray<T> {
+	/// A single value.
+	Value(T),
+	/// An array of values.
+	Array(Vec<T>),
+}
+
+impl<T: PartialEq> ValueOrArray<T> {
+	/// Whether the value is accepted.
+	pub fn contains(&self, value: &T) -> bool {
+		match self {
+			ValueOrArray::Value(v) => v == value,
+			ValueOrArray::Array(values) => values.contains(value),
+		}
+	}
+}
+
+/// A filter of logs, as given to `eth_getLogs` and `eth_newFilter`.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Filter {
+	/// The first block, the latest one by default.
+	pub from_block: Option<BlockNumber>,
+	/// The last block, the latest one by default.
+	pub to_block: Option<BlockNumber>,
+	/// The only block, instead of `from_block` and `to_block`.
+	pub block_hash: Option<H256>,
+	/// The emitting contracts, any by default.
+	pub address: Option<ValueOrArray<H160>>,
+	/// The topics at each position, any for a missing or `null` position.
+	pub topics: Option<Vec<Option<ValueOrArray<H256>>>>,
+}
+
+impl Filter {
+	/// Whether a log emitted by the given address with the given topics passes the filter.
+	pub fn matches(&self, address: &H160, topics: &[H256]) -> bool {
+		if let Some(ref addresses) = self.address {
+			if !addresses.contains(address) {
+				return false;
+			}
+		}
+
+		self.topics.iter().flatten().enumerate().all(|(i, accepted)| match accepted {
+			Some(accepted) => topics.get(i).map_or(false, |topic| accepted.contains(topic)),
+			None => true,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn block_number_should_serialize_deserialize_properly() {
+		fn test(expected: &str, number: BlockNumber) {
+			assert_eq!(serde_json::from_str::<BlockNumber>(expected).unwrap(), number);
+			assert_eq!(serde_json::to_string(&number).unwrap(), expected);
+		}
+		test(r#""latest""#, BlockNumber::Latest);
+		test(r#""earliest""#, BlockNumber::Earliest);
+		test(r#""pending""#, BlockNumber::Pending);
+		test(r#""0x1b4""#, BlockNumber::Num(436));
+		assert!(serde_json::from_str::<BlockNumber>(r#""436""#).is_err());
+	}
+
+	#[test]
+	fn call_request_should_deserialize_properly() {
+		let req: CallRequest = serde_json::from_str(r#"
+		{
+			"from": "0xb60e8dd61c5d32be8058bb8eb970870f07233155",
+			"to": "0xd46e8dd67c5d32be8058bb8eb970870f07244567",
+			"gas": "0x76c0",
+			"gasPrice": "0x9184e72a000",
+			"value": "0x9184e72a",
+			"data": "0xd46e8dd67c5d32be8d46e8dd67c5d32be8058bb8eb970870f072445675058bb8eb970870f072445675"
+		}
+		"#).unwrap();
+		assert_eq!(req.gas, Some(U256::from(30400)));
+		assert_eq!(req.gas_price, Some(U256::from(10_000_000_000_000u64)));
+		assert_eq!(req.nonce, None);
+		assert_eq!(req.data.unwrap().len(), 41);
+	}
+
+	#[test]
+	fn filter_should_deserialize_properly() {
+		let filter: Filter = serde_json::from_str(r#"
+		{
+			"fromBlock": "0x1",
+			"address": "0x8888f1f195afa192cfee860698584c030f4c9db1",
+			"topics": [
+				"0x000000000000000000000000a94f5374fce5edbc8e2a8697c15331677e6ebf0b",
+				null,
+				["0x000000000000000000000000a94f5374fce5edbc8e2a8697c15331677e6ebf0b", "0x0000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebccc"]
+			]
+		}
+		"#).unwrap();
+		assert_eq!(filter.from_block, Some(BlockNumber::Num(1)));
+		assert_eq!(filter.to_block, None);
+		assert_eq!(
+			filter.address,
+			Some(ValueOrArray::Value(
+				H160::from_str("8888f1f195afa192cfee860698584c030f4c9db1").unwrap(),
+			)),
+		);
+		let topics = filter.topics.unwrap();
+		assert_eq!(topics.len(), 3);
+		assert_eq!(topics[1], None);
+		assert!(matches!(topics[2], Some(ValueOrArray::Array(ref topics)) if topics.len() == 2));
+	}
+
+	#[test]
+	fn filter_matches_addresses_and_topics() {
+		let address = H160::repeat_byte(1);
+		let (a, b, c) = (H256::repeat_byte(0xa), H256::repeat_byte(0xb), H256::repeat_byte(0xc));
+
+		assert!(Filter::default().matches(&address, &[]));
+
+		let filter = Filter {
+			address: Some(ValueOrArray::Array(vec![address, H160::repeat_byte(2)])),
+			..Default::default()
+		};
+		assert!(filter.matches(&address, &[a]));
+		assert!(!filter.matches(&H160::repeat_byte(3), &[a]));
+
+		let filter = Filter {
+			topics: Some(vec![None, Some(ValueOrArray::Array(vec![b, c]))]),
+			..Default::default()
+		};
+		assert!(filter.matches(&address, &[a, b]));
+		assert!(filter.matches(&address, &[b, c, a]));
+		assert!(!filter.matches(&address, &[a, a]));
+		assert!(!filter.matches(&address, &[c]));
+	}
+
+	#[test]
+	fn logs_bloom_contains_addresses_and_topics() {
+		let log = Log {
+			address: H160::repeat_byte(1),
+			topics: vec![H256::repeat_byte(0xa)],
+			data: Bytes(Vec::new()),
+			block_hash: H256::zero(),
+			block_number: U256::zero(),
+			transaction_hash: H256::zero(),
+			transaction_index: U256::zero(),
+			log_index: U256::zero(),
+			removed: false,
+		};
+		let bloom = logs_bloom(&[log]);
+
+		assert!(bloom.contains_input(BloomInput::Raw(H160::repeat_byte(1).as_bytes())));
+		assert!(bloom.contains_input(BloomInput::Raw(H256::repeat_byte(0xa).as_bytes())));
+		assert!(!bloom.contains_input(BloomInput::Raw(H160::repeat_byte(2).as_bytes())));
+		assert!(logs_bloom(&[]).is_empty());
+	}
+}
@@ -1,5 +1,7 @@
 use sp_std::marker::PhantomData;
 use sp_std::vec::Vec;
+use sp_std::cell::RefCell;
+use sp_std::collections::btree_map::BTreeMap;
 #[cfg(feature = "std")]
 use serde::{Serialize, Deserialize};
 use codec::{Encode, Decode};
@@ -46,13 +48,43 @@ pub struct Vicinity {
 /// Substrate backend for EVM.
 pub struct Backend<'vicinity, T> {
 	vicinity: &'vicinity Vicinity,
+	/// The accounts as first read by the execution, the runtime may change them meanwhile.
+	original: RefCell<BTreeMap<H160, Account>>,
 	_marker: PhantomData<T>,
 }
 
 impl<'vicinity, T> Backend<'vicinity, T> {
 	/// Create a new backend with given vicinity.
 	pub fn new(vicinity: &'vicinity Vicinity) -> Self {
-		Self { vicinity, _marker: PhantomData }
+		Self { vicinity, original: RefCell::new(BTreeMap::new()), _marker: PhantomData }
+	}
+}
+
+impl<'vicinity, T: Trait> Backend<'vicinity, T> {
+	/// Whether the changes of the execution may be applied on top of the changes of the
+	/// runtime during the execution, e.g. by the `Dispatch` precompile, without any balance or
+	/// nonce dropping below zero.
+	pub fn can_apply<I>(&self, values: &[Apply<I>]) -> bool {
+		values.iter().all(|apply| match apply {
+			Apply::Modify { address, basic, .. } => self.merge_basic(address, basic).is_some(),
+			Apply::Delete { .. } => true,
+		})
+	}
+
+	/// The account with the changes of the execution, i.e. the difference between `basic` and
+	/// the account first read, applied to the current account.
+	fn merge_basic(&self, address: &H160, basic: &evm::backend::Basic) -> Option<Account> {
+		match self.original.borrow().get(address) {
+			Some(original) => {
+				let current = Module::<T>::account_basic(address);
+				Some(Account {
+					nonce: current.nonce.checked_add(basic.nonce)?.checked_sub(original.nonce)?,
+					balance: current.balance.checked_add(basic.balance)?
+						.checked_sub(original.balance)?,
+				})
+			},
+			None => Some(Account { nonce: basic.nonce, balance: basic.balance }),
+		}
 	}
 }
 
@@ -101,6 +133,7 @@ impl<'vicinity, T: Trait> BackendT for Backend<'vicinity, T> {
 
 	fn basic(&self, address: H160) -> evm::backend::Basic {
 		let account = Module::<T>::account_basic(&address);
+		self.original.borrow_mut().entry(address).or_insert_with(|| account.clone());
 
 		evm::backend::Basic {
 			balance: account.balance,
@@ -141,10 +174,11 @@ impl<'vicinity, T: Trait> ApplyBackend for Backend<'vicinity, T> {
 				Apply::Modify {
 					address, basic, code, storage, reset_storage,
 				} => {
-					Module::<T>::mutate_account_basic(&address, Account {
+					let account = self.merge_basic(&address, &basic).unwrap_or(Account {
 						nonce: basic.nonce,
 						balance: basic.balance,
 					});
+					Module::<T>::mutate_account_basic(&address, account);
 
 					if let Some(code) = code {
 						debug::debug!(
@@ -23,12 +23,17 @@
 mod backend;
 mod tests;
 pub mod precompiles;
+pub mod transaction;
 
-pub use crate::precompiles::{Precompile, Precompiles};
+pub use crate::precompiles::{
+	Precompile, Precompiles, IstanbulPrecompiles, WithPrecompile, Dispatch, Random,
+};
 pub use crate::backend::{Account, Log, Vicinity, Backend};
+pub use crate::transaction::{
+	EthTransaction, TransactionAction, TransactionSignature, TransactionStatus,
+};
 
 use sp_std::vec::Vec;
-#[cfg(feature = "std")]
 use codec::{Encode, Decode};
 #[cfg(feature = "std")]
 use serde::{Serialize, Deserialize};
@@ -36,9 +41,17 @@ use frame_support::{debug, ensure, decl_module, decl_storage, decl_event, decl_e
 use frame_support::weights::{Weight, Pays};
 use frame_support::traits::{Currency, ExistenceRequirement, Get};
 use frame_support::dispatch::DispatchResultWithPostInfo;
-use frame_system::RawOrigin;
+use frame_support::storage::with_transaction;
+use frame_system::{RawOrigin, ensure_none};
 use sp_core::{U256, H256, H160, Hasher};
-use sp_runtime::{AccountId32, traits::{UniqueSaturatedInto, SaturatedConversion, BadOrigin}};
+use sp_runtime::{
+	AccountId32, TransactionOutcome,
+	traits::{UniqueSaturatedInto, SaturatedConversion, BadOrigin},
+	transaction_validity::{
+		TransactionValidity, ValidTransaction, InvalidTransaction, TransactionSource,
+		TransactionPriority,
+	},
+};
 use sha3::{Digest, Keccak256};
 pub use evm::{ExitReason, ExitSucceed, ExitError, ExitRevert, ExitFatal};
 use evm::Config;
@@ -176,6 +189,24 @@ impl<H: Hasher<Out=H256>> AddressMapping<AccountId32> for HashedAddressMapping<H
 	}
 }
 
+/// Conversion between the gas of the EVM and the weight of the runtime.
+pub trait GasWeightMapping {
+	/// The weight of the given gas.
+	fn gas_to_weight(gas: usize) -> Weight;
+	/// The gas of the given weight.
+	fn weight_to_gas(weight: Weight) -> usize;
+}
+
+impl GasWeightMapping for () {
+	fn gas_to_weight(gas: usize) -> Weight {
+		gas.saturated_into()
+	}
+
+	fn weight_to_gas(weight: Weight) -> usize {
+		weight.saturated_into()
+	}
+}
+
 /// Substrate system chain ID.
 pub struct SystemChainId;
 
@@ -208,6 +239,8 @@ pub trait Trait: frame_system::Trait + pallet_timestamp::Trait {
 	type Precompiles: Precompiles;
 	/// Chain ID of EVM.
 	type ChainId: Get<u64>;
+	/// Conversion between gas and weight, e.g. for the `Dispatch` precompile.
+	type GasWeightMapping: GasWeightMapping;
 
 	/// EVM config used in the module.
 	fn config() -> &'static Config {
@@ -229,11 +262,32 @@ pub struct GenesisAccount {
 	pub code: Vec<u8>,
 }
 
+#[derive(Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+/// Outcome of an EVM execution whose state is not applied, e.g. for `eth_call`.
+pub struct ExecutionInfo {
+	/// Whether the execution succeeded.
+	pub succeeded: bool,
+	/// The return value of a call, empty for a contract creation.
+	pub value: Vec<u8>,
+	/// The address of the created contract.
+	pub contract_address: Option<H160>,
+	/// The gas used by the execution.
+	pub used_gas: U256,
+	/// The logs emitted by the execution.
+	pub logs: Vec<Log>,
+}
+
 decl_storage! {
 	trait Store for Module<T: Trait> as EVM {
 		AccountCodes get(fn account_codes): map hasher(blake2_128_concat) H160 => Vec<u8>;
 		AccountStorages get(fn account_storages):
 			double_map hasher(blake2_128_concat) H160, hasher(blake2_128_concat) H256 => H256;
+		/// The statuses of the Ethereum transactions executed in the current block.
+		TransactionStatuses get(fn transaction_statuses): Vec<TransactionStatus>;
+		/// The source and the target of the EVM execution in progress, if any. The target is `None`
+		/// for contract creations.
+		CurrentExecution get(fn current_execution): Option<(H160, Option<H160>)>;
 	}
 
 	add_extra_genesis {
@@ -290,6 +344,12 @@ decl_error! {
 		GasPriceTooLow,
 		/// Nonce is invalid
 		InvalidNonce,
+		/// Ethereum transaction is signed for another chain
+		InvalidChainId,
+		/// Ethereum transaction signature is invalid
+		InvalidSignature,
+		/// Gas limit of the Ethereum transaction exceeds the limit of the EVM
+		GasLimitTooHigh,
 	}
 }
 
@@ -299,6 +359,11 @@ decl_module! {
 
 		fn deposit_event() = default;
 
+		fn on_initialize(_n: T::BlockNumber) -> Weight {
+			TransactionStatuses::kill();
+			T::DbWeight::get().writes(1)
+		}
+
 		/// Withdraw balance from EVM into currency/balances module.
 		#[weight = 0]
 		fn withdraw(origin, address: H160, value: BalanceOf<T>) {
@@ -416,6 +481,111 @@ decl_module! {
 
 			Ok(Pays::No.into())
 		}
+
+		/// Execute an Ethereum transaction signed as specified in EIP-155. The transaction is
+		/// authorized by its signature, hence the call is unsigned, and the status of its
+		/// execution is kept until the end of the block for the Ethereum JSON-RPC.
+		#[weight = T::GasWeightMapping::gas_to_weight(transaction.gas_limit.saturated_into())]
+		fn transact(origin, transaction: EthTransaction) -> DispatchResultWithPostInfo {
+			ensure_none(origin)?;
+
+			let source = Self::check_transaction(&transaction)?;
+			let transaction_hash = transaction.hash();
+			let transaction_index = TransactionStatuses::decode_len().unwrap_or(0) as u32;
+			let gas_limit = transaction.gas_limit.low_u32();
+
+			let (to, contract_address, reason, used_gas, logs) = match transaction.action {
+				TransactionAction::Call(target) => {
+					let (reason, _, used_gas, logs) = Self::execute_call(
+						source,
+						target,
+						transaction.input,
+						transaction.value,
+						gas_limit,
+						transaction.gas_price,
+						Some(transaction.nonce),
+						true,
+					)?;
+					match reason {
+						ExitReason::Succeed(_) =>
+							Module::<T>::deposit_event(Event::<T>::Executed(target)),
+						_ => Module::<T>::deposit_event(Event::<T>::ExecutedFailed(target)),
+					}
+					(Some(target), None, reason, used_gas, logs)
+				},
+				TransactionAction::Create => {
+					let (reason, create_address, used_gas, logs) = Self::execute_create(
+						source,
+						transaction.input,
+						transaction.value,
+						gas_limit,
+						transaction.gas_price,
+						Some(transaction.nonce),
+						true,
+					)?;
+					match reason {
+						ExitReason::Succeed(_) =>
+							Module::<T>::deposit_event(Event::<T>::Created(create_address)),
+						_ => Module::<T>::deposit_event(Event::<T>::CreatedFailed(create_address)),
+					}
+					(None, Some(create_address), reason, used_gas, logs)
+				},
+			};
+
+			TransactionStatuses::append(TransactionStatus {
+				transaction_hash,
+				transaction_index,
+				from: source,
+				to,
+				contract_address,
+				succeeded: matches!(reason, ExitReason::Succeed(_)),
+				used_gas,
+				logs,
+			});
+
+			Ok(Pays::No.into())
+		}
+	}
+}
+
+impl<T: Trait> frame_support::unsigned::ValidateUnsigned for Module<T> {
+	type Call = Call<T>;
+
+	fn validate_unsigned(_source: TransactionSource, call: &Self::Call) -> TransactionValidity {
+		let transaction = match call {
+			Call::transact(transaction) => transaction,
+			_ => return InvalidTransaction::Call.into(),
+		};
+
+		let source = Self::check_transaction(transaction).map_err(|e| match e {
+			Error::<T>::InvalidSignature => InvalidTransaction::BadProof,
+			Error::<T>::GasLimitTooHigh => InvalidTransaction::ExhaustsResources,
+			_ => InvalidTransaction::Call,
+		})?;
+
+		let account = Self::account_basic(&source);
+		if transaction.nonce < account.nonce {
+			return InvalidTransaction::Stale.into();
+		}
+		if transaction.gas_price < T::FeeCalculator::min_gas_price() {
+			return InvalidTransaction::Payment.into();
+		}
+		let total_payment = transaction.gas_price.checked_mul(transaction.gas_limit)
+			.and_then(|fee| fee.checked_add(transaction.value))
+			.ok_or(InvalidTransaction::Payment)?;
+		if account.balance < total_payment {
+			return InvalidTransaction::Payment.into();
+		}
+
+		let mut builder = ValidTransaction::with_tag_prefix("EvmTransact")
+			.priority(transaction.gas_price.saturated_into::<TransactionPriority>())
+			.and_provides((source, transaction.nonce))
+			.propagate(true);
+		// a transaction from the future waits in the pool for its predecessor
+		if transaction.nonce > account.nonce {
+			builder = builder.and_requires((source, transaction.nonce - U256::one()));
+		}
+		builder.build()
 	}
 }
 
@@ -463,6 +633,56 @@ impl<T: Trait> Module<T> {
 		}
 	}
 
+	/// Check an Ethereum transaction before its execution and recover its sender.
+	pub fn check_transaction(transaction: &EthTransaction) -> Result<H160, Error<T>> {
+		ensure!(
+			transaction.signature.chain_id() == Some(T::ChainId::get()),
+			Error::<T>::InvalidChainId,
+		);
+		ensure!(transaction.gas_limit <= U256::from(u32::max_value()), Error::<T>::GasLimitTooHigh);
+		transaction.sender().ok_or(Error::<T>::InvalidSignature)
+	}
+
+	/// Execute a call or a contract creation without applying its state, e.g. to answer
+	/// `eth_call` or `eth_estimateGas`. The gas price is not checked.
+	pub fn simulate(
+		source: H160,
+		action: TransactionAction,
+		input: Vec<u8>,
+		value: U256,
+		gas_limit: u32,
+		gas_price: U256,
+		nonce: Option<U256>,
+	) -> Result<ExecutionInfo, Error<T>> {
+		let info = match action {
+			TransactionAction::Call(target) => {
+				let (reason, value, used_gas, logs) = Self::execute_call(
+					source, target, input, value, gas_limit, gas_price, nonce, false,
+				)?;
+				ExecutionInfo {
+					succeeded: matches!(reason, ExitReason::Succeed(_)),
+					value,
+					contract_address: None,
+					used_gas,
+					logs,
+				}
+			},
+			TransactionAction::Create => {
+				let (reason, address, used_gas, logs) = Self::execute_create(
+					source, input, value, gas_limit, gas_price, nonce, false,
+				)?;
+				ExecutionInfo {
+					succeeded: matches!(reason, ExitReason::Succeed(_)),
+					value: Vec::new(),
+					contract_address: Some(address),
+					used_gas,
+					logs,
+				}
+			},
+		};
+		Ok(info)
+	}
+
 	/// Get the account basic in EVM format.
 	pub fn account_basic(address: &H160) -> Account {
 		let account_id = T::AddressMapping::into_account_id(*address);
@@ -488,6 +708,7 @@ impl<T: Trait> Module<T> {
 	) -> Result<(ExitReason, H160, U256, Vec<Log>), Error<T>> {
 		Self::execute_evm(
 			source,
+			None,
 			value,
 			gas_limit,
 			gas_price,
@@ -521,6 +742,7 @@ impl<T: Trait> Module<T> {
 		let code_hash = H256::from_slice(Keccak256::digest(&init).as_slice());
 		Self::execute_evm(
 			source,
+			None,
 			value,
 			gas_limit,
 			gas_price,
@@ -554,6 +776,7 @@ impl<T: Trait> Module<T> {
 	) -> Result<(ExitReason, Vec<u8>, U256, Vec<Log>), Error<T>> {
 		Self::execute_evm(
 			source,
+			Some(target),
 			value,
 			gas_limit,
 			gas_price,
@@ -569,9 +792,10 @@ impl<T: Trait> Module<T> {
 		)
 	}
 
-	/// Execute an EVM operation.
+	/// Execute an EVM operation of `source` calling `target`, or creating a contract if `None`.
 	fn execute_evm<F, R>(
 		source: H160,
+		target: Option<H160>,
 		value: U256,
 		gas_limit: u32,
 		gas_price: U256,
@@ -604,31 +828,64 @@ impl<T: Trait> Module<T> {
 			.ok_or(Error::<T>::FeeOverflow)?;
 		let total_payment = value.checked_add(total_fee).ok_or(Error::<T>::PaymentOverflow)?;
 		let source_account = Self::account_basic(&source);
-		ensure!(source_account.balance >= total_payment, Error::<T>::BalanceLow);
-		executor.withdraw(source, total_fee).map_err(|_| Error::<T>::WithdrawFailed)?;
-
 		if let Some(nonce) = nonce {
 			ensure!(source_account.nonce == nonce, Error::<T>::InvalidNonce);
 		}
 
-		let (retv, reason) = f(&mut executor);
+		ensure!(source_account.balance >= total_payment, Error::<T>::BalanceLow);
+		executor.withdraw(source, total_fee).map_err(|_| Error::<T>::WithdrawFailed)?;
 
-		let used_gas = U256::from(executor.used_gas());
-		let actual_fee = executor.fee(gas_price);
-		debug::debug!(
-			target: "evm",
-			"Execution {:?} [source: {:?}, value: {}, gas_limit: {}, used_gas: {}, actual_fee: {}]",
-			retv,
-			source,
-			value,
-			gas_limit,
-			used_gas,
-			actual_fee
-		);
-		executor.deposit(source, total_fee.saturating_sub(actual_fee));
+		let previous_execution = CurrentExecution::get();
+		CurrentExecution::put((source, target));
+		// Runtime calls dispatched by the execution are only kept if its state is applied.
+		let result = with_transaction(|| {
+			let (retv, reason) = f(&mut executor);
+
+			let used_gas = U256::from(executor.used_gas());
+			let actual_fee = executor.fee(gas_price);
+			debug::debug!(
+				target: "evm",
+				"Execution {:?} [source: {:?}, value: {}, gas_limit: {}, used_gas: {}, actual_fee: {}]",
+				retv,
+				source,
+				value,
+				gas_limit,
+				used_gas,
+				actual_fee
+			);
+			executor.deposit(source, total_fee.saturating_sub(actual_fee));
+
+			let (values, logs) = executor.deconstruct();
+			let values = values.into_iter().collect::<Vec<_>>();
+			let logs = logs.into_iter().collect::<Vec<_>>();
+			match retv {
+				ExitReason::Succeed(_) if apply_state => if backend.can_apply(&values) {
+					TransactionOutcome::Commit(Ok((retv, reason, used_gas, values, logs)))
+				} else {
+					TransactionOutcome::Rollback(Err((reason, used_gas, actual_fee)))
+				},
+				_ => TransactionOutcome::Rollback(Ok((retv, reason, used_gas, values, logs))),
+			}
+		});
+		CurrentExecution::set(previous_execution);
+		let (retv, reason, used_gas, values, logs_data) = match result {
+			Ok(result) => result,
+			Err((reason, used_gas, actual_fee)) => {
+				// The runtime calls dispatched by the execution spent funds it also spent. Both
+				// are reverted and the execution fails, but its fee is charged and the nonce of
+				// the source is bumped as for any other failed execution.
+				let account = Self::account_basic(&source);
+				Self::mutate_account_basic(&source, Account {
+					nonce: account.nonce.saturating_add(U256::one()),
+					balance: account.balance.saturating_sub(actual_fee),
+				});
+				let retv = ExitReason::Error(
+					ExitError::Other("Dispatched calls spent funds of the execution"),
+				);
+				return Ok((retv, reason, used_gas, Vec::new()))
+			},
+		};
 
-		let (values, logs) = executor.deconstruct();
-		let logs_data = logs.into_iter().map(|x| x ).collect::<Vec<_>>();
 		let logs_result = logs_data.clone().into_iter().map(|it| {
 			Log {
 				address: it.address,
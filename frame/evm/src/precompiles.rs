@@ -17,11 +17,34 @@
 
 //! Builtin precompiles.
 
-use sp_std::{cmp::min, vec::Vec};
-use sp_core::H160;
+use sp_std::{cmp::{min, max}, marker::PhantomData, vec, vec::Vec};
+use sp_core::{H160, H256, U256};
+use codec::Decode;
+use frame_support::{
+	dispatch::{Dispatchable, PostDispatchInfo},
+	storage::with_transaction,
+	traits::{Filter, Get, Randomness},
+	weights::GetDispatchInfo,
+};
+use frame_system::RawOrigin;
+use sp_runtime::TransactionOutcome;
 use evm::{ExitError, ExitSucceed};
 use ripemd160::Digest;
 use impl_trait_for_tuples::impl_for_tuples;
+use crate::{Trait, Module, AddressMapping, GasWeightMapping};
+
+/// The precompiles of Ethereum at their addresses `0x01` to `0x09` as of the Istanbul hard fork.
+pub type IstanbulPrecompiles = (
+	ECRecover,
+	Sha256,
+	Ripemd160,
+	Identity,
+	Modexp,
+	Bn128Add,
+	Bn128Mul,
+	Bn128Pairing,
+	Blake2F,
+);
 
 /// Custom precompiles to be used by EVM engine.
 pub trait Precompiles {
@@ -71,6 +94,24 @@ impl Precompiles for Tuple {
 	}
 }
 
+/// Precompiles of `P` and in addition the precompile `E` at the address `A`, e.g. `Dispatch`
+/// next to `IstanbulPrecompiles`.
+pub struct WithPrecompile<P, E, A>(PhantomData<(P, E, A)>);
+
+impl<P: Precompiles, E: Precompile, A: Get<H160>> Precompiles for WithPrecompile<P, E, A> {
+	fn execute(
+		address: H160,
+		input: &[u8],
+		target_gas: Option<usize>,
+	) -> Option<core::result::Result<(ExitSucceed, Vec<u8>, usize), ExitError>> {
+		if address == A::get() {
+			return Some(E::execute(input, target_gas))
+		}
+
+		P::execute(address, input, target_gas)
+	}
+}
+
 /// Linear gas cost
 fn ensure_linear_cost(
 	target_gas: Option<usize>,
@@ -91,6 +132,24 @@ fn ensure_linear_cost(
 	Ok(cost)
 }
 
+/// Fail if the cost exceeds the target gas.
+fn ensure_cost(target_gas: Option<usize>, cost: usize) -> Result<usize, ExitError> {
+	match target_gas {
+		Some(target_gas) if cost > target_gas => Err(ExitError::OutOfGas),
+		_ => Ok(cost),
+	}
+}
+
+/// Read `len` bytes of the input from `offset`, padded with zeros past its end.
+fn read_input(input: &[u8], offset: usize, len: usize) -> Vec<u8> {
+	let mut buf = vec![0u8; len];
+	if offset < input.len() {
+		let available = min(len, input.len() - offset);
+		buf[..available].copy_from_slice(&input[offset..offset + available]);
+	}
+	buf
+}
+
 /// The identity precompile.
 pub struct Identity;
 
@@ -165,3 +224,347 @@ impl Precompile for Sha256 {
 		Ok((ExitSucceed::Returned, ret.to_vec(), cost))
 	}
 }
+
+/// The modexp precompile, as specified in EIP-198.
+pub struct Modexp;
+
+impl Modexp {
+	/// The complexity of multiplying numbers of `len` bytes, `None` on overflow.
+	fn mult_complexity(len: U256) -> Option<U256> {
+		let square = len.checked_mul(len)?;
+		if len <= U256::from(64) {
+			Some(square)
+		} else if len <= U256::from(1024) {
+			(square / 4).checked_add(len.checked_mul(U256::from(96))?)?
+				.checked_sub(U256::from(3072))
+		} else {
+			(square / 16).checked_add(len.checked_mul(U256::from(480))?)?
+				.checked_sub(U256::from(199680))
+		}
+	}
+}
+
+impl Precompile for Modexp {
+	fn execute(
+		input: &[u8],
+		target_gas: Option<usize>,
+	) -> core::result::Result<(ExitSucceed, Vec<u8>, usize), ExitError> {
+		let base_len = U256::from_big_endian(&read_input(input, 0, 32));
+		let exp_len = U256::from_big_endian(&read_input(input, 32, 32));
+		let mod_len = U256::from_big_endian(&read_input(input, 64, 32));
+
+		// the first 32 bytes of the exponent, offsets past the end of the input read zeros
+		let exp_offset = min(U256::from(96).saturating_add(base_len), U256::from(input.len()));
+		let exp_head_len = min(exp_len, U256::from(32));
+		let exp_head =
+			read_input(input, exp_offset.low_u64() as usize, exp_head_len.low_u64() as usize);
+		let exp_head_bits = U256::from(U256::from_big_endian(&exp_head).bits().saturating_sub(1));
+		let adjusted_exp_len = if exp_len <= U256::from(32) {
+			exp_head_bits
+		} else {
+			(exp_len - U256::from(32)).saturating_mul(U256::from(8)).saturating_add(exp_head_bits)
+		};
+
+		let cost = Self::mult_complexity(max(base_len, mod_len))
+			.and_then(|c| c.checked_mul(max(adjusted_exp_len, U256::one())))
+			.map(|c| c / 20)
+			.filter(|c| *c <= U256::from(usize::max_value()))
+			.ok_or(ExitError::OutOfGas)?;
+		let cost = ensure_cost(target_gas, cost.low_u64() as usize)?;
+
+		if mod_len.is_zero() {
+			return Ok((ExitSucceed::Returned, Vec::new(), cost))
+		}
+		// the lengths are bounded by the cost of a non-empty modulus
+		let max_len = U256::from(usize::max_value());
+		if base_len > max_len || exp_len > max_len || mod_len > max_len {
+			return Err(ExitError::OutOfGas)
+		}
+		let (base_len, exp_len, mod_len) =
+			(base_len.low_u64() as usize, exp_len.low_u64() as usize, mod_len.low_u64() as usize);
+
+		let base = read_input(input, 96, base_len);
+		let exponent = read_input(input, 96usize.saturating_add(base_len), exp_len);
+		let modulus = read_input(
+			input,
+			96usize.saturating_add(base_len).saturating_add(exp_len),
+			mod_len,
+		);
+
+		let mut output = vec![0u8; mod_len];
+		if modulus.iter().any(|b| *b != 0) {
+			let result = num_bigint::BigUint::from_bytes_be(&base).modpow(
+				&num_bigint::BigUint::from_bytes_be(&exponent),
+				&num_bigint::BigUint::from_bytes_be(&modulus),
+			).to_bytes_be();
+			output[mod_len - result.len()..].copy_from_slice(&result);
+		}
+
+		Ok((ExitSucceed::Returned, output, cost))
+	}
+}
+
+/// Read a field element of the alt_bn128 curve.
+fn read_fq(input: &[u8], offset: usize) -> Result<bn::Fq, ExitError> {
+	bn::Fq::from_slice(&read_input(input, offset, 32))
+		.map_err(|_| ExitError::Other("Invalid field element"))
+}
+
+/// Read a point of G1 of the alt_bn128 curve, `(0, 0)` is the point at infinity.
+fn read_g1(input: &[u8], offset: usize) -> Result<bn::G1, ExitError> {
+	use bn::Group;
+
+	let (x, y) = (read_fq(input, offset)?, read_fq(input, offset + 32)?);
+	if x.is_zero() && y.is_zero() {
+		Ok(bn::G1::zero())
+	} else {
+		bn::AffineG1::new(x, y).map(Into::into).map_err(|_| ExitError::Other("Invalid point"))
+	}
+}
+
+/// Encode a point of G1 of the alt_bn128 curve.
+fn encode_g1(point: bn::G1) -> Result<Vec<u8>, ExitError> {
+	let mut output = vec![0u8; 64];
+	if let Some(point) = bn::AffineG1::from_jacobian(point) {
+		point.x().to_big_endian(&mut output[0..32])
+			.and_then(|_| point.y().to_big_endian(&mut output[32..64]))
+			.map_err(|_| ExitError::Other("Cannot encode point"))?;
+	}
+	Ok(output)
+}
+
+/// The alt_bn128 addition precompile, as specified in EIP-196 and priced in EIP-1108.
+pub struct Bn128Add;
+
+impl Precompile for Bn128Add {
+	fn execute(
+		input: &[u8],
+		target_gas: Option<usize>,
+	) -> core::result::Result<(ExitSucceed, Vec<u8>, usize), ExitError> {
+		let cost = ensure_cost(target_gas, 150)?;
+
+		let sum = read_g1(input, 0)? + read_g1(input, 64)?;
+		Ok((ExitSucceed::Returned, encode_g1(sum)?, cost))
+	}
+}
+
+/// The alt_bn128 scalar multiplication precompile, as specified in EIP-196 and priced in
+/// EIP-1108.
+pub struct Bn128Mul;
+
+impl Precompile for Bn128Mul {
+	fn execute(
+		input: &[u8],
+		target_gas: Option<usize>,
+	) -> core::result::Result<(ExitSucceed, Vec<u8>, usize), ExitError> {
+		let cost = ensure_cost(target_gas, 6000)?;
+
+		let point = read_g1(input, 0)?;
+		let scalar = bn::Fr::from_slice(&read_input(input, 64, 32))
+			.map_err(|_| ExitError::Other("Invalid scalar"))?;
+		Ok((ExitSucceed::Returned, encode_g1(point * scalar)?, cost))
+	}
+}
+
+/// The alt_bn128 pairing check precompile, as specified in EIP-197 and priced in EIP-1108.
+pub struct Bn128Pairing;
+
+impl Precompile for Bn128Pairing {
+	fn execute(
+		input: &[u8],
+		target_gas: Option<usize>,
+	) -> core::result::Result<(ExitSucceed, Vec<u8>, usize), ExitError> {
+		use bn::Group;
+
+		if input.len() % 192 != 0 {
+			return Err(ExitError::Other("Invalid input length"))
+		}
+		let pairs_count = input.len() / 192;
+		let cost = 34000usize.checked_mul(pairs_count)
+			.and_then(|c| c.checked_add(45000))
+			.ok_or(ExitError::OutOfGas)?;
+		let cost = ensure_cost(target_gas, cost)?;
+
+		let mut pairs = Vec::with_capacity(pairs_count);
+		for offset in (0..input.len()).step_by(192) {
+			let a = read_g1(input, offset)?;
+			// the imaginary parts of the coordinates come first
+			let x = bn::Fq2::new(read_fq(input, offset + 96)?, read_fq(input, offset + 64)?);
+			let y = bn::Fq2::new(read_fq(input, offset + 160)?, read_fq(input, offset + 128)?);
+			let b = if x.is_zero() && y.is_zero() {
+				bn::G2::zero()
+			} else {
+				bn::AffineG2::new(x, y).map(Into::into)
+					.map_err(|_| ExitError::Other("Invalid point"))?
+			};
+			pairs.push((a, b));
+		}
+
+		let mut output = [0u8; 32];
+		if pairs.is_empty() || bn::pairing_batch(&pairs) == bn::Gt::one() {
+			output[31] = 1;
+		}
+		Ok((ExitSucceed::Returned, output.to_vec(), cost))
+	}
+}
+
+const BLAKE2F_IV: [u64; 8] = [
+	0x6a09e667f3bcc908, 0xbb67ae8584caa73b, 0x3c6ef372fe94f82b, 0xa54ff53a5f1d36f1,
+	0x510e527fade682d1, 0x9b05688c2b3e6c1f, 0x1f83d9abfb41bd6b, 0x5be0cd19137e2179,
+];
+
+const BLAKE2F_SIGMA: [[usize; 16]; 10] = [
+	[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+	[14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+	[11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+	[7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+	[9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+	[2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+	[12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+	[13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+	[6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+	[10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+/// The mixing function G of BLAKE2b.
+fn blake2f_mix(v: &mut [u64; 16], a: usize, b: usize, c: usize, d: usize, x: u64, y: u64) {
+	v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+	v[d] = (v[d] ^ v[a]).rotate_right(32);
+	v[c] = v[c].wrapping_add(v[d]);
+	v[b] = (v[b] ^ v[c]).rotate_right(24);
+	v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+	v[d] = (v[d] ^ v[a]).rotate_right(16);
+	v[c] = v[c].wrapping_add(v[d]);
+	v[b] = (v[b] ^ v[c]).rotate_right(63);
+}
+
+/// The compression function F of BLAKE2b with a configurable number of rounds.
+fn blake2f_compress(h: &mut [u64; 8], m: &[u64; 16], t: [u64; 2], f: bool, rounds: u32) {
+	let mut v = [0u64; 16];
+	v[..8].copy_from_slice(h);
+	v[8..].copy_from_slice(&BLAKE2F_IV);
+	v[12] ^= t[0];
+	v[13] ^= t[1];
+	if f {
+		v[14] = !v[14];
+	}
+
+	for round in 0..rounds as usize {
+		let s = &BLAKE2F_SIGMA[round % 10];
+		blake2f_mix(&mut v, 0, 4, 8, 12, m[s[0]], m[s[1]]);
+		blake2f_mix(&mut v, 1, 5, 9, 13, m[s[2]], m[s[3]]);
+		blake2f_mix(&mut v, 2, 6, 10, 14, m[s[4]], m[s[5]]);
+		blake2f_mix(&mut v, 3, 7, 11, 15, m[s[6]], m[s[7]]);
+		blake2f_mix(&mut v, 0, 5, 10, 15, m[s[8]], m[s[9]]);
+		blake2f_mix(&mut v, 1, 6, 11, 12, m[s[10]], m[s[11]]);
+		blake2f_mix(&mut v, 2, 7, 8, 13, m[s[12]], m[s[13]]);
+		blake2f_mix(&mut v, 3, 4, 9, 14, m[s[14]], m[s[15]]);
+	}
+
+	for i in 0..8 {
+		h[i] ^= v[i] ^ v[i + 8];
+	}
+}
+
+/// The blake2f precompile, as specified in EIP-152.
+pub struct Blake2F;
+
+impl Precompile for Blake2F {
+	fn execute(
+		input: &[u8],
+		target_gas: Option<usize>,
+	) -> core::result::Result<(ExitSucceed, Vec<u8>, usize), ExitError> {
+		if input.len() != 213 {
+			return Err(ExitError::Other("Invalid input length"))
+		}
+
+		let mut rounds = [0u8; 4];
+		rounds.copy_from_slice(&input[0..4]);
+		let rounds = u32::from_be_bytes(rounds);
+		let cost = ensure_cost(target_gas, rounds as usize)?;
+
+		let f = match input[212] {
+			0 => false,
+			1 => true,
+			_ => return Err(ExitError::Other("Invalid final block indicator")),
+		};
+		let read_words = |words: &mut [u64], offset: usize| {
+			for (i, word) in words.iter_mut().enumerate() {
+				let mut bytes = [0u8; 8];
+				bytes.copy_from_slice(&input[offset + i * 8..offset + i * 8 + 8]);
+				*word = u64::from_le_bytes(bytes);
+			}
+		};
+		let (mut h, mut m, mut t) = ([0u64; 8], [0u64; 16], [0u64; 2]);
+		read_words(&mut h, 4);
+		read_words(&mut m, 68);
+		read_words(&mut t, 196);
+
+		blake2f_compress(&mut h, &m, t, f, rounds);
+
+		let output = h.iter().flat_map(|word| word.to_le_bytes().to_vec()).collect();
+		Ok((ExitSucceed::Returned, output, cost))
+	}
+}
+
+/// A precompile returning the random value of the subject given as input, e.g. of the
+/// randomness beacon.
+pub struct Random<R>(PhantomData<R>);
+
+impl<R: Randomness<H256>> Precompile for Random<R> {
+	fn execute(
+		input: &[u8],
+		target_gas: Option<usize>,
+	) -> core::result::Result<(ExitSucceed, Vec<u8>, usize), ExitError> {
+		// as a storage read and hashing the subject
+		let cost = ensure_linear_cost(target_gas, input.len(), 800, 12)?;
+
+		Ok((ExitSucceed::Returned, R::random(input).as_bytes().to_vec(), cost))
+	}
+}
+
+/// A precompile at the address `A` dispatching the SCALE encoded runtime call `C` given as input,
+/// on behalf of the account of the caller mapped by `AddressMapping`. Only calls passing the
+/// filter `F` are dispatched, e.g. balance transfers.
+///
+/// The gas of the call is converted from its weight by `GasWeightMapping`. As precompiles don't
+/// know their caller, the precompile must be called directly by the transaction, whose source is
+/// then the caller, and fails if called by a contract. The call is thus reverted with the
+/// execution, which is the frame of the precompile.
+pub struct Dispatch<T, C, F, A>(PhantomData<(T, C, F, A)>);
+
+impl<T, C, F, A> Precompile for Dispatch<T, C, F, A> where
+	T: Trait,
+	C: Dispatchable<Origin=T::Origin, PostInfo=PostDispatchInfo> + GetDispatchInfo + Decode,
+	F: Filter<C>,
+	A: Get<H160>,
+{
+	fn execute(
+		input: &[u8],
+		target_gas: Option<usize>,
+	) -> core::result::Result<(ExitSucceed, Vec<u8>, usize), ExitError> {
+		let source = match Module::<T>::current_execution() {
+			Some((source, Some(target))) if target == A::get() => source,
+			_ => return Err(ExitError::Other("Dispatch must be called by the transaction")),
+		};
+
+		let call = C::decode(&mut &input[..]).map_err(|_| ExitError::Other("Invalid call"))?;
+		if !F::filter(&call) {
+			return Err(ExitError::Other("Call is not allowed"))
+		}
+
+		let info = call.get_dispatch_info();
+		let cost = ensure_cost(target_gas, T::GasWeightMapping::weight_to_gas(info.weight))?;
+
+		let origin = RawOrigin::Signed(T::AddressMapping::into_account_id(source)).into();
+
+		let post_info = with_transaction(|| match call.dispatch(origin) {
+			Ok(post_info) => TransactionOutcome::Commit(Ok(post_info)),
+			Err(_) => TransactionOutcome::Rollback(Err(ExitError::Other("Call failed"))),
+		})?;
+		let used_gas = post_info.actual_weight
+			.map_or(cost, |weight| min(cost, T::GasWeightMapping::weight_to_gas(weight)));
+
+		Ok((ExitSucceed::Stopped, Vec::new(), used_gas))
+	}
+}
@@ -4,9 +4,11 @@ use super::*;
 
 use std::{str::FromStr, collections::BTreeMap};
 use frame_support::{
-	assert_ok, impl_outer_origin, parameter_types, impl_outer_dispatch,
+	assert_ok, assert_noop, impl_outer_origin, parameter_types, impl_outer_dispatch,
+	traits::{Filter, OnInitialize, TestRandomness}, unsigned::ValidateUnsigned,
 };
 use sp_core::{Blake2Hasher, H256};
+use hex_literal::hex;
 use sp_runtime::{
 	Perbill,
 	testing::Header,
@@ -20,6 +22,7 @@ impl_outer_origin! {
 impl_outer_dispatch! {
 	pub enum OuterCall for Test where origin: Origin {
 		self::EVM,
+		pallet_balances::Balances,
 	}
 }
 
@@ -82,6 +85,19 @@ impl pallet_timestamp::Trait for Test {
 	type WeightInfo = ();
 }
 
+parameter_types! {
+	pub const ChainId: u64 = 1;
+	pub DispatchAddress: H160 = H160::from_low_u64_be(0x400);
+}
+
+/// Only balance transfers may be dispatched by contracts.
+pub struct TransfersOnly;
+impl Filter<OuterCall> for TransfersOnly {
+	fn filter(call: &OuterCall) -> bool {
+		matches!(call, OuterCall::Balances(pallet_balances::Call::transfer(..)))
+	}
+}
+
 /// Fixed gas price of `0`.
 pub struct FixedGasPrice;
 impl FeeCalculator for FixedGasPrice {
@@ -101,8 +117,13 @@ impl Trait for Test {
 	type Currency = Balances;
 
 	type Event = Event<Test>;
-	type Precompiles = ();
-	type ChainId = SystemChainId;
+	type Precompiles = WithPrecompile<
+		IstanbulPrecompiles,
+		Dispatch<Test, OuterCall, TransfersOnly, DispatchAddress>,
+		DispatchAddress,
+	>;
+	type ChainId = ChainId;
+	type GasWeightMapping = ();
 }
 
 type System = frame_system::Module<Test>;
@@ -187,3 +208,357 @@ fn mutate_account_works() {
 		});
 	});
 }
+
+// the example transaction of EIP-155
+const EIP155_TRANSACTION: [u8; 110] = hex!["f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83"];
+
+fn eip155_sender() -> H160 {
+	H160::from(hex!["9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f"])
+}
+
+fn eip155_transaction() -> EthTransaction {
+	EthTransaction::decode_rlp(&EIP155_TRANSACTION).unwrap()
+}
+
+fn fund_eip155_sender(nonce: u64) {
+	EVM::mutate_account_basic(&eip155_sender(), Account {
+		nonce: U256::from(nonce),
+		balance: U256::from(2_000_000_000_000_000_000u64),
+	});
+}
+
+fn validate_transact(transaction: EthTransaction) -> TransactionValidity {
+	<EVM as ValidateUnsigned>::validate_unsigned(
+		TransactionSource::External,
+		&Call::transact(transaction),
+	)
+}
+
+#[test]
+fn eip155_transaction_is_decoded() {
+	new_test_ext().execute_with(|| {
+		let transaction = eip155_transaction();
+		assert_eq!(transaction.nonce, U256::from(9));
+		assert_eq!(transaction.gas_price, U256::from(20_000_000_000u64));
+		assert_eq!(transaction.gas_limit, U256::from(21000));
+		assert_eq!(transaction.action, TransactionAction::Call(H160::repeat_byte(0x35)));
+		assert_eq!(transaction.value, U256::from(1_000_000_000_000_000_000u64));
+		assert!(transaction.input.is_empty());
+		assert_eq!(transaction.signature.chain_id(), Some(1));
+
+		assert_eq!(
+			transaction.signing_hash(1),
+			H256::from(hex!["daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53"]),
+		);
+		assert_eq!(transaction.sender(), Some(eip155_sender()));
+		assert_eq!(transaction.encode_rlp(), EIP155_TRANSACTION.to_vec());
+		assert_eq!(transaction.hash(), H256::from(sp_io::hashing::keccak_256(&EIP155_TRANSACTION)));
+	});
+}
+
+#[test]
+fn eip155_transaction_is_checked() {
+	new_test_ext().execute_with(|| {
+		assert_eq!(EVM::check_transaction(&eip155_transaction()).ok(), Some(eip155_sender()));
+
+		// signed for chain 2
+		let mut transaction = eip155_transaction();
+		transaction.signature.v += 2;
+		assert!(matches!(
+			EVM::check_transaction(&transaction),
+			Err(Error::<Test>::InvalidChainId)
+		));
+
+		// the malleable twin of the signature, with a high `s`
+		let mut transaction = eip155_transaction();
+		let n = U256::from_big_endian(
+			&hex!["fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141"],
+		);
+		let s = U256::from_big_endian(transaction.signature.s.as_bytes());
+		transaction.signature.s = H256::from(<[u8; 32]>::from(n - s));
+		assert!(matches!(
+			EVM::check_transaction(&transaction),
+			Err(Error::<Test>::InvalidSignature)
+		));
+
+		let mut transaction = eip155_transaction();
+		transaction.value += U256::one();
+		assert_ne!(EVM::check_transaction(&transaction).ok(), Some(eip155_sender()));
+	});
+}
+
+#[test]
+fn transact_executes_eip155_transaction() {
+	new_test_ext().execute_with(|| {
+		fund_eip155_sender(9);
+		System::set_block_number(1);
+
+		assert_noop!(
+			EVM::transact(Origin::root(), eip155_transaction()),
+			BadOrigin,
+		);
+		assert_ok!(EVM::transact(Origin::none(), eip155_transaction()));
+
+		assert_eq!(EVM::account_basic(&eip155_sender()), Account {
+			nonce: U256::from(10),
+			balance: U256::from(999_580_000_000_000_000u64),
+		});
+		assert_eq!(
+			EVM::account_basic(&H160::repeat_byte(0x35)).balance,
+			U256::from(1_000_000_000_000_000_000u64),
+		);
+		assert_eq!(EVM::transaction_statuses(), vec![TransactionStatus {
+			transaction_hash: eip155_transaction().hash(),
+			transaction_index: 0,
+			from: eip155_sender(),
+			to: Some(H160::repeat_byte(0x35)),
+			contract_address: None,
+			succeeded: true,
+			used_gas: U256::from(21000),
+			logs: Vec::new(),
+		}]);
+
+		// replayed
+		assert_noop!(
+			EVM::transact(Origin::none(), eip155_transaction()),
+			Error::<Test>::InvalidNonce,
+		);
+
+		EVM::on_initialize(2);
+		assert!(EVM::transaction_statuses().is_empty());
+	});
+}
+
+#[test]
+fn transact_is_validated_unsigned() {
+	new_test_ext().execute_with(|| {
+		// the sender cannot pay
+		assert_eq!(validate_transact(eip155_transaction()), InvalidTransaction::Payment.into());
+
+		fund_eip155_sender(9);
+		let valid = validate_transact(eip155_transaction()).unwrap();
+		assert_eq!(valid.priority, 20_000_000_000);
+		assert_eq!(
+			valid.provides,
+			vec![("EvmTransact", (eip155_sender(), U256::from(9))).encode()],
+		);
+		assert!(valid.requires.is_empty());
+
+		let mut transaction = eip155_transaction();
+		transaction.signature.v += 2;
+		assert_eq!(validate_transact(transaction), InvalidTransaction::Call.into());
+
+		fund_eip155_sender(10);
+		assert_eq!(validate_transact(eip155_transaction()), InvalidTransaction::Stale.into());
+	});
+}
+
+#[test]
+fn future_transaction_requires_its_predecessor() {
+	new_test_ext().execute_with(|| {
+		fund_eip155_sender(8);
+		let valid = validate_transact(eip155_transaction()).unwrap();
+		assert_eq!(
+			valid.requires,
+			vec![("EvmTransact", (eip155_sender(), U256::from(8))).encode()],
+		);
+	});
+}
+
+/// A big-endian word of the EVM.
+fn word(value: u64) -> Vec<u8> {
+	H256::from_low_u64_be(value).as_bytes().to_vec()
+}
+
+#[test]
+fn modexp_works() {
+	// the first example of EIP-198, 3^(p - 1) mod p for the prime p = 2^256 - 2^32 - 977
+	let mut input = Vec::new();
+	input.extend_from_slice(&word(1));
+	input.extend_from_slice(&word(32));
+	input.extend_from_slice(&word(32));
+	input.push(3);
+	input.extend_from_slice(&hex!["fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2e"]);
+	input.extend_from_slice(&hex!["fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f"]);
+
+	let (_, output, cost) = precompiles::Modexp::execute(&input, None).unwrap();
+	assert_eq!(output, word(1));
+	assert_eq!(cost, 13056);
+	assert!(matches!(
+		precompiles::Modexp::execute(&input, Some(13055)),
+		Err(ExitError::OutOfGas)
+	));
+
+	// an empty modulus
+	let input = [word(1), word(1), word(0), vec![2, 3]].concat();
+	assert_eq!(precompiles::Modexp::execute(&input, None).unwrap().1, Vec::<u8>::new());
+}
+
+// the generator of the group G1 of alt_bn128 and its double
+const BN128_G1: [u8; 64] = hex!["0000000000000000000000000000000000000000000000000000000000000001 0000000000000000000000000000000000000000000000000000000000000002"];
+const BN128_G1_DOUBLE: [u8; 64] = hex!["030644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd3 15ed738c0e0a7c92e7845f96b2ae9c0a68a6a449e3538fc7ff3ebf7a5a18a2c4"];
+
+#[test]
+fn bn128_add_and_mul_work() {
+	let input = [BN128_G1, BN128_G1].concat();
+	let (_, output, cost) = precompiles::Bn128Add::execute(&input, None).unwrap();
+	assert_eq!(output, BN128_G1_DOUBLE.to_vec());
+	assert_eq!(cost, 150);
+
+	let input = [BN128_G1.to_vec(), word(2)].concat();
+	let (_, output, cost) = precompiles::Bn128Mul::execute(&input, None).unwrap();
+	assert_eq!(output, BN128_G1_DOUBLE.to_vec());
+	assert_eq!(cost, 6000);
+
+	// the point at infinity is the neutral element
+	let (_, output, _) = precompiles::Bn128Add::execute(&BN128_G1, None).unwrap();
+	assert_eq!(output, BN128_G1.to_vec());
+
+	// not on the curve
+	let mut input = BN128_G1;
+	input[63] = 3;
+	assert!(precompiles::Bn128Add::execute(&input, None).is_err());
+}
+
+#[test]
+fn bn128_pairing_works() {
+	let (_, output, cost) = precompiles::Bn128Pairing::execute(&[], None).unwrap();
+	assert_eq!(output, word(1));
+	assert_eq!(cost, 45000);
+
+	// paired with the point at infinity of G2
+	let input = [BN128_G1.to_vec(), vec![0; 128]].concat();
+	let (_, output, cost) = precompiles::Bn128Pairing::execute(&input, None).unwrap();
+	assert_eq!(output, word(1));
+	assert_eq!(cost, 79000);
+
+	assert!(precompiles::Bn128Pairing::execute(&input[1..], None).is_err());
+}
+
+#[test]
+fn blake2f_works() {
+	// the fifth test vector of EIP-152
+	let input = [
+		&hex!["0000000c"][..],
+		&hex!["48c9bdf267e6096a3ba7ca8485ae67bb2bf894fe72f36e3cf1361d5f3af54fa5d182e6ad7f520e511f6c3e2b8c68059b6bbd41fbabd9831f79217e1319cde05b"][..],
+		&hex!["6162630000000000000000000000000000000000000000000000000000000000"][..],
+		&[0u8; 96][..],
+		&hex!["0300000000000000 0000000000000000 01"][..],
+	].concat();
+
+	let (_, output, cost) = precompiles::Blake2F::execute(&input, None).unwrap();
+	assert_eq!(output, hex!["ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d17d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923"].to_vec());
+	assert_eq!(cost, 12);
+
+	assert!(precompiles::Blake2F::execute(&input[1..], None).is_err());
+	let mut input = input;
+	input[212] = 2;
+	assert!(precompiles::Blake2F::execute(&input, None).is_err());
+}
+
+#[test]
+fn random_returns_randomness_of_subject() {
+	let (_, output, cost) =
+		precompiles::Random::<TestRandomness>::execute(b"subject", None).unwrap();
+	assert_eq!(output, [&b"subject"[..], &[0; 25]].concat());
+	assert_eq!(cost, 812);
+}
+
+fn dispatch_source() -> (H160, AccountId32) {
+	let source = H160::repeat_byte(0x11);
+	let account = <Test as Trait>::AddressMapping::into_account_id(source);
+	let _ = Balances::deposit_creating(&account, 1000);
+	(source, account)
+}
+
+fn transfer(dest: AccountId32, value: u64) -> Vec<u8> {
+	OuterCall::Balances(pallet_balances::Call::transfer(dest, value)).encode()
+}
+
+fn call_evm(
+	source: H160,
+	target: H160,
+	input: Vec<u8>,
+	value: u64,
+) -> DispatchResultWithPostInfo {
+	EVM::call(
+		Origin::root(),
+		source,
+		target,
+		input,
+		U256::from(value),
+		u32::max_value(),
+		U256::default(),
+		None,
+	)
+}
+
+#[test]
+fn dispatch_precompile_dispatches_on_behalf_of_caller() {
+	new_test_ext().execute_with(|| {
+		let (source, account) = dispatch_source();
+		let dest = AccountId32::from([9u8; 32]);
+
+		assert_ok!(call_evm(source, DispatchAddress::get(), transfer(dest.clone(), 100), 0));
+		assert_eq!(Balances::free_balance(&dest), 100);
+		// the state of the execution is applied on top of the transfer
+		assert_eq!(Balances::free_balance(&account), 900);
+		assert_eq!(EVM::account_basic(&source).nonce, U256::one());
+		assert_eq!(EVM::current_execution(), None);
+
+		// only transfers may be dispatched
+		let transfer_keep_alive =
+			OuterCall::Balances(pallet_balances::Call::transfer_keep_alive(dest.clone(), 100));
+		assert_ok!(call_evm(source, DispatchAddress::get(), transfer_keep_alive.encode(), 0));
+		assert_eq!(Balances::free_balance(&dest), 100);
+		assert_eq!(Balances::free_balance(&account), 900);
+	});
+}
+
+#[test]
+fn dispatch_precompile_is_not_called_by_contracts() {
+	new_test_ext().execute_with(|| {
+		let (source, account) = dispatch_source();
+		let dest = AccountId32::from([9u8; 32]);
+
+		// a contract forwarding its input to the dispatch precompile
+		let contract = H160::repeat_byte(0x22);
+		AccountCodes::insert(contract, [
+			// CALLDATACOPY(0, 0, CALLDATASIZE)
+			&hex!["36 6000 6000 37"][..],
+			// POP(CALL(GAS, 0x400, 0, 0, CALLDATASIZE, 0, 0))
+			&hex!["6000 6000 36 6000 6000 610400 5a f1 50"][..],
+		].concat());
+
+		assert_ok!(call_evm(source, contract, transfer(dest.clone(), 100), 0));
+		assert_eq!(Balances::free_balance(&dest), 0);
+		assert_eq!(Balances::free_balance(&account), 1000);
+		assert_eq!(EVM::account_basic(&source).nonce, U256::one());
+	});
+}
+
+#[test]
+fn dispatch_cannot_spend_funds_of_execution() {
+	new_test_ext().execute_with(|| {
+		let (source, account) = dispatch_source();
+		let _ = Balances::deposit_creating(&account, 999_999_000);
+		let dest = AccountId32::from([9u8; 32]);
+
+		// 999_999_900 are transferred by the runtime while 500 are transferred by the EVM
+		assert_ok!(EVM::call(
+			Origin::root(),
+			source,
+			DispatchAddress::get(),
+			transfer(dest.clone(), 999_999_900),
+			U256::from(500),
+			500_000_000,
+			U256::one(),
+			None,
+		));
+		assert_eq!(Balances::free_balance(&dest), 0);
+		assert_eq!(EVM::account_basic(&source).nonce, U256::one());
+		// only the fee is charged
+		let balance = Balances::free_balance(&account);
+		assert!(balance < 1_000_000_000 && balance > 500_000_000);
+	});
+}
@@ -0,0 +1,203 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Ethereum transactions, signed by their senders as specified in EIP-155, and the statuses of
+//! their execution kept for the Ethereum JSON-RPC.
+
+use sp_std::vec::Vec;
+#[cfg(feature = "std")]
+use serde::{Serialize, Deserialize};
+use codec::{Encode, Decode};
+use sp_core::{U256, H256, H160};
+use rlp::{Rlp, RlpStream, DecoderError};
+use crate::backend::Log;
+
+/// Half of the order of the secp256k1 curve, signatures with a larger `s` are malleable
+/// (EIP-2).
+const SECP256K1_HALF_N: [u8; 32] = [
+	0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+	0x5d, 0x57, 0x6e, 0x73, 0x57, 0xa4, 0x50, 0x1d, 0xdf, 0xe9, 0x2f, 0x46, 0x68, 0x1b, 0x20, 0xa0,
+];
+
+#[derive(Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+/// The recipient of an Ethereum transaction.
+pub enum TransactionAction {
+	/// A message call of the given address.
+	Call(H160),
+	/// A contract creation, the input of the transaction is the init code.
+	Create,
+}
+
+#[derive(Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+/// The signature of an Ethereum transaction.
+pub struct TransactionSignature {
+	/// `chain_id * 2 + 35 + recovery_id` as specified in EIP-155.
+	pub v: u64,
+	/// The `r` value of the ECDSA signature.
+	pub r: H256,
+	/// The `s` value of the ECDSA signature.
+	pub s: H256,
+}
+
+impl TransactionSignature {
+	/// The chain the transaction was signed for, `None` for signatures preceding EIP-155,
+	/// which could be replayed on every chain.
+	pub fn chain_id(&self) -> Option<u64> {
+		match self.v {
+			v if v >= 35 => Some((v - 35) / 2),
+			_ => None,
+		}
+	}
+
+	/// The signature in the format of `secp256k1_ecdsa_recover`, `None` if it is malformed.
+	fn to_recoverable(&self) -> Option<[u8; 65]> {
+		let chain_id = self.chain_id()?;
+		if self.r == H256::zero() || self.s == H256::zero() ||
+			self.s.as_bytes() > &SECP256K1_HALF_N[..]
+		{
+			return None;
+		}
+
+		let mut sig = [0u8; 65];
+		sig[0..32].copy_from_slice(self.r.as_bytes());
+		sig[32..64].copy_from_slice(self.s.as_bytes());
+		sig[64] = (self.v - chain_id * 2 - 35) as u8;
+		Some(sig)
+	}
+}
+
+#[derive(Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+/// An Ethereum transaction signed as specified in EIP-155. It is submitted in its RLP
+/// encoding, e.g. with `eth_sendRawTransaction`, and carried SCALE encoded by the `transact`
+/// call.
+pub struct EthTransaction {
+	/// The nonce of the sender.
+	pub nonce: U256,
+	/// The price of gas the sender pays.
+	pub gas_price: U256,
+	/// The most gas the execution may use.
+	pub gas_limit: U256,
+	/// The recipient of the transaction.
+	pub action: TransactionAction,
+	/// The value transferred to the recipient.
+	pub value: U256,
+	/// The input of the call or the init code of the contract.
+	pub input: Vec<u8>,
+	/// The signature of the sender.
+	pub signature: TransactionSignature,
+}
+
+impl EthTransaction {
+	/// Decode a transaction from its RLP encoding.
+	pub fn decode_rlp(bytes: &[u8]) -> Result<Self, DecoderError> {
+		let rlp = Rlp::new(bytes);
+		if rlp.item_count()? != 9 {
+			return Err(DecoderError::RlpIncorrectListLen);
+		}
+
+		let action = rlp.at(3)?;
+		let action = match action.is_empty() {
+			true => TransactionAction::Create,
+			false => TransactionAction::Call(action.as_val()?),
+		};
+		Ok(EthTransaction {
+			nonce: rlp.val_at(0)?,
+			gas_price: rlp.val_at(1)?,
+			gas_limit: rlp.val_at(2)?,
+			action,
+			value: rlp.val_at(4)?,
+			input: rlp.val_at(5)?,
+			signature: TransactionSignature {
+				v: rlp.val_at(6)?,
+				r: H256::from(<[u8; 32]>::from(rlp.val_at::<U256>(7)?)),
+				s: H256::from(<[u8; 32]>::from(rlp.val_at::<U256>(8)?)),
+			},
+		})
+	}
+
+	/// The RLP encoding of the signed transaction.
+	pub fn encode_rlp(&self) -> Vec<u8> {
+		let mut stream = RlpStream::new_list(9);
+		self.append_unsigned(&mut stream);
+		stream.append(&self.signature.v);
+		stream.append(&U256::from_big_endian(self.signature.r.as_bytes()));
+		stream.append(&U256::from_big_endian(self.signature.s.as_bytes()));
+		stream.out()
+	}
+
+	/// The hash of the transaction, i.e. the Keccak-256 hash of its RLP encoding.
+	pub fn hash(&self) -> H256 {
+		H256::from(sp_io::hashing::keccak_256(&self.encode_rlp()))
+	}
+
+	/// The message signed by the sender, which commits to the chain of the transaction.
+	pub fn signing_hash(&self, chain_id: u64) -> H256 {
+		let mut stream = RlpStream::new_list(9);
+		self.append_unsigned(&mut stream);
+		stream.append(&chain_id);
+		stream.append(&0u8);
+		stream.append(&0u8);
+		H256::from(sp_io::hashing::keccak_256(&stream.out()))
+	}
+
+	/// The address of the sender, `None` if the signature is invalid or precedes EIP-155.
+	pub fn sender(&self) -> Option<H160> {
+		let chain_id = self.signature.chain_id()?;
+		let sig = self.signature.to_recoverable()?;
+		let msg = self.signing_hash(chain_id);
+		let pubkey = sp_io::crypto::secp256k1_ecdsa_recover(&sig, msg.as_fixed_bytes()).ok()?;
+		Some(H160::from(H256::from(sp_io::hashing::keccak_256(&pubkey))))
+	}
+
+	fn append_unsigned(&self, stream: &mut RlpStream) {
+		stream.append(&self.nonce);
+		stream.append(&self.gas_price);
+		stream.append(&self.gas_limit);
+		match self.action {
+			TransactionAction::Call(ref address) => stream.append(address),
+			TransactionAction::Create => stream.append_empty_data(),
+		};
+		stream.append(&self.value);
+		stream.append(&self.input);
+	}
+}
+
+#[derive(Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+/// The outcome of an Ethereum transaction executed in the current block, the receipts of the
+/// Ethereum JSON-RPC are derived from it.
+pub struct TransactionStatus {
+	/// The hash of the transaction.
+	pub transaction_hash: H256,
+	/// The index of the transaction among the Ethereum transactions of the block.
+	pub transaction_index: u32,
+	/// The sender of the transaction.
+	pub from: H160,
+	/// The recipient of a message call.
+	pub to: Option<H160>,
+	/// The address of the contract created by the transaction.
+	pub contract_address: Option<H160>,
+	/// Whether the execution succeeded, otherwise only the fee was charged.
+	pub succeeded: bool,
+	/// The gas used by the execution.
+	pub used_gas: U256,
+	/// The logs emitted by the execution.
+	pub logs: Vec<Log>,
+}
@@ -20,33 +20,123 @@
 //! a given seed is correct for a particular block or not. Internally, such a verifier
 //! keeps a joint public key for BLS threshold signatures.
 //! In every block of height `s + p*k` for `k=1, 2, 3, ..` where s = StartHeight and
-//! p = RandomnessPeriod,  there is an inherent which is supposed to contain the seed for
+//! p = RandomnessPeriod, unless both are overridden by the `schedule` of the genesis config,
+//! there is an inherent which is supposed to contain the seed for
 //! the current block. Correctness of this seed is checked using the randomness verifier
 //! and the whole block is discarded as incorrect in case it outputs false.
+//! The verifier is (re)loaded from `T::RandomnessVerifier` at every block returned by
+//! `T::RandomnessVerifierReady`, which allows to rotate the key without restarting the chain.
+//! Only seeds signed with `T::SignatureVersion` are accepted, so a chain may switch to a new
+//! version of signatures with a runtime upgrade.
+//!
+//! If fewer than `threshold` committee members are online, no seed can be created. Proposers
+//! give up waiting for the seed after a grace period and author the block without the inherent.
+//! Such a block is accepted only if at least `T::GracePeriod` passed since its parent block,
+//! otherwise the author could withhold a seed it does not like. It emits `BeaconMissed` and the
+//! previous seed stays in use, `seed_freshness` tells consumers how old the seed is and how many
+//! beacon blocks were missed since it was set.
+//!
+//! The seeds of the latest `T::HistoryDepth` rounds are kept, see `round_randomness` and
+//! `random_at`. Through `RandomnessRequest` other pallets may book the randomness of a future
+//! round for a subject committed to in advance, which makes it impossible to choose the subject
+//! knowing the seed. Once the round is sealed, `T::OnRandomnessSealed` is called with the random
+//! value of the subject. The requests of a round without a seed are moved to the next round, or
+//! to the following ones if the next round has `T::MaxRequestsPerRound` requests already.
+//!
+//! Between the beacon rounds `BlockRandomness` gives a fresh value in every block by mixing the
+//! seed with the VRF output of the author of the block, which the author puts into a
+//! `vrf::PreDigest`. The output is verified against the key `T::FindAuthor` finds for the
+//! block, a block with an invalid output, or without one while its author is found, is invalid.
+//!
+//! By default the nonce of a round is the hash of the block `p` blocks before the seed is due,
+//! which its author may influence and which differs between forks. With `T::ChainedRounds` the
+//! nonce of round `r` is `H(r || sig)` instead, where `sig` is the signature of the latest seed,
+//! as in drand. The seeds of such a chain do not depend on the blocks and may be verified in
+//! sequence from the first round with `RandomnessVerifier::verify_chain`.
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
 use codec::Encode;
+use core::time::Duration;
 use frame_support::{
-	debug, decl_error, decl_module, decl_storage, decl_event, traits::Get, traits::Randomness as RandomnessT,
+	debug, decl_error, decl_module, decl_storage, decl_event, ensure,
+	traits::{FindAuthor, Get, Randomness as RandomnessT, UnixTime},
 	weights::Weight,
+	ConsensusEngineId,
 };
 use frame_system::ensure_none;
 use sp_inherents::{InherentData, InherentIdentifier, ProvideInherent};
 use sp_randomness_beacon::{
-	inherents::{InherentError, INHERENT_IDENTIFIER},
-	Randomness, RandomnessVerifier,
+	inherents::{CheckContext, InherentError, CHECK_CONTEXT_IDENTIFIER, INHERENT_IDENTIFIER},
+	vrf::{self, VrfRandomness},
+	Randomness, RandomnessVerifier, Round, SeedFreshness, SignatureVersion,
 };
-use sp_runtime::traits::Hash;
+use sp_runtime::{
+	traits::{Hash, Saturating, UniqueSaturatedInto, Zero},
+	DispatchError,
+};
+
+use sp_std::{marker::PhantomData, prelude::*, result};
+
+/// The weight of verifying the VRF output of the author of a block.
+const AUTHOR_VRF_WEIGHT: Weight = 50_000_000;
+
+/// The identifier of a request for randomness.
+pub type RequestId = u64;
+
+/// Requests for the randomness of future rounds.
+pub trait RandomnessRequest {
+	/// The earliest round whose randomness may be requested in the current block, i.e. the
+	/// first round whose nonce is not known yet.
+	fn earliest_round() -> Round;
+
+	/// Books the randomness of the round for the subject. Once the round is sealed,
+	/// `OnRandomnessSealed` is called with the returned id.
+	fn request(round: Round, subject: Vec<u8>) -> Result<RequestId, DispatchError>;
+}
+
+/// A hook called for each request when the seed of its round is set.
+pub trait OnRandomnessSealed<Hash> {
+	/// The randomness of the round was set, `random` is the hash of the subject of the request
+	/// together with the seed.
+	fn on_randomness_sealed(request: RequestId, round: Round, random: &Hash);
+}
 
-use sp_std::result;
+#[impl_trait_for_tuples::impl_for_tuples(30)]
+impl<Hash> OnRandomnessSealed<Hash> for Tuple {
+	fn on_randomness_sealed(request: RequestId, round: Round, random: &Hash) {
+		for_tuples!( #( Tuple::on_randomness_sealed(request, round, random); )* );
+	}
+}
 
 pub trait Trait: frame_system::Trait {
 	type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
 	type StartHeight: Get<Self::BlockNumber>;
 	type RandomnessPeriod: Get<Self::BlockNumber>;
+	/// The block at which the next verifier becomes available.
 	type RandomnessVerifierReady: Get<Self::BlockNumber>;
+	/// The verifier to be used starting from `RandomnessVerifierReady`.
 	type RandomnessVerifier: Get<Option<RandomnessVerifier>>;
+	/// The version of signatures accepted as randomness seeds.
+	type SignatureVersion: Get<SignatureVersion>;
+	/// The number of the latest rounds whose seeds are kept.
+	type HistoryDepth: Get<u32>;
+	/// The maximal number of requests for the randomness of a single round.
+	type MaxRequestsPerRound: Get<u32>;
+	/// Called for the requests of a round when its seed is set.
+	type OnRandomnessSealed: OnRandomnessSealed<Self::Hash>;
+	/// The sr25519 key of a block author, with which its VRF output is verified.
+	type AuthorVrfKey: AsRef<[u8]>;
+	/// Finds the key of the author of a block among its pre-runtime digests.
+	type FindAuthor: FindAuthor<Self::AuthorVrfKey>;
+	/// Whether the nonce of a round is chained to the latest seed instead of being the hash of
+	/// a block, see `chained_nonce`.
+	type ChainedRounds: Get<bool>;
+	/// The time of the current block.
+	type UnixTime: UnixTime;
+	/// The time since the parent block after which a block at which a seed is due may be
+	/// authored without one.
+	type GracePeriod: Get<Duration>;
 }
 
 decl_storage! {
@@ -55,8 +145,37 @@ decl_storage! {
 		Seed: Randomness<T::Hash>;
 		/// Specifies when was Seed last set
 		LastUpdate: T::BlockNumber;
+		/// The number of beacon blocks without a new seed since Seed was last set
+		MissedBeacons: u32;
+		/// The time of the parent of the current beacon block in milliseconds, removed at the
+		/// end of the block
+		ParentTime: u64;
 		/// Stores verifier needed to check randomness in blocks
-		Verifier get(fn verifier): RandomnessVerifier
+		Verifier get(fn verifier): RandomnessVerifier;
+		/// The latest round whose seed was set
+		LatestRound get(fn latest_round): Option<Round>;
+		/// The seeds of the latest rounds, the seed of a round is kept under the round modulo
+		/// HistoryDepth
+		History: map hasher(twox_64_concat) u32 => Option<(Round, Randomness<T::Hash>)>;
+		/// The ids and subjects of the requests for the randomness of future rounds
+		Requests: map hasher(twox_64_concat) Round => Vec<(RequestId, Vec<u8>)>;
+		/// The id of the next request
+		NextRequestId: RequestId;
+		/// The randomness of the VRF output of the author of the current block, removed at the
+		/// end of the block
+		AuthorVrfRandomness get(fn author_vrf_randomness): Option<VrfRandomness>;
+		/// The start height and the period of the beacon set in the genesis config, overriding
+		/// `T::StartHeight` and `T::RandomnessPeriod`
+		Schedule: Option<(T::BlockNumber, T::BlockNumber)>;
+	}
+	add_extra_genesis {
+		config(schedule): Option<(T::BlockNumber, T::BlockNumber)>;
+		build(|config| {
+			if let Some((start, period)) = config.schedule {
+				assert!(!period.is_zero(), "The period of the beacon must be positive.");
+				<Schedule<T>>::put((start, period));
+			}
+		})
 	}
 }
 
@@ -67,12 +186,26 @@ decl_event!(
 		SeedSet(BlockNumber),
 		/// The Randomness Beacon was initalized with a key box from DKG.
 		KeyInitialized(BlockNumber),
+		/// The verifier of the Randomness Beacon was replaced by a new one.
+		KeyRotated(BlockNumber),
+		/// No seed was set in a block at which it was due, the previous seed stays in use.
+		BeaconMissed(BlockNumber),
 	}
 );
 
 decl_error! {
 	pub enum Error for Module<T: Trait> {
 		SeedNotAvailable,
+		/// The nonce of the requested round is already known.
+		RoundTooEarly,
+		/// The round has the maximal number of requests already.
+		TooManyRequests,
+		/// The beacon has not started yet.
+		BeaconNotStarted,
+		/// No seed is due at the current block.
+		NotBeaconHeight,
+		/// The seed was already set in the current block.
+		SeedAlreadySet,
 	}
 }
 
@@ -84,53 +217,83 @@ decl_module! {
 		fn deposit_event() = default;
 
 		fn on_initialize(now: T::BlockNumber) -> Weight {
+			let mut weight = Self::note_author_vrf(now);
+
+			// the time of the parent block, the time of the current one is not set yet
+			if Self::is_beacon_height(now) {
+				<Self as Store>::ParentTime::put(T::UnixTime::now().as_millis() as u64);
+				weight = weight.saturating_add(T::DbWeight::get().writes(1));
+			}
+
 			if now == T::RandomnessVerifierReady::get() {
-				assert!(!Verifier::exists());
-				assert!(Self::set_master_key());
-				Self::deposit_event(RawEvent::KeyInitialized(now));
+				let initialized = Verifier::exists();
+				if Self::set_master_key() {
+					if initialized {
+						Self::deposit_event(RawEvent::KeyRotated(now));
+					} else {
+						Self::deposit_event(RawEvent::KeyInitialized(now));
+					}
+				} else {
+					debug::info!("No new randomness verifier available at block {:?}.", now);
+				}
 			}
 
-			0
+			weight
 		}
 
-		#[weight = 10_000_000]
+		// the hooks of the requests of the round are called as well
+		#[weight = 10_000_000 * (1 + T::MaxRequestsPerRound::get() as Weight)]
 		fn set_randomness(origin, randomness: Randomness<T::Hash>)  {
 			ensure_none(origin)?;
 
 			let now = <frame_system::Module<T>>::block_number();
-			assert!(now > T::StartHeight::get(), "Randomness beacon starts at height > {:?} but now is {:?}",
-				T::StartHeight::get(),
-				now
-			);
-			assert!((now -T::StartHeight::get())% T::RandomnessPeriod::get() == 0.into(),
-				"Wrong block number for randomness inclusion: {:?}", now);
+			ensure!(now > Self::start_beacon_height(), Error::<T>::BeaconNotStarted);
+			let round = Self::round_at(now).ok_or(Error::<T>::NotBeaconHeight)?;
 
 			let last_update = <Self as Store>::LastUpdate::try_get();
-			assert!(last_update != Ok(now), "Randomness must be set only once in the block");
+			ensure!(last_update != Ok(now), Error::<T>::SeedAlreadySet);
 
-
-			let expected_nonce = <frame_system::Module<T>>::block_hash(now - T::RandomnessPeriod::get());
-			assert!(randomness.nonce() == expected_nonce,"Wrong nonce in set_randomness, expected: {:?}, got {:?}.",
+			let expected_nonce = Self::chained_nonce(round).unwrap_or_else(|| {
+				<frame_system::Module<T>>::block_hash(now - Self::beacon_period())
+			});
+			// the block is invalid, otherwise the author could replace a seed it does not like
+			// with an invalid one
+			assert!(randomness.nonce() == expected_nonce, "Wrong nonce in set_randomness, expected: {:?}, got {:?}.",
 				expected_nonce,
 				randomness.nonce()
 			);
-
+			assert!(randomness.version() == T::SignatureVersion::get(), "Wrong signature version in set_randomness, expected: {:?}, got {:?}.",
+				T::SignatureVersion::get(),
+				randomness.version()
+			);
 			assert!(Self::verifier().verify(&randomness), "Randomness verification failed in set_randomness at block {:?}.", now);
 
-			<Self as Store>::Seed::put(randomness);
+			<Self as Store>::Seed::put(randomness.clone());
 			<Self as Store>::LastUpdate::put(now);
+			<Self as Store>::MissedBeacons::kill();
+			Self::seal_round(round, randomness);
 
 			Self::deposit_event(RawEvent::SeedSet(now));
 		}
 
 		fn on_finalize(bn: T::BlockNumber) {
-			if bn > T::StartHeight::get() {
-				if (bn - T::StartHeight::get()) % T::RandomnessPeriod::get() == 0.into() {
-					let last_update = <Self as Store>::LastUpdate::try_get();
-					assert!(last_update == Ok(bn), "Randomness not set in block {:?}",
-						bn
-					);
-				}
+			<Self as Store>::AuthorVrfRandomness::kill();
+
+			let round = match Self::round_at(bn) {
+				Some(round) => round,
+				None => return,
+			};
+			let parent_time = Duration::from_millis(<Self as Store>::ParentTime::take());
+			if <Self as Store>::LastUpdate::try_get() != Ok(bn) {
+				let waited = T::UnixTime::now().checked_sub(parent_time).unwrap_or_default();
+				assert!(waited >= T::GracePeriod::get(), "Randomness not set in block {:?} within {:?} of its parent.",
+					bn,
+					T::GracePeriod::get()
+				);
+				debug::warn!("Randomness not set in block {:?}, the previous seed stays in use.", bn);
+				<Self as Store>::MissedBeacons::mutate(|missed| *missed = missed.saturating_add(1));
+				Self::postpone_requests(round);
+				Self::deposit_event(RawEvent::BeaconMissed(bn));
 			}
 		}
 	}
@@ -138,11 +301,159 @@ decl_module! {
 
 impl<T: Trait> Module<T> {
 	pub fn start_beacon_height() -> T::BlockNumber {
-		T::StartHeight::get()
+		<Schedule<T>>::get().map_or_else(T::StartHeight::get, |(start, _)| start)
 	}
 
 	pub fn beacon_period() -> T::BlockNumber {
-		T::RandomnessPeriod::get()
+		<Schedule<T>>::get().map_or_else(T::RandomnessPeriod::get, |(_, period)| period)
+	}
+
+	pub fn signature_version() -> SignatureVersion {
+		T::SignatureVersion::get()
+	}
+
+	/// The grace period in milliseconds.
+	pub fn grace_period() -> u64 {
+		T::GracePeriod::get().as_millis() as u64
+	}
+
+	/// The latest seed and the block at which it was set.
+	pub fn latest_randomness() -> Option<(T::BlockNumber, Randomness<T::Hash>)> {
+		match <Self as Store>::LastUpdate::try_get() {
+			Ok(bn) => Some((bn, <Self as Store>::Seed::get())),
+			Err(_) => None,
+		}
+	}
+
+	/// How fresh the current seed is, `None` if no seed was set yet.
+	pub fn seed_freshness() -> Option<SeedFreshness<T::BlockNumber>> {
+		let set_at = <Self as Store>::LastUpdate::try_get().ok()?;
+		let now = <frame_system::Module<T>>::block_number();
+		Some(SeedFreshness {
+			set_at,
+			age: now - set_at,
+			missed: <Self as Store>::MissedBeacons::get(),
+		})
+	}
+
+	/// The seed of the round, or of the latest sealed round if `None`, together with the round.
+	/// `None` if the seed is not stored.
+	pub fn round_randomness(round: Option<Round>) -> Option<(Round, Randomness<T::Hash>)> {
+		let round = round.or_else(Self::latest_round)?;
+		match <Self as Store>::History::get(Self::history_index(round)) {
+			Some((stored, randomness)) if stored == round => Some((round, randomness)),
+			_ => None,
+		}
+	}
+
+	/// The random value of the subject in the round, `None` if the seed of the round is not
+	/// stored.
+	pub fn random_at(round: Round, subject: &[u8]) -> Option<T::Hash> {
+		Self::round_randomness(Some(round)).map(|(_, randomness)| Self::random_with(subject, &randomness))
+	}
+
+	/// The round whose seed is due at the given block, if any.
+	pub fn round_at(bn: T::BlockNumber) -> Option<Round> {
+		if Self::is_beacon_height(bn) {
+			Some(UniqueSaturatedInto::<Round>::unique_saturated_into(
+				(bn - Self::start_beacon_height()) / Self::beacon_period(),
+			))
+		} else {
+			None
+		}
+	}
+
+	/// The nonce of the round in the chained mode, chained to the latest seed. `None` if the
+	/// nonces are the hashes of blocks.
+	pub fn chained_nonce(round: Round) -> Option<T::Hash> {
+		if !T::ChainedRounds::get() {
+			return None;
+		}
+		let previous = <Self as Store>::Seed::try_get().ok();
+		Some(sp_randomness_beacon::chained_nonce::<T::Hashing>(
+			round,
+			previous.as_ref().map(|seed| seed.signature()),
+		))
+	}
+
+	/// Whether a seed is due at the given block.
+	fn is_beacon_height(bn: T::BlockNumber) -> bool {
+		let start = Self::start_beacon_height();
+		bn > start && (bn - start) % Self::beacon_period() == 0.into()
+	}
+
+	// moves the requests of the round to the next rounds, each of them keeps at most
+	// MaxRequestsPerRound requests, so that the weight of set_randomness holds
+	fn postpone_requests(round: Round) {
+		let mut postponed = <Self as Store>::Requests::take(round);
+		let max_requests = T::MaxRequestsPerRound::get().max(1) as usize;
+		let mut next = round;
+		while !postponed.is_empty() {
+			next += 1;
+			<Self as Store>::Requests::mutate(next, |requests| {
+				let free = max_requests.saturating_sub(requests.len()).min(postponed.len());
+				requests.extend(postponed.drain(..free));
+			});
+		}
+	}
+
+	/// The index of `History` under which the seed of the round is kept.
+	pub fn history_index(round: Round) -> u32 {
+		(round % T::HistoryDepth::get().max(1) as Round) as u32
+	}
+
+	// stores the seed of the round and serves the requests for its randomness
+	fn seal_round(round: Round, randomness: Randomness<T::Hash>) {
+		<Self as Store>::History::insert(Self::history_index(round), (round, randomness.clone()));
+		<Self as Store>::LatestRound::put(round);
+		for (id, subject) in <Self as Store>::Requests::take(round) {
+			let random = Self::random_with(&subject, &randomness);
+			T::OnRandomnessSealed::on_randomness_sealed(id, round, &random);
+		}
+	}
+
+	fn random_with(subject: &[u8], randomness: &Randomness<T::Hash>) -> T::Hash {
+		let seed = randomness.encode();
+		let mut subject = subject.to_vec();
+		subject.reserve(seed.len());
+		subject.extend_from_slice(&seed);
+
+		<T as frame_system::Trait>::Hashing::hash(&subject[..])
+	}
+
+	/// The key of the author of a block with the given pre-runtime digests, see `T::FindAuthor`.
+	pub fn author_vrf_key(pre_digests: Vec<(ConsensusEngineId, Vec<u8>)>) -> Option<Vec<u8>> {
+		T::FindAuthor::find_author(pre_digests.iter().map(|(id, data)| (*id, &data[..])))
+			.map(|author| author.as_ref().to_vec())
+	}
+
+	// verifies the VRF output of the author of the block, it is evaluated on the current seed
+	fn note_author_vrf(now: T::BlockNumber) -> Weight {
+		let digest = <frame_system::Module<T>>::digest();
+		let pre_runtime_digests = digest.logs.iter().filter_map(|log| log.as_pre_runtime());
+		let pre_digest = vrf::PreDigest::find(pre_runtime_digests.clone());
+		let author = T::FindAuthor::find_author(pre_runtime_digests);
+		let (pre_digest, author) = match (pre_digest, author) {
+			(Some(pre_digest), Some(author)) => (pre_digest, author),
+			(Some(_), None) => {
+				debug::warn!("No author of block {:?} to verify its VRF output with.", now);
+				return AUTHOR_VRF_WEIGHT;
+			}
+			// otherwise the author could leave out an output it does not like
+			(None, Some(_)) => panic!("No VRF output of the author of block {:?}.", now),
+			(None, None) => return 0,
+		};
+
+		let seed = <Self as Store>::Seed::try_get().ok();
+		let seed = vrf::seed_input(seed.as_ref());
+		let number = UniqueSaturatedInto::<u64>::unique_saturated_into(now);
+		match vrf::verify(&pre_digest, author.as_ref(), &seed, number) {
+			Some(randomness) => <Self as Store>::AuthorVrfRandomness::put(randomness),
+			// otherwise the author could choose the randomness of its block
+			None => panic!("Invalid VRF output of the author of block {:?}.", now),
+		}
+
+		AUTHOR_VRF_WEIGHT
 	}
 
 	fn set_master_key() -> bool {
@@ -155,21 +466,16 @@ impl<T: Trait> Module<T> {
 	}
 }
 
-/// Extracts the randomness seed for the current block from inherent data.
-fn extract_random_bytes<T: Trait>(inherent_data: &InherentData) -> Randomness<T::Hash> {
-	let randomness: Result<Option<Randomness<T::Hash>>, _> =
-		inherent_data.get_data(&INHERENT_IDENTIFIER);
-	assert!(
-		randomness.is_ok(),
-		"Panic because of error in retrieving inherent_data with err {:?}.",
-		randomness.err().unwrap()
-	);
-	let randomness = randomness.unwrap();
-	assert!(
-		randomness.is_some(),
-		"Panic because no random_bytes found in inherent_data."
-	);
-	randomness.unwrap()
+/// Extracts the randomness seed for the current block from inherent data, `None` if the
+/// proposer gave up waiting for it.
+fn extract_random_bytes<T: Trait>(inherent_data: &InherentData) -> Option<Randomness<T::Hash>> {
+	match inherent_data.get_data::<Randomness<T::Hash>>(&INHERENT_IDENTIFIER) {
+		Ok(randomness) => randomness,
+		Err(e) => {
+			debug::warn!("Error in retrieving the randomness from inherent data: {:?}.", e);
+			None
+		}
+	}
 }
 
 impl<T: Trait> ProvideInherent for Module<T> {
@@ -181,36 +487,65 @@ impl<T: Trait> ProvideInherent for Module<T> {
 	/// for the current block. This seed is provided to the pallet via inherent data.
 	fn create_inherent(data: &InherentData) -> Option<Self::Call> {
 		let now = <frame_system::Module<T>>::block_number();
-		if now > T::StartHeight::get() {
-			if (now - T::StartHeight::get()) % T::RandomnessPeriod::get() == 0.into() {
+		if !Self::is_beacon_height(now) {
+			return None;
+		}
+		match extract_random_bytes::<T>(data) {
+			Some(randomness) => {
 				debug::info!("Including randomness in the block nr {:?} that we author.", now);
-				return Some(Self::Call::set_randomness(extract_random_bytes::<T>(data)));
+				Some(Self::Call::set_randomness(randomness))
+			}
+			None => {
+				debug::warn!("No randomness available for the block nr {:?} that we author.", now);
+				None
 			}
 		}
-		None
 	}
 
 	/// Checks whether the inherent corresponding to the randomness beacon contains
-	/// a correct randomness seed for the current block.
-	fn check_inherent(call: &Self::Call, _: &InherentData) -> result::Result<(), Self::Error> {
-		let now = <frame_system::Module<T>>::block_number();
+	/// a correct randomness seed for the checked block.
+	///
+	/// Inherents are checked in the state of the parent block, which knows neither the number
+	/// of the checked block nor the hash of its parent. Both come from the `CheckContext` in
+	/// the inherent data, without it only the signature of the seed is checked.
+	fn check_inherent(call: &Self::Call, data: &InherentData) -> result::Result<(), Self::Error> {
+		let randomness = match call {
+			Call::set_randomness(ref random_bytes) => random_bytes,
+			_ => return Ok(()),
+		};
 
-		if now <= T::StartHeight::get() {
-			return Ok(());
-		}
-		if (now - T::StartHeight::get()) % T::RandomnessPeriod::get() == 0.into() {
-			return Ok(());
+		let context = data
+			.get_data::<CheckContext<T::BlockNumber, T::Hash>>(&CHECK_CONTEXT_IDENTIFIER)
+			.ok()
+			.flatten();
+		if let Some(ref context) = context {
+			if !Self::is_beacon_height(context.number) {
+				return Err(InherentError::WrongHeight);
+			}
+			// in the chained mode the nonce is known to the runtime
+			let nonce = match Self::round_at(context.number).and_then(Self::chained_nonce) {
+				Some(nonce) => Some(nonce),
+				None => context.nonce,
+			};
+			if nonce != Some(randomness.nonce()) {
+				return Err(InherentError::WrongNonce);
+			}
 		}
 
-		if !Verifier::exists() {
-			return Err(sp_randomness_beacon::inherents::InherentError::VerifyKeyNotSet);
+		if randomness.version() != T::SignatureVersion::get() {
+			return Err(InherentError::WrongSignatureVersion);
 		}
-		let randomness = match call {
-			Call::set_randomness(ref random_bytes) => random_bytes.clone(),
-			_ => return Ok(()),
+
+		// the verifier is replaced at the beginning of the block it becomes ready at
+		let verifier = match context {
+			Some(ref context) if context.number == T::RandomnessVerifierReady::get() => {
+				T::RandomnessVerifier::get().or_else(|| Verifier::try_get().ok())
+			}
+			_ => Verifier::try_get().ok(),
 		};
-		if !Self::verifier().verify(&randomness) {
-			return Err(sp_randomness_beacon::inherents::InherentError::InvalidRandomBytes);
+		let verifier = verifier.ok_or(InherentError::VerifyKeyNotSet)?;
+		if !verifier.verify(randomness) {
+			return Err(InherentError::InvalidRandomBytes);
 		}
 
 		Ok(())
@@ -220,18 +555,75 @@ impl<T: Trait> ProvideInherent for Module<T> {
 impl<T: Trait> RandomnessT<T::Hash> for Module<T> {
 	fn random(subject: &[u8]) -> T::Hash {
 		if <Self as Store>::Seed::exists() {
-			let seed = &<Self as Store>::Seed::get().encode()[..];
-			let mut subject = subject.to_vec();
-			subject.reserve(seed.len());
-			subject.extend_from_slice(seed);
-
-			return <T as frame_system::Trait>::Hashing::hash(&subject[..]);
+			return Self::random_with(subject, &<Self as Store>::Seed::get());
 		}
 
 		T::Hash::default()
 	}
 }
 
+/// Randomness of the current block, i.e. the beacon seed mixed with the VRF output of the
+/// author of the block. In blocks without a VRF output it is equal to the randomness of the
+/// seed given by `Module`.
+///
+/// The VRF output is unique for the key of the author and the block, and nobody but the author
+/// knows it before the block is published. The author can still choose to publish the block
+/// without the output or not at all, so it chooses among at most two values of the randomness
+/// of its block, and `k` colluding authors of consecutive blocks among at most `2^k` values of
+/// the last one. Since the author learns the value first, it is not suitable for deciding
+/// anything the author may profit from, for which `RandomnessRequest` should be used instead.
+pub struct BlockRandomness<T>(PhantomData<T>);
+
+impl<T: Trait> RandomnessT<T::Hash> for BlockRandomness<T> {
+	fn random(subject: &[u8]) -> T::Hash {
+		let vrf_randomness = match <Module<T> as Store>::AuthorVrfRandomness::get() {
+			Some(vrf_randomness) => vrf_randomness,
+			None => return Module::<T>::random(subject),
+		};
+
+		let seed = <Module<T> as Store>::Seed::try_get().ok();
+		let mut subject = subject.to_vec();
+		subject.extend(vrf::seed_input(seed.as_ref()));
+		subject.extend_from_slice(&vrf_randomness);
+
+		<T as frame_system::Trait>::Hashing::hash(&subject[..])
+	}
+}
+
+impl<T: Trait> RandomnessRequest for Module<T> {
+	fn earliest_round() -> Round {
+		// the nonce of round `r` is the block `period` blocks before it, which must not be
+		// known yet, i.e. `start + r * period >= now + period`, in the chained mode it is chained
+		// to the seed set in that block
+		let now = <frame_system::Module<T>>::block_number();
+		let period = Self::beacon_period();
+		let distance = now.saturating_add(period).saturating_sub(Self::start_beacon_height());
+		let round = UniqueSaturatedInto::<Round>::unique_saturated_into(
+			(distance + period - 1.into()) / period,
+		);
+		round.max(1)
+	}
+
+	fn request(round: Round, subject: Vec<u8>) -> Result<RequestId, DispatchError> {
+		ensure!(round >= Self::earliest_round(), Error::<T>::RoundTooEarly);
+		let mut requests = <Self as Store>::Requests::get(round);
+		ensure!(
+			(requests.len() as u32) < T::MaxRequestsPerRound::get(),
+			Error::<T>::TooManyRequests
+		);
+
+		let id = <Self as Store>::NextRequestId::mutate(|next| {
+			let id = *next;
+			*next = next.wrapping_add(1);
+			id
+		});
+		requests.push((id, subject));
+		<Self as Store>::Requests::insert(round, requests);
+
+		Ok(id)
+	}
+}
+
 #[cfg(feature = "runtime-benchmarks")]
 mod benchmarking {
 	use super::*;
@@ -249,15 +641,19 @@ mod benchmarking {
 		let mvk = VerifyKey::from_raw_secret(secret);
 		let vks = vec![mvk.clone()];
 		let threshold = 1;
-		let start = T::StartHeight::get();
-		let period = T::RandomnessPeriod::get();
+		let start = RandomnessBeacon::<T>::start_beacon_height();
+		let period = RandomnessBeacon::<T>::beacon_period();
 		let now = start + period;
 		frame_system::Module::<T>::set_block_number(now);
-		let rbbox = RBBox::new(ix, Some(secret), vks, mvk.clone(), threshold);
+		let rbbox = RBBox::new(ix, Some(secret), vks, mvk.clone(), threshold, T::SignatureVersion::get());
 		let rv = RandomnessVerifier::new(mvk);
 		<RandomnessBeacon<T> as Store>::Verifier::put(rv);
 
-		let shares = vec![rbbox.generate_randomness_share(Default::default()).unwrap()];
+		// the hash of the block before the start of the chain is the default one
+		let nonce = RandomnessBeacon::<T>::round_at(now)
+			.and_then(RandomnessBeacon::<T>::chained_nonce)
+			.unwrap_or_default();
+		let shares = vec![rbbox.generate_randomness_share(nonce).unwrap()];
 
 		rbbox.combine_shares(&shares)
 	}
@@ -314,10 +710,11 @@ mod benchmarking {
 mod tests {
 	use super::*;
 	use frame_support::traits::{Get, OnFinalize, OnInitialize};
-	use frame_support::{assert_ok, impl_outer_origin, parameter_types, weights::Weight};
-	use sp_core::H256;
+	use frame_support::{assert_noop, assert_ok, impl_outer_origin, parameter_types, weights::Weight};
+	use sp_core::{crypto::KeyTypeId, sr25519, H256};
 	use sp_dkg::ShareProvider;
 	use sp_io::TestExternalities;
+	use sp_keystore::{testing::KeyStore, SyncCryptoStore};
 	use sp_runtime::{
 		testing::Header,
 		traits::{BlakeTwo256, IdentityLookup},
@@ -325,6 +722,13 @@ mod tests {
 	};
 
 	pub fn new_test_ext() -> TestExternalities {
+		// the tests may run on the same thread
+		SEALED.with(|sealed| sealed.borrow_mut().clear());
+		AUTHOR.with(|author| *author.borrow_mut() = None);
+		VERIFIER_READY.with(|ready| ready.set(2));
+		CHAINED.with(|chained| chained.set(false));
+		NOW.with(|now| now.set(0));
+		GRACE_PERIOD.with(|grace_period| grace_period.set(0));
 		let t = frame_system::GenesisConfig::default()
 			.build_storage::<Test>()
 			.unwrap();
@@ -374,9 +778,107 @@ mod tests {
 	}
 
 	parameter_types! {
-		pub const RandomnessVerifierReady: <Test as frame_system::Trait>::BlockNumber = 2;
 		pub const StartHeight: <Test as frame_system::Trait>::BlockNumber = 2;
 		pub const RandomnessPeriod: <Test as frame_system::Trait>::BlockNumber = 1;
+		pub const BeaconSignatureVersion: SignatureVersion = SignatureVersion::V0;
+		pub const HistoryDepth: u32 = 3;
+		pub const MaxRequestsPerRound: u32 = 2;
+	}
+
+	thread_local! {
+		static SEALED: std::cell::RefCell<Vec<(RequestId, Round, H256)>> = Default::default();
+	}
+
+	// records the requests served
+	pub struct RecordSealed;
+	impl OnRandomnessSealed<H256> for RecordSealed {
+		fn on_randomness_sealed(request: RequestId, round: Round, random: &H256) {
+			SEALED.with(|sealed| sealed.borrow_mut().push((request, round, *random)));
+		}
+	}
+
+	fn sealed() -> Vec<(RequestId, Round, H256)> {
+		SEALED.with(|sealed| sealed.borrow_mut().drain(..).collect())
+	}
+
+	thread_local! {
+		static AUTHOR: std::cell::RefCell<Option<sr25519::Public>> = Default::default();
+	}
+
+	// the author of every block is the one set with `set_author`
+	pub struct TestAuthor;
+	impl FindAuthor<sr25519::Public> for TestAuthor {
+		fn find_author<'a, I>(_digests: I) -> Option<sr25519::Public>
+		where
+			I: 'a + IntoIterator<Item = (ConsensusEngineId, &'a [u8])>,
+		{
+			AUTHOR.with(|author| author.borrow().clone())
+		}
+	}
+
+	fn set_author(author: sr25519::Public) {
+		AUTHOR.with(|current| *current.borrow_mut() = Some(author));
+	}
+
+	thread_local! {
+		static CHAINED: std::cell::Cell<bool> = std::cell::Cell::new(false);
+	}
+
+	// the nonces are the hashes of blocks unless the test calls `set_chained`
+	pub struct ChainedRounds;
+	impl Get<bool> for ChainedRounds {
+		fn get() -> bool {
+			CHAINED.with(|chained| chained.get())
+		}
+	}
+
+	fn set_chained() {
+		CHAINED.with(|chained| chained.set(true));
+	}
+
+	thread_local! {
+		static VERIFIER_READY: std::cell::Cell<u64> = std::cell::Cell::new(2);
+	}
+
+	// the verifier is ready at block 2 unless the test calls `set_verifier_ready`
+	pub struct RandomnessVerifierReady;
+	impl Get<u64> for RandomnessVerifierReady {
+		fn get() -> u64 {
+			VERIFIER_READY.with(|ready| ready.get())
+		}
+	}
+
+	fn set_verifier_ready(bn: u64) {
+		VERIFIER_READY.with(|ready| ready.set(bn));
+	}
+
+	thread_local! {
+		static NOW: std::cell::Cell<u64> = std::cell::Cell::new(0);
+		static GRACE_PERIOD: std::cell::Cell<u64> = std::cell::Cell::new(0);
+	}
+
+	// the time in milliseconds is the one set with `set_now`
+	pub struct TestTime;
+	impl UnixTime for TestTime {
+		fn now() -> Duration {
+			Duration::from_millis(NOW.with(|now| now.get()))
+		}
+	}
+
+	fn set_now(ms: u64) {
+		NOW.with(|now| now.set(ms));
+	}
+
+	// beacon blocks may be missed at once unless the test calls `set_grace_period`
+	pub struct GracePeriod;
+	impl Get<Duration> for GracePeriod {
+		fn get() -> Duration {
+			Duration::from_millis(GRACE_PERIOD.with(|grace_period| grace_period.get()))
+		}
+	}
+
+	fn set_grace_period(ms: u64) {
+		GRACE_PERIOD.with(|grace_period| grace_period.set(ms));
 	}
 
 	pub struct GetRandomnessVerifier;
@@ -390,6 +892,15 @@ mod tests {
 		type RandomnessVerifier = GetRandomnessVerifier;
 		type RandomnessVerifierReady = RandomnessVerifierReady;
 		type RandomnessPeriod = RandomnessPeriod;
+		type SignatureVersion = BeaconSignatureVersion;
+		type HistoryDepth = HistoryDepth;
+		type MaxRequestsPerRound = MaxRequestsPerRound;
+		type OnRandomnessSealed = RecordSealed;
+		type AuthorVrfKey = sr25519::Public;
+		type FindAuthor = TestAuthor;
+		type ChainedRounds = ChainedRounds;
+		type UnixTime = TestTime;
+		type GracePeriod = GracePeriod;
 		type Event = ();
 	}
 
@@ -400,10 +911,32 @@ mod tests {
 		new_test_ext().execute_with(|| {
 			assert_eq!(RBeacon::on_initialize(0), 0);
 			System::set_block_number(3);
+			assert_eq!(RBeacon::latest_randomness(), None);
 			assert_ok!(RBeacon::set_randomness(
 				Origin::none(),
 				Randomness::default()
 			));
+			assert_eq!(RBeacon::latest_randomness(), Some((3, Randomness::default())));
+		});
+	}
+
+	#[test]
+	fn genesis_schedule_overrides_the_constants() {
+		new_test_ext().execute_with(|| {
+			assert_eq!((RBeacon::start_beacon_height(), RBeacon::beacon_period()), (2, 1));
+		});
+		let mut t = frame_system::GenesisConfig::default()
+			.build_storage::<Test>()
+			.unwrap();
+		GenesisConfig::<Test> { schedule: Some((5, 3)) }
+			.assimilate_storage(&mut t)
+			.unwrap();
+		TestExternalities::new(t).execute_with(|| {
+			assert_eq!((RBeacon::start_beacon_height(), RBeacon::beacon_period()), (5, 3));
+			assert_eq!(RBeacon::round_at(5), None);
+			assert_eq!(RBeacon::round_at(7), None);
+			assert_eq!(RBeacon::round_at(8), Some(1));
+			assert_eq!(RBeacon::round_at(11), Some(2));
 		});
 	}
 
@@ -414,14 +947,47 @@ mod tests {
 			assert_eq!(RBeacon::on_initialize(0), 0);
 			System::set_block_number(3);
 			let share_provider = ShareProvider::from_raw_secret(1, [1, 7, 2, 9]);
-			let signature = share_provider.sign(&H256::default().encode());
+			let signature = share_provider.sign(&H256::default().encode(), SignatureVersion::V0);
+			let randomness = Randomness::<H256>::new(Default::default(), signature);
+			let _ = RBeacon::set_randomness(Origin::none(), randomness);
+		});
+	}
+
+	#[test]
+	#[should_panic(expected = "Wrong signature version in set_randomness, expected: V0, got V1.")]
+	fn randomness_beacon_rejects_wrong_signature_version() {
+		new_test_ext().execute_with(|| {
+			let share_provider = ShareProvider::from_raw_secret(0, [1, 7, 2, 9]);
+			<RBeacon as Store>::Verifier::put(RandomnessVerifier::new(share_provider.verify_key()));
+			System::set_block_number(3);
+			let signature = share_provider.sign(&H256::default().encode(), SignatureVersion::V1);
 			let randomness = Randomness::<H256>::new(Default::default(), signature);
-			assert_ok!(RBeacon::set_randomness(Origin::none(), randomness));
+			let _ = RBeacon::set_randomness(Origin::none(), randomness);
+		});
+	}
+
+	#[test]
+	fn randomness_beacon_rejects_randomness_out_of_schedule() {
+		new_test_ext().execute_with(|| {
+			System::set_block_number(2);
+			assert_noop!(
+				RBeacon::set_randomness(Origin::none(), Randomness::default()),
+				Error::<Test>::BeaconNotStarted
+			);
+		});
+	}
+
+	#[test]
+	#[should_panic(expected = "Wrong nonce in set_randomness")]
+	fn randomness_beacon_rejects_wrong_nonce() {
+		new_test_ext().execute_with(|| {
+			System::set_block_number(3);
+			let randomness = Randomness::new(H256::repeat_byte(1), Default::default());
+			let _ = RBeacon::set_randomness(Origin::none(), randomness);
 		});
 	}
 
 	#[test]
-	#[should_panic(expected = "Randomness must be set only once in the block")]
 	fn double_randomness_should_fail() {
 		new_test_ext().execute_with(|| {
 			assert_eq!(RBeacon::on_initialize(0), 0);
@@ -430,7 +996,10 @@ mod tests {
 				Origin::none(),
 				Randomness::default()
 			));
-			let _ = RBeacon::set_randomness(Origin::none(), Randomness::default());
+			assert_noop!(
+				RBeacon::set_randomness(Origin::none(), Randomness::default()),
+				Error::<Test>::SeedAlreadySet
+			);
 		});
 	}
 
@@ -443,13 +1012,389 @@ mod tests {
 	}
 
 	#[test]
-	#[should_panic(expected = "Randomness not set in block 5")]
-	fn no_randomness_should_fail() {
+	fn missing_randomness_keeps_previous_seed() {
+		new_test_ext().execute_with(|| {
+			set_grace_period(10_000);
+			System::set_block_number(3);
+			assert_ok!(RBeacon::set_randomness(
+				Origin::none(),
+				Randomness::default()
+			));
+			RBeacon::on_finalize(3);
+			assert_eq!(RBeacon::seed_freshness(), Some(SeedFreshness { set_at: 3, age: 0, missed: 0 }));
+
+			// the proposers of blocks 4 and 5 gave up waiting for the seed
+			for bn in 4..6 {
+				System::set_block_number(bn);
+				RBeacon::on_initialize(bn);
+				set_now(bn * 10_000);
+				assert_eq!(RBeacon::create_inherent(&InherentData::new()), None);
+				RBeacon::on_finalize(bn);
+			}
+			assert_eq!(RBeacon::latest_randomness(), Some((3, Randomness::default())));
+			assert_eq!(RBeacon::seed_freshness(), Some(SeedFreshness { set_at: 3, age: 2, missed: 2 }));
+
+			System::set_block_number(6);
+			assert_ok!(RBeacon::set_randomness(
+				Origin::none(),
+				Randomness::default()
+			));
+			assert_eq!(RBeacon::seed_freshness(), Some(SeedFreshness { set_at: 6, age: 0, missed: 0 }));
+		});
+	}
+
+	#[test]
+	#[should_panic(expected = "Randomness not set in block 4 within 10s of its parent.")]
+	fn missing_randomness_fails_within_grace_period() {
+		new_test_ext().execute_with(|| {
+			set_grace_period(10_000);
+			set_now(30_000);
+			System::set_block_number(4);
+			RBeacon::on_initialize(4);
+			set_now(39_999);
+			RBeacon::on_finalize(4);
+		});
+	}
+
+	fn check(
+		randomness: Randomness<H256>,
+		context: Option<CheckContext<u64, H256>>,
+	) -> Result<(), InherentError> {
+		let mut data = InherentData::new();
+		if let Some(context) = context {
+			data.put_data(CHECK_CONTEXT_IDENTIFIER, &context).unwrap();
+		}
+		RBeacon::check_inherent(&Call::set_randomness(randomness), &data)
+	}
+
+	fn context(number: u64, nonce: H256) -> Option<CheckContext<u64, H256>> {
+		Some(CheckContext { number, nonce: Some(nonce) })
+	}
+
+	#[test]
+	fn check_inherent_accepts_correct_randomness() {
+		new_test_ext().execute_with(|| {
+			let share_provider = ShareProvider::from_raw_secret(0, [1, 7, 2, 9]);
+			<RBeacon as Store>::Verifier::put(RandomnessVerifier::new(share_provider.verify_key()));
+			let nonce = H256::repeat_byte(4);
+			let signature = share_provider.sign(&nonce.encode(), SignatureVersion::V0);
+			let randomness = Randomness::new(nonce, signature);
+
+			assert_ok!(check(randomness.clone(), context(5, nonce)));
+			// only the signature is checked without the context
+			assert_ok!(check(randomness, None));
+		});
+	}
+
+	#[test]
+	fn check_inherent_rejects_malicious_randomness() {
+		new_test_ext().execute_with(|| {
+			let share_provider = ShareProvider::from_raw_secret(0, [1, 7, 2, 9]);
+			let nonce = H256::repeat_byte(4);
+			let signature = share_provider.sign(&nonce.encode(), SignatureVersion::V0);
+			let randomness = Randomness::new(nonce, signature);
+
+			// the key is not known yet
+			assert_eq!(check(randomness.clone(), context(5, nonce)), Err(InherentError::VerifyKeyNotSet));
+			<RBeacon as Store>::Verifier::put(RandomnessVerifier::new(share_provider.verify_key()));
+
+			// a seed in a block before the start of the beacon
+			assert_eq!(check(randomness.clone(), context(2, nonce)), Err(InherentError::WrongHeight));
+			// a correct signature of a different block
+			assert_eq!(
+				check(randomness.clone(), context(5, H256::repeat_byte(3))),
+				Err(InherentError::WrongNonce)
+			);
+			// a seed in a block at which no seed is due
+			assert_eq!(
+				check(randomness, Some(CheckContext { number: 5, nonce: None })),
+				Err(InherentError::WrongNonce)
+			);
+
+			let signature = share_provider.sign(&nonce.encode(), SignatureVersion::V1);
+			assert_eq!(
+				check(Randomness::new(nonce, signature), context(5, nonce)),
+				Err(InherentError::WrongSignatureVersion)
+			);
+
+			let other_provider = ShareProvider::from_raw_secret(0, [2, 7, 2, 9]);
+			let signature = other_provider.sign(&nonce.encode(), SignatureVersion::V0);
+			assert_eq!(
+				check(Randomness::new(nonce, signature), context(5, nonce)),
+				Err(InherentError::InvalidRandomBytes)
+			);
+		});
+	}
+
+	#[test]
+	fn check_inherent_uses_new_verifier_at_rotation() {
+		new_test_ext().execute_with(|| {
+			// the key is rotated at a beacon height, `GetRandomnessVerifier` returns the default
+			// verifier, which accepts default seeds
+			set_verifier_ready(3);
+			let share_provider = ShareProvider::from_raw_secret(0, [1, 7, 2, 9]);
+			<RBeacon as Store>::Verifier::put(RandomnessVerifier::new(share_provider.verify_key()));
+			let randomness = Randomness::<H256>::default();
+
+			assert_eq!(
+				check(randomness.clone(), context(4, Default::default())),
+				Err(InherentError::InvalidRandomBytes)
+			);
+			assert_ok!(check(randomness, context(RandomnessVerifierReady::get(), Default::default())));
+		});
+	}
+
+	#[test]
+	fn chained_rounds_accept_nonces_chained_to_latest_seed() {
+		new_test_ext().execute_with(|| {
+			set_chained();
+			let share_provider = ShareProvider::from_raw_secret(0, [1, 7, 2, 9]);
+			<RBeacon as Store>::Verifier::put(RandomnessVerifier::new(share_provider.verify_key()));
+			let sign = |nonce: H256| Randomness::new(nonce, share_provider.sign(&nonce.encode(), SignatureVersion::V0));
+
+			// the nonce of the first round is not chained to any seed
+			System::set_block_number(3);
+			let first = sign(sp_randomness_beacon::chained_nonce::<BlakeTwo256>(1, None));
+			assert_eq!(RBeacon::chained_nonce(1), Some(first.nonce()));
+			// the hash of the block is not accepted in the chained mode
+			assert_eq!(
+				check(sign(System::block_hash(2)), context(3, System::block_hash(2))),
+				Err(InherentError::WrongNonce)
+			);
+			assert_ok!(RBeacon::set_randomness(Origin::none(), first.clone()));
+			RBeacon::on_finalize(3);
+
+			// round 3 is missed, round 4 is chained to the seed of round 2
+			System::set_block_number(4);
+			let second = sign(RBeacon::chained_nonce(2).unwrap());
+			assert_ok!(RBeacon::set_randomness(Origin::none(), second.clone()));
+			RBeacon::on_finalize(4);
+			System::set_block_number(5);
+			RBeacon::on_finalize(5);
+			System::set_block_number(6);
+			let fourth = sign(RBeacon::chained_nonce(4).unwrap());
+			let third = RBeacon::chained_nonce(3).unwrap();
+			assert_eq!(check(sign(third), context(6, third)), Err(InherentError::WrongNonce));
+			assert_ok!(RBeacon::set_randomness(Origin::none(), fourth.clone()));
+
+			let chain = vec![(1, first), (2, second), (4, fourth)];
+			assert!(RBeacon::verifier().verify_chain::<BlakeTwo256>(&chain));
+		});
+	}
+
+	#[test]
+	fn check_inherent_uses_chained_nonces() {
+		new_test_ext().execute_with(|| {
+			set_chained();
+			let share_provider = ShareProvider::from_raw_secret(0, [1, 7, 2, 9]);
+			<RBeacon as Store>::Verifier::put(RandomnessVerifier::new(share_provider.verify_key()));
+			let nonce = RBeacon::chained_nonce(1).unwrap();
+			let randomness = Randomness::new(nonce, share_provider.sign(&nonce.encode(), SignatureVersion::V0));
+
+			// the nonce of the context is ignored
+			assert_ok!(check(randomness.clone(), Some(CheckContext { number: 3, nonce: None })));
+			assert_eq!(
+				check(randomness.clone(), context(4, nonce)),
+				Err(InherentError::WrongNonce)
+			);
+			let block_nonce = H256::repeat_byte(2);
+			let signature = share_provider.sign(&block_nonce.encode(), SignatureVersion::V0);
+			assert_eq!(
+				check(Randomness::new(block_nonce, signature), context(3, block_nonce)),
+				Err(InherentError::WrongNonce)
+			);
+		});
+	}
+
+	// sets the seed at the given block, the seeds are equal but for their nonces
+	fn seal(bn: u64) {
+		System::set_block_number(bn);
+		let randomness = Randomness::new(H256::repeat_byte(bn as u8 - 1), Default::default());
+		<RBeacon as Store>::Seed::put(randomness.clone());
+		<RBeacon as Store>::LastUpdate::put(bn);
+		RBeacon::seal_round(RBeacon::round_at(bn).unwrap(), randomness);
+		RBeacon::on_finalize(bn);
+	}
+
+	#[test]
+	fn keeps_history_of_latest_rounds() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(RBeacon::round_at(2), None);
+			assert_eq!(RBeacon::round_at(3), Some(1));
+			assert_eq!(RBeacon::round_randomness(None), None);
+
+			// rounds 1 to 4, the seed of round 3 is missing
+			seal(3);
+			seal(4);
+			System::set_block_number(5);
+			RBeacon::on_finalize(5);
+			seal(6);
+
+			let (round, randomness) = RBeacon::round_randomness(None).unwrap();
+			assert_eq!(round, 4);
+			assert_eq!(randomness.nonce(), H256::repeat_byte(5));
+			assert_eq!(RBeacon::round_randomness(Some(2)).unwrap().1.nonce(), H256::repeat_byte(3));
+			assert_eq!(RBeacon::round_randomness(Some(3)), None);
+			// the seed of round 1 was overwritten by the one of round 4
+			assert_eq!(RBeacon::round_randomness(Some(1)), None);
+			assert_eq!(RBeacon::random_at(1, b"subject"), None);
+
+			// the random values of the current round are equal to the ones of `random`
+			assert_eq!(RBeacon::random_at(4, b"subject"), Some(RBeacon::random(b"subject")));
+			assert_ne!(RBeacon::random_at(2, b"subject"), RBeacon::random_at(4, b"subject"));
+		});
+	}
+
+	#[test]
+	fn serves_requests_for_future_rounds() {
+		new_test_ext().execute_with(|| {
+			// the nonce of round 1 is block 2, it is not known in block 2
+			System::set_block_number(2);
+			assert_eq!(RBeacon::earliest_round(), 1);
+			System::set_block_number(3);
+			assert_eq!(RBeacon::earliest_round(), 2);
+			assert_noop!(RBeacon::request(1, b"late".to_vec()), Error::<Test>::RoundTooEarly);
+
+			assert_eq!(RBeacon::request(2, b"first".to_vec()), Ok(0));
+			assert_eq!(RBeacon::request(2, b"second".to_vec()), Ok(1));
+			assert_noop!(RBeacon::request(2, b"third".to_vec()), Error::<Test>::TooManyRequests);
+			assert_eq!(RBeacon::request(3, b"third".to_vec()), Ok(2));
+
+			seal(4);
+			let expected = vec![
+				(0, 2, RBeacon::random_at(2, b"first").unwrap()),
+				(1, 2, RBeacon::random_at(2, b"second").unwrap()),
+			];
+			assert_eq!(sealed(), expected);
+
+			// round 3 is missed, its requests are served in round 4
+			System::set_block_number(5);
+			RBeacon::on_finalize(5);
+			assert!(sealed().is_empty());
+			seal(6);
+			assert_eq!(sealed(), vec![(2, 4, RBeacon::random_at(4, b"third").unwrap())]);
+		});
+	}
+
+	#[test]
+	fn postponed_requests_spill_over_full_rounds() {
+		new_test_ext().execute_with(|| {
+			System::set_block_number(3);
+			assert_eq!(RBeacon::request(3, b"a".to_vec()), Ok(0));
+			assert_eq!(RBeacon::request(3, b"b".to_vec()), Ok(1));
+			assert_eq!(RBeacon::request(4, b"c".to_vec()), Ok(2));
+			assert_eq!(RBeacon::request(5, b"d".to_vec()), Ok(3));
+
+			// round 3 is missed, round 4 takes only one of its requests
+			System::set_block_number(5);
+			RBeacon::on_finalize(5);
+			let ids = |round| <RBeacon as Store>::Requests::get(round)
+				.into_iter()
+				.map(|(id, _)| id)
+				.collect::<Vec<_>>();
+			assert!(ids(3).is_empty());
+			assert_eq!(ids(4), vec![2, 0]);
+			assert_eq!(ids(5), vec![3, 1]);
+		});
+	}
+
+	const VRF_KEY_TYPE: KeyTypeId = KeyTypeId(*b"test");
+
+	// starts block `bn` authored by a new author with the VRF output of the author on `seed`
+	fn start_block_with_vrf(bn: u64, seed: &[u8]) -> (KeyStore, sr25519::Public) {
+		let keystore = KeyStore::new();
+		let author = keystore.sr25519_generate_new(VRF_KEY_TYPE, None).unwrap();
+		set_author(author.clone());
+		let signature = keystore
+			.sr25519_vrf_sign(VRF_KEY_TYPE, &author, vrf::make_transcript_data(seed, bn))
+			.unwrap();
+		let pre_digest = vrf::PreDigest {
+			vrf_output: vrf::VRFOutput(signature.output),
+			vrf_proof: vrf::VRFProof(signature.proof),
+		};
+		System::set_block_number(bn);
+		System::deposit_log(pre_digest.to_digest_item());
+		(keystore, author)
+	}
+
+	#[test]
+	fn mixes_author_vrf_output_into_block_randomness() {
+		new_test_ext().execute_with(|| {
+			seal(3);
+			let seed = vrf::seed_input(Some(&<RBeacon as Store>::Seed::get()));
+			start_block_with_vrf(4, &seed);
+
+			assert!(RBeacon::on_initialize(4) > 0);
+			assert!(RBeacon::author_vrf_randomness().is_some());
+			let block_random = BlockRandomness::<Test>::random(b"subject");
+			assert_ne!(block_random, RBeacon::random(b"subject"));
+			assert_ne!(block_random, BlockRandomness::<Test>::random(b"other"));
+
+			// the output is removed at the end of the block
+			RBeacon::on_finalize(4);
+			assert_eq!(RBeacon::author_vrf_randomness(), None);
+			assert_eq!(BlockRandomness::<Test>::random(b"subject"), RBeacon::random(b"subject"));
+		});
+	}
+
+	#[test]
+	fn verifies_author_vrf_output_before_first_seed() {
+		new_test_ext().execute_with(|| {
+			let (_, author) = start_block_with_vrf(1, &[]);
+			RBeacon::on_initialize(1);
+			assert!(RBeacon::author_vrf_randomness().is_some());
+			assert_ne!(BlockRandomness::<Test>::random(b"subject"), H256::default());
+			assert_eq!(
+				RBeacon::author_vrf_key(vec![(*b"aura", vec![])]),
+				Some(author.0.to_vec())
+			);
+		});
+	}
+
+	#[test]
+	#[should_panic(expected = "Invalid VRF output of the author of block 4.")]
+	fn rejects_vrf_output_on_another_seed() {
+		new_test_ext().execute_with(|| {
+			seal(3);
+			start_block_with_vrf(4, &vrf::seed_input::<H256>(None));
+			RBeacon::on_initialize(4);
+		});
+	}
+
+	#[test]
+	#[should_panic(expected = "Invalid VRF output of the author of block 4.")]
+	fn rejects_vrf_output_of_another_author() {
+		new_test_ext().execute_with(|| {
+			seal(3);
+			let seed = vrf::seed_input(Some(&<RBeacon as Store>::Seed::get()));
+			start_block_with_vrf(4, &seed);
+			let other = KeyStore::new().sr25519_generate_new(VRF_KEY_TYPE, None).unwrap();
+			set_author(other);
+			RBeacon::on_initialize(4);
+		});
+	}
+
+	#[test]
+	#[should_panic(expected = "No VRF output of the author of block 4.")]
+	fn rejects_block_without_vrf_output_of_author() {
+		new_test_ext().execute_with(|| {
+			seal(3);
+			let author = KeyStore::new().sr25519_generate_new(VRF_KEY_TYPE, None).unwrap();
+			set_author(author);
+			System::set_block_number(4);
+			RBeacon::on_initialize(4);
+		});
+	}
+
+	#[test]
+	fn no_randomness_before_first_seed() {
 		new_test_ext().execute_with(|| {
-			assert_eq!(RBeacon::on_initialize(0), 0);
 			System::set_block_number(5);
 			assert_eq!(RBeacon::on_initialize(5), 0);
-			let _ = RBeacon::on_finalize(5);
+			RBeacon::on_finalize(5);
+			assert_eq!(RBeacon::latest_randomness(), None);
+			assert_eq!(RBeacon::seed_freshness(), None);
+			assert_eq!(<RBeacon as Store>::MissedBeacons::get(), 1);
 		});
 	}
 }
@@ -0,0 +1,57 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use sp_dkg::{batch_verify, find_invalid_signatures, ShareProvider, Signature, SignatureVersion, VerifyKey};
+
+// signatures of one message by the given number of signers, as collected by the beacon
+fn signatures(n: u64) -> (Vec<u8>, Vec<VerifyKey>, Vec<Signature>) {
+	let msg = b"randomness beacon nonce".to_vec();
+	let pairs: Vec<_> = (0..n).map(ShareProvider::generate).collect();
+	let keys = pairs.iter().map(ShareProvider::verify_key).collect();
+	let signatures = pairs
+		.iter()
+		.map(|pair| pair.sign(&msg, SignatureVersion::LATEST))
+		.collect();
+	(msg, keys, signatures)
+}
+
+fn bench_share_verification(c: &mut Criterion) {
+	let mut group = c.benchmark_group("share_verification");
+	group.sample_size(10);
+
+	for n in [16u64, 64, 256].iter() {
+		let (msg, keys, mut signatures) = signatures(*n);
+		group.throughput(Throughput::Elements(*n));
+
+		group.bench_with_input(BenchmarkId::new("per_share", n), n, |b, _| {
+			b.iter(|| {
+				keys.iter()
+					.zip(signatures.iter())
+					.all(|(key, sgn)| key.verify(&msg, sgn))
+			})
+		});
+
+		let items: Vec<_> = keys
+			.iter()
+			.zip(signatures.iter())
+			.map(|(key, sgn)| (key, &msg[..], sgn))
+			.collect();
+		group.bench_with_input(BenchmarkId::new("batch", n), n, |b, _| {
+			b.iter(|| batch_verify(&items))
+		});
+
+		// one invalid share, found by bisection
+		signatures[0] = signatures[1].clone();
+		let items: Vec<_> = keys
+			.iter()
+			.zip(signatures.iter())
+			.map(|(key, sgn)| (key, &msg[..], sgn))
+			.collect();
+		group.bench_with_input(BenchmarkId::new("batch_one_invalid", n), n, |b, _| {
+			b.iter(|| find_invalid_signatures(&items))
+		});
+	}
+
+	group.finish();
+}
+
+criterion_group!(benches, bench_share_verification);
+criterion_main!(benches);
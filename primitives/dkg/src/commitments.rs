@@ -1,20 +1,20 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
-use codec::{Decode, Encode, EncodeLike, Error, Input, Output};
+use codec::{Compact, Decode, Encode, EncodeLike, Error, Input, Output};
 use sp_std::vec::Vec;
 
+use crate::host;
 use crate::threshold_signatures::VerifyKey;
 
 pub use bls12_381::Scalar;
 use bls12_381::{G1Affine, G2Affine, G2Projective};
-use pairing::PairingCurveAffine;
 
-use aes_soft::cipher::generic_array::GenericArray;
-use aes_soft::cipher::{BlockCipher, NewBlockCipher};
-use aes_soft::Aes256;
-use sha3::{Digest, Sha3_256};
+use chacha20poly1305::aead::generic_array::GenericArray;
+use chacha20poly1305::aead::{Aead, NewAead, Payload};
+use chacha20poly1305::ChaCha20Poly1305;
+use sha2::{Digest, Sha256, Sha512};
 
-use super::RawSecret;
+use super::{AuthIndex, RawSecret};
 
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct EncryptionPublicKey {
@@ -49,10 +49,12 @@ impl Decode for EncryptionPublicKey {
 		// need to check if both exponents are the same
 		let g1point = g1point.unwrap();
 		let g2point = g2point.unwrap();
-		let p1 = G1Affine::generator().pairing_with(&g2point);
-		let p2 = g1point.pairing_with(&G2Affine::generator());
+		let exponents_match = host::pairing_check(&[
+			(G1Affine::generator(), g2point),
+			(g1point, -G2Affine::generator()),
+		]);
 
-		if p1 == p2 {
+		if exponents_match {
 			Ok(EncryptionPublicKey {
 				g1point: g1point,
 				g2point: g2point,
@@ -73,15 +75,117 @@ impl EncryptionPublicKey {
 	}
 
 	pub fn from_scalar(scalar: Scalar) -> Self {
-		let g1point = G1Affine::from(G1Affine::generator() * scalar);
-		let g2point = G2Affine::from(G2Affine::generator() * scalar);
+		let g1point = host::g1_multi_exp(&[G1Affine::generator()], &[scalar]);
+		let g2point = host::g2_multi_exp(&[G2Affine::generator()], &[scalar]);
 
 		EncryptionPublicKey { g1point, g2point }
 	}
 
 	pub fn to_encryption_key(&self, secret: Scalar) -> EncryptionKey {
-		EncryptionKey(G1Affine::from(self.g1point * secret))
+		EncryptionKey(host::g1_multi_exp(&[self.g1point], &[secret]))
 	}
+
+	/// Derives the encryption key shared with the owner of this public key together with
+	/// a proof that it was derived from the secret behind `EncryptionPublicKey::from_scalar(secret)`.
+	pub fn to_encryption_key_with_proof(&self, secret: Scalar) -> (EncryptionKey, DleqProof) {
+		let key = self.to_encryption_key(secret);
+		let issuer = host::g1_multi_exp(&[G1Affine::generator()], &[secret]);
+
+		// the nonce is derived deterministically, so that no randomness is needed to prove
+		let nonce = hash_to_scalar(&[
+			DLEQ_DOMAIN,
+			b"nonce",
+			&secret.to_bytes(),
+			&self.g1point.to_compressed(),
+			&key.0.to_compressed(),
+		]);
+		let commit1 = host::g1_multi_exp(&[G1Affine::generator()], &[nonce]);
+		let commit2 = host::g1_multi_exp(&[self.g1point], &[nonce]);
+		let challenge = dleq_challenge(&issuer, &self.g1point, &key.0, &commit1, &commit2);
+		let response = nonce + challenge * secret;
+
+		(
+			key,
+			DleqProof {
+				challenge,
+				response,
+			},
+		)
+	}
+}
+
+const DLEQ_DOMAIN: &[u8] = b"DKG-DLEQ-V1";
+
+/// A Chaum-Pedersen proof that an encryption key `K` shared by the issuer with another party
+/// satisfies `log_g(X) == log_Y(K)`, where `X` and `Y` are the encryption public keys of
+/// the issuer and the other party respectively. Unlike a pairing check it requires only a few
+/// multiplications in G1 to verify.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DleqProof {
+	challenge: Scalar,
+	response: Scalar,
+}
+
+impl Encode for DleqProof {
+	fn encode_to<T: Output>(&self, dest: &mut T) {
+		let mut bytes = self.challenge.to_bytes().to_vec();
+		bytes.append(&mut self.response.to_bytes().to_vec());
+		Encode::encode_to(&bytes, dest);
+	}
+}
+
+impl Decode for DleqProof {
+	fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
+		let vec = Vec::decode(input)?;
+		if vec.len() != 64 {
+			return Err("wrong length of an encoded proof".into());
+		}
+
+		let mut bytes = [0u8; 32];
+		bytes.copy_from_slice(&vec[..32]);
+		let challenge = Scalar::from_bytes(&bytes);
+		bytes.copy_from_slice(&vec[32..]);
+		let response = Scalar::from_bytes(&bytes);
+		if challenge.is_none().unwrap_u8() == 1 || response.is_none().unwrap_u8() == 1 {
+			return Err("could not decode scalar".into());
+		}
+
+		Ok(DleqProof {
+			challenge: challenge.unwrap(),
+			response: response.unwrap(),
+		})
+	}
+}
+
+impl EncodeLike for DleqProof {}
+
+fn hash_to_scalar(parts: &[&[u8]]) -> Scalar {
+	let mut hasher = Sha512::new();
+	for part in parts {
+		hasher.update(part);
+	}
+	let mut bytes = [0u8; 64];
+	bytes.copy_from_slice(&hasher.finalize()[..]);
+
+	Scalar::from_bytes_wide(&bytes)
+}
+
+fn dleq_challenge(
+	issuer: &G1Affine,
+	other: &G1Affine,
+	key: &G1Affine,
+	commit1: &G1Affine,
+	commit2: &G1Affine,
+) -> Scalar {
+	hash_to_scalar(&[
+		DLEQ_DOMAIN,
+		&G1Affine::generator().to_compressed(),
+		&issuer.to_compressed(),
+		&other.to_compressed(),
+		&key.to_compressed(),
+		&commit1.to_compressed(),
+		&commit2.to_compressed(),
+	])
 }
 
 #[derive(Clone, Debug, Default, PartialEq)]
@@ -109,46 +213,122 @@ impl Decode for EncryptionKey {
 
 impl EncodeLike for EncryptionKey {}
 
-pub type EncryptedShare = [u8; 32];
+/// The format of an encrypted share.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode)]
+pub enum ShareCipherVersion {
+	/// ChaCha20-Poly1305 under a key derived from the encryption key, the indices of the dealer
+	/// and the recipient, and the hash of the block ending round 0.
+	V1 = 1,
+}
 
-impl EncryptionKey {
-	// It may seem as a bad design to create the cipher before every use, but we always use it only
-	// once and this design is necessary for validation
-	fn cipher(&self) -> Aes256 {
-		let key = self.encode();
-		let mut hasher = Sha3_256::new();
-		hasher.input(key);
-		let key = hasher.result();
-		let key = GenericArray::from_slice(&key[..]);
-
-		Aes256::new(&key)
-	}
-
-	pub fn encrypt(&self, scalar: &Scalar) -> EncryptedShare {
-		let cipher = self.cipher();
-		let bytes = scalar.to_bytes();
-		let mut block1 = GenericArray::clone_from_slice(&bytes[..16]);
-		let mut block2 = GenericArray::clone_from_slice(&bytes[16..32]);
-		cipher.encrypt_block(&mut block1);
-		cipher.encrypt_block(&mut block2);
+impl Default for ShareCipherVersion {
+	fn default() -> Self {
+		ShareCipherVersion::V1
+	}
+}
 
-		let mut bytes = [0u8; 32];
-		bytes[..16].copy_from_slice(block1.as_slice());
-		bytes[16..32].copy_from_slice(block2.as_slice());
+/// The length of the ciphertext of a share, a scalar followed by the authentication tag.
+pub const SHARE_CIPHERTEXT_LEN: usize = 32 + 16;
+
+/// A secret share encrypted by its dealer for a single recipient.
+#[derive(Clone, Debug, Default, PartialEq, Encode)]
+pub struct EncryptedShare {
+	version: ShareCipherVersion,
+	ciphertext: Vec<u8>,
+}
+
+// the shares come in unsigned transactions, so the length of the ciphertext is checked before
+// anything is allocated for it
+impl Decode for EncryptedShare {
+	fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
+		let version = ShareCipherVersion::decode(input)?;
+		let len = <Compact<u32>>::decode(input)?.0 as usize;
+		if len != SHARE_CIPHERTEXT_LEN {
+			return Err("wrong length of an encrypted share".into());
+		}
+		let mut ciphertext = sp_std::vec![0u8; len];
+		input.read(&mut ciphertext)?;
+
+		Ok(EncryptedShare {
+			version,
+			ciphertext,
+		})
+	}
+}
+
+impl EncryptedShare {
+	pub fn version(&self) -> ShareCipherVersion {
+		self.version
+	}
+}
+
+const SHARE_KEY_DOMAIN: &[u8] = b"DKG-SHARE-KEY-V1";
 
-		bytes
+impl EncryptionKey {
+	// Every key is used to encrypt a single share only, as it is bound to the dealer, the recipient
+	// and the DKG run, hence the constant nonce. Revealing the key in a dispute allows anyone
+	// to decrypt the share and check it against the commitments of the dealer.
+	fn cipher(
+		&self,
+		dealer: AuthIndex,
+		recipient: AuthIndex,
+		round_hash: &[u8],
+	) -> ChaCha20Poly1305 {
+		let mut hasher = Sha256::new();
+		hasher.update(SHARE_KEY_DOMAIN);
+		hasher.update(self.encode());
+		hasher.update(dealer.to_le_bytes());
+		hasher.update(recipient.to_le_bytes());
+		hasher.update(round_hash);
+		let key = hasher.finalize();
+
+		ChaCha20Poly1305::new(GenericArray::from_slice(&key[..]))
 	}
 
-	pub fn decrypt(&self, bytes: &EncryptedShare) -> Option<Scalar> {
-		let cipher = self.cipher();
-		let mut block1 = GenericArray::clone_from_slice(&bytes[..16]);
-		let mut block2 = GenericArray::clone_from_slice(&bytes[16..32]);
-		cipher.decrypt_block(&mut block1);
-		cipher.decrypt_block(&mut block2);
+	pub fn encrypt(
+		&self,
+		scalar: &Scalar,
+		dealer: AuthIndex,
+		recipient: AuthIndex,
+		round_hash: &[u8],
+	) -> EncryptedShare {
+		let version = ShareCipherVersion::V1;
+		let cipher = self.cipher(dealer, recipient, round_hash);
+		let payload = Payload {
+			msg: &scalar.to_bytes(),
+			aad: &version.encode(),
+		};
+		let ciphertext = cipher
+			.encrypt(GenericArray::from_slice(&[0u8; 12]), payload)
+			.expect("encrypting 32 bytes does not fail");
+
+		EncryptedShare {
+			version,
+			ciphertext,
+		}
+	}
+
+	pub fn decrypt(
+		&self,
+		share: &EncryptedShare,
+		dealer: AuthIndex,
+		recipient: AuthIndex,
+		round_hash: &[u8],
+	) -> Option<Scalar> {
+		let cipher = self.cipher(dealer, recipient, round_hash);
+		let payload = Payload {
+			msg: &share.ciphertext,
+			aad: &share.version.encode(),
+		};
+		let plaintext = cipher
+			.decrypt(GenericArray::from_slice(&[0u8; 12]), payload)
+			.ok()?;
+		if plaintext.len() != 32 {
+			return None;
+		}
 
 		let mut bytes = [0u8; 32];
-		bytes[..16].copy_from_slice(block1.as_slice());
-		bytes[16..32].copy_from_slice(block2.as_slice());
+		bytes.copy_from_slice(&plaintext);
 		let scalar = Scalar::from_bytes(&bytes);
 
 		if scalar.is_none().unwrap_u8() == 1 {
@@ -159,10 +339,27 @@ impl EncryptionKey {
 	}
 
 	pub fn is_correct(&self, epk1: &EncryptionPublicKey, epk2: &EncryptionPublicKey) -> bool {
-		let p1 = self.0.pairing_with(&G2Affine::generator());
-		let p2 = epk1.g1point.pairing_with(&epk2.g2point);
+		host::pairing_check(&[
+			(self.0, -G2Affine::generator()),
+			(epk1.g1point, epk2.g2point),
+		])
+	}
 
-		p1 == p2
+	/// Checks the proof that this key was derived by the owner of `issuer` for the owner
+	/// of `other`. Equivalent to `is_correct(other, issuer)` given a correct proof.
+	pub fn verify_proof(
+		&self,
+		proof: &DleqProof,
+		issuer: &EncryptionPublicKey,
+		other: &EncryptionPublicKey,
+	) -> bool {
+		let x = issuer.g1point;
+		let y = other.g1point;
+		let scalars = [proof.response, -proof.challenge];
+		let commit1 = host::g1_multi_exp(&[G1Affine::generator(), x], &scalars);
+		let commit2 = host::g1_multi_exp(&[y, self.0], &scalars);
+
+		proof.challenge == dleq_challenge(&x, &y, &self.0, &commit1, &commit2)
 	}
 }
 
@@ -174,7 +371,7 @@ pub struct Commitment {
 impl Commitment {
 	pub fn new(coeff: Scalar) -> Self {
 		Commitment {
-			g2point: G2Affine::from(G2Affine::generator() * coeff),
+			g2point: host::g2_multi_exp(&[G2Affine::generator()], &[coeff]),
 		}
 	}
 
@@ -189,19 +386,37 @@ impl Commitment {
 	}
 
 	pub fn poly_eval(coeffs: &Vec<Self>, x: &Scalar) -> Self {
-		let mut eval = G2Projective::identity();
-		for coeff in coeffs.iter().rev().map(|c| G2Projective::from(c.g2point)) {
-			eval *= x;
-			eval += coeff;
+		let points: Vec<G2Affine> = coeffs.iter().map(|c| c.g2point).collect();
+		let mut powers = Vec::with_capacity(coeffs.len());
+		let mut power = Scalar::one();
+		for _ in 0..coeffs.len() {
+			powers.push(power);
+			power *= x;
 		}
 
 		Commitment {
-			g2point: G2Affine::from(eval),
+			g2point: host::g2_multi_exp(&points, &powers),
 		}
 	}
 
 	pub fn verify_share(&self, share: &Scalar) -> bool {
-		self.g2point == G2Affine::from(G2Affine::generator() * share)
+		self.g2point == host::g2_multi_exp(&[G2Affine::generator()], &[*share])
+	}
+
+	/// Checks whether the commitment is a commitment to the secret behind the given key.
+	pub fn matches_key(&self, key: &VerifyKey) -> bool {
+		self.g2point == key.point
+	}
+
+	/// Derives a key as the linear combination of the commitments with the given weights,
+	/// used for deriving keys from a resharing of an existing secret.
+	pub fn derive_key_weighted(comms: Vec<Commitment>, weights: &Vec<Scalar>) -> VerifyKey {
+		assert!(comms.len() == weights.len());
+		let points: Vec<G2Affine> = comms.into_iter().map(|c| c.g2point).collect();
+
+		VerifyKey {
+			point: host::g2_multi_exp(&points, weights),
+		}
 	}
 }
 
@@ -244,6 +459,41 @@ mod tests {
 		assert!(key.is_correct(&pk1, &pk2));
 	}
 
+	#[test]
+	fn encrypted_share_length_is_checked_on_decode() {
+		let key = EncryptionPublicKey::from_raw_scalar([2, 1, 3, 7])
+			.to_encryption_key(Scalar::from_raw([1, 7, 2, 9]));
+		let share = key.encrypt(&Scalar::from(5), 0, 1, &[]);
+		assert_eq!(EncryptedShare::decode(&mut &share.encode()[..]).ok(), Some(share.clone()));
+
+		let long = EncryptedShare {
+			version: share.version,
+			ciphertext: sp_std::vec![0u8; 1 << 20],
+		};
+		assert!(EncryptedShare::decode(&mut &long.encode()[..]).is_err());
+	}
+
+	#[test]
+	fn dleq_proof() {
+		let secret1 = Scalar::from_raw([1, 7, 2, 9]);
+		let pk1 = EncryptionPublicKey::from_scalar(secret1);
+		let secret2 = Scalar::from_raw([2, 1, 3, 7]);
+		let pk2 = EncryptionPublicKey::from_scalar(secret2);
+		let (key, proof) = pk2.to_encryption_key_with_proof(secret1);
+
+		assert_eq!(key, pk2.to_encryption_key(secret1));
+		assert!(key.is_correct(&pk2, &pk1));
+		assert!(key.verify_proof(&proof, &pk1, &pk2));
+		assert!(!key.verify_proof(&proof, &pk2, &pk1));
+
+		let wrong_key = pk2.to_encryption_key(secret2);
+		assert!(!wrong_key.verify_proof(&proof, &pk1, &pk2));
+
+		let decoded = DleqProof::decode(&mut &proof.encode()[..]);
+		assert!(decoded.is_ok());
+		assert_eq!(decoded.unwrap(), proof);
+	}
+
 	#[test]
 	fn encrypt_decrypt() {
 		let raw_scalar = [1, 7, 2, 9];
@@ -252,11 +502,38 @@ mod tests {
 		let key = pk.to_encryption_key(secret);
 
 		let secret_share = Scalar::from_raw([2, 1, 3, 7]);
-		let decrypted = key.decrypt(&key.encrypt(&secret_share));
+		let round_hash = [7u8; 32];
+		let decrypted = key.decrypt(
+			&key.encrypt(&secret_share, 0, 1, &round_hash),
+			0,
+			1,
+			&round_hash,
+		);
 		assert!(decrypted.is_some());
 		assert_eq!(decrypted.unwrap(), secret_share);
 	}
 
+	#[test]
+	fn encrypted_share_is_bound_to_context() {
+		let pk = EncryptionPublicKey::from_raw_scalar([1, 7, 2, 9]);
+		let key = pk.to_encryption_key(Scalar::from_raw([2, 1, 3, 7]));
+		let round_hash = [7u8; 32];
+		let enc_share = key.encrypt(&Scalar::from(5), 0, 1, &round_hash);
+		assert_eq!(enc_share.version(), ShareCipherVersion::V1);
+
+		assert!(key.decrypt(&enc_share, 1, 0, &round_hash).is_none());
+		assert!(key.decrypt(&enc_share, 0, 2, &round_hash).is_none());
+		assert!(key.decrypt(&enc_share, 0, 1, &[8u8; 32]).is_none());
+
+		let mut tampered = enc_share.clone();
+		tampered.ciphertext[0] ^= 1;
+		assert!(key.decrypt(&tampered, 0, 1, &round_hash).is_none());
+
+		let decoded = EncryptedShare::decode(&mut &enc_share.encode()[..]).unwrap();
+		assert_eq!(decoded, enc_share);
+		assert_eq!(enc_share.encode()[0], 1);
+	}
+
 	#[test]
 	fn encode_decode_encryption_pk() {
 		let raw_scalar = [1, 7, 2, 9];
@@ -267,6 +544,66 @@ mod tests {
 		assert_eq!(decoded.unwrap(), key);
 	}
 
+	#[test]
+	fn resharing_preserves_master_key() {
+		use crate::threshold_signatures::lagrange_coefs_at_zero;
+
+		fn poly_eval(coeffs: &Vec<Scalar>, x: &Scalar) -> Scalar {
+			let mut eval = Scalar::zero();
+			for coeff in coeffs.iter().rev() {
+				eval *= x;
+				eval += coeff;
+			}
+			eval
+		}
+
+		// old committee of 3 with threshold 2
+		let master_poly = vec![Scalar::from(7), Scalar::from(11)];
+		let master_key = VerifyKey::from_secret(&master_poly[0]);
+		let old_shares: Vec<Scalar> = (0..3u64)
+			.map(|i| poly_eval(&master_poly, &Scalar::from(i + 1)))
+			.collect();
+
+		// dealers 0 and 2 reshare to a new committee of 4 with threshold 3
+		let dealers = vec![0u64, 2];
+		let weights = lagrange_coefs_at_zero(&dealers);
+		let sub_polys: Vec<Vec<Scalar>> = dealers
+			.iter()
+			.map(|d| {
+				vec![
+					old_shares[*d as usize],
+					Scalar::from(d + 3),
+					Scalar::from(d + 5),
+				]
+			})
+			.collect();
+		for (d, poly) in dealers.iter().zip(sub_polys.iter()) {
+			let vk = VerifyKey::from_secret(&old_shares[*d as usize]);
+			assert!(Commitment::new(poly[0]).matches_key(&vk));
+		}
+
+		let comms = sub_polys.iter().map(|p| Commitment::new(p[0])).collect();
+		assert_eq!(Commitment::derive_key_weighted(comms, &weights), master_key);
+
+		for j in 0..4u64 {
+			let x = Scalar::from(j + 1);
+			let new_share = sub_polys
+				.iter()
+				.zip(weights.iter())
+				.fold(Scalar::zero(), |acc, (p, w)| acc + poly_eval(p, &x) * w);
+			let comms = sub_polys
+				.iter()
+				.map(|p| {
+					Commitment::poly_eval(&p.iter().map(|c| Commitment::new(*c)).collect(), &x)
+				})
+				.collect();
+			assert_eq!(
+				Commitment::derive_key_weighted(comms, &weights),
+				VerifyKey::from_secret(&new_share)
+			);
+		}
+	}
+
 	#[test]
 	fn encode_decode_commitment() {
 		let coef = Scalar::from_raw([1, 7, 2, 9]);
@@ -0,0 +1,316 @@
+//! BLS12-381 operations of the DKG and the randomness beacon executed natively by the host.
+//!
+//! Pairings and scalar multiplications are an order of magnitude slower when compiled into the
+//! WASM runtime, so the runtime calls them through the `bls_12381` runtime interface. Native
+//! code calls the same implementations directly, without going through the interface.
+//!
+//! Points cross the interface in the uncompressed affine form and scalars in the canonical
+//! little-endian form. The host rejects points that are not in the prime order subgroup and
+//! non-canonical scalars.
+
+use sp_runtime_interface::runtime_interface;
+use sp_std::vec::Vec;
+
+use bls12_381::{G1Affine, G2Affine, Scalar};
+
+#[cfg(feature = "std")]
+use bls12_381::{multi_miller_loop, G1Projective, G2Prepared, G2Projective, Gt};
+#[cfg(feature = "std")]
+use codec::Decode;
+
+#[cfg(feature = "std")]
+use crate::threshold_signatures::{lagrange_coefs_at_zero, Signature, VerifyKey};
+
+pub type G1Bytes = [u8; 96];
+pub type G2Bytes = [u8; 192];
+pub type ScalarBytes = [u8; 32];
+
+#[runtime_interface]
+pub trait Bls12381 {
+	/// Checks that the product of the pairings of the given pairs of points is the identity.
+	/// Returns `false` if any of the points is invalid.
+	fn pairing_check(terms: Vec<(G1Bytes, G2Bytes)>) -> bool {
+		let mut points = Vec::with_capacity(terms.len());
+		for (p, q) in terms.iter() {
+			match (read_g1(p), read_g2(q)) {
+				(Some(p), Some(q)) => points.push((p, q)),
+				_ => return false,
+			}
+		}
+		native_pairing_check(&points)
+	}
+
+	/// Returns the sum of the points multiplied by the corresponding scalars, `None` if the
+	/// numbers of points and scalars differ or any of them is invalid.
+	fn g1_multi_exp(points: Vec<G1Bytes>, scalars: Vec<ScalarBytes>) -> Option<G1Bytes> {
+		if points.len() != scalars.len() {
+			return None;
+		}
+		let points = points.iter().map(read_g1).collect::<Option<Vec<_>>>()?;
+		let scalars = scalars.iter().map(read_scalar).collect::<Option<Vec<_>>>()?;
+		Some(native_g1_multi_exp(&points, &scalars).to_uncompressed())
+	}
+
+	/// Returns the sum of the points multiplied by the corresponding scalars, `None` if the
+	/// numbers of points and scalars differ or any of them is invalid.
+	fn g2_multi_exp(points: Vec<G2Bytes>, scalars: Vec<ScalarBytes>) -> Option<G2Bytes> {
+		if points.len() != scalars.len() {
+			return None;
+		}
+		let points = points.iter().map(read_g2).collect::<Option<Vec<_>>>()?;
+		let scalars = scalars.iter().map(read_scalar).collect::<Option<Vec<_>>>()?;
+		Some(native_g2_multi_exp(&points, &scalars).to_uncompressed())
+	}
+
+	/// Interpolates at zero the values in G1 known at points `id + 1` for the given ids, which
+	/// combines signature shares into a signature. Returns `None` if the ids are not pairwise
+	/// distinct, their number differs from the number of points or any point is invalid.
+	fn g1_lagrange_at_zero(ids: Vec<u64>, points: Vec<G1Bytes>) -> Option<G1Bytes> {
+		if ids.len() != points.len() || has_duplicates(&ids) {
+			return None;
+		}
+		let points = points.iter().map(read_g1).collect::<Option<Vec<_>>>()?;
+		Some(native_g1_lagrange_at_zero(&ids, &points).to_uncompressed())
+	}
+
+	/// Verifies a signature of the message under the verification key, both given in their
+	/// SCALE encoding. Returns `false` if either of them can not be decoded.
+	fn verify_signature(verify_key: &[u8], msg: &[u8], signature: &[u8]) -> bool {
+		match (
+			VerifyKey::decode(&mut &verify_key[..]),
+			Signature::decode(&mut &signature[..]),
+		) {
+			(Ok(verify_key), Ok(signature)) => verify_key.verify_native(&msg.to_vec(), &signature),
+			_ => false,
+		}
+	}
+}
+
+/// Checks that the product of the pairings of the given pairs of points is the identity.
+pub(crate) fn pairing_check(terms: &[(G1Affine, G2Affine)]) -> bool {
+	#[cfg(feature = "std")]
+	{
+		native_pairing_check(terms)
+	}
+	#[cfg(not(feature = "std"))]
+	{
+		bls_12381::pairing_check(
+			terms
+				.iter()
+				.map(|(p, q)| (p.to_uncompressed(), q.to_uncompressed()))
+				.collect(),
+		)
+	}
+}
+
+/// The sum of the points multiplied by the corresponding scalars.
+pub(crate) fn g1_multi_exp(points: &[G1Affine], scalars: &[Scalar]) -> G1Affine {
+	assert!(points.len() == scalars.len());
+	#[cfg(feature = "std")]
+	{
+		native_g1_multi_exp(points, scalars)
+	}
+	#[cfg(not(feature = "std"))]
+	{
+		let result = bls_12381::g1_multi_exp(
+			points.iter().map(G1Affine::to_uncompressed).collect(),
+			scalars.iter().map(Scalar::to_bytes).collect(),
+		)
+		.expect("points and scalars are valid and of equal numbers; qed");
+		G1Affine::from_uncompressed_unchecked(&result).unwrap()
+	}
+}
+
+/// The sum of the points multiplied by the corresponding scalars.
+pub(crate) fn g2_multi_exp(points: &[G2Affine], scalars: &[Scalar]) -> G2Affine {
+	assert!(points.len() == scalars.len());
+	#[cfg(feature = "std")]
+	{
+		native_g2_multi_exp(points, scalars)
+	}
+	#[cfg(not(feature = "std"))]
+	{
+		let result = bls_12381::g2_multi_exp(
+			points.iter().map(G2Affine::to_uncompressed).collect(),
+			scalars.iter().map(Scalar::to_bytes).collect(),
+		)
+		.expect("points and scalars are valid and of equal numbers; qed");
+		G2Affine::from_uncompressed_unchecked(&result).unwrap()
+	}
+}
+
+/// Interpolates at zero the values known at points `id + 1` for the given ids. The ids must
+/// be pairwise distinct.
+pub(crate) fn g1_lagrange_at_zero(ids: &[u64], points: &[G1Affine]) -> G1Affine {
+	assert!(ids.len() == points.len());
+	#[cfg(feature = "std")]
+	{
+		native_g1_lagrange_at_zero(ids, points)
+	}
+	#[cfg(not(feature = "std"))]
+	{
+		let result = bls_12381::g1_lagrange_at_zero(
+			ids.to_vec(),
+			points.iter().map(G1Affine::to_uncompressed).collect(),
+		)
+		.expect("ids are distinct and points are valid; qed");
+		G1Affine::from_uncompressed_unchecked(&result).unwrap()
+	}
+}
+
+#[cfg(feature = "std")]
+fn native_pairing_check(terms: &[(G1Affine, G2Affine)]) -> bool {
+	let prepared: Vec<_> = terms
+		.iter()
+		.map(|(p, q)| (p, G2Prepared::from(*q)))
+		.collect();
+	let terms: Vec<_> = prepared.iter().map(|(p, q)| (*p, q)).collect();
+	multi_miller_loop(&terms).final_exponentiation() == Gt::identity()
+}
+
+#[cfg(feature = "std")]
+fn native_g1_multi_exp(points: &[G1Affine], scalars: &[Scalar]) -> G1Affine {
+	points
+		.iter()
+		.zip(scalars.iter())
+		.map(|(p, s)| p * s)
+		.fold(G1Projective::identity(), |a, b| a + b)
+		.into()
+}
+
+#[cfg(feature = "std")]
+fn native_g2_multi_exp(points: &[G2Affine], scalars: &[Scalar]) -> G2Affine {
+	points
+		.iter()
+		.zip(scalars.iter())
+		.map(|(p, s)| p * s)
+		.fold(G2Projective::identity(), |a, b| a + b)
+		.into()
+}
+
+#[cfg(feature = "std")]
+fn native_g1_lagrange_at_zero(ids: &[u64], points: &[G1Affine]) -> G1Affine {
+	native_g1_multi_exp(points, &lagrange_coefs_at_zero(ids))
+}
+
+#[cfg(feature = "std")]
+fn has_duplicates(ids: &[u64]) -> bool {
+	let mut sorted = ids.to_vec();
+	sorted.sort_unstable();
+	sorted.windows(2).any(|pair| pair[0] == pair[1])
+}
+
+#[cfg(feature = "std")]
+fn read_g1(bytes: &G1Bytes) -> Option<G1Affine> {
+	Option::from(G1Affine::from_uncompressed(bytes))
+}
+
+#[cfg(feature = "std")]
+fn read_g2(bytes: &G2Bytes) -> Option<G2Affine> {
+	Option::from(G2Affine::from_uncompressed(bytes))
+}
+
+#[cfg(feature = "std")]
+fn read_scalar(bytes: &ScalarBytes) -> Option<Scalar> {
+	Option::from(Scalar::from_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn scalar(n: u64) -> Scalar {
+		Scalar::from_raw([n, 3 * n + 1, 7, n * n])
+	}
+
+	#[test]
+	fn pairing_check_through_interface() {
+		let (a, b) = (scalar(2), scalar(5));
+		let p = G1Affine::from(G1Affine::generator() * a);
+		let q = G2Affine::from(G2Affine::generator() * b);
+		let pq = G1Affine::from(G1Affine::generator() * (a * b));
+
+		// e(a * g1, b * g2) * e(ab * g1, -g2) == 1
+		let terms = vec![(p, q), (pq, -G2Affine::generator())];
+		assert!(pairing_check(&terms));
+		let encoded = terms
+			.iter()
+			.map(|(p, q)| (p.to_uncompressed(), q.to_uncompressed()))
+			.collect();
+		assert!(bls_12381::pairing_check(encoded));
+
+		let wrong = vec![(p, q), (p, -G2Affine::generator())];
+		assert!(!pairing_check(&wrong));
+
+		// a point off the curve is rejected
+		let mut invalid = p.to_uncompressed();
+		invalid[95] ^= 1;
+		assert!(!bls_12381::pairing_check(vec![(
+			invalid,
+			q.to_uncompressed()
+		)]));
+	}
+
+	#[test]
+	fn multi_exp_through_interface() {
+		let points: Vec<_> = (1..5)
+			.map(|n| G2Affine::from(G2Affine::generator() * scalar(n)))
+			.collect();
+		let scalars: Vec<_> = (10..14).map(scalar).collect();
+		let expected = (1..5)
+			.map(|n| scalar(n) * scalar(n + 9))
+			.fold(Scalar::zero(), |a, b| a + b);
+
+		let result = g2_multi_exp(&points, &scalars);
+		assert_eq!(result, G2Affine::from(G2Affine::generator() * expected));
+		assert_eq!(
+			bls_12381::g2_multi_exp(
+				points.iter().map(G2Affine::to_uncompressed).collect(),
+				scalars.iter().map(Scalar::to_bytes).collect(),
+			),
+			Some(result.to_uncompressed()),
+		);
+		assert_eq!(
+			bls_12381::g2_multi_exp(
+				points.iter().map(G2Affine::to_uncompressed).collect(),
+				Vec::new()
+			),
+			None
+		);
+
+		let g1_points: Vec<_> = (1..5)
+			.map(|n| G1Affine::from(G1Affine::generator() * scalar(n)))
+			.collect();
+		assert_eq!(
+			g1_multi_exp(&g1_points, &scalars),
+			G1Affine::from(G1Affine::generator() * expected)
+		);
+	}
+
+	#[test]
+	fn lagrange_at_zero_through_interface() {
+		// the values of the polynomial 3 + 2x at points 1, 3 and 4
+		let ids = vec![0, 2, 3];
+		let points: Vec<_> = ids
+			.iter()
+			.map(|id| G1Affine::from(G1Affine::generator() * Scalar::from(3 + 2 * (id + 1))))
+			.collect();
+		let secret = G1Affine::from(G1Affine::generator() * Scalar::from(3));
+
+		assert_eq!(g1_lagrange_at_zero(&ids, &points), secret);
+		assert_eq!(
+			bls_12381::g1_lagrange_at_zero(
+				ids.clone(),
+				points.iter().map(G1Affine::to_uncompressed).collect()
+			),
+			Some(secret.to_uncompressed()),
+		);
+		assert_eq!(
+			bls_12381::g1_lagrange_at_zero(
+				vec![0, 0, 3],
+				points.iter().map(G1Affine::to_uncompressed).collect()
+			),
+			None
+		);
+	}
+}
@@ -0,0 +1,43 @@
+//! Storage of the secret shares of the node in its keystore, available to the offchain worker of
+//! the DKG pallet through a runtime interface.
+
+#[cfg(feature = "std")]
+use sp_externalities::ExternalitiesExt;
+#[cfg(feature = "std")]
+use sp_keystore::{KeystoreExt, SyncCryptoStore};
+use sp_runtime_interface::runtime_interface;
+
+#[cfg(feature = "std")]
+use crate::SECRET_KEY_TYPE;
+
+#[runtime_interface]
+pub trait SecretShares {
+	/// Puts the secret share into the keystore of the node under its encoded verification key.
+	/// Returns `false` if there is no keystore or the share could not be stored.
+	fn store(&mut self, verify_key: &[u8], secret: &[u8; 32]) -> bool {
+		match self.extension::<KeystoreExt>() {
+			Some(keystore) => {
+				SyncCryptoStore::insert_secret(&***keystore, SECRET_KEY_TYPE, verify_key, secret)
+					.is_ok()
+			}
+			None => false,
+		}
+	}
+
+	/// Returns the secret share stored under the encoded verification key.
+	fn get(&mut self, verify_key: &[u8]) -> Option<[u8; 32]> {
+		let keystore = self.extension::<KeystoreExt>()?;
+		let secret = SyncCryptoStore::secret(&***keystore, SECRET_KEY_TYPE, verify_key).ok()??;
+		read_secret(&secret)
+	}
+}
+
+/// Reads a secret share as stored in the keystore.
+pub fn read_secret(secret: &[u8]) -> Option<[u8; 32]> {
+	if secret.len() != 32 {
+		return None;
+	}
+	let mut bytes = [0u8; 32];
+	bytes.copy_from_slice(secret);
+	Some(bytes)
+}
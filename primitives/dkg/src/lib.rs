@@ -1,16 +1,32 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
 mod commitments;
+pub mod host;
+pub mod keystore;
 mod threshold_signatures;
 
-pub use commitments::{Commitment, EncryptedShare, EncryptionKey, EncryptionPublicKey, Scalar};
-pub use threshold_signatures::{KeyBox, Share, ShareProvider, Signature, VerifyKey};
+pub use commitments::{
+	Commitment, DleqProof, EncryptedShare, EncryptionKey, EncryptionPublicKey, Scalar,
+	ShareCipherVersion,
+};
+pub use threshold_signatures::{
+	batch_verify, find_invalid_signatures, hash_to_curve, lagrange_coefs_at_zero, KeyBox, Share,
+	ShareProvider, Signature, SignatureVersion, VerifyKey, HASH_TO_G1_DST,
+};
+
+use codec::{Decode, Encode};
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+use sp_runtime::RuntimeDebug;
 
 pub type AuthIndex = u64;
+pub type EpochIndex = u64;
 pub type RawSecret = [u64; 4];
 
 use sp_core::crypto::KeyTypeId;
 pub const KEY_TYPE: KeyTypeId = KeyTypeId(*b"dkg!");
+/// The key type under which the secret shares derived in the DKG are kept in the keystore.
+pub const SECRET_KEY_TYPE: KeyTypeId = KeyTypeId(*b"dkgs");
 
 pub mod crypto {
 	use super::KEY_TYPE;
@@ -20,15 +36,54 @@ pub mod crypto {
 	pub type AuthorityId = Public;
 }
 
+/// The phase of the DKG run of the current epoch.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum DkgPhase {
+	/// No DKG was started yet.
+	Idle,
+	/// The members post their encryption keys.
+	Round0,
+	/// The dealers post their commitments and encrypted shares.
+	Round1,
+	/// The members post disputes against the dealers.
+	Round2,
+	/// The keys are derived on chain, the members derive their secret keys offchain.
+	Round3,
+	/// The keys of the epoch are active.
+	Completed,
+	/// Too few dealers qualified, the epoch produced no keys.
+	Failed,
+}
+
+impl Default for DkgPhase {
+	fn default() -> Self {
+		DkgPhase::Idle
+	}
+}
+
 use sp_runtime::traits::NumberFor;
 use sp_std::vec::Vec;
 sp_api::decl_runtime_apis! {
+	/// Version 3 removed `storage_key_sk`, since the secret share of the node is kept in the
+	/// keystore under `SECRET_KEY_TYPE` instead of the offchain storage. Version 4 added
+	/// `active_threshold`.
+	#[api_version(4)]
 	pub trait DKGApi {
 		fn master_verification_key() -> Option<VerifyKey>;
 		fn master_key_ready() -> NumberFor<Block>;
 		fn threshold() -> u64;
 		fn verification_keys() -> Option<Vec<VerifyKey>>;
 		fn public_keybox_parts() -> Option<(Option<AuthIndex>, Vec<VerifyKey>, VerifyKey, u64)>;
-		fn storage_key_sk() -> Option<Vec<u8>>;
+		fn current_epoch() -> EpochIndex;
+		fn epoch_master_key(epoch: EpochIndex) -> Option<VerifyKey>;
+		/// The phase of the DKG run of the current epoch, added in version 2.
+		fn dkg_phase() -> DkgPhase;
+		/// The dealers of the current epoch that posted commitments and were not disqualified,
+		/// added in version 2.
+		fn qualified_dealers() -> Vec<AuthIndex>;
+		/// The threshold of the active keys, which differs from `threshold` while the keys are
+		/// reshared to a new committee, added in version 4.
+		fn active_threshold() -> u64;
 	}
 }
@@ -6,31 +6,89 @@ use sp_std::vec::Vec;
 #[cfg(feature = "std")]
 use rand::{thread_rng, Rng};
 
+use bls12_381::hash_to_curve::{ExpandMsgXmd, HashToCurve};
 use bls12_381::{G1Affine, G1Projective, G2Affine, Scalar};
-use pairing::PairingCurveAffine;
 
+use crate::host;
+
+use sha2::Sha256;
 use sha3::{Digest, Sha3_256};
 
+/// The domain separation tag used when hashing messages to G1, as in the BLS signature
+/// ciphersuite with signatures in G1 and public keys in G2.
+pub const HASH_TO_G1_DST: &[u8] = b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_NUL_";
+
+/// The way a message is hashed to G1 before it is signed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+pub enum SignatureVersion {
+	/// `generator * sha3(msg)`. INSECURE since the discrete log of the hash is known, kept
+	/// only so that chains created before V1 may still verify their old signatures.
+	V0,
+	/// The `BLS12381G1_XMD:SHA-256_SSWU_RO_` suite of the IETF hash-to-curve spec with
+	/// the `HASH_TO_G1_DST` domain separation tag.
+	V1,
+}
+
+impl SignatureVersion {
+	pub const LATEST: SignatureVersion = SignatureVersion::V1;
+}
+
+impl Default for SignatureVersion {
+	fn default() -> Self {
+		SignatureVersion::V0
+	}
+}
+
 #[derive(Clone, Debug, Default, PartialEq)]
-pub struct Signature(G1Affine);
+pub struct Signature {
+	version: SignatureVersion,
+	point: G1Affine,
+}
 
+// V0 signatures are encoded as a compressed point only, which keeps the encoding of
+// signatures created before versioning was introduced. Later versions prepend
+// the version byte.
 impl Encode for Signature {
 	fn encode_to<T: Output>(&self, dest: &mut T) {
-		Encode::encode_to(&self.0.to_compressed().to_vec(), dest);
+		let mut bytes = Vec::with_capacity(49);
+		if self.version != SignatureVersion::V0 {
+			bytes.push(self.version as u8);
+		}
+		bytes.extend_from_slice(&self.point.to_compressed());
+		Encode::encode_to(&bytes, dest);
 	}
 }
 
 impl Decode for Signature {
 	fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
-		let mut bytes = [0u8; 48];
 		let vec = Vec::decode(input)?;
-		bytes.copy_from_slice(&vec[..]);
+		let (version, point_bytes) = match vec.len() {
+			48 => (SignatureVersion::V0, &vec[..]),
+			49 => (SignatureVersion::decode(&mut &vec[..1])?, &vec[1..]),
+			_ => return Err("wrong length of an encoded signature".into()),
+		};
+		if version == SignatureVersion::V0 && vec.len() != 48 {
+			return Err("V0 signature encoded with a version byte".into());
+		}
+
+		let mut bytes = [0u8; 48];
+		bytes.copy_from_slice(point_bytes);
 		let point = G1Affine::from_compressed(&bytes);
 		if point.is_none().unwrap_u8() == 1 {
 			return Err("could not decode G1Affine point".into());
 		}
 
-		Ok(Signature { 0: point.unwrap() })
+		Ok(Signature {
+			version,
+			point: point.unwrap(),
+		})
+	}
+}
+
+impl Signature {
+	pub fn version(&self) -> SignatureVersion {
+		self.version
 	}
 }
 
@@ -67,15 +125,29 @@ impl EncodeLike for VerifyKey {}
 
 impl VerifyKey {
 	pub fn verify(&self, msg: &Vec<u8>, sgn: &Signature) -> bool {
-		let p1 = sgn.0.pairing_with(&G2Affine::generator());
-		let p2 = hash_to_curve(msg).pairing_with(&self.point);
+		#[cfg(feature = "std")]
+		{
+			self.verify_native(msg, sgn)
+		}
+		#[cfg(not(feature = "std"))]
+		{
+			crate::host::bls_12381::verify_signature(&self.encode(), msg, &sgn.encode())
+		}
+	}
 
-		p1 == p2
+	// e(sgn, g2) == e(H(msg), key), run by the host for the runtime as hashing to the curve is
+	// costly in WASM as well
+	#[cfg(feature = "std")]
+	pub(crate) fn verify_native(&self, msg: &Vec<u8>, sgn: &Signature) -> bool {
+		host::pairing_check(&[
+			(sgn.point, -G2Affine::generator()),
+			(hash_to_curve(msg, sgn.version), self.point),
+		])
 	}
 
 	pub fn from_secret(secret: &Scalar) -> Self {
 		VerifyKey {
-			point: G2Affine::from(G2Affine::generator() * secret),
+			point: host::g2_multi_exp(&[G2Affine::generator()], &[*secret]),
 		}
 	}
 
@@ -133,7 +205,7 @@ fn scalar_from_raw_secret(raw_secret: RawSecret) -> Scalar {
 
 impl ShareProvider {
 	pub fn is_correct(&self) -> bool {
-		let g2power = G2Affine::from(G2Affine::generator() * self.secret);
+		let g2power = host::g2_multi_exp(&[G2Affine::generator()], &[self.secret]);
 		return g2power == self.verify.point;
 	}
 
@@ -153,10 +225,13 @@ impl ShareProvider {
 		Self::from_secret(id, secret)
 	}
 
-	pub fn sign(&self, msg: &Vec<u8>) -> Signature {
-		let point = hash_to_curve(msg);
+	pub fn sign(&self, msg: &Vec<u8>, version: SignatureVersion) -> Signature {
+		let point = hash_to_curve(msg, version);
 
-		Signature(G1Affine::from(point * self.secret))
+		Signature {
+			version,
+			point: G1Affine::from(point * self.secret),
+		}
 	}
 
 	pub fn verify(&self, msg: &Vec<u8>, sgn: &Signature) -> bool {
@@ -166,6 +241,14 @@ impl ShareProvider {
 	pub fn verify_key(&self) -> VerifyKey {
 		self.verify.clone()
 	}
+
+	pub fn id(&self) -> u64 {
+		self.id
+	}
+
+	pub fn secret(&self) -> Scalar {
+		self.secret
+	}
 }
 
 #[derive(PartialEq, Clone, Decode, Encode)]
@@ -196,6 +279,16 @@ fn lagrange_coef(knots: &Vec<Scalar>, knot: Scalar, target: Scalar) -> Scalar {
 	num * den.invert().unwrap()
 }
 
+/// Lagrange coefficients for interpolating at zero a polynomial known at points `id + 1`
+/// for the given ids. The ids must be pairwise distinct.
+pub fn lagrange_coefs_at_zero(ids: &[u64]) -> Vec<Scalar> {
+	let knots: Vec<Scalar> = ids.iter().map(|id| Scalar::from(id + 1)).collect();
+	knots
+		.iter()
+		.map(|knot| lagrange_coef(&knots, *knot, Scalar::zero()))
+		.collect()
+}
+
 /// Threshold BLS keys.
 impl KeyBox {
 	pub fn new(
@@ -212,11 +305,11 @@ impl KeyBox {
 		}
 	}
 
-	pub fn generate_share(&self, msg: &Vec<u8>) -> Option<Share> {
+	pub fn generate_share(&self, msg: &Vec<u8>, version: SignatureVersion) -> Option<Share> {
 		if let Some(ref share_provider) = self.share_provider {
 			return Some(Share {
 				creator: share_provider.id,
-				data: share_provider.sign(msg),
+				data: share_provider.sign(msg, version),
 			});
 		}
 
@@ -224,20 +317,45 @@ impl KeyBox {
 	}
 
 	pub fn verify_share(&self, msg: &Vec<u8>, share: &Share) -> bool {
-		self.verify_keys[share.creator as usize].verify(msg, &share.data)
+		match self.verify_keys.get(share.creator as usize) {
+			Some(key) => key.verify(msg, &share.data),
+			None => false,
+		}
+	}
+
+	/// Returns the indices of the shares that are not valid shares of signatures of the
+	/// corresponding messages, see `find_invalid_signatures`.
+	pub fn invalid_shares(&self, shares: &[(&[u8], &Share)]) -> Vec<usize> {
+		let mut invalid = Vec::new();
+		let mut items = Vec::with_capacity(shares.len());
+		let mut positions = Vec::with_capacity(shares.len());
+		for (ix, (msg, share)) in shares.iter().enumerate() {
+			match self.verify_keys.get(share.creator as usize) {
+				Some(key) => {
+					items.push((key, *msg, &share.data));
+					positions.push(ix);
+				}
+				None => invalid.push(ix),
+			}
+		}
+
+		invalid.extend(find_invalid_signatures(&items).into_iter().map(|i| positions[i]));
+		invalid.sort_unstable();
+		invalid
 	}
 
 	// Some(share) if succeeded and None if failed for some reason (e.g. not enough shares)
-	// Assumption: shares are for the same msg, are valid, and there are exactly threshold of them
+	// Assumption: shares are for the same msg, are valid, have the same version, and there are
+	// exactly threshold of them
 	pub fn combine_shares(&self, shares: &Vec<Share>) -> Signature {
 		assert!(shares.len() as u64 == self.threshold);
-		let mut sum = G1Projective::identity();
-		let knots = shares.iter().map(|s| Scalar::from(s.creator + 1)).collect();
-		for (i, share) in shares.iter().enumerate() {
-			sum += share.data.0 * lagrange_coef(&knots, knots[i], Scalar::from(0));
-		}
+		let ids: Vec<u64> = shares.iter().map(|s| s.creator).collect();
+		let points: Vec<G1Affine> = shares.iter().map(|s| s.data.point).collect();
 
-		Signature(G1Affine::from(sum))
+		Signature {
+			version: shares[0].data.version,
+			point: host::g1_lagrange_at_zero(&ids, &points),
+		}
 	}
 
 	pub fn verify_signature(&self, msg: &Vec<u8>, signature: &Signature) -> bool {
@@ -253,10 +371,134 @@ impl KeyBox {
 	}
 }
 
+impl Share {
+	pub fn version(&self) -> SignatureVersion {
+		self.data.version
+	}
+
+	pub fn creator(&self) -> u64 {
+		self.creator
+	}
+}
+
+/// Verifies many signatures at once with a single multi-Miller loop.
+///
+/// Every signature is weighted with a 128-bit scalar derived from the hash of all the items,
+/// so that invalid signatures can not cancel out, and it is checked that
+/// `e(sum_i w_i * sgn_i, g2) == prod_m e(H(m), sum_{i: msg_i = m} w_i * key_i)`.
+/// The messages are hashed to the curve once per distinct message.
+pub fn batch_verify(items: &[(&VerifyKey, &[u8], &Signature)]) -> bool {
+	if items.is_empty() {
+		return true;
+	}
+
+	let weights = batch_weights(items);
+	let mut groups: Vec<(&[u8], SignatureVersion, Vec<G2Affine>, Vec<Scalar>)> = Vec::new();
+	for ((key, msg, sgn), weight) in items.iter().zip(weights.iter()) {
+		match groups
+			.iter_mut()
+			.find(|(m, version, _, _)| m == msg && *version == sgn.version)
+		{
+			Some((_, _, keys, key_weights)) => {
+				keys.push(key.point);
+				key_weights.push(*weight);
+			}
+			None => groups.push((*msg, sgn.version, vec![key.point], vec![*weight])),
+		}
+	}
+
+	let signatures: Vec<G1Affine> = items.iter().map(|(_, _, sgn)| sgn.point).collect();
+	let mut terms = Vec::with_capacity(groups.len() + 1);
+	terms.push((
+		host::g1_multi_exp(&signatures, &weights),
+		-G2Affine::generator(),
+	));
+	for (msg, version, keys, key_weights) in groups.iter() {
+		terms.push((
+			hash_to_curve(msg, *version),
+			host::g2_multi_exp(keys, key_weights),
+		));
+	}
+
+	host::pairing_check(&terms)
+}
+
+/// Returns the indices of the invalid signatures among the items. All of them are checked
+/// with one `batch_verify`, and a failing batch is split in halves until the invalid
+/// signatures are found, so a few invalid signatures cost a logarithmic number of batches.
+pub fn find_invalid_signatures(items: &[(&VerifyKey, &[u8], &Signature)]) -> Vec<usize> {
+	let mut invalid = Vec::new();
+	bisect_invalid(items, 0, &mut invalid);
+	invalid
+}
+
+fn bisect_invalid(
+	items: &[(&VerifyKey, &[u8], &Signature)],
+	offset: usize,
+	invalid: &mut Vec<usize>,
+) {
+	if items.is_empty() || batch_verify(items) {
+		return;
+	}
+	if items.len() == 1 {
+		invalid.push(offset);
+		return;
+	}
+
+	let mid = items.len() / 2;
+	bisect_invalid(&items[..mid], offset, invalid);
+	bisect_invalid(&items[mid..], offset + mid, invalid);
+}
+
+// the weights are derived from all the items, so a signer can not choose a signature that
+// cancels out with another one after learning the weights
+fn batch_weights(items: &[(&VerifyKey, &[u8], &Signature)]) -> Vec<Scalar> {
+	let mut hasher = Sha3_256::new();
+	hasher.input(b"DKG-BATCH-VERIFY-V1");
+	for (key, msg, sgn) in items.iter() {
+		hasher.input(&key.encode());
+		hasher.input(&msg.encode());
+		hasher.input(&sgn.encode());
+	}
+	let seed = hasher.result();
+
+	(0..items.len() as u64)
+		.map(|ix| {
+			let mut hasher = Sha3_256::new();
+			hasher.input(&seed);
+			hasher.input(&ix.to_le_bytes());
+			let data = hasher.result();
+
+			let mut lo = [0u8; 8];
+			let mut hi = [0u8; 8];
+			lo.copy_from_slice(&data[..8]);
+			hi.copy_from_slice(&data[8..16]);
+			let weight = Scalar::from_raw([u64::from_le_bytes(lo), u64::from_le_bytes(hi), 0, 0]);
+			if weight == Scalar::zero() {
+				Scalar::one()
+			} else {
+				weight
+			}
+		})
+		.collect()
+}
+
+pub fn hash_to_curve(msg: &[u8], version: SignatureVersion) -> G1Affine {
+	match version {
+		SignatureVersion::V0 => hash_to_curve_v0(msg),
+		SignatureVersion::V1 => hash_to_g1(msg, HASH_TO_G1_DST),
+	}
+}
+
+// hash_to_curve of the IETF spec, constant time in the message
+fn hash_to_g1(msg: &[u8], dst: &[u8]) -> G1Affine {
+	let point = <G1Projective as HashToCurve<ExpandMsgXmd<Sha256>>>::hash_to_curve(msg, dst);
+	G1Affine::from(point)
+}
+
 // WARNING: this hashing function gen ^ hash(msg) is not secure because the log of the hash is known.
-// The bls12_381 library does not provide a Hash to G1 implementation. Need to replace this by a secure
-// hash to G1 implementation as soon as it is available.
-pub fn hash_to_curve(msg: &Vec<u8>) -> G1Affine {
+// It is used only to verify V0 signatures, new signatures should use SignatureVersion::LATEST.
+fn hash_to_curve_v0(msg: &[u8]) -> G1Affine {
 	let mut hasher = Sha3_256::new();
 	hasher.input(msg);
 	let data = hasher.result();
@@ -276,6 +518,7 @@ pub fn hash_to_curve(msg: &Vec<u8>) -> G1Affine {
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use hex_literal::hex;
 
 	fn random_msg() -> Vec<u8> {
 		rand::thread_rng().gen::<[u8; 32]>().to_vec()
@@ -323,9 +566,61 @@ mod tests {
 	#[test]
 	fn hash() {
 		let msg = random_msg();
-		let point = hash_to_curve(&msg);
-		assert_eq!(point.is_on_curve().unwrap_u8(), 1);
-		assert_eq!(point.is_torsion_free().unwrap_u8(), 1);
+		for &version in [SignatureVersion::V0, SignatureVersion::V1].iter() {
+			let point = hash_to_curve(&msg, version);
+			assert_eq!(point.is_on_curve().unwrap_u8(), 1);
+			assert_eq!(point.is_torsion_free().unwrap_u8(), 1);
+		}
+	}
+
+	// test vectors of the BLS12381G1_XMD:SHA-256_SSWU_RO_ suite from the IETF hash-to-curve spec
+	#[test]
+	fn hash_to_g1_test_vectors() {
+		const DST: &[u8] = b"QUUX-V01-CS02-with-BLS12381G1_XMD:SHA-256_SSWU_RO_";
+		let q128 = [&b"q128_"[..], &[b'q'; 128][..]].concat();
+		let a512 = [&b"a512_"[..], &[b'a'; 512][..]].concat();
+		let vectors: [(&[u8], [u8; 96]); 5] = [
+			(
+				b"",
+				hex!(
+					"052926add2207b76ca4fa57a8734416c8dc95e24501772c814278700eed6d1e4e8cf62d9c09db0fac349612b759e79a1
+					 08ba738453bfed09cb546dbb0783dbb3a5f1f566ed67bb6be0e8c67e2e81a4cc68ee29813bb7994998f3eae0c9c6a265"
+				),
+			),
+			(
+				b"abc",
+				hex!(
+					"03567bc5ef9c690c2ab2ecdf6a96ef1c139cc0b2f284dca0a9a7943388a49a3aee664ba5379a7655d3c68900be2f6903
+					 0b9c15f3fe6e5cf4211f346271d7b01c8f3b28be689c8429c85b67af215533311f0b8dfaaa154fa6b88176c229f2885d"
+				),
+			),
+			(
+				b"abcdef0123456789",
+				hex!(
+					"11e0b079dea29a68f0383ee94fed1b940995272407e3bb916bbf268c263ddd57a6a27200a784cbc248e84f357ce82d98
+					 03a87ae2caf14e8ee52e51fa2ed8eefe80f02457004ba4d486d6aa1f517c0889501dc7413753f9599b099ebcbbd2d709"
+				),
+			),
+			(
+				&q128,
+				hex!(
+					"15f68eaa693b95ccb85215dc65fa81038d69629f70aeee0d0f677cf22285e7bf58d7cb86eefe8f2e9bc3f8cb84fac488
+					 1807a1d50c29f430b8cafc4f8638dfeeadf51211e1602a5f184443076715f91bb90a48ba1e370edce6ae1062f5e6dd38"
+				),
+			),
+			(
+				&a512,
+				hex!(
+					"082aabae8b7dedb0e78aeb619ad3bfd9277a2f77ba7fad20ef6aabdc6c31d19ba5a6d12283553294c1825c4b3ca2dcfe
+					 05b84ae5a942248eea39e1d91030458c40153f3b654ab7872d779ad1e942856a20c438e8d99bc8abfbf74729ce1f7ac8"
+				),
+			),
+		];
+
+		for (msg, expected) in vectors.iter() {
+			let point = hash_to_g1(msg, DST);
+			assert_eq!(&point.to_uncompressed()[..], &expected[..]);
+		}
 	}
 
 	#[test]
@@ -341,22 +636,50 @@ mod tests {
 	#[test]
 	fn encode_decode_signature() {
 		let scalar = random_scalar();
-		let sig = Signature {
-			0: G1Affine::from(G1Affine::generator() * scalar),
-		};
+		for &version in [SignatureVersion::V0, SignatureVersion::V1].iter() {
+			let sig = Signature {
+				version,
+				point: G1Affine::from(G1Affine::generator() * scalar),
+			};
+
+			let decoded = Signature::decode(&mut &sig.encode()[..]);
+			assert!(decoded.is_ok());
+			assert_eq!(decoded.unwrap(), sig);
+		}
+	}
 
-		let decoded = Signature::decode(&mut &sig.encode()[..]);
-		assert!(decoded.is_ok());
-		assert_eq!(decoded.unwrap(), sig);
+	#[test]
+	fn decode_unversioned_signature() {
+		let point = G1Affine::from(G1Affine::generator() * random_scalar());
+		let encoded = point.to_compressed().to_vec().encode();
+
+		let decoded = Signature::decode(&mut &encoded[..]).unwrap();
+		assert_eq!(decoded.version(), SignatureVersion::V0);
+		assert_eq!(decoded.point, point);
+
+		let mut bytes = sp_std::vec![0u8];
+		bytes.extend_from_slice(&point.to_compressed());
+		assert!(Signature::decode(&mut &bytes.encode()[..]).is_err());
 	}
 
 	#[test]
 	fn correct_sign() {
 		let pair = ShareProvider::generate(7);
 		let msg = random_msg();
-		let sgn = pair.sign(&msg);
+		for &version in [SignatureVersion::V0, SignatureVersion::V1].iter() {
+			let sgn = pair.sign(&msg, version);
+			assert!(pair.verify(&msg, &sgn));
+		}
+	}
 
-		assert!(pair.verify(&msg, &sgn));
+	#[test]
+	fn version_is_bound_to_signature() {
+		let pair = ShareProvider::generate(7);
+		let msg = random_msg();
+		let mut sgn = pair.sign(&msg, SignatureVersion::V1);
+		sgn.version = SignatureVersion::V0;
+
+		assert!(!pair.verify(&msg, &sgn));
 	}
 
 	#[test]
@@ -381,11 +704,83 @@ mod tests {
 		let msg = random_msg();
 		let mut shares = Vec::new();
 		for id in 0..threshold {
-			shares.push(kbs[id].generate_share(&msg).unwrap());
+			shares.push(
+				kbs[id]
+					.generate_share(&msg, SignatureVersion::LATEST)
+					.unwrap(),
+			);
 		}
 
 		let signature = kbs[0].combine_shares(&shares);
 
 		assert!(kbs[0].verify_signature(&msg, &signature));
 	}
+
+	#[test]
+	fn batch_verify_finds_invalid_signatures() {
+		let pairs: Vec<_> = (0..10).map(ShareProvider::generate).collect();
+		let keys: Vec<_> = pairs.iter().map(ShareProvider::verify_key).collect();
+		// two messages, so that the signatures fall into two groups
+		let msgs = [random_msg(), random_msg()];
+		let mut signatures: Vec<_> = pairs
+			.iter()
+			.enumerate()
+			.map(|(ix, pair)| pair.sign(&msgs[ix % 2], SignatureVersion::LATEST))
+			.collect();
+		fn items<'a>(
+			keys: &'a [VerifyKey],
+			msgs: &'a [Vec<u8>],
+			signatures: &'a [Signature],
+		) -> Vec<(&'a VerifyKey, &'a [u8], &'a Signature)> {
+			(0..keys.len())
+				.map(|ix| (&keys[ix], &msgs[ix % 2][..], &signatures[ix]))
+				.collect()
+		}
+
+		assert!(batch_verify(&items(&keys, &msgs, &signatures)));
+		assert!(find_invalid_signatures(&items(&keys, &msgs, &signatures)).is_empty());
+
+		// a signature of the other message, and a signature with a wrong version
+		signatures[3] = pairs[3].sign(&msgs[0], SignatureVersion::LATEST);
+		signatures[8].version = SignatureVersion::V0;
+		assert!(!batch_verify(&items(&keys, &msgs, &signatures)));
+		assert_eq!(find_invalid_signatures(&items(&keys, &msgs, &signatures)), vec![3, 8]);
+
+		// invalid signatures do not cancel out: sgn_1 + d and sgn_2 - d
+		let mut signatures: Vec<_> = (0..10)
+			.map(|ix| pairs[ix].sign(&msgs[0], SignatureVersion::LATEST))
+			.collect();
+		let delta = G1Affine::from(G1Affine::generator() * random_scalar());
+		signatures[1].point = G1Affine::from(G1Projective::from(signatures[1].point) + delta);
+		signatures[2].point = G1Affine::from(G1Projective::from(signatures[2].point) - delta);
+		let items: Vec<_> = (0..10)
+			.map(|ix| (&keys[ix], &msgs[0][..], &signatures[ix]))
+			.collect();
+		assert_eq!(find_invalid_signatures(&items), vec![1, 2]);
+	}
+
+	#[test]
+	fn invalid_shares_of_keybox() {
+		let (n_members, threshold) = (5, 3);
+		let (share_providers, master_key) = generate_threshold_pairs(n_members, threshold);
+		let verifiers = share_providers.iter().map(|sp| sp.verify_key()).collect();
+		let kb = KeyBox::new(None, verifiers, master_key, threshold as u64);
+
+		let msg = random_msg();
+		let mut shares: Vec<_> = share_providers
+			.iter()
+			.map(|sp| Share {
+				creator: sp.id(),
+				data: sp.sign(&msg, SignatureVersion::LATEST),
+			})
+			.collect();
+		shares[1].data = share_providers[1].sign(&random_msg(), SignatureVersion::LATEST);
+		shares[4].creator = n_members as u64;
+
+		let items: Vec<_> = shares.iter().map(|share| (&msg[..], share)).collect();
+		assert_eq!(kb.invalid_shares(&items), vec![1, 4]);
+		for (ix, share) in shares.iter().enumerate() {
+			assert_eq!(kb.verify_share(&msg, share), ix != 1 && ix != 4);
+		}
+	}
 }
@@ -101,6 +101,27 @@ pub trait CryptoStore: Send + Sync {
 		_public: &[u8]
 	) -> Result<(), ()>;
 
+	/// Insert a raw secret that is not a key pair of any supported crypto, e.g. a share of a
+	/// threshold key, under the given public key.
+	///
+	/// Keystores that cannot keep such secrets return `Error::Unavailable`.
+	async fn insert_secret(
+		&self,
+		_id: KeyTypeId,
+		_public: &[u8],
+		_secret: &[u8],
+	) -> Result<(), Error> {
+		Err(Error::Unavailable)
+	}
+
+	/// Returns the raw secret inserted with `insert_secret` under the given public key, `None`
+	/// if there is no such secret.
+	///
+	/// Keystores that cannot keep such secrets return `Error::Unavailable`.
+	async fn secret(&self, _id: KeyTypeId, _public: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+		Err(Error::Unavailable)
+	}
+
 	/// Find intersection between provided keys and supported keys
 	///
 	/// Provided a list of (CryptoTypeId,[u8]) pairs, this would return
@@ -258,6 +279,22 @@ pub trait SyncCryptoStore: CryptoStore + Send + Sync {
 	/// `Err` if there's some sort of weird filesystem error, but should generally be `Ok`.
 	fn insert_unknown(&self, key_type: KeyTypeId, suri: &str, public: &[u8]) -> Result<(), ()>;
 
+	/// Insert a raw secret that is not a key pair of any supported crypto, e.g. a share of a
+	/// threshold key, under the given public key.
+	///
+	/// Keystores that cannot keep such secrets return `Error::Unavailable`.
+	fn insert_secret(&self, _id: KeyTypeId, _public: &[u8], _secret: &[u8]) -> Result<(), Error> {
+		Err(Error::Unavailable)
+	}
+
+	/// Returns the raw secret inserted with `insert_secret` under the given public key, `None`
+	/// if there is no such secret.
+	///
+	/// Keystores that cannot keep such secrets return `Error::Unavailable`.
+	fn secret(&self, _id: KeyTypeId, _public: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+		Err(Error::Unavailable)
+	}
+
 	/// Find intersection between provided keys and supported keys
 	///
 	/// Provided a list of (CryptoTypeId,[u8]) pairs, this would return
@@ -19,6 +19,7 @@
 
 use sp_core::crypto::KeyTypeId;
 use sp_core::{
+	bytes::{from_hex, to_hex},
 	crypto::{Pair, Public, CryptoTypePublicPair},
 	ed25519, sr25519, ecdsa,
 };
@@ -115,6 +116,14 @@ impl CryptoStore for KeyStore {
 		SyncCryptoStore::insert_unknown(self, id, suri, public)
 	}
 
+	async fn insert_secret(&self, id: KeyTypeId, public: &[u8], secret: &[u8]) -> Result<(), Error> {
+		SyncCryptoStore::insert_secret(self, id, public, secret)
+	}
+
+	async fn secret(&self, id: KeyTypeId, public: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+		SyncCryptoStore::secret(self, id, public)
+	}
+
 	async fn has_keys(&self, public_keys: &[(Vec<u8>, KeyTypeId)]) -> bool {
 		SyncCryptoStore::has_keys(self, public_keys)
 	}
@@ -260,6 +269,20 @@ impl SyncCryptoStore for KeyStore {
 		Ok(())
 	}
 
+	fn insert_secret(&self, id: KeyTypeId, public: &[u8], secret: &[u8]) -> Result<(), Error> {
+		self.keys.write().entry(id).or_default().insert(public.to_owned(), to_hex(secret, false));
+		Ok(())
+	}
+
+	fn secret(&self, id: KeyTypeId, public: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+		match self.keys.read().get(&id).and_then(|s| s.get(public)) {
+			Some(secret) => from_hex(secret)
+				.map(Some)
+				.map_err(|_| Error::ValidationError("Not a raw secret".into())),
+			None => Ok(None),
+		}
+	}
+
 	fn has_keys(&self, public_keys: &[(Vec<u8>, KeyTypeId)]) -> bool {
 		public_keys.iter().all(|(k, t)| self.keys.read().get(&t).and_then(|s| s.get(k)).is_some())
 	}
@@ -372,6 +395,19 @@ mod tests {
 		assert!(public_keys.contains(&key_pair.public().into()));
 	}
 
+	#[test]
+	fn store_secret_and_extract_it() {
+		let store = KeyStore::new();
+		let public = [1u8; 48];
+
+		assert_eq!(SyncCryptoStore::secret(&store, SR25519, &public).unwrap(), None);
+		SyncCryptoStore::insert_secret(&store, SR25519, &public, &[7u8; 32])
+			.expect("Inserts secret");
+
+		assert_eq!(SyncCryptoStore::secret(&store, SR25519, &public).unwrap(), Some(vec![7u8; 32]));
+		assert!(SyncCryptoStore::has_keys(&store, &[(public.to_vec(), SR25519)]));
+	}
+
 	#[test]
 	fn vrf_sign() {
 		let store = KeyStore::new();
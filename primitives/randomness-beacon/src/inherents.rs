@@ -3,14 +3,29 @@
 use codec::{Decode, Encode};
 use sp_inherents::{InherentIdentifier, IsFatalError};
 pub const INHERENT_IDENTIFIER: InherentIdentifier = *b"randbecn";
+/// The identifier of the `CheckContext` of a block in the inherent data.
+pub const CHECK_CONTEXT_IDENTIFIER: InherentIdentifier = *b"randctxt";
+
+/// The facts about the checked block that the runtime can not learn by itself, as inherents
+/// are checked in the state of the parent block. Provided by the block import of the beacon.
+#[derive(Encode, Decode, Clone, PartialEq, sp_runtime::RuntimeDebug)]
+pub struct CheckContext<Number, Hash> {
+	/// The number of the checked block.
+	pub number: Number,
+	/// The hash of the block whose signature is the seed of the checked block, `None` if no
+	/// seed is due at the block.
+	pub nonce: Option<Hash>,
+}
 
 /// Errors that can occur while checking the inherent
-#[derive(Encode, sp_runtime::RuntimeDebug)]
+#[derive(Encode, PartialEq, sp_runtime::RuntimeDebug)]
 #[cfg_attr(feature = "std", derive(Decode))]
 pub enum InherentError {
 	WrongHeight,
 	InvalidRandomBytes,
 	VerifyKeyNotSet,
+	WrongNonce,
+	WrongSignatureVersion,
 }
 
 impl InherentError {
@@ -30,6 +45,8 @@ impl IsFatalError for InherentError {
 			InherentError::WrongHeight => true,
 			InherentError::InvalidRandomBytes => true,
 			InherentError::VerifyKeyNotSet => true,
+			InherentError::WrongNonce => true,
+			InherentError::WrongSignatureVersion => true,
 		}
 	}
 }
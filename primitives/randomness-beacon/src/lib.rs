@@ -3,21 +3,57 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
 pub mod inherents;
+pub mod vrf;
 
 use codec::{Decode, Encode};
-pub use sp_dkg::VerifyKey;
 use sp_dkg::{KeyBox, RawSecret, Share, ShareProvider, Signature};
-use sp_runtime::traits::NumberFor;
+pub use sp_dkg::{SignatureVersion, VerifyKey};
+use sp_runtime::{
+	traits::{Hash as HashT, NumberFor},
+	ConsensusEngineId,
+};
 use sp_std::marker;
 use sp_std::vec::Vec;
 
 sp_api::decl_runtime_apis! {
+	#[api_version(9)]
 	pub trait RandomnessBeaconApi {
 		fn start_beacon_height() -> NumberFor<Block>;
 		fn beacon_period() -> NumberFor<Block>;
+		/// The version of signatures accepted as randomness. Runtimes implementing only
+		/// version 1 of this api accept `SignatureVersion::V0`.
+		fn signature_version() -> SignatureVersion;
+		/// The latest randomness seed together with the block at which it was set, added
+		/// in version 3.
+		fn latest_randomness() -> Option<(NumberFor<Block>, Randomness<Block::Hash>)>;
+		/// How fresh the current seed is, added in version 4.
+		fn seed_freshness() -> Option<SeedFreshness<NumberFor<Block>>>;
+		/// The seed of the given round, or of the latest sealed round if `None`, together with
+		/// the round. The seed is the threshold signature of its nonce, so it is its own proof.
+		/// Only the seeds of the latest rounds are stored. Added in version 5.
+		fn round_randomness(round: Option<Round>) -> Option<(Round, Randomness<Block::Hash>)>;
+		/// The index of the storage map under which the seed of the round is kept, needed to
+		/// prove the seed. Added in version 6.
+		fn history_index(round: Round) -> u32;
+		/// The key with which the author of a block with the given pre-runtime digests signs
+		/// the VRF output of the block, see `vrf`. Added in version 7.
+		fn author_vrf_key(pre_digests: Vec<(ConsensusEngineId, Vec<u8>)>) -> Option<Vec<u8>>;
+		/// The nonce of the round in the chained mode, see `chained_nonce`, computed from the
+		/// latest seed. `None` if the nonces are the hashes of blocks. Added in version 8.
+		fn chained_nonce(round: Round) -> Option<Block::Hash>;
+		/// The time in milliseconds since its parent after which a block at which a seed is due
+		/// may be authored without one. Added in version 9.
+		fn grace_period() -> u64;
 	}
 }
 
+/// The number of a round of the beacon. The seed of round `r` is due at the block
+/// `start_beacon_height + r * beacon_period`, so the first round is 1.
+pub type Round = u64;
+
+/// A randomness seed, i.e. the threshold signature of the nonce. The nonce of a round is either
+/// the hash of the block `beacon_period` blocks before the seed is due, or in the chained mode
+/// the hash of the round and the previous seed, see `chained_nonce`.
 #[derive(Encode, Decode, Clone, Debug, Default, PartialEq)]
 pub struct Randomness<Nonce> {
 	nonce: Nonce,
@@ -32,6 +68,38 @@ impl<Nonce: Clone> Randomness<Nonce> {
 	pub fn nonce(&self) -> Nonce {
 		self.nonce.clone()
 	}
+
+	pub fn version(&self) -> SignatureVersion {
+		self.data.version()
+	}
+
+	pub fn signature(&self) -> &Signature {
+		&self.data
+	}
+}
+
+/// The nonce of the round in the chained mode, i.e. `H(round || previous)` as in drand, where
+/// `previous` is the signature of the latest seed set before the round, `None` for the first
+/// one. Such nonces do not depend on the blocks, so neither block authors nor forks influence
+/// them, and the seeds may be verified in sequence from the first one, see
+/// `RandomnessVerifier::verify_chain`.
+pub fn chained_nonce<Hashing: HashT>(round: Round, previous: Option<&Signature>) -> Hashing::Output {
+	let mut message = round.encode();
+	if let Some(previous) = previous {
+		message.extend(previous.encode());
+	}
+	<Hashing as HashT>::hash(&message)
+}
+
+/// How fresh the current randomness seed is.
+#[derive(Encode, Decode, Clone, Debug, Default, PartialEq)]
+pub struct SeedFreshness<BlockNumber> {
+	/// The block at which the seed was set.
+	pub set_at: BlockNumber,
+	/// The number of blocks since the seed was set.
+	pub age: BlockNumber,
+	/// The number of beacon blocks produced without a new seed since the seed was set.
+	pub missed: u32,
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Encode, Decode)]
@@ -48,6 +116,26 @@ impl RandomnessVerifier {
 		self.master_key
 			.verify(&randomness.nonce.encode(), &randomness.data)
 	}
+
+	/// Verifies the seeds of the chained mode in sequence, starting from the first seed set.
+	/// Each seed has to be a valid signature of the nonce chained to the seed before it. Rounds
+	/// without a seed may be left out, as their successors are chained to the latest seed.
+	pub fn verify_chain<Hashing: HashT>(
+		&self,
+		seeds: &[(Round, Randomness<Hashing::Output>)],
+	) -> bool {
+		let mut previous: Option<&Randomness<Hashing::Output>> = None;
+		let mut previous_round = 0;
+		for (round, randomness) in seeds {
+			let nonce = chained_nonce::<Hashing>(*round, previous.map(|seed| seed.signature()));
+			if *round <= previous_round || randomness.nonce != nonce || !self.verify(randomness) {
+				return false;
+			}
+			previous = Some(randomness);
+			previous_round = *round;
+		}
+		true
+	}
 }
 
 #[derive(Clone, Encode, Decode)]
@@ -56,6 +144,17 @@ pub struct RandomnessShare<Nonce: Encode + Decode> {
 	share: Share,
 }
 
+impl<Nonce: Encode + Decode + Clone> RandomnessShare<Nonce> {
+	pub fn nonce(&self) -> Nonce {
+		self.nonce.clone()
+	}
+
+	/// The index of the committee member who created the share.
+	pub fn creator(&self) -> u64 {
+		self.share.creator()
+	}
+}
+
 impl<Nonce: Encode + Decode> PartialEq<RandomnessShare<Nonce>> for RandomnessShare<Nonce> {
 	fn eq(&self, rhs: &RandomnessShare<Nonce>) -> bool {
 		self.share == rhs.share
@@ -64,6 +163,7 @@ impl<Nonce: Encode + Decode> PartialEq<RandomnessShare<Nonce>> for RandomnessSha
 
 pub struct RBBox<Nonce> {
 	keybox: KeyBox,
+	version: SignatureVersion,
 	_marker: marker::PhantomData<Nonce>,
 }
 
@@ -74,17 +174,19 @@ impl<Nonce: Encode + Decode + Clone> RBBox<Nonce> {
 		verification_keys: Vec<VerifyKey>,
 		master_key: VerifyKey,
 		threshold: u64,
+		version: SignatureVersion,
 	) -> Self {
 		let sp = raw_secret.map(|rs| ShareProvider::from_raw_secret(ix.unwrap(), rs));
 		RBBox {
 			keybox: KeyBox::new(sp, verification_keys, master_key, threshold),
+			version,
 			_marker: marker::PhantomData,
 		}
 	}
 
 	pub fn generate_randomness_share(&self, nonce: Nonce) -> Option<RandomnessShare<Nonce>> {
 		let msg = nonce.encode();
-		let maybe_share = self.keybox.generate_share(&msg);
+		let maybe_share = self.keybox.generate_share(&msg, self.version);
 		if let Some(share) = maybe_share {
 			return Some(RandomnessShare { nonce, share });
 		}
@@ -92,10 +194,45 @@ impl<Nonce: Encode + Decode + Clone> RBBox<Nonce> {
 	}
 
 	pub fn verify_randomness_share(&self, randomness_share: &RandomnessShare<Nonce>) -> bool {
+		if randomness_share.share.version() != self.version {
+			return false;
+		}
 		let msg = randomness_share.nonce.encode();
 		self.keybox.verify_share(&msg, &randomness_share.share)
 	}
 
+	/// Returns the indices of the invalid shares, checking all the shares of the correct version
+	/// in one batch. Equivalent to calling `verify_randomness_share` for every share.
+	pub fn invalid_randomness_shares(
+		&self,
+		randomness_shares: &[RandomnessShare<Nonce>],
+	) -> Vec<usize> {
+		let msgs: Vec<Vec<u8>> = randomness_shares
+			.iter()
+			.map(|rs| rs.nonce.encode())
+			.collect();
+		let mut invalid = Vec::new();
+		let mut items = Vec::with_capacity(randomness_shares.len());
+		let mut positions = Vec::with_capacity(randomness_shares.len());
+		for (ix, rs) in randomness_shares.iter().enumerate() {
+			if rs.share.version() != self.version {
+				invalid.push(ix);
+			} else {
+				items.push((&msgs[ix][..], &rs.share));
+				positions.push(ix);
+			}
+		}
+
+		invalid.extend(
+			self.keybox
+				.invalid_shares(&items)
+				.into_iter()
+				.map(|i| positions[i]),
+		);
+		invalid.sort_unstable();
+		invalid
+	}
+
 	pub fn combine_shares(
 		&self,
 		randomness_shares: &Vec<RandomnessShare<Nonce>>,
@@ -104,13 +241,59 @@ impl<Nonce: Encode + Decode + Clone> RBBox<Nonce> {
 			.iter()
 			.map(|rs| rs.share.clone())
 			.collect();
-		Randomness {
-			nonce: randomness_shares[0].nonce.clone(),
-			data: self.keybox.combine_shares(&shares),
-		}
+		Randomness::new(
+			randomness_shares[0].nonce.clone(),
+			self.keybox.combine_shares(&shares),
+		)
 	}
 
 	pub fn threshold(&self) -> u64 {
 		self.keybox.threshold()
 	}
+
+	/// The size of the committee whose shares the box verifies.
+	pub fn n_members(&self) -> usize {
+		self.keybox.n_members()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_runtime::traits::BlakeTwo256;
+
+	type Seed = Randomness<<BlakeTwo256 as HashT>::Output>;
+
+	fn seed(provider: &ShareProvider, round: Round, previous: Option<&Seed>) -> Seed {
+		let nonce = chained_nonce::<BlakeTwo256>(round, previous.map(|seed| seed.signature()));
+		let signature = provider.sign(&nonce.encode(), SignatureVersion::V0);
+		Randomness::new(nonce, signature)
+	}
+
+	#[test]
+	fn verifies_chained_seeds_in_sequence() {
+		let provider = ShareProvider::from_raw_secret(0, [1, 7, 2, 9]);
+		let verifier = RandomnessVerifier::new(provider.verify_key());
+		let first = seed(&provider, 1, None);
+		let second = seed(&provider, 2, Some(&first));
+		// the seed of round 3 is missing, round 4 is chained to round 2
+		let fourth = seed(&provider, 4, Some(&second));
+		let chain = vec![(1, first.clone()), (2, second.clone()), (4, fourth.clone())];
+		assert!(verifier.verify_chain::<BlakeTwo256>(&chain));
+
+		// the nonce depends on the round
+		assert_ne!(
+			chained_nonce::<BlakeTwo256>(3, Some(second.signature())),
+			fourth.nonce()
+		);
+		assert!(!verifier.verify_chain::<BlakeTwo256>(&[(1, first.clone()), (3, second.clone())]));
+		// a seed left out breaks the chain
+		assert!(!verifier.verify_chain::<BlakeTwo256>(&[(1, first.clone()), (4, fourth.clone())]));
+		// a valid signature of an unchained nonce
+		let unchained = seed(&provider, 2, None);
+		assert!(!verifier.verify_chain::<BlakeTwo256>(&[(1, first), (2, unchained)]));
+
+		let other = RandomnessVerifier::new(ShareProvider::from_raw_secret(0, [2, 7, 2, 9]).verify_key());
+		assert!(!other.verify_chain::<BlakeTwo256>(&chain));
+	}
 }
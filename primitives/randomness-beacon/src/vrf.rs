@@ -0,0 +1,151 @@
+//! Per-block randomness from the VRF outputs of block authors.
+//!
+//! The beacon seed changes only every `beacon_period` blocks. To get a fresh value in every
+//! block, the author of a block evaluates a schnorrkel VRF, as in BABE, on the latest seed and
+//! the number of the block, and puts the output together with its proof into a pre-runtime
+//! digest of the block. The runtime verifies the proof with the key of the author and mixes
+//! the output with the seed.
+
+use codec::{Decode, Encode};
+use merlin::Transcript;
+use sp_runtime::{generic::DigestItem, ConsensusEngineId};
+use sp_std::vec::Vec;
+
+pub use sp_consensus_vrf::schnorrkel::{
+	PublicKey, Randomness as VrfRandomness, VRFOutput, VRFProof, RANDOMNESS_LENGTH,
+};
+
+#[cfg(feature = "std")]
+use sp_keystore::vrf::{VRFTranscriptData, VRFTranscriptValue};
+
+use crate::Randomness;
+
+/// The engine id of the pre-runtime digest carrying the VRF output of the author.
+pub const VRF_ENGINE_ID: ConsensusEngineId = *b"rbvf";
+
+/// The context with which the VRF output is turned into randomness.
+pub const VRF_INOUT_CONTEXT: &[u8] = b"RandomnessBeaconVRFInOutContext";
+
+/// The VRF output of the author of a block and its proof.
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+pub struct PreDigest {
+	pub vrf_output: VRFOutput,
+	pub vrf_proof: VRFProof,
+}
+
+impl PreDigest {
+	/// The digest item under which the pre-digest is put into the header.
+	pub fn to_digest_item<Hash>(&self) -> DigestItem<Hash> {
+		DigestItem::PreRuntime(VRF_ENGINE_ID, self.encode())
+	}
+
+	/// Finds the pre-digest among the pre-runtime digests of a block.
+	pub fn find<'a, I>(digests: I) -> Option<Self>
+	where
+		I: 'a + IntoIterator<Item = (ConsensusEngineId, &'a [u8])>,
+	{
+		digests
+			.into_iter()
+			.filter(|(id, _)| *id == VRF_ENGINE_ID)
+			.find_map(|(_, mut data)| PreDigest::decode(&mut data).ok())
+	}
+}
+
+/// The part of the seed the VRF is evaluated on, empty before the first seed is set.
+pub fn seed_input<Nonce: Encode + Decode + Clone>(seed: Option<&Randomness<Nonce>>) -> Vec<u8> {
+	seed.map(|seed| (seed.nonce(), seed.signature()).encode())
+		.unwrap_or_default()
+}
+
+/// The transcript of the VRF of the author of the block with the given number.
+pub fn make_transcript(seed: &[u8], number: u64) -> Transcript {
+	let mut transcript = Transcript::new(&VRF_ENGINE_ID);
+	transcript.append_u64(b"block number", number);
+	transcript.append_message(b"beacon seed", seed);
+	transcript
+}
+
+/// The data of `make_transcript` in the form accepted by the keystore.
+#[cfg(feature = "std")]
+pub fn make_transcript_data(seed: &[u8], number: u64) -> VRFTranscriptData {
+	VRFTranscriptData {
+		label: &VRF_ENGINE_ID,
+		items: vec![
+			("block number", VRFTranscriptValue::U64(number)),
+			("beacon seed", VRFTranscriptValue::Bytes(seed.to_vec())),
+		],
+	}
+}
+
+/// Verifies the pre-digest of the block with the given number against the key of its author
+/// and returns the randomness of the VRF output. `None` if the key or the proof is invalid.
+pub fn verify(
+	pre_digest: &PreDigest,
+	author: &[u8],
+	seed: &[u8],
+	number: u64,
+) -> Option<VrfRandomness> {
+	let public = PublicKey::from_bytes(author).ok()?;
+	let (inout, _) = public
+		.vrf_verify(
+			make_transcript(seed, number),
+			&pre_digest.vrf_output,
+			&pre_digest.vrf_proof,
+		)
+		.ok()?;
+	Some(inout.make_bytes::<VrfRandomness>(VRF_INOUT_CONTEXT))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::{crypto::KeyTypeId, sr25519, H256};
+	use sp_keystore::{testing::KeyStore, SyncCryptoStore};
+
+	const KEY_TYPE: KeyTypeId = KeyTypeId(*b"test");
+
+	fn sign(keystore: &KeyStore, author: &sr25519::Public, seed: &[u8], number: u64) -> PreDigest {
+		let signature = keystore
+			.sr25519_vrf_sign(KEY_TYPE, author, make_transcript_data(seed, number))
+			.unwrap();
+		PreDigest {
+			vrf_output: VRFOutput(signature.output),
+			vrf_proof: VRFProof(signature.proof),
+		}
+	}
+
+	#[test]
+	fn verifies_outputs_of_the_author() {
+		let keystore = KeyStore::new();
+		let author = keystore.sr25519_generate_new(KEY_TYPE, None).unwrap();
+		let other = keystore.sr25519_generate_new(KEY_TYPE, None).unwrap();
+		let seed = seed_input(Some(&Randomness::<u64>::default()));
+		let pre_digest = sign(&keystore, &author, &seed, 7);
+
+		let randomness = verify(&pre_digest, author.as_ref(), &seed, 7);
+		assert!(randomness.is_some());
+		// the output is unique for the key and the input
+		assert_eq!(
+			verify(&sign(&keystore, &author, &seed, 7), author.as_ref(), &seed, 7),
+			randomness
+		);
+		assert_eq!(verify(&pre_digest, other.as_ref(), &seed, 7), None);
+		assert_eq!(verify(&pre_digest, author.as_ref(), &seed, 8), None);
+		assert_eq!(verify(&pre_digest, author.as_ref(), &seed_input::<u64>(None), 7), None);
+		assert_eq!(verify(&pre_digest, &[1, 2, 3], &seed, 7), None);
+	}
+
+	#[test]
+	fn finds_pre_digest_in_digests() {
+		let keystore = KeyStore::new();
+		let author = keystore.sr25519_generate_new(KEY_TYPE, None).unwrap();
+		let pre_digest = sign(&keystore, &author, &[], 1);
+		let item = pre_digest.to_digest_item::<H256>();
+		let other = DigestItem::PreRuntime(*b"aura", 5u64.encode());
+		let logs = vec![other, item];
+
+		let found = PreDigest::find(logs.iter().filter_map(|log| log.as_pre_runtime()));
+		assert_eq!(found, Some(pre_digest));
+		assert_eq!(PreDigest::find(logs[..1].iter().filter_map(|log| log.as_pre_runtime())), None);
+	}
+}
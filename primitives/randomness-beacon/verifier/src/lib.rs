@@ -0,0 +1,371 @@
+//! A standalone verifier of the seeds of the randomness beacon.
+//!
+//! A `BeaconProof` carries the seed of a round, i.e. the threshold signature of its nonce, the
+//! master verification key and a read proof of both of them against the state of a finalized
+//! header. Checking it needs only the hash of that header, so light clients, bridges and
+//! off-chain services may use the seeds without running a node of the chain. The signature is
+//! checked in pure Rust, without the host functions of `sp-dkg`, so the crate may be used in
+//! the runtimes of other chains as well.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use bls12_381::hash_to_curve::{ExpandMsgXmd, HashToCurve};
+use bls12_381::{multi_miller_loop, G1Affine, G1Projective, G2Affine, G2Prepared, Gt, Scalar};
+use codec::{Decode, Encode};
+use sha2::Sha256;
+use sha3::{Digest, Sha3_256};
+use sp_core::hashing::{twox_128, twox_64};
+use sp_runtime::traits::{Hash as HashT, Header as HeaderT};
+use sp_std::vec::Vec;
+use sp_trie::{read_trie_value, Layout, StorageProof};
+
+/// The prefix of the storage of the randomness beacon pallet.
+pub const PALLET_PREFIX: &[u8] = b"RandomnessBeacon";
+
+/// The domain separation tag of `SignatureVersion::V1` signatures, the same as in `sp-dkg`.
+pub const HASH_TO_G1_DST: &[u8] = b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_NUL_";
+
+/// The seed of a round together with everything needed to check it against a finalized header.
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode)]
+pub struct BeaconProof<Header: HeaderT> {
+	/// The finalized header whose state contains the seed.
+	pub header: Header,
+	/// The round of the seed.
+	pub round: u64,
+	/// The hash of the block which was signed.
+	pub nonce: Header::Hash,
+	/// The threshold signature of the nonce, encoded as the signatures of `sp-dkg`: a
+	/// compressed G1 point, preceded by the version byte for versions other than V0.
+	pub signature: Vec<u8>,
+	/// The master verification key, a compressed G2 point.
+	pub master_key: Vec<u8>,
+	/// The index under which the pallet keeps the seed, the round modulo the history depth.
+	pub history_index: u32,
+	/// The trie nodes proving the seed and the master key in the state of the header.
+	pub storage_proof: Vec<Vec<u8>>,
+}
+
+/// A seed whose proof was checked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifiedSeed<Hash> {
+	/// The round of the seed.
+	pub round: u64,
+	/// The hash of the block which was signed.
+	pub nonce: Hash,
+	/// The threshold signature of the nonce.
+	pub signature: Vec<u8>,
+}
+
+impl<Hash: Encode> VerifiedSeed<Hash> {
+	/// The random value of the subject in the round of the seed, the same as the one the
+	/// pallet gives for the subject in that round.
+	pub fn random<Hashing: HashT>(&self, subject: &[u8]) -> Hashing::Output {
+		let mut data = subject.to_vec();
+		(&self.nonce, &self.signature).encode_to(&mut data);
+		<Hashing as HashT>::hash(&data[..])
+	}
+}
+
+/// The reasons a proof is rejected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+	/// The header of the proof is not the trusted one.
+	UnknownHeader,
+	/// The storage proof does not contain the seed or the master key.
+	InvalidStorageProof,
+	/// The seed of the proof differs from the one in the state.
+	SeedMismatch,
+	/// The master key of the proof differs from the one in the state.
+	KeyMismatch,
+	/// The master key is not a valid G2 point.
+	MalformedKey,
+	/// The signature is not a valid G1 point or its version is unknown.
+	MalformedSignature,
+	/// The signature of the nonce does not verify under the master key.
+	InvalidSignature,
+}
+
+/// The storage key of the seed kept under the given index of the history.
+pub fn history_storage_key(index: u32) -> Vec<u8> {
+	let mut key = storage_prefix(b"History");
+	let index = index.encode();
+	key.extend_from_slice(&twox_64(&index));
+	key.extend_from_slice(&index);
+	key
+}
+
+/// The storage key of the verifier of the seeds.
+pub fn verifier_storage_key() -> Vec<u8> {
+	storage_prefix(b"Verifier")
+}
+
+fn storage_prefix(item: &[u8]) -> Vec<u8> {
+	let mut key = twox_128(PALLET_PREFIX).to_vec();
+	key.extend_from_slice(&twox_128(item));
+	key
+}
+
+/// Checks the proof against the hash of a header the caller trusts to be finalized, returns
+/// the seed of the proof if it is correct.
+///
+/// The master key must be the active one at the header, so the seeds signed before a rotation
+/// of the key must be proven against a header older than the rotation.
+pub fn verify<Header: HeaderT>(
+	proof: &BeaconProof<Header>,
+	finalized: &Header::Hash,
+) -> Result<VerifiedSeed<Header::Hash>, Error> {
+	if proof.header.hash() != *finalized {
+		return Err(Error::UnknownHeader);
+	}
+
+	let db = StorageProof::new(proof.storage_proof.clone()).into_memory_db::<Header::Hashing>();
+	let read = |key: &[u8]| {
+		read_trie_value::<Layout<Header::Hashing>, _>(&db, proof.header.state_root(), key)
+			.ok()
+			.flatten()
+			.ok_or(Error::InvalidStorageProof)
+	};
+
+	// the pallet keeps `(Round, Randomness)`, where the seed is encoded as its nonce and its
+	// signature
+	let seed = read(&history_storage_key(proof.history_index))?;
+	let (round, nonce, signature) =
+		<(u64, Header::Hash, Vec<u8>)>::decode(&mut &seed[..])
+			.map_err(|_| Error::InvalidStorageProof)?;
+	if round != proof.round || nonce != proof.nonce || signature != proof.signature {
+		return Err(Error::SeedMismatch);
+	}
+
+	let verifier = read(&verifier_storage_key())?;
+	let master_key = Vec::<u8>::decode(&mut &verifier[..]).map_err(|_| Error::InvalidStorageProof)?;
+	if master_key != proof.master_key {
+		return Err(Error::KeyMismatch);
+	}
+
+	verify_signature(&proof.master_key, &proof.nonce.encode(), &proof.signature)?;
+
+	Ok(VerifiedSeed {
+		round,
+		nonce,
+		signature,
+	})
+}
+
+/// Checks the threshold signature of the message under the master key, both encoded as in
+/// `sp-dkg`.
+pub fn verify_signature(master_key: &[u8], msg: &[u8], signature: &[u8]) -> Result<(), Error> {
+	let key = read_g2(master_key).ok_or(Error::MalformedKey)?;
+
+	// V0 signatures have no version byte, and a V0 signature with one is not valid
+	let hash = match signature.len() {
+		48 => hash_to_curve_v0(msg),
+		49 if signature[0] == 1 => hash_to_g1(msg),
+		_ => return Err(Error::MalformedSignature),
+	};
+	let point = read_g1(&signature[signature.len() - 48..]).ok_or(Error::MalformedSignature)?;
+
+	// e(sgn, g2) == e(H(msg), key)
+	let result = multi_miller_loop(&[
+		(&point, &G2Prepared::from(-G2Affine::generator())),
+		(&hash, &G2Prepared::from(key)),
+	])
+	.final_exponentiation();
+
+	if result == Gt::identity() {
+		Ok(())
+	} else {
+		Err(Error::InvalidSignature)
+	}
+}
+
+fn read_g1(bytes: &[u8]) -> Option<G1Affine> {
+	let mut compressed = [0u8; 48];
+	if bytes.len() != compressed.len() {
+		return None;
+	}
+	compressed.copy_from_slice(bytes);
+	let point = G1Affine::from_compressed(&compressed);
+	if point.is_some().unwrap_u8() == 1 {
+		Some(point.unwrap())
+	} else {
+		None
+	}
+}
+
+fn read_g2(bytes: &[u8]) -> Option<G2Affine> {
+	let mut compressed = [0u8; 96];
+	if bytes.len() != compressed.len() {
+		return None;
+	}
+	compressed.copy_from_slice(bytes);
+	let point = G2Affine::from_compressed(&compressed);
+	if point.is_some().unwrap_u8() == 1 {
+		Some(point.unwrap())
+	} else {
+		None
+	}
+}
+
+// the hashing of `SignatureVersion::V1` in `sp-dkg`
+fn hash_to_g1(msg: &[u8]) -> G1Affine {
+	let point = <G1Projective as HashToCurve<ExpandMsgXmd<Sha256>>>::hash_to_curve(msg, HASH_TO_G1_DST);
+	G1Affine::from(point)
+}
+
+// the hashing of `SignatureVersion::V0` in `sp-dkg`, which is insecure and kept only to verify
+// the seeds of chains created before V1
+fn hash_to_curve_v0(msg: &[u8]) -> G1Affine {
+	let mut hasher = Sha3_256::new();
+	hasher.input(msg);
+	let data = hasher.result();
+
+	let mut scalar_raw = [0u64; 4];
+	for i in 0usize..4 {
+		let mut bytes = [0u8; 8];
+		bytes.copy_from_slice(&data[i * 8..(i + 1) * 8]);
+		scalar_raw[i] = u64::from_le_bytes(bytes);
+	}
+
+	G1Affine::from(G1Affine::generator() * Scalar::from_raw(scalar_raw))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::H256;
+	use sp_dkg::{ShareProvider, SignatureVersion, VerifyKey};
+	use sp_randomness_beacon::{Randomness, RandomnessVerifier};
+	use sp_runtime::{testing::Header, traits::BlakeTwo256};
+	use sp_state_machine::{prove_read, InMemoryBackend};
+	use std::collections::BTreeMap;
+
+	const HISTORY_INDEX: u32 = 3;
+
+	fn signer(secret: u64) -> ShareProvider {
+		ShareProvider::from_raw_secret(0, [secret, 0, 0, 0])
+	}
+
+	// a proof of the seed of round 7 signed by `signer` and stored with the given key
+	fn proof_with(
+		signer: &ShareProvider,
+		stored_key: &VerifyKey,
+		version: SignatureVersion,
+	) -> BeaconProof<Header> {
+		let nonce = H256::repeat_byte(7);
+		let signature = signer.sign(&nonce.encode(), version);
+		let randomness = Randomness::new(nonce, signature.clone());
+
+		let mut state = BTreeMap::new();
+		state.insert(history_storage_key(HISTORY_INDEX), (7u64, randomness).encode());
+		state.insert(verifier_storage_key(), RandomnessVerifier::new(stored_key.clone()).encode());
+		state.insert(b"unrelated".to_vec(), vec![1, 2, 3]);
+		let backend = InMemoryBackend::<BlakeTwo256>::from(state);
+		let state_root = *backend.root();
+		let storage_proof = prove_read(backend, &[history_storage_key(HISTORY_INDEX), verifier_storage_key()])
+			.expect("keys are in the state")
+			.iter_nodes()
+			.collect();
+
+		BeaconProof {
+			header: Header::new(10, Default::default(), state_root, Default::default(), Default::default()),
+			round: 7,
+			nonce,
+			// the encodings of `sp-dkg` are the bytes prefixed with their length
+			signature: Vec::decode(&mut &signature.encode()[..]).unwrap(),
+			master_key: Vec::decode(&mut &stored_key.encode()[..]).unwrap(),
+			history_index: HISTORY_INDEX,
+			storage_proof,
+		}
+	}
+
+	fn proof(version: SignatureVersion) -> BeaconProof<Header> {
+		let signer = signer(5);
+		proof_with(&signer, &signer.verify_key(), version)
+	}
+
+	#[test]
+	fn accepts_correct_proofs() {
+		for version in [SignatureVersion::V0, SignatureVersion::V1].iter() {
+			let proof = proof(*version);
+			let seed = verify(&proof, &proof.header.hash()).expect("the proof is correct");
+			assert_eq!(seed.round, 7);
+			assert_eq!(seed.nonce, proof.nonce);
+			assert_eq!(seed.signature, proof.signature);
+		}
+	}
+
+	#[test]
+	fn proofs_survive_encoding() {
+		let proof = proof(SignatureVersion::V1);
+		let decoded = BeaconProof::<Header>::decode(&mut &proof.encode()[..]).unwrap();
+		assert_eq!(decoded, proof);
+		assert!(verify(&decoded, &proof.header.hash()).is_ok());
+	}
+
+	#[test]
+	fn rejects_untrusted_headers() {
+		let proof = proof(SignatureVersion::V1);
+		assert_eq!(verify(&proof, &H256::repeat_byte(1)), Err(Error::UnknownHeader));
+
+		let mut forged = proof;
+		forged.header.state_root = H256::repeat_byte(2);
+		assert_eq!(verify(&forged, &forged.header.hash()), Err(Error::InvalidStorageProof));
+	}
+
+	#[test]
+	fn rejects_seeds_not_in_the_state() {
+		let proof = proof(SignatureVersion::V1);
+		let hash = proof.header.hash();
+
+		let mut forged = proof.clone();
+		forged.round = 8;
+		assert_eq!(verify(&forged, &hash), Err(Error::SeedMismatch));
+
+		let mut forged = proof.clone();
+		forged.nonce = H256::repeat_byte(8);
+		assert_eq!(verify(&forged, &hash), Err(Error::SeedMismatch));
+
+		let mut forged = proof.clone();
+		forged.history_index = HISTORY_INDEX + 1;
+		assert_eq!(verify(&forged, &hash), Err(Error::InvalidStorageProof));
+
+		let mut forged = proof.clone();
+		forged.storage_proof.clear();
+		assert_eq!(verify(&forged, &hash), Err(Error::InvalidStorageProof));
+
+		let mut forged = proof;
+		forged.master_key = Vec::decode(&mut &signer(6).verify_key().encode()[..]).unwrap();
+		assert_eq!(verify(&forged, &hash), Err(Error::KeyMismatch));
+	}
+
+	#[test]
+	fn rejects_seeds_signed_with_other_keys() {
+		let proof = proof_with(&signer(6), &signer(5).verify_key(), SignatureVersion::V1);
+		assert_eq!(verify(&proof, &proof.header.hash()), Err(Error::InvalidSignature));
+	}
+
+	#[test]
+	fn verifies_signatures_as_sp_dkg() {
+		let signer = signer(5);
+		let key = signer.verify_key();
+		let msg = b"message".to_vec();
+		let raw_key = Vec::<u8>::decode(&mut &key.encode()[..]).unwrap();
+
+		for version in [SignatureVersion::V0, SignatureVersion::V1].iter() {
+			let signature = signer.sign(&msg, *version);
+			let raw_signature = Vec::<u8>::decode(&mut &signature.encode()[..]).unwrap();
+			assert!(key.verify(&msg, &signature));
+			assert_eq!(verify_signature(&raw_key, &msg, &raw_signature), Ok(()));
+			assert_eq!(
+				verify_signature(&raw_key, b"other message", &raw_signature),
+				Err(Error::InvalidSignature),
+			);
+		}
+
+		// V0 signatures must not carry a version byte
+		let mut raw_signature = Vec::<u8>::decode(&mut &signer.sign(&msg, SignatureVersion::V0).encode()[..]).unwrap();
+		raw_signature.insert(0, 0);
+		assert_eq!(verify_signature(&raw_key, &msg, &raw_signature), Err(Error::MalformedSignature));
+		assert_eq!(verify_signature(&raw_key[1..], &msg, &raw_signature[1..]), Err(Error::MalformedKey));
+	}
+}